@@ -0,0 +1,75 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Access logging, sampling successful requests
+//!
+//! At high request rates, logging every request is expensive, so only 1 in every
+//! `sample_rate` successful requests is logged.  Errors are always logged, and every request
+//! is counted in the metrics regardless of whether it's logged.  The sampling is done here, so
+//! requests are logged without the webserver's own error rate limiting.
+
+use crate::counters::REQUESTS;
+use aptos_warp_webserver::log_request_unsampled;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use warp::log::{custom, Info, Log};
+
+/// Logs every request by default
+pub const DEFAULT_ACCESS_LOG_SAMPLE_RATE: u64 = 1;
+
+/// Access logger, logging 1 in every `sample_rate` successful requests, and all errors
+pub fn access_logger(sample_rate: u64) -> Log<impl Fn(Info) + Clone> {
+    let successes = Arc::new(AtomicU64::new(0));
+    custom(move |info: Info| {
+        let status = info.status().as_u16();
+        REQUESTS.with_label_values(&[&status.to_string()]).inc();
+
+        let success_count = if is_error(status) {
+            None
+        } else {
+            Some(successes.fetch_add(1, Ordering::Relaxed))
+        };
+        if should_log(success_count, sample_rate) {
+            log_request_unsampled(&info);
+        }
+    })
+}
+
+fn is_error(status: u16) -> bool {
+    status >= 400
+}
+
+/// Whether to log a request, given how many successes came before it, or `None` if it's an error
+fn should_log(success_count: Option<u64>, sample_rate: u64) -> bool {
+    match success_count {
+        None => true,
+        // A rate of 0 is treated as logging everything, rather than nothing
+        Some(count) => count % sample_rate.max(1) == 0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_error, should_log};
+
+    #[test]
+    fn successes_are_sampled() {
+        let logged = |sample_rate: u64| {
+            (0..100)
+                .filter(|count| should_log(Some(*count), sample_rate))
+                .count()
+        };
+        assert_eq!(100, logged(1));
+        assert_eq!(100, logged(0));
+        assert_eq!(10, logged(10));
+        assert_eq!(1, logged(1000));
+
+        // Errors are always logged
+        assert!(should_log(None, 1000));
+        assert!(is_error(400));
+        assert!(is_error(503));
+        assert!(!is_error(200));
+    }
+}
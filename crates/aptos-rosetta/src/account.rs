@@ -5,14 +5,19 @@
 //!
 //! See: [Account API Spec](https://www.rosetta-api.org/docs/AccountApi.html)
 //!
+//! `/account/balances` is an Aptos specific extension, which isn't part of the spec.
 
 use crate::{
     common::{
         check_network, get_block_index_from_request, handle_request, native_coin, native_coin_tag,
-        with_context,
+        with_context, with_upstream_timeout,
     },
     error::{ApiError, ApiResult},
-    types::{AccountBalanceRequest, AccountBalanceResponse, Amount, Currency, *},
+    timeouts::EndpointClass,
+    types::{
+        AccountBalanceRequest, AccountBalanceResponse, AccountBalanceResult,
+        AccountBalancesRequest, AccountBalancesResponse, Amount, Currency, *,
+    },
     RosettaContext,
 };
 use aptos_logger::{debug, trace, warn};
@@ -20,9 +25,13 @@ use aptos_types::{
     account_address::AccountAddress,
     account_config::{AccountResource, CoinStoreResource},
 };
+use futures::StreamExt;
 use std::{collections::HashSet, str::FromStr};
 use warp::Filter;
 
+/// Number of accounts in an `/account/balances` request fetched from the upstream at once
+const BALANCE_BATCH_CONCURRENCY: usize = 10;
+
 /// Account routes e.g. balance
 pub fn routes(
     server_context: RosettaContext,
@@ -30,8 +39,18 @@ pub fn routes(
     warp::post().and(
         warp::path!("account" / "balance")
             .and(warp::body::json())
-            .and(with_context(server_context))
-            .and_then(handle_request(account_balance)),
+            .and(with_context(server_context.clone()))
+            .and_then(handle_request(with_upstream_timeout(
+                EndpointClass::Account,
+                account_balance,
+            )))
+            .or(warp::path!("account" / "balances")
+                .and(warp::body::json())
+                .and(with_context(server_context))
+                .and_then(handle_request(with_upstream_timeout(
+                    EndpointClass::Account,
+                    account_balances,
+                )))),
     )
 }
 
@@ -53,6 +72,7 @@ async fn account_balance(
     let network_identifier = request.network_identifier;
 
     check_network(network_identifier, &server_context)?;
+    server_context.check_online()?;
     let rest_client = server_context.rest_client()?;
 
     // Retrieve the block index to read
@@ -84,6 +104,117 @@ async fn account_balance(
     })
 }
 
+/// Account balances command, for many accounts at once
+///
+/// This is an Aptos specific extension, and not part of the Rosetta spec.  All of the balances
+/// are read at the same block, and are returned in the same order as the accounts in the
+/// request.  An account whose balances can't be retrieved has its error in its result, rather
+/// than failing the whole request.
+async fn account_balances(
+    request: AccountBalancesRequest,
+    server_context: RosettaContext,
+) -> ApiResult<AccountBalancesResponse> {
+    debug!("/account/balances");
+    trace!(
+        request = ?request,
+        server_context = ?server_context,
+        "account_balances for {} accounts",
+        request.account_identifiers.len()
+    );
+
+    check_network(request.network_identifier, &server_context)?;
+    server_context.check_online()?;
+    check_balance_batch_size(
+        request.account_identifiers.len(),
+        server_context.max_balance_batch_size,
+    )?;
+    let rest_client = server_context.rest_client()?;
+
+    // Retrieve the block index to read, shared by all of the accounts
+    let block_height =
+        get_block_index_from_request(&server_context, request.block_identifier.clone()).await?;
+    let block_info = server_context
+        .block_cache()?
+        .get_block_info_by_height(block_height, server_context.chain_id)
+        .await?;
+
+    let results = get_balances_batch(
+        &rest_client,
+        request.account_identifiers,
+        block_info.last_version,
+        request.currencies,
+        BALANCE_BATCH_CONCURRENCY,
+    )
+    .await;
+
+    Ok(AccountBalancesResponse {
+        block_identifier: block_info.block_id,
+        results,
+    })
+}
+
+/// Checks that a batch has at least one account, and no more than the maximum
+fn check_balance_batch_size(num_accounts: usize, max_batch_size: usize) -> ApiResult<()> {
+    if num_accounts == 0 {
+        Err(ApiError::InvalidInput(Some(
+            "Must provide at least one account".to_string(),
+        )))
+    } else if num_accounts > max_batch_size {
+        Err(ApiError::InvalidInput(Some(format!(
+            "Requested {} accounts, but at most {} can be requested at once",
+            num_accounts, max_batch_size
+        ))))
+    } else {
+        Ok(())
+    }
+}
+
+/// Retrieve the balances for many accounts, at most `concurrency` at a time
+///
+/// Results are in the same order as the accounts, with the error for any account whose
+/// balances couldn't be retrieved.
+async fn get_balances_batch(
+    rest_client: &aptos_rest_client::Client,
+    accounts: Vec<AccountIdentifier>,
+    version: u64,
+    maybe_filter_currencies: Option<Vec<Currency>>,
+    concurrency: usize,
+) -> Vec<AccountBalanceResult> {
+    futures::stream::iter(accounts)
+        .map(|account| {
+            let maybe_filter_currencies = maybe_filter_currencies.clone();
+            async move {
+                match get_balances(
+                    rest_client,
+                    account.clone(),
+                    version,
+                    maybe_filter_currencies,
+                )
+                .await
+                {
+                    Ok((sequence_number, operators, balances)) => AccountBalanceResult {
+                        account_identifier: account,
+                        balances: Some(balances),
+                        metadata: Some(AccountBalanceMetadata {
+                            sequence_number: sequence_number.into(),
+                            operators,
+                        }),
+                        error: None,
+                    },
+                    Err(err) => AccountBalanceResult {
+                        account_identifier: account,
+                        balances: None,
+                        metadata: None,
+                        error: Some(err.into_error()),
+                    },
+                }
+            }
+        })
+        .buffered(concurrency)
+        .collect()
+        .await
+}
+
 /// Retrieve the balances for an account
 #[allow(clippy::manual_retain)]
 async fn get_balances(
@@ -125,6 +256,7 @@ async fn get_balances(
                                 balances.push(Amount {
                                     value: coin_store.coin().to_string(),
                                     currency: native_coin(),
+                                    metadata: None,
                                 });
                             }
                         }
@@ -170,6 +302,7 @@ async fn get_balances(
                             balances.push(Amount {
                                 value: balance.to_string(),
                                 currency: native_coin(),
+                                metadata: None,
                             })
                         }
                     } /* TODO: Right now operator stake is not supported
@@ -215,6 +348,7 @@ async fn get_balances(
                 balances.push(Amount {
                     value: 0.to_string(),
                     currency,
+                    metadata: None,
                 });
             }
         }
@@ -225,6 +359,134 @@ async fn get_balances(
         Ok((0, None, vec![Amount {
             value: 0.to_string(),
             currency: native_coin(),
+            metadata: None,
         }]))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{check_balance_batch_size, get_balances_batch};
+    use crate::{
+        common::native_coin,
+        error::ApiError,
+        test_utils::{self, MockLedger},
+        types::{AccountIdentifier, Amount},
+    };
+    use aptos_types::{
+        account_address::AccountAddress,
+        account_config::{AccountResource, CoinStoreResource},
+        event::{EventHandle, EventKey},
+    };
+    use move_core_types::move_resource::MoveStructType;
+    use std::{collections::BTreeMap, convert::Infallible, str::FromStr};
+    use warp::{http::StatusCode, Filter, Rejection, Reply};
+
+    /// Serves the resources of `address` in BCS, and a 404 for any other account
+    fn mock_upstream(address: AccountAddress, sequence_number: u64, coin: u64) -> url::Url {
+        let event_handle = || EventHandle::new(EventKey::new(0, address), 0);
+        let mut resources: BTreeMap<_, Vec<u8>> = BTreeMap::new();
+        resources.insert(
+            AccountResource::struct_tag(),
+            bcs::to_bytes(&AccountResource::new(
+                sequence_number,
+                address.to_vec(),
+                event_handle(),
+                event_handle(),
+            ))
+            .unwrap(),
+        );
+        resources.insert(
+            CoinStoreResource::struct_tag(),
+            bcs::to_bytes(&CoinStoreResource::new(
+                coin,
+                false,
+                event_handle(),
+                event_handle(),
+            ))
+            .unwrap(),
+        );
+        let resources = bcs::to_bytes(&resources).unwrap();
+
+        let route = warp::path!("v1" / "accounts" / String / "resources")
+            .map(move |requested: String| {
+                if AccountAddress::from_str(&requested).ok() != Some(address) {
+                    return StatusCode::NOT_FOUND.into_response();
+                }
+                resources.clone().into_response()
+            })
+            .recover(|_: Rejection| async { Ok::<_, Infallible>(StatusCode::NOT_FOUND) });
+        test_utils::mock_upstream(MockLedger::default(), route)
+    }
+
+    #[tokio::test]
+    async fn batch_of_existing_and_missing_accounts() {
+        let existing = AccountAddress::from_hex_literal("0x1234").unwrap();
+        let missing = AccountAddress::from_hex_literal("0x5678").unwrap();
+        let rest_client = aptos_rest_client::Client::new(mock_upstream(existing, 7, 500));
+        let invalid = AccountIdentifier {
+            address: "not an address".to_string(),
+            sub_account: None,
+        };
+        let amount = |value: u64| Amount {
+            value: value.to_string(),
+            currency: native_coin(),
+            metadata: None,
+        };
+
+        // Fetch one at a time, so the order of the results can't come from the upstream
+        let results = get_balances_batch(
+            &rest_client,
+            vec![
+                AccountIdentifier::base_account(missing),
+                AccountIdentifier::base_account(existing),
+                invalid.clone(),
+            ],
+            100,
+            None,
+            1,
+        )
+        .await;
+        assert_eq!(3, results.len());
+
+        // Missing accounts have no balance, like `/account/balance`
+        assert_eq!(
+            AccountIdentifier::base_account(missing),
+            results[0].account_identifier
+        );
+        assert_eq!(Some(vec![amount(0)]), results[0].balances);
+        assert_eq!(0, results[0].metadata.as_ref().unwrap().sequence_number.0);
+        assert_eq!(None, results[0].error);
+
+        assert_eq!(
+            AccountIdentifier::base_account(existing),
+            results[1].account_identifier
+        );
+        assert_eq!(Some(vec![amount(500)]), results[1].balances);
+        assert_eq!(7, results[1].metadata.as_ref().unwrap().sequence_number.0);
+        assert_eq!(None, results[1].error);
+
+        // Errors are returned inline, without failing the others
+        assert_eq!(invalid, results[2].account_identifier);
+        assert_eq!(None, results[2].balances);
+        assert_eq!(None, results[2].metadata);
+        assert_eq!(
+            ApiError::InvalidInput(None).code(),
+            results[2].error.as_ref().unwrap().code
+        );
+    }
+
+    #[test]
+    fn balance_batch_size_is_bounded() {
+        check_balance_batch_size(1, 100).unwrap();
+        check_balance_batch_size(100, 100).unwrap();
+        assert!(matches!(
+            check_balance_batch_size(0, 100),
+            Err(ApiError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            check_balance_batch_size(101, 100),
+            Err(ApiError::InvalidInput(_))
+        ));
+    }
+}
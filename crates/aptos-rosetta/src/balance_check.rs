@@ -0,0 +1,274 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Sanity check that the coin operations in a block balance
+//!
+//! Every coin moved by a successful transaction is withdrawn from one account and deposited
+//! into another, so deposits and withdrawals should net to zero per currency.  Gas fees are
+//! burned and staking rewards are minted, so neither is included.  Any imbalance means an
+//! event was mapped to the wrong operation, or not mapped at all.
+//!
+//! Minting coins directly (e.g. on test networks) deposits without a withdrawal, adding to the
+//! supply.  Deposits by transactions calling the framework's mint entry function are a valid
+//! source of supply, so they're left out of the totals.  Mints from scripts can't be told
+//! apart from other scripts, so they're still reported as an imbalance.
+
+use crate::{
+    error::{ApiError, ApiResult},
+    types::{
+        Block, Currency, Operation, OperationStatusType, OperationType, APTOS_COIN_MODULE,
+        MINT_FUNCTION,
+    },
+};
+use aptos_logger::error;
+use aptos_rest_client::aptos_api_types::TransactionOnChainData;
+use aptos_types::{
+    account_address::AccountAddress,
+    transaction::{Transaction, TransactionPayload},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+/// What to do when a block's operations don't balance
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BalanceCheckMode {
+    /// Log the imbalance, and return the block as is
+    Log,
+    /// Fail the request with the imbalance
+    Error,
+}
+
+impl BalanceCheckMode {
+    const ERROR: &'static str = "error";
+    const LOG: &'static str = "log";
+}
+
+impl FromStr for BalanceCheckMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().trim() {
+            Self::LOG => Ok(BalanceCheckMode::Log),
+            Self::ERROR => Ok(BalanceCheckMode::Error),
+            _ => Err(anyhow::anyhow!(
+                "Invalid balance check mode '{}', expected '{}' or '{}'",
+                s,
+                Self::LOG,
+                Self::ERROR
+            )),
+        }
+    }
+}
+
+/// Versions of the transactions which mint coins, whose deposits add to the supply
+pub fn mint_versions(txns: &[TransactionOnChainData]) -> HashSet<u64> {
+    txns.iter()
+        .filter(|txn| match txn.transaction {
+            Transaction::UserTransaction(ref user_txn) => is_mint(user_txn.payload()),
+            _ => false,
+        })
+        .map(|txn| txn.version)
+        .collect()
+}
+
+/// Whether the payload calls the framework's mint entry function
+fn is_mint(payload: &TransactionPayload) -> bool {
+    match payload {
+        TransactionPayload::EntryFunction(inner) => {
+            *inner.module().address() == AccountAddress::ONE
+                && inner.module().name().as_str() == APTOS_COIN_MODULE
+                && inner.function().as_str() == MINT_FUNCTION
+        },
+        _ => false,
+    }
+}
+
+/// Checks that the coin operations in the block balance, handling any imbalance by `mode`
+///
+/// Deposits by the transactions at `mint_versions` are minted, so they don't need a withdrawal.
+pub fn check_block_balances(
+    block: &Block,
+    mint_versions: &HashSet<u64>,
+    mode: BalanceCheckMode,
+) -> ApiResult<()> {
+    let imbalances = imbalances(block.transactions.iter().flat_map(|txn| {
+        let minted = mint_versions.contains(&txn.metadata.version.0);
+        txn.operations.iter().filter(move |operation| {
+            !(minted && operation.operation_type == OperationType::Deposit.to_string())
+        })
+    }))?;
+    if imbalances.is_empty() {
+        return Ok(());
+    }
+
+    let details = imbalances
+        .iter()
+        .map(|(currency, imbalance)| format!("{}: {}", currency.symbol, imbalance))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let message = format!(
+        "Operations in block {} don't balance: {}",
+        block.block_identifier.index, details
+    );
+    match mode {
+        BalanceCheckMode::Log => {
+            error!("{}", message);
+            Ok(())
+        },
+        BalanceCheckMode::Error => Err(ApiError::InternalError(Some(message))),
+    }
+}
+
+/// Nets the successful deposits and withdrawals by currency, returning the non-zero totals
+fn imbalances<'a>(
+    operations: impl Iterator<Item = &'a Operation>,
+) -> ApiResult<Vec<(Currency, i128)>> {
+    let success = OperationStatusType::Success.to_string();
+    let mut totals: HashMap<Currency, i128> = HashMap::new();
+    for operation in operations {
+        if operation.status.as_ref() != Some(&success) {
+            continue;
+        }
+        match OperationType::from_str(&operation.operation_type)? {
+            OperationType::Deposit | OperationType::Withdraw => {},
+            _ => continue,
+        }
+        if let Some(ref amount) = operation.amount {
+            let value = i128::from_str(&amount.value).map_err(|err| {
+                ApiError::InternalError(Some(format!(
+                    "Invalid amount '{}' in operation: {}",
+                    amount.value, err
+                )))
+            })?;
+            *totals.entry(amount.currency.clone()).or_default() += value;
+        }
+    }
+
+    Ok(totals
+        .into_iter()
+        .filter(|(_, total)| *total != 0)
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_block_balances, imbalances, is_mint, BalanceCheckMode};
+    use crate::{
+        common::native_coin,
+        types::{
+            AccountIdentifier, Block, BlockIdentifier, Operation, OperationStatusType, Transaction,
+            TransactionIdentifier, TransactionMetadata, TransactionType,
+        },
+    };
+    use aptos_types::{
+        account_address::AccountAddress,
+        transaction::{EntryFunction, TransactionPayload},
+    };
+    use move_core_types::{identifier::Identifier, language_storage::ModuleId};
+    use std::collections::HashSet;
+
+    fn account(byte: u8) -> AccountIdentifier {
+        AccountIdentifier::base_account(AccountAddress::new([byte; AccountAddress::LENGTH]))
+    }
+
+    #[test]
+    fn transfer_balances() {
+        let success = Some(OperationStatusType::Success);
+        let operations = vec![
+            Operation::withdraw(0, success, account(1), native_coin(), 100),
+            Operation::deposit(1, success, account(2), native_coin(), 100),
+            Operation::gas_fee(2, AccountAddress::ONE, 10, 100),
+        ];
+        assert!(imbalances(operations.iter()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn unmatched_deposit_is_reported() {
+        let success = Some(OperationStatusType::Success);
+        let operations = vec![
+            Operation::withdraw(0, success, account(1), native_coin(), 100),
+            Operation::deposit(1, success, account(2), native_coin(), 150),
+            // Failed operations never moved any coins
+            Operation::deposit(
+                2,
+                Some(OperationStatusType::Failure),
+                account(2),
+                native_coin(),
+                50,
+            ),
+        ];
+        assert_eq!(
+            vec![(native_coin(), 50)],
+            imbalances(operations.iter()).unwrap()
+        );
+    }
+
+    fn entry_function(module: &str, function: &str) -> TransactionPayload {
+        TransactionPayload::EntryFunction(EntryFunction::new(
+            ModuleId::new(AccountAddress::ONE, Identifier::new(module).unwrap()),
+            Identifier::new(function).unwrap(),
+            vec![],
+            vec![],
+        ))
+    }
+
+    #[test]
+    fn mints_are_recognized() {
+        assert!(is_mint(&entry_function("aptos_coin", "mint")));
+        assert!(!is_mint(&entry_function("coin", "transfer")));
+        assert!(!is_mint(&entry_function(
+            "aptos_coin",
+            "claim_mint_capability"
+        )));
+    }
+
+    #[test]
+    fn minted_deposits_balance() {
+        let success = Some(OperationStatusType::Success);
+        let transaction = |version: u64, operations: Vec<Operation>| Transaction {
+            transaction_identifier: TransactionIdentifier {
+                hash: format!("{:#x}", version),
+            },
+            operations,
+            metadata: TransactionMetadata {
+                transaction_type: TransactionType::User,
+                version: version.into(),
+                failed: false,
+                vm_status: "Executed successfully".to_string(),
+            },
+        };
+        let block = Block {
+            block_identifier: BlockIdentifier {
+                index: 1,
+                hash: "0x1".to_string(),
+            },
+            parent_block_identifier: BlockIdentifier {
+                index: 0,
+                hash: "0x0".to_string(),
+            },
+            timestamp: 0,
+            transactions: vec![
+                transaction(10, vec![Operation::deposit(
+                    0,
+                    success,
+                    account(2),
+                    native_coin(),
+                    100,
+                )]),
+                transaction(11, vec![
+                    Operation::withdraw(0, success, account(1), native_coin(), 100),
+                    Operation::deposit(1, success, account(2), native_coin(), 100),
+                ]),
+            ],
+        };
+
+        let mint_versions: HashSet<u64> = [10].into_iter().collect();
+        check_block_balances(&block, &mint_versions, BalanceCheckMode::Error).unwrap();
+        // Only the minting transaction's deposits are left out
+        check_block_balances(&block, &HashSet::new(), BalanceCheckMode::Error).unwrap_err();
+        let mint_versions: HashSet<u64> = [11].into_iter().collect();
+        check_block_balances(&block, &mint_versions, BalanceCheckMode::Error).unwrap_err();
+    }
+}
@@ -2,17 +2,33 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
+    balance_check::{check_block_balances, mint_versions},
     common::{
         check_network, get_block_index_from_request, get_timestamp, handle_request, with_context,
-        BlockHash, Y2K_MS,
+        with_upstream_timeout, BlockHash, Y2K_MS,
     },
-    error::ApiResult,
-    types::{Block, BlockIdentifier, BlockRequest, BlockResponse, Transaction},
+    counters::{BLOCK_REORGS_DETECTED, BLOCK_TIMESTAMP_CACHE_LOOKUPS, SLOW_BLOCK_FETCHES},
+    error::{ApiError, ApiResult},
+    timeouts::EndpointClass,
+    types::{
+        Block, BlockEventsRequest, BlockEventsResponse, BlockIdentifier, BlockRequest,
+        BlockResponse, RawEvent, Transaction,
+    },
+    upstream::UpstreamClients,
     RosettaContext,
 };
-use aptos_logger::{debug, trace};
+use aptos_crypto::HashValue;
+use aptos_infallible::RwLock;
+use aptos_logger::{debug, trace, warn};
 use aptos_types::chain_id::ChainId;
-use std::sync::Arc;
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 use warp::Filter;
 
 pub fn block_route(
@@ -22,7 +38,23 @@ pub fn block_route(
         .and(warp::post())
         .and(warp::body::json())
         .and(with_context(server_context))
-        .and_then(handle_request(block))
+        .and_then(handle_request(with_upstream_timeout(
+            EndpointClass::Block,
+            block,
+        )))
+}
+
+pub fn events_route(
+    server_context: RosettaContext,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("block" / "events")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_context(server_context))
+        .and_then(handle_request(with_upstream_timeout(
+            EndpointClass::Block,
+            block_events,
+        )))
 }
 
 /// Retrieves a block (in this case a single transaction) given it's identifier.
@@ -40,6 +72,7 @@ async fn block(request: BlockRequest, server_context: RosettaContext) -> ApiResu
     );
 
     check_network(request.network_identifier, &server_context)?;
+    server_context.check_online()?;
 
     // Retrieve by block or by hash, both or neither is not allowed
     let block_index =
@@ -57,6 +90,11 @@ async fn block(request: BlockRequest, server_context: RosettaContext) -> ApiResu
         .as_ref()
         .and_then(|inner| inner.keep_empty_transactions)
         .unwrap_or_default();
+    // Mints are only known from the transaction payloads, which aren't kept in the block
+    let mint_versions = match (&server_context.balance_check, &block.transactions) {
+        (Some(_), Some(txns)) => mint_versions(txns),
+        _ => Default::default(),
+    };
     let block = build_block(
         &server_context,
         parent_transaction,
@@ -66,9 +104,59 @@ async fn block(request: BlockRequest, server_context: RosettaContext) -> ApiResu
     )
     .await?;
 
+    if let Some(mode) = server_context.balance_check {
+        check_block_balances(&block, &mint_versions, mode)?;
+    }
+
     Ok(BlockResponse { block })
 }
 
+/// Retrieves the raw events of every transaction in a block given it's identifier.
+///
+/// This is an Aptos specific extension, events are returned as is, without being
+/// converted to operations.
+async fn block_events(
+    request: BlockEventsRequest,
+    server_context: RosettaContext,
+) -> ApiResult<BlockEventsResponse> {
+    debug!("/block/events");
+    trace!(
+        request = ?request,
+        server_context = ?server_context,
+        "/block/events",
+    );
+
+    check_network(request.network_identifier, &server_context)?;
+    server_context.check_online()?;
+
+    let block_index =
+        get_block_index_from_request(&server_context, request.block_identifier).await?;
+    let block = server_context
+        .block_cache()?
+        .get_block_by_height(block_index, true)
+        .await?;
+    let block_identifier = BlockIdentifier::from_block(&block, server_context.chain_id);
+
+    let mut events = Vec::new();
+    if let Some(txns) = block.transactions {
+        for txn in txns {
+            events.extend(
+                txn.events
+                    .iter()
+                    .map(|event| RawEvent::from_event(txn.version, event)),
+            );
+        }
+    }
+
+    // Ensure the events are in version order, keeping the order within a transaction
+    events.sort_by(|first, second| first.version.0.cmp(&second.version.0));
+
+    Ok(BlockEventsResponse {
+        block_identifier,
+        events,
+    })
+}
+
 /// Build up the transaction, which should contain the `operations` as the change set
 async fn build_block(
     server_context: &RosettaContext,
@@ -83,10 +171,24 @@ async fn build_block(
 
     // Convert the transactions and build the block
     let mut transactions: Vec<Transaction> = Vec::new();
+    let mut num_operations: usize = 0;
+
     // TODO: Parallelize these and then sort at end
     if let Some(txns) = block.transactions {
         for txn in txns {
             let transaction = Transaction::from_transaction(server_context, txn).await?;
+
+            // Bail out before converting the rest of an oversized block
+            num_operations += transaction.operations.len();
+            if let Some(max_operations) = server_context.max_operations_per_block {
+                if num_operations > max_operations {
+                    return Err(ApiError::BlockTooLarge(Some(format!(
+                        "Block {} has more than {} operations",
+                        block.block_height, max_operations
+                    ))));
+                }
+            }
+
             if keep_empty_transactions || !transaction.operations.is_empty() {
                 transactions.push(transaction)
             }
@@ -155,19 +257,251 @@ impl BlockInfo {
     }
 }
 
+/// How far behind the latest block seen an upstream can be without being treated as rolled
+/// back, as failover replicas may lag slightly behind
+pub const MAX_UPSTREAM_LAG_BLOCKS: u64 = 100;
+
+/// The most recent block seen from upstream, to check later blocks against
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct LatestBlock {
+    height: u64,
+    hash: HashValue,
+    last_version: u64,
+}
+
 /// A cache of [`BlockInfo`] to allow us to keep track of the block boundaries
+///
+/// Reorgs shouldn't happen, but if the upstream node is swapped or rolled back, the cached
+/// blocks may be from a superseded chain.  Each fetched block is checked against the latest
+/// block seen, and the cache is flushed if they're inconsistent.
+///
+/// Fetches are cancel safe.  Upstream requests are awaited in place rather than spawned, so
+/// dropping a fetch (e.g. when the client disconnects) aborts its in-flight upstream request,
+/// and the cache is only updated once a fetch has completed successfully.
 #[derive(Debug)]
 pub struct BlockRetriever {
     page_size: u16,
-    rest_client: Arc<aptos_rest_client::Client>,
+    rest_clients: Arc<UpstreamClients>,
+    /// Block fetches from upstream slower than this are logged, and counted as slow
+    slow_fetch_threshold: Duration,
+    /// Block height to millisecond timestamp, filled in lazily by timestamp lookups
+    block_timestamps: RwLock<BTreeMap<u64, u64>>,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    latest_block: RwLock<Option<LatestBlock>>,
+}
+
+/// Hit and miss counts for a cache
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// Fraction of lookups that were hits, if there have been any lookups
+    pub fn hit_ratio(&self) -> Option<f64> {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            None
+        } else {
+            Some(self.hits as f64 / lookups as f64)
+        }
+    }
 }
 
 impl BlockRetriever {
-    pub fn new(page_size: u16, rest_client: Arc<aptos_rest_client::Client>) -> Self {
+    pub fn new(
+        page_size: u16,
+        rest_clients: Arc<UpstreamClients>,
+        slow_fetch_threshold: Duration,
+    ) -> Self {
         BlockRetriever {
             page_size,
-            rest_client,
+            rest_clients,
+            slow_fetch_threshold,
+            block_timestamps: RwLock::new(BTreeMap::new()),
+            cache_hits: AtomicU64::new(0),
+            cache_misses: AtomicU64::new(0),
+            latest_block: RwLock::new(None),
+        }
+    }
+
+    /// Hit and miss counts of the block timestamp cache
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.cache_hits.load(Ordering::Relaxed),
+            misses: self.cache_misses.load(Ordering::Relaxed),
+        }
+    }
+
+    fn record_lookup(&self, hit: bool) {
+        let (count, result) = if hit {
+            (&self.cache_hits, "hit")
+        } else {
+            (&self.cache_misses, "miss")
+        };
+        count.fetch_add(1, Ordering::Relaxed);
+        BLOCK_TIMESTAMP_CACHE_LOOKUPS
+            .with_label_values(&[result])
+            .inc();
+    }
+
+    /// Logs and counts a block fetch from upstream, if it was slower than the threshold
+    ///
+    /// Together with the cache hit and miss counts, this shows whether slow block queries are
+    /// from a cold cache or a slow upstream.
+    fn record_fetch(&self, height: u64, duration: Duration) {
+        if duration > self.slow_fetch_threshold {
+            warn!(
+                "Slow upstream fetch of block {} took {:?}, over the threshold of {:?}",
+                height, duration, self.slow_fetch_threshold
+            );
+            SLOW_BLOCK_FETCHES.inc();
+        }
+    }
+
+    /// Checks a block fetched from upstream against the latest block seen, flushing the cache if
+    /// they're inconsistent.  Returns true if they were.
+    ///
+    /// A block at the same height must have the same hash, and the next block must start right
+    /// after the latest block's last version.
+    fn check_block(
+        &self,
+        height: u64,
+        hash: HashValue,
+        first_version: u64,
+        last_version: u64,
+    ) -> bool {
+        let block = LatestBlock {
+            height,
+            hash,
+            last_version,
+        };
+        let mut latest_block = self.latest_block.write();
+        let consistent = match *latest_block {
+            Some(latest) if latest.height == height => latest.hash == hash,
+            Some(latest) if latest.height + 1 == height => latest.last_version + 1 == first_version,
+            _ => true,
+        };
+
+        if !consistent {
+            warn!(
+                "Block {} ({}) is inconsistent with the latest block {:?}, flushing the block cache",
+                height, hash, *latest_block
+            );
+            BLOCK_REORGS_DETECTED.inc();
+            self.block_timestamps.write().clear();
+            *latest_block = Some(block);
+        } else if latest_block.map_or(true, |latest| height > latest.height) {
+            *latest_block = Some(block);
+        }
+        !consistent
+    }
+
+    /// Checks the upstream's latest block height against the latest block seen, flushing the
+    /// cache if upstream was rolled back.  Returns true if it was.
+    ///
+    /// Failing over to a replica that's slightly behind also lowers the height, so only falling
+    /// more than [`MAX_UPSTREAM_LAG_BLOCKS`] behind is counted as a rollback.  A smaller rollback
+    /// is still caught by [`BlockRetriever::check_block`] once a replaced block is fetched.
+    fn check_ledger_height(&self, block_height: u64) -> bool {
+        let mut latest_block = self.latest_block.write();
+        match *latest_block {
+            Some(latest)
+                if latest.height > block_height.saturating_add(MAX_UPSTREAM_LAG_BLOCKS) =>
+            {
+                warn!(
+                    "Upstream latest block {} is before the latest block seen {}, flushing the block cache",
+                    block_height, latest.height
+                );
+                BLOCK_REORGS_DETECTED.inc();
+                self.block_timestamps.write().clear();
+                *latest_block = None;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Retrieves the first block with a timestamp at or after `timestamp_ms`
+    ///
+    /// Block timestamps never decrease, so this binary searches over the available blocks.
+    pub async fn get_block_by_timestamp(
+        &self,
+        timestamp_ms: u64,
+        chain_id: ChainId,
+    ) -> ApiResult<BlockIdentifier> {
+        if timestamp_ms < Y2K_MS {
+            return Err(ApiError::InvalidInput(Some(format!(
+                "Timestamp {} is before the genesis timestamp {}",
+                timestamp_ms, Y2K_MS
+            ))));
+        }
+
+        let response = self
+            .rest_clients
+            .available()?
+            .get_ledger_information()
+            .await?;
+        let state = response.state();
+        self.check_ledger_height(state.block_height);
+        let latest_timestamp = self
+            .get_block_timestamp(state.block_height, chain_id)
+            .await?;
+        if timestamp_ms > latest_timestamp {
+            return Err(ApiError::BlockNotFound(Some(format!(
+                "Timestamp {} is after the latest block {} at {}",
+                timestamp_ms, state.block_height, latest_timestamp
+            ))));
+        }
+
+        // The genesis block is always available, but blocks before the oldest one are pruned
+        let mut low = state.oldest_block_height;
+        if low > 0 {
+            if self.get_block_timestamp(low, chain_id).await? >= timestamp_ms {
+                return Err(ApiError::BlockPruned(Some(format!(
+                    "First block at or after timestamp {} is at or before the oldest block {}",
+                    timestamp_ms, low
+                ))));
+            }
+        } else if timestamp_ms <= Y2K_MS {
+            return self
+                .get_block_info_by_height(0, chain_id)
+                .await
+                .map(|info| info.block_id);
+        }
+
+        // Invariant: the block at `low` is before the timestamp, and the block at `high` isn't
+        let mut high = state.block_height;
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if self.get_block_timestamp(mid, chain_id).await? < timestamp_ms {
+                low = mid;
+            } else {
+                high = mid;
+            }
         }
+
+        self.get_block_info_by_height(high, chain_id)
+            .await
+            .map(|info| info.block_id)
+    }
+
+    /// Retrieves a block's timestamp in milliseconds, caching it for later lookups
+    async fn get_block_timestamp(&self, height: u64, chain_id: ChainId) -> ApiResult<u64> {
+        if let Some(timestamp) = self.block_timestamps.read().get(&height) {
+            self.record_lookup(true);
+            return Ok(*timestamp);
+        }
+        self.record_lookup(false);
+
+        let timestamp = self
+            .get_block_info_by_height(height, chain_id)
+            .await?
+            .timestamp;
+        self.block_timestamps.write().insert(height, timestamp);
+        Ok(timestamp)
     }
 
     pub async fn get_block_info_by_height(
@@ -191,23 +525,420 @@ impl BlockRetriever {
         Ok(BlockInfo::from_block(&block, chain_id))
     }
 
+    /// Retrieves the block containing the transaction at `version`
+    pub async fn get_block_info_by_version(
+        &self,
+        version: u64,
+        chain_id: ChainId,
+    ) -> ApiResult<BlockInfo> {
+        let block = self
+            .rest_clients
+            .available()?
+            .get_block_by_version_bcs(version, false)
+            .await?
+            .into_inner();
+        self.check_block(
+            block.block_height,
+            block.block_hash,
+            block.first_version,
+            block.last_version,
+        );
+        Ok(BlockInfo::from_block(&block, chain_id))
+    }
+
     pub async fn get_block_by_height(
         &self,
         height: u64,
         with_transactions: bool,
     ) -> ApiResult<aptos_rest_client::aptos_api_types::BcsBlock> {
-        if with_transactions {
-            Ok(self
-                .rest_client
+        let rest_client = self.rest_clients.available()?;
+        let start = Instant::now();
+        let response = if with_transactions {
+            rest_client
                 .get_full_block_by_height_bcs(height, self.page_size)
-                .await?
-                .into_inner())
+                .await
         } else {
-            Ok(self
-                .rest_client
-                .get_block_by_height_bcs(height, false)
-                .await?
-                .into_inner())
+            rest_client.get_block_by_height_bcs(height, false).await
+        };
+        // Failed fetches are still timed, as a slow failure is upstream slowness too
+        self.record_fetch(height, start.elapsed());
+        let block = response?.into_inner();
+        self.check_block(
+            block.block_height,
+            block.block_hash,
+            block.first_version,
+            block.last_version,
+        );
+        Ok(block)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{build_block, BlockRetriever, CacheStats, MAX_UPSTREAM_LAG_BLOCKS};
+    use crate::{
+        counters::SLOW_BLOCK_FETCHES,
+        error::ApiError,
+        test_utils::{self, MockLedger},
+        types::{AccountIdentifier, BlockIdentifier, OperationType},
+        upstream::UpstreamClients,
+        RosettaConfig, RosettaContext, RosettaMode, DEFAULT_SLOW_BLOCK_FETCH_THRESHOLD,
+    };
+    use aptos_cached_packages::aptos_stdlib;
+    use aptos_crypto::{ed25519::Ed25519PrivateKey, HashValue, PrivateKey};
+    use aptos_rest_client::aptos_api_types::{BcsBlock, TransactionOnChainData};
+    use aptos_types::{
+        account_address::AccountAddress,
+        chain_id::ChainId,
+        contract_event::ContractEvent,
+        event::EventKey,
+        transaction::{
+            authenticator::AuthenticationKey, ExecutionStatus, RawTransaction, Transaction,
+            TransactionInfo,
+        },
+        write_set::WriteSet,
+    };
+    use move_core_types::language_storage::TypeTag;
+    use std::{
+        convert::TryFrom,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+    use tokio::sync::Notify;
+    use warp::Filter;
+
+    /// Marks the upstream request as aborted when its handler is dropped
+    struct AbortGuard(Arc<AtomicBool>);
+
+    impl Drop for AbortGuard {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::Release);
+        }
+    }
+
+    #[tokio::test]
+    async fn dropped_fetch_aborts_upstream_request() {
+        // An upstream which never responds, so the fetch is always in flight
+        let started = Arc::new(Notify::new());
+        let aborted = Arc::new(AtomicBool::new(false));
+        let route = warp::any().then({
+            let started = started.clone();
+            let aborted = aborted.clone();
+            move || {
+                let started = started.clone();
+                let aborted = aborted.clone();
+                async move {
+                    let _guard = AbortGuard(aborted);
+                    started.notify_one();
+                    futures::future::pending::<String>().await
+                }
+            }
+        });
+        let client = aptos_rest_client::Client::new(test_utils::serve(route));
+        let retriever = BlockRetriever::new(
+            100,
+            Arc::new(UpstreamClients::new(ChainId::test(), vec![client], None)),
+            DEFAULT_SLOW_BLOCK_FETCH_THRESHOLD,
+        );
+
+        // Drop the fetch once the upstream has received the request
+        tokio::select! {
+            _ = retriever.get_block_timestamp(5, ChainId::test()) => {
+                panic!("Fetch should never complete")
+            },
+            _ = started.notified() => {},
+        }
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while !aborted.load(Ordering::Acquire) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Upstream request should be aborted when the fetch is dropped");
+
+        // Nothing should be cached from the canceled fetch
+        assert!(retriever.block_timestamps.read().is_empty());
+    }
+
+    /// An upstream which serves the same block at every height, after the delay
+    fn delayed_upstream(delay: Duration) -> aptos_rest_client::Client {
+        let route = warp::any().then(move || async move {
+            tokio::time::sleep(delay).await;
+            let block = BcsBlock {
+                block_height: 5,
+                block_hash: HashValue::new([5; HashValue::LENGTH]),
+                block_timestamp: 1_000_000_000,
+                first_version: 50,
+                last_version: 59,
+                transactions: None,
+            };
+            bcs::to_bytes(&block).unwrap()
+        });
+        aptos_rest_client::Client::new(test_utils::mock_upstream(MockLedger::default(), route))
+    }
+
+    #[tokio::test]
+    async fn slow_block_fetches_are_counted() {
+        let delay = Duration::from_millis(200);
+        let fetch = |slow_fetch_threshold: Duration| async move {
+            let retriever = BlockRetriever::new(
+                100,
+                Arc::new(UpstreamClients::new(
+                    ChainId::test(),
+                    vec![delayed_upstream(delay)],
+                    None,
+                )),
+                slow_fetch_threshold,
+            );
+            let before = SLOW_BLOCK_FETCHES.get();
+            let block = retriever.get_block_by_height(5, false).await.unwrap();
+            assert_eq!(5, block.block_height);
+            SLOW_BLOCK_FETCHES.get() - before
+        };
+
+        // Under the threshold, the fetch isn't slow
+        assert_eq!(0, fetch(Duration::from_secs(60)).await);
+        // Over the threshold, it's counted
+        assert_eq!(1, fetch(Duration::from_millis(10)).await);
+    }
+
+    #[tokio::test]
+    async fn cache_stats_count_lookups() {
+        // Nothing listens on the upstream, so any miss fails
+        let client = aptos_rest_client::Client::new(url::Url::parse("http://127.0.0.1:1").unwrap());
+        let retriever = BlockRetriever::new(
+            100,
+            Arc::new(UpstreamClients::new(ChainId::test(), vec![client], None)),
+            DEFAULT_SLOW_BLOCK_FETCH_THRESHOLD,
+        );
+        assert_eq!(None, retriever.stats().hit_ratio());
+
+        retriever.block_timestamps.write().insert(5, 1000);
+        assert_eq!(
+            1000,
+            retriever
+                .get_block_timestamp(5, ChainId::test())
+                .await
+                .unwrap()
+        );
+        retriever
+            .get_block_timestamp(6, ChainId::test())
+            .await
+            .unwrap_err();
+
+        let stats = retriever.stats();
+        assert_eq!(CacheStats { hits: 1, misses: 1 }, stats);
+        assert_eq!(Some(0.5), stats.hit_ratio());
+    }
+
+    #[test]
+    fn inconsistent_blocks_flush_cache() {
+        let client = aptos_rest_client::Client::new(url::Url::parse("http://127.0.0.1:1").unwrap());
+        let retriever = BlockRetriever::new(
+            100,
+            Arc::new(UpstreamClients::new(ChainId::test(), vec![client], None)),
+            DEFAULT_SLOW_BLOCK_FETCH_THRESHOLD,
+        );
+        let hash = |seed: u8| HashValue::new([seed; HashValue::LENGTH]);
+
+        // Consistent blocks keep the cache
+        retriever.block_timestamps.write().insert(5, 1000);
+        assert!(!retriever.check_block(10, hash(10), 100, 109));
+        assert!(!retriever.check_block(10, hash(10), 100, 109));
+        assert!(!retriever.check_block(11, hash(11), 110, 119));
+        // Older blocks and gaps can't be checked
+        assert!(!retriever.check_block(3, hash(3), 30, 39));
+        assert!(!retriever.check_block(20, hash(20), 200, 209));
+        assert!(!retriever.check_ledger_height(20));
+        assert_eq!(1, retriever.block_timestamps.read().len());
+
+        // A different block at the same height
+        assert!(retriever.check_block(20, hash(21), 200, 209));
+        assert!(retriever.block_timestamps.read().is_empty());
+
+        // A next block that doesn't follow on from the latest block
+        retriever.block_timestamps.write().insert(5, 1000);
+        assert!(retriever.check_block(21, hash(22), 205, 209));
+        assert!(retriever.block_timestamps.read().is_empty());
+
+        // A lagging upstream, e.g. after failing over to a replica, isn't a rollback
+        retriever.block_timestamps.write().insert(5, 1000);
+        assert!(!retriever.check_block(200, hash(200), 2000, 2009));
+        assert!(!retriever.check_ledger_height(200 - MAX_UPSTREAM_LAG_BLOCKS));
+        assert_eq!(1, retriever.block_timestamps.read().len());
+
+        // Upstream rolled back
+        assert!(retriever.check_ledger_height(199 - MAX_UPSTREAM_LAG_BLOCKS));
+        assert!(retriever.block_timestamps.read().is_empty());
+        assert!(!retriever.check_block(16, hash(16), 160, 169));
+    }
+
+    /// A block of transfers with no changes
+    ///
+    /// Successful transfers are only a gas fee operation, and failed ones also have the failed
+    /// withdraw and deposit
+    fn block_of_transfers(count: u64, status: ExecutionStatus) -> BcsBlock {
+        block_of_transfers_with_events(count, status, 0)
+    }
+
+    /// A block of transfers, each with events that don't convert into operations
+    fn block_of_transfers_with_events(
+        count: u64,
+        status: ExecutionStatus,
+        events_per_transfer: u64,
+    ) -> BcsBlock {
+        let private_key = Ed25519PrivateKey::try_from([1u8; 32].as_slice()).unwrap();
+        let sender = AuthenticationKey::ed25519(&private_key.public_key()).derived_address();
+        let transactions = (0..count)
+            .map(|sequence_number| {
+                let txn = RawTransaction::new(
+                    sender,
+                    sequence_number,
+                    aptos_stdlib::aptos_account_transfer(AccountAddress::ONE, 100),
+                    1000,
+                    100,
+                    u64::MAX,
+                    ChainId::test(),
+                )
+                .sign(&private_key, private_key.public_key())
+                .unwrap()
+                .into_inner();
+                TransactionOnChainData {
+                    version: 50 + sequence_number,
+                    transaction: Transaction::UserTransaction(txn.clone()),
+                    info: TransactionInfo::new(
+                        txn.committed_hash(),
+                        HashValue::zero(),
+                        HashValue::zero(),
+                        None,
+                        10,
+                        status.clone(),
+                    ),
+                    events: (0..events_per_transfer)
+                        .map(|sequence_number| {
+                            ContractEvent::new(
+                                EventKey::new(0, sender),
+                                sequence_number,
+                                TypeTag::Bool,
+                                bcs::to_bytes(&true).unwrap(),
+                            )
+                        })
+                        .collect(),
+                    accumulator_root_hash: HashValue::zero(),
+                    changes: WriteSet::default(),
+                }
+            })
+            .collect();
+        BcsBlock {
+            block_height: 5,
+            block_hash: HashValue::new([5; HashValue::LENGTH]),
+            block_timestamp: 1_000_000_000,
+            first_version: 50,
+            last_version: 50 + count - 1,
+            transactions: Some(transactions),
+        }
+    }
+
+    #[tokio::test]
+    async fn oversized_blocks_are_rejected() {
+        let build = |max_operations_per_block: Option<usize>, block: BcsBlock| async move {
+            let server_context = RosettaContext::new(
+                RosettaMode::Offline,
+                None,
+                ChainId::test(),
+                None,
+                vec![],
+                &RosettaConfig {
+                    max_operations_per_block,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+            let parent = BlockIdentifier {
+                index: 4,
+                hash: HashValue::zero().to_hex(),
+            };
+            build_block(&server_context, parent, block, ChainId::test(), false).await
+        };
+        let transfers = || block_of_transfers(3, ExecutionStatus::Success);
+
+        // Without a limit, or at the limit, the whole block is built
+        assert_eq!(
+            3,
+            build(None, transfers()).await.unwrap().transactions.len()
+        );
+        assert_eq!(
+            3,
+            build(Some(3), transfers())
+                .await
+                .unwrap()
+                .transactions
+                .len()
+        );
+
+        // Over it, the block is rejected rather than truncated
+        let err = build(Some(2), transfers()).await.unwrap_err();
+        assert!(matches!(err, ApiError::BlockTooLarge(_)));
+
+        // Only events converted into operations count, so more events than the limit is fine
+        let with_events = block_of_transfers_with_events(3, ExecutionStatus::Success, 2);
+        assert_eq!(
+            3,
+            build(Some(3), with_events)
+                .await
+                .unwrap()
+                .transactions
+                .len()
+        );
+    }
+
+    #[tokio::test]
+    async fn operations_are_attributed_to_gas_payer() {
+        let server_context = RosettaContext::new(
+            RosettaMode::Offline,
+            None,
+            ChainId::test(),
+            None,
+            vec![],
+            &RosettaConfig::default(),
+        )
+        .await
+        .unwrap();
+        let parent = BlockIdentifier {
+            index: 4,
+            hash: HashValue::zero().to_hex(),
+        };
+        let block = build_block(
+            &server_context,
+            parent,
+            block_of_transfers(1, ExecutionStatus::OutOfGas),
+            ChainId::test(),
+            false,
+        )
+        .await
+        .unwrap();
+
+        // The failed withdraw and deposit, and the gas fee, are all paid for by the sender
+        let operations = &block.transactions[0].operations;
+        assert_eq!(3, operations.len());
+        let sender = operations
+            .iter()
+            .find(|operation| operation.operation_type == OperationType::Fee.to_string())
+            .unwrap()
+            .account
+            .clone()
+            .unwrap();
+        assert_ne!(AccountIdentifier::base_account(AccountAddress::ONE), sender);
+        for operation in operations {
+            assert_eq!(
+                Some(&sender),
+                operation.metadata.as_ref().unwrap().gas_payer.as_ref()
+            );
         }
     }
 }
@@ -3,17 +3,20 @@
 
 use crate::{
     common::native_coin,
+    error::ApiError,
     types::{
-        AccountBalanceRequest, AccountBalanceResponse, AccountIdentifier, BlockRequest,
-        BlockResponse, ConstructionCombineRequest, ConstructionCombineResponse,
-        ConstructionDeriveRequest, ConstructionDeriveResponse, ConstructionHashRequest,
-        ConstructionMetadata, ConstructionMetadataRequest, ConstructionMetadataResponse,
-        ConstructionParseRequest, ConstructionParseResponse, ConstructionPayloadsRequest,
-        ConstructionPayloadsResponse, ConstructionPreprocessRequest,
-        ConstructionPreprocessResponse, ConstructionSubmitRequest, ConstructionSubmitResponse,
-        Error, MetadataRequest, NetworkIdentifier, NetworkListResponse, NetworkOptionsResponse,
-        NetworkRequest, NetworkStatusResponse, Operation, PreprocessMetadata, PublicKey, Signature,
-        SignatureType, TransactionIdentifier, TransactionIdentifierResponse,
+        AccountBalanceRequest, AccountBalanceResponse, AccountIdentifier, BlockEventsRequest,
+        BlockEventsResponse, BlockIdentifier, BlockRequest, BlockResponse,
+        ConstructionCombineRequest, ConstructionCombineResponse, ConstructionDeriveRequest,
+        ConstructionDeriveResponse, ConstructionHashRequest, ConstructionMetadata,
+        ConstructionMetadataRequest, ConstructionMetadataResponse, ConstructionParseRequest,
+        ConstructionParseResponse, ConstructionPayloadsRequest, ConstructionPayloadsResponse,
+        ConstructionPreprocessRequest, ConstructionPreprocessResponse, ConstructionSubmitRequest,
+        ConstructionSubmitResponse, Error, MetadataRequest, NetworkGasPricesResponse,
+        NetworkIdentifier, NetworkListResponse, NetworkOptionsResponse, NetworkRequest,
+        NetworkStatusResponse, Operation, PreprocessMetadata, PublicKey, SearchTransactionsRequest,
+        SearchTransactionsResponse, Signature, SignatureType, SubmitMetadata,
+        TransactionIdentifier, TransactionIdentifierResponse,
     },
 };
 use anyhow::anyhow;
@@ -21,12 +24,112 @@ use aptos_crypto::{
     ed25519::Ed25519PrivateKey, PrivateKey, SigningKey, ValidCryptoMaterialStringExt,
 };
 use aptos_rest_client::aptos_api_types::mime_types::JSON;
-use aptos_types::{account_address::AccountAddress, transaction::RawTransaction};
+use aptos_types::{
+    account_address::AccountAddress, chain_id::ChainId, transaction::RawTransaction,
+};
 use reqwest::{header::CONTENT_TYPE, Client as ReqwestClient};
 use serde::{de::DeserializeOwned, Serialize};
 use std::{collections::HashMap, convert::TryInto, fmt::Debug, str::FromStr};
 use url::Url;
 
+/// Outcome of submitting a transaction, for deciding whether it's safe to submit it again
+///
+/// A successful submit is [`SubmitOutcome::Committed`] only if the server waited for the
+/// transaction and it committed successfully.  If it committed but failed, it's
+/// [`SubmitOutcome::Rejected`], and otherwise it's [`SubmitOutcome::Accepted`].
+///
+/// Errors from the server are classified by their code:
+///
+/// * Errors about the transaction itself, e.g. [`ApiError::VmError`],
+///   [`ApiError::SequenceNumberTooOld`], [`ApiError::InvalidSignature`], or any other invalid
+///   input, are [`SubmitOutcome::Rejected`].
+/// * Every other error, e.g. [`ApiError::MempoolIsFull`] or [`ApiError::UpstreamTimeout`], says
+///   nothing about the transaction, so it's [`SubmitOutcome::Transient`], retryable if the
+///   server says the error is retriable.
+/// * Failing to get a response from the server at all is [`SubmitOutcome::Transient`] and
+///   retryable.
+///
+/// Resubmitting the same signed transaction can't execute it twice, as it has the same sequence
+/// number.  So after a transient failure, a later [`ApiError::SequenceNumberTooOld`] rejection
+/// may mean the earlier submit went through, and the transaction should be looked up by hash.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SubmitOutcome {
+    /// Accepted by the fullnode, but not yet committed, so it should be looked up by its hash
+    /// to wait for it to commit
+    Accepted(TransactionIdentifier),
+    /// Committed, and executed successfully
+    Committed(TransactionIdentifier),
+    /// Rejected or failed, and submitting it again will fail the same way
+    Rejected { reason: String },
+    /// Failed for a reason unrelated to the transaction
+    Transient { retryable: bool, reason: String },
+}
+
+impl SubmitOutcome {
+    /// Classifies a successful response from the server for a submit
+    pub fn from_response(response: ConstructionSubmitResponse) -> SubmitOutcome {
+        match response.metadata {
+            Some(SubmitMetadata {
+                confirmed: true,
+                failed: Some(true),
+                vm_status,
+                ..
+            }) => SubmitOutcome::Rejected {
+                reason: format!(
+                    "Transaction committed, but failed: {}",
+                    vm_status.unwrap_or_default()
+                ),
+            },
+            Some(SubmitMetadata {
+                confirmed: true, ..
+            }) => SubmitOutcome::Committed(response.transaction_identifier),
+            _ => SubmitOutcome::Accepted(response.transaction_identifier),
+        }
+    }
+
+    /// Classifies an error returned by the server for a submit
+    pub fn from_error(error: Error) -> SubmitOutcome {
+        let reason = match error.details {
+            Some(ref details) => format!("{}: {}", error.message, details.details),
+            None => error.message.clone(),
+        };
+        if Self::is_rejection(error.code) {
+            SubmitOutcome::Rejected { reason }
+        } else {
+            SubmitOutcome::Transient {
+                retryable: error.retriable,
+                reason,
+            }
+        }
+    }
+
+    /// Whether the error with the code is about the transaction itself
+    fn is_rejection(code: u32) -> bool {
+        use ApiError::*;
+        ApiError::all()
+            .into_iter()
+            .find(|err| err.code() == code)
+            .map_or(false, |err| {
+                matches!(
+                    err,
+                    NetworkIdentifierMismatch
+                        | ChainIdMismatch
+                        | DeserializationFailed(_)
+                        | InvalidSignatureType
+                        | MaxGasFeeTooLow(_)
+                        | UnsupportedSignatureCount(_)
+                        | TransactionParseError(_)
+                        | InvalidInput(_)
+                        | InvalidTransactionUpdate(_)
+                        | SequenceNumberTooOld(_)
+                        | VmError(_)
+                        | InvalidSignature(_)
+                        | UnsupportedRosettaVersion(_)
+                )
+            })
+    }
+}
+
 /// Client for testing & interacting with a Rosetta service
 #[derive(Debug, Clone)]
 pub struct RosettaClient {
@@ -53,6 +156,91 @@ impl RosettaClient {
         self.make_call("block", request).await
     }
 
+    pub async fn block_events(
+        &self,
+        request: &BlockEventsRequest,
+    ) -> anyhow::Result<BlockEventsResponse> {
+        self.make_call("block/events", request).await
+    }
+
+    /// Resolves the [`BlockIdentifier`] of the block containing the given ledger version
+    ///
+    /// Blocks are contiguous ranges of versions, so this binary searches the block heights
+    /// between the oldest (unpruned) block and the current block from `/network/status`.
+    pub async fn block_for_version(
+        &self,
+        network_identifier: &NetworkIdentifier,
+        version: u64,
+    ) -> anyhow::Result<BlockIdentifier> {
+        let chain_id = network_identifier.chain_id()?;
+
+        // Genesis is always its own block with only the genesis transaction
+        if version == 0 {
+            return Ok(self.block_version_range(chain_id, 0).await?.0);
+        }
+
+        let status = self
+            .network_status(&NetworkRequest {
+                network_identifier: network_identifier.clone(),
+            })
+            .await?;
+
+        // Versions past the latest block can't be resolved yet
+        let (latest_block, _, latest_version) = self
+            .block_version_range(chain_id, status.current_block_identifier.index)
+            .await?;
+        if version > latest_version {
+            return Err(anyhow!(
+                "Version {} is newer than the latest block {} which ends at version {}",
+                version,
+                latest_block.index,
+                latest_version
+            ));
+        }
+
+        // Search over [low, high) until the block containing the version is found
+        let mut low = status.oldest_block_identifier.index;
+        let mut high = status.current_block_identifier.index + 1;
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let (block_identifier, first_version, last_version) =
+                self.block_version_range(chain_id, mid).await?;
+            if version < first_version {
+                high = mid;
+            } else if version > last_version {
+                low = mid + 1;
+            } else {
+                return Ok(block_identifier);
+            }
+        }
+
+        Err(anyhow!(
+            "Version {} is not in any available block, it may have been pruned",
+            version
+        ))
+    }
+
+    /// Retrieves a block by index, along with the first and last versions in the block
+    async fn block_version_range(
+        &self,
+        chain_id: ChainId,
+        index: u64,
+    ) -> anyhow::Result<(BlockIdentifier, u64, u64)> {
+        // Empty transactions must be kept, otherwise the block's version range is incomplete
+        let block = self
+            .block(&BlockRequest::by_index(chain_id, index).with_empty_transactions())
+            .await?
+            .block;
+        let versions = block.transactions.iter().map(|txn| txn.metadata.version.0);
+
+        match (versions.clone().min(), versions.max()) {
+            (Some(first_version), Some(last_version)) => {
+                Ok((block.block_identifier, first_version, last_version))
+            },
+            _ => Err(anyhow!("Block {} has no transactions", index)),
+        }
+    }
+
     pub async fn combine(
         &self,
         request: &ConstructionCombineRequest,
@@ -109,6 +297,21 @@ impl RosettaClient {
         self.make_call("construction/submit", request).await
     }
 
+    /// Submits a transaction, classifying the outcome so callers know whether to retry
+    pub async fn submit_with_outcome(&self, request: &ConstructionSubmitRequest) -> SubmitOutcome {
+        let result: anyhow::Result<Result<ConstructionSubmitResponse, Error>> =
+            self.try_call("construction/submit", request).await;
+        match result {
+            Ok(Ok(response)) => SubmitOutcome::from_response(response),
+            Ok(Err(error)) => SubmitOutcome::from_error(error),
+            // The server couldn't be reached, or didn't respond as expected e.g. a proxy error
+            Err(err) => SubmitOutcome::Transient {
+                retryable: true,
+                reason: err.to_string(),
+            },
+        }
+    }
+
     pub async fn network_list(&self) -> anyhow::Result<NetworkListResponse> {
         self.make_call("network/list", &MetadataRequest {}).await
     }
@@ -127,11 +330,36 @@ impl RosettaClient {
         self.make_call("network/status", request).await
     }
 
+    pub async fn network_gas_prices(
+        &self,
+        request: &NetworkRequest,
+    ) -> anyhow::Result<NetworkGasPricesResponse> {
+        self.make_call("network/gas_prices", request).await
+    }
+
+    pub async fn search_transactions(
+        &self,
+        request: &SearchTransactionsRequest,
+    ) -> anyhow::Result<SearchTransactionsResponse> {
+        self.make_call("search/transactions", request).await
+    }
+
     async fn make_call<'a, I: Serialize + Debug, O: DeserializeOwned>(
         &'a self,
         path: &'static str,
         request: &'a I,
     ) -> anyhow::Result<O> {
+        self.try_call(path, request)
+            .await?
+            .map_err(|error| anyhow!("Failed API with: {:?}", error))
+    }
+
+    /// Makes a call, returning an error from the API separately from failing to make the call
+    async fn try_call<'a, I: Serialize + Debug, O: DeserializeOwned>(
+        &'a self,
+        path: &'static str,
+        request: &'a I,
+    ) -> anyhow::Result<Result<O, Error>> {
         let response = self
             .inner
             .post(self.address.join(path)?)
@@ -140,11 +368,10 @@ impl RosettaClient {
             .send()
             .await?;
         if !response.status().is_success() {
-            let error: Error = response.json().await?;
-            return Err(anyhow!("Failed API with: {:?}", error));
+            return Ok(Err(response.json().await?));
         }
 
-        Ok(response.json().await?)
+        Ok(Ok(response.json().await?))
     }
 
     pub async fn create_account(
@@ -479,6 +706,7 @@ impl RosettaClient {
             .derive(&ConstructionDeriveRequest {
                 network_identifier,
                 public_key,
+                auth_scheme: None,
             })
             .await?
             .account_identifier)
@@ -503,6 +731,7 @@ impl RosettaClient {
                 operations,
                 metadata: Some(PreprocessMetadata {
                     expiry_time_secs: Some(expiry_time_secs.into()),
+                    expiry_offset_secs: None,
                     sequence_number: sequence_number.map(|inner| inner.into()),
                     max_gas_amount: max_gas.map(|inner| inner.into()),
                     gas_price: gas_unit_price.map(|inner| inner.into()),
@@ -512,8 +741,10 @@ impl RosettaClient {
                         .public_key()
                         .try_into()
                         .unwrap()]),
+                    auth_scheme: None,
                     gas_price_multiplier: None,
                     gas_price_priority: None,
+                    gas_currency: None,
                 }),
             })
             .await?;
@@ -553,6 +784,7 @@ impl RosettaClient {
                 operations: operations.clone(),
                 metadata: Some(metadata),
                 public_keys: Some(public_keys),
+                auth_scheme: None,
             })
             .await?;
 
@@ -562,6 +794,7 @@ impl RosettaClient {
                 network_identifier,
                 signed: false,
                 transaction: payloads.unsigned_transaction.clone(),
+                strict: false,
             })
             .await?;
 
@@ -620,6 +853,8 @@ impl RosettaClient {
                 network_identifier: network_identifier.clone(),
                 unsigned_transaction: unsigned_response.unsigned_transaction,
                 signatures,
+                auth_scheme: None,
+                signature_encoding: None,
             })
             .await?;
 
@@ -629,6 +864,7 @@ impl RosettaClient {
                 network_identifier,
                 signed: true,
                 transaction: signed_response.signed_transaction.clone(),
+                strict: false,
             })
             .await?;
 
@@ -667,8 +903,170 @@ impl RosettaClient {
             .submit(&ConstructionSubmitRequest {
                 network_identifier,
                 signed_transaction,
+                wait_for_commit_secs: None,
             })
             .await?
             .transaction_identifier)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{RosettaClient, SubmitOutcome};
+    use crate::{
+        error::ApiError,
+        test_utils,
+        types::{
+            ConstructionSubmitRequest, ConstructionSubmitResponse, NetworkIdentifier,
+            SubmitMetadata, TransactionIdentifier,
+        },
+    };
+    use aptos_rest_client::aptos_api_types::U64;
+    use aptos_types::chain_id::ChainId;
+    use std::convert::Infallible;
+    use warp::{http::StatusCode, Filter, Rejection, Reply};
+
+    /// A Rosetta server which responds to every submit with `result`
+    fn mock_server(result: Result<ConstructionSubmitResponse, ApiError>) -> url::Url {
+        let route = warp::path!("construction" / "submit")
+            .map(move || match result.clone() {
+                Ok(response) => warp::reply::json(&response).into_response(),
+                Err(err) => warp::reply::with_status(
+                    warp::reply::json(&err.into_error()),
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                )
+                .into_response(),
+            })
+            .recover(|_: Rejection| async { Ok::<_, Infallible>(StatusCode::NOT_FOUND) });
+        test_utils::serve(route)
+    }
+
+    async fn submit(address: url::Url) -> SubmitOutcome {
+        RosettaClient::new(address)
+            .submit_with_outcome(&ConstructionSubmitRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                signed_transaction: "00".to_string(),
+                wait_for_commit_secs: None,
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn submit_outcomes() {
+        let transaction_identifier = TransactionIdentifier {
+            hash: "0x1234".to_string(),
+        };
+        let submitted = |metadata: Option<SubmitMetadata>| {
+            mock_server(Ok(ConstructionSubmitResponse {
+                transaction_identifier: transaction_identifier.clone(),
+                metadata,
+            }))
+        };
+        assert_eq!(
+            SubmitOutcome::Accepted(transaction_identifier.clone()),
+            submit(submitted(None)).await
+        );
+        // Waiting for the commit timed out
+        assert_eq!(
+            SubmitOutcome::Accepted(transaction_identifier.clone()),
+            submit(submitted(Some(SubmitMetadata {
+                confirmed: false,
+                ..Default::default()
+            })))
+            .await
+        );
+        assert_eq!(
+            SubmitOutcome::Committed(transaction_identifier.clone()),
+            submit(submitted(Some(SubmitMetadata {
+                confirmed: true,
+                version: Some(U64(7)),
+                failed: Some(false),
+                vm_status: Some("Success".to_string()),
+                gas_used: Some(U64(150)),
+            })))
+            .await
+        );
+        assert_eq!(
+            SubmitOutcome::Rejected {
+                reason: "Transaction committed, but failed: Out of gas".to_string()
+            },
+            submit(submitted(Some(SubmitMetadata {
+                confirmed: true,
+                version: Some(U64(7)),
+                failed: Some(true),
+                vm_status: Some("Out of gas".to_string()),
+                gas_used: Some(U64(1000)),
+            })))
+            .await
+        );
+
+        assert_eq!(
+            SubmitOutcome::Rejected {
+                reason: "Transaction submission failed due to VM error: Out of gas".to_string()
+            },
+            submit(mock_server(Err(ApiError::VmError(Some(
+                "Out of gas".to_string()
+            )))))
+            .await
+        );
+        assert!(matches!(
+            submit(mock_server(Err(ApiError::MempoolIsFull(None)))).await,
+            SubmitOutcome::Transient {
+                retryable: true,
+                ..
+            }
+        ));
+
+        // Nothing listens on the server, so the submit never reaches it
+        assert!(matches!(
+            submit(url::Url::parse("http://127.0.0.1:1").unwrap()).await,
+            SubmitOutcome::Transient {
+                retryable: true,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn errors_are_classified_by_code() {
+        let classify = |err: ApiError| SubmitOutcome::from_error(err.into_error());
+        for err in [
+            ApiError::SequenceNumberTooOld(None),
+            ApiError::InvalidSignature(None),
+            ApiError::DeserializationFailed(None),
+            ApiError::ChainIdMismatch,
+            ApiError::MaxGasFeeTooLow(None),
+        ] {
+            assert!(
+                matches!(classify(err.clone()), SubmitOutcome::Rejected { .. }),
+                "{:?}",
+                err
+            );
+        }
+        for err in [
+            ApiError::MempoolIsFull(None),
+            ApiError::Overloaded(None),
+            ApiError::UpstreamTimeout(None),
+            ApiError::RateLimited(None),
+        ] {
+            assert!(
+                matches!(classify(err.clone()), SubmitOutcome::Transient {
+                    retryable: true,
+                    ..
+                }),
+                "{:?}",
+                err
+            );
+        }
+        for err in [ApiError::NodeIsOffline, ApiError::InternalError(None)] {
+            assert!(
+                matches!(classify(err.clone()), SubmitOutcome::Transient {
+                    retryable: false,
+                    ..
+                }),
+                "{:?}",
+                err
+            );
+        }
+    }
+}
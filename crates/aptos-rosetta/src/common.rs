@@ -2,7 +2,9 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
+    counters::{UPSTREAM_REQUESTS, UPSTREAM_REQUEST_LATENCY},
     error::{ApiError, ApiResult},
+    timeouts::EndpointClass,
     types::{
         Currency, CurrencyMetadata, MetadataRequest, NetworkIdentifier, PartialBlockIdentifier,
         APTOS_COIN_MODULE, APTOS_COIN_RESOURCE,
@@ -19,7 +21,7 @@ use aptos_sdk::move_types::{
 use aptos_types::{account_address::AccountAddress, chain_id::ChainId};
 use futures::future::BoxFuture;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use std::{convert::Infallible, fmt::LowerHex, future::Future, str::FromStr};
+use std::{convert::Infallible, fmt::LowerHex, future::Future, str::FromStr, time::Instant};
 use warp::Filter;
 
 /// The year 2000 in milliseconds, as this is the lower limit for Rosetta API implementations
@@ -46,7 +48,7 @@ pub fn check_network(
 pub fn with_context(
     context: RosettaContext,
 ) -> impl Filter<Extract = (RosettaContext,), Error = Infallible> + Clone {
-    warp::any().map(move || context.clone())
+    warp::any().map(move || context.for_request())
 }
 
 pub fn with_empty_request() -> impl Filter<Extract = (MetadataRequest,), Error = Infallible> + Clone
@@ -55,6 +57,10 @@ pub fn with_empty_request() -> impl Filter<Extract = (MetadataRequest,), Error =
 }
 
 /// Handles a generic request to warp
+///
+/// Requests are counted and timed by the upstream fullnode serving them, which is the current
+/// one when the request starts.  The outcome of requests sent to an upstream is recorded, so
+/// an unreachable upstream is failed over from right away.
 pub fn handle_request<'a, F, R, Req, Resp>(
     handler: F,
 ) -> impl Fn(
@@ -70,28 +76,69 @@ where
 {
     move |request, options| {
         let fut = async move {
-            match handler(request, options).await {
+            let upstream = options.upstream_label().to_string();
+            let start = Instant::now();
+            let result = handler(request, options.clone()).await;
+            options.record_request(result.as_ref().err());
+            let (reply, status) = match result {
                 Ok(response) => {
                     debug!("Response: {:?}", serde_json::to_string_pretty(&response));
-                    Ok(warp::reply::with_status(
-                        warp::reply::json(&response),
-                        warp::http::StatusCode::OK,
-                    ))
+                    let status = warp::http::StatusCode::OK;
+                    (
+                        warp::reply::with_status(warp::reply::json(&response), status),
+                        status,
+                    )
                 },
                 Err(api_error) => {
                     debug!("Error: {:?}", api_error);
                     let status = api_error.status_code();
-                    Ok(warp::reply::with_status(
-                        warp::reply::json(&api_error.into_error()),
+                    (
+                        warp::reply::with_status(
+                            warp::reply::json(&api_error.into_error()),
+                            status,
+                        ),
                         status,
-                    ))
+                    )
                 },
-            }
+            };
+            UPSTREAM_REQUEST_LATENCY
+                .with_label_values(&[&upstream])
+                .observe(start.elapsed().as_secs_f64());
+            UPSTREAM_REQUESTS
+                .with_label_values(&[&upstream, status.as_str()])
+                .inc();
+            Ok(reply)
         };
         Box::pin(fut)
     }
 }
 
+/// Fails the request with [`ApiError::UpstreamTimeout`] if `handler` takes longer than the
+/// timeout for its class of endpoint
+pub fn with_upstream_timeout<F, R, Req, Resp>(
+    class: EndpointClass,
+    handler: F,
+) -> impl FnOnce(Req, RosettaContext) -> BoxFuture<'static, ApiResult<Resp>>
+       + Clone
+       + Copy
+       + Send
+       + 'static
+where
+    F: FnOnce(Req, RosettaContext) -> R + Clone + Copy + Send + 'static,
+    R: Future<Output = ApiResult<Resp>> + Send + 'static,
+    Req: Send + 'static,
+    Resp: Send + 'static,
+{
+    move |request, server_context: RosettaContext| {
+        let upstream_timeouts = server_context.upstream_timeouts.clone();
+        Box::pin(async move {
+            upstream_timeouts
+                .run(class, handler(request, server_context))
+                .await
+        })
+    }
+}
+
 pub async fn get_account(
     rest_client: &aptos_rest_client::Client,
     address: AccountAddress,
@@ -171,7 +218,7 @@ pub async fn get_block_index_from_request(
     server_context: &RosettaContext,
     partial_block_identifier: Option<PartialBlockIdentifier>,
 ) -> ApiResult<u64> {
-    Ok(match partial_block_identifier {
+    let block_index = match partial_block_identifier {
         Some(PartialBlockIdentifier {
             index: Some(block_index),
             hash: Some(_),
@@ -194,9 +241,44 @@ pub async fn get_block_index_from_request(
                 .await?;
             let state = response.state();
 
-            state.block_height
+            return Ok(state.block_height);
         },
-    })
+    };
+
+    if let Some(max_historical_depth) = server_context.max_historical_depth {
+        let response = server_context
+            .rest_client()?
+            .get_ledger_information()
+            .await?;
+        let state = response.state();
+        let oldest_block_height = oldest_queryable_block(
+            state.block_height,
+            state.oldest_block_height,
+            Some(max_historical_depth),
+        );
+        if block_index < oldest_block_height {
+            return Err(ApiError::BlockTooOld(Some(format!(
+                "Block {} is more than {} blocks old, the oldest queryable block is {}",
+                block_index, max_historical_depth, oldest_block_height
+            ))));
+        }
+    }
+    Ok(block_index)
+}
+
+/// The oldest block that can be queried, limited to `max_historical_depth` blocks before the latest
+pub fn oldest_queryable_block(
+    latest_block_height: u64,
+    oldest_block_height: u64,
+    max_historical_depth: Option<u64>,
+) -> u64 {
+    match max_historical_depth {
+        Some(max_historical_depth) => std::cmp::max(
+            oldest_block_height,
+            latest_block_height.saturating_sub(max_historical_depth),
+        ),
+        None => oldest_block_height,
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -294,7 +376,7 @@ pub fn parse_currency(address: AccountAddress, module: &str, name: &str) -> ApiR
 
 #[cfg(test)]
 mod test {
-    use crate::common::BlockHash;
+    use crate::common::{oldest_queryable_block, BlockHash};
     use aptos_types::chain_id::{ChainId, NamedChain};
     use std::str::FromStr;
 
@@ -309,6 +391,16 @@ mod test {
             .expect_err("Mismatch chain id should not work");
     }
 
+    #[test]
+    pub fn historical_depth_boundary() {
+        // Exactly `max_historical_depth` blocks back is still queryable
+        assert_eq!(90, oldest_queryable_block(100, 0, Some(10)));
+        // Pruned blocks aren't queryable either way
+        assert_eq!(95, oldest_queryable_block(100, 95, Some(10)));
+        assert_eq!(0, oldest_queryable_block(5, 0, Some(10)));
+        assert_eq!(95, oldest_queryable_block(100, 95, None));
+    }
+
     #[test]
     pub fn chain_id_string_check() {
         let block_hash = BlockHash::new(ChainId::test(), 0);
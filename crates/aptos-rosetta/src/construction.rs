@@ -27,18 +27,23 @@
 use crate::{
     common::{
         check_network, decode_bcs, decode_key, encode_bcs, get_account, handle_request,
-        native_coin, parse_currency, with_context,
+        native_coin, parse_currency, with_context, with_upstream_timeout,
     },
+    counters::CONSTRUCTION_OPERATIONS,
     error::{ApiError, ApiResult},
+    sequence_numbers::RESERVATION_TTL,
+    timeouts::EndpointClass,
     types::{InternalOperation, *},
     RosettaContext,
 };
 use aptos_crypto::{
-    ed25519::{Ed25519PublicKey, Ed25519Signature},
-    signing_message, ValidCryptoMaterialStringExt,
+    ed25519::{Ed25519PublicKey, Ed25519Signature, ED25519_SIGNATURE_LENGTH},
+    multi_ed25519::{MultiEd25519PublicKey, MultiEd25519Signature},
+    signing_message, HashValue, Signature as _, ValidCryptoMaterial, ValidCryptoMaterialStringExt,
 };
 use aptos_global_constants::adjust_gas_headroom;
 use aptos_logger::debug;
+use aptos_rest_client::aptos_api_types::{GasEstimation, MoveFunction, MoveType, TransactionData};
 use aptos_sdk::{
     move_types::language_storage::{StructTag, TypeTag},
     transaction_builder::TransactionFactory,
@@ -47,13 +52,14 @@ use aptos_types::{
     account_address::AccountAddress,
     chain_id::ChainId,
     transaction::{
-        authenticator::AuthenticationKey, RawTransaction, SignedTransaction, TransactionPayload,
+        authenticator::{AuthenticationKey, TransactionAuthenticator},
+        RawTransaction, SignedTransaction, TransactionPayload,
     },
 };
 use serde::de::DeserializeOwned;
 use std::{
     convert::TryFrom,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use warp::Filter;
 
@@ -94,7 +100,10 @@ pub fn metadata_route(
         .and(warp::post())
         .and(warp::body::json())
         .and(with_context(server_context))
-        .and_then(handle_request(construction_metadata))
+        .and_then(handle_request(with_upstream_timeout(
+            EndpointClass::Construction,
+            construction_metadata,
+        )))
 }
 
 pub fn parse_route(
@@ -162,17 +171,61 @@ async fn construction_combine(
 
     let signature = &request.signatures[0];
 
+    // The signature must be from the signer requested in preprocess and payloads
+    if signature
+        .signing_payload
+        .account_identifier
+        .account_address()?
+        != unsigned_txn.sender()
+    {
+        return Err(ApiError::InvalidInput(Some(format!(
+            "Signature is from {:?} but the transaction is sent by {}",
+            signature.signing_payload.account_identifier,
+            unsigned_txn.sender()
+        ))));
+    }
+
     if signature.signature_type != SignatureType::Ed25519
         || signature.public_key.curve_type != CurveType::Edwards25519
     {
         return Err(ApiError::InvalidSignatureType);
     }
 
-    let public_key: Ed25519PublicKey =
-        decode_key(&signature.public_key.hex_bytes, "Ed25519PublicKey")?;
-    let signature: Ed25519Signature = decode_key(&signature.hex_bytes, "Ed25519Signature")?;
+    // The authenticator is built for the sender's scheme
+    let auth_scheme = request.auth_scheme.unwrap_or_default();
+    let signature_encoding = request.signature_encoding.unwrap_or_default();
+    if auth_scheme != AuthScheme::Ed25519 && signature_encoding != SignatureEncoding::Rosetta {
+        return Err(ApiError::InvalidInput(Some(format!(
+            "Signature encoding {} is only supported for auth_scheme {}",
+            signature_encoding,
+            AuthScheme::Ed25519
+        ))));
+    }
 
-    let signed_txn = SignedTransaction::new(unsigned_txn, public_key, signature);
+    let signed_txn = match auth_scheme {
+        AuthScheme::Ed25519 => SignedTransaction::new(
+            unsigned_txn,
+            decode_for_scheme::<Ed25519PublicKey>(
+                &signature.public_key.hex_bytes,
+                "public key",
+                auth_scheme,
+            )?,
+            decode_ed25519_signature(&signature.hex_bytes, signature_encoding)?,
+        ),
+        AuthScheme::MultiEd25519 => SignedTransaction::new_multisig(
+            unsigned_txn,
+            decode_for_scheme::<MultiEd25519PublicKey>(
+                &signature.public_key.hex_bytes,
+                "public key",
+                auth_scheme,
+            )?,
+            decode_for_scheme::<MultiEd25519Signature>(
+                &signature.hex_bytes,
+                "signature",
+                auth_scheme,
+            )?,
+        ),
+    };
 
     Ok(ConstructionCombineResponse {
         signed_transaction: encode_bcs(&signed_txn)?,
@@ -193,15 +246,187 @@ async fn construction_derive(
     debug!("/construction/derive {:?}", request);
     check_network(request.network_identifier, &server_context)?;
 
-    let public_key: Ed25519PublicKey =
-        decode_key(&request.public_key.hex_bytes, "Ed25519PublicKey")?;
-    let address = AuthenticationKey::ed25519(&public_key).derived_address();
+    let address =
+        auth_key_for_scheme(&request.public_key, request.auth_scheme.unwrap_or_default())?
+            .derived_address();
 
     Ok(ConstructionDeriveResponse {
         account_identifier: AccountIdentifier::base_account(address),
     })
 }
 
+/// Decodes a key or signature of the authentication scheme
+///
+/// Keys and signatures of different schemes have different lengths, so material of the wrong
+/// scheme fails to decode.
+pub fn decode_for_scheme<T: DeserializeOwned + ValidCryptoMaterial>(
+    hex_bytes: &str,
+    material: &str,
+    auth_scheme: AuthScheme,
+) -> ApiResult<T> {
+    T::from_encoded_string(hex_bytes).map_err(|_| {
+        ApiError::InvalidInput(Some(format!(
+            "Invalid {} for auth_scheme {}, check the auth_scheme matches the key",
+            material, auth_scheme
+        )))
+    })
+}
+
+/// Decodes an Ed25519 signature, normalizing it from the HSM encodings
+fn decode_ed25519_signature(
+    hex_bytes: &str,
+    signature_encoding: SignatureEncoding,
+) -> ApiResult<Ed25519Signature> {
+    let bytes = match signature_encoding {
+        SignatureEncoding::Rosetta => {
+            return decode_for_scheme(hex_bytes, "signature", AuthScheme::Ed25519)
+        },
+        SignatureEncoding::Raw => hex::decode(hex_bytes)?,
+        SignatureEncoding::Der => der_signature_to_raw(&hex::decode(hex_bytes)?)?,
+    };
+    if bytes.len() != ED25519_SIGNATURE_LENGTH {
+        return Err(ApiError::InvalidInput(Some(format!(
+            "Raw signature must be {} bytes, but is {} bytes",
+            ED25519_SIGNATURE_LENGTH,
+            bytes.len()
+        ))));
+    }
+    Ed25519Signature::try_from(bytes.as_slice()).map_err(|err| {
+        ApiError::InvalidInput(Some(format!(
+            "Invalid {} encoded signature: {}",
+            signature_encoding, err
+        )))
+    })
+}
+
+const DER_SEQUENCE: u8 = 0x30;
+const DER_INTEGER: u8 = 0x02;
+
+/// Converts a DER `SEQUENCE` of the `INTEGER`s `r` and `s` to the 64 byte `r || s`
+fn der_signature_to_raw(der: &[u8]) -> ApiResult<Vec<u8>> {
+    let malformed =
+        |reason: &str| ApiError::InvalidInput(Some(format!("Malformed DER signature: {}", reason)));
+
+    let (sequence, rest) =
+        der_element(der, DER_SEQUENCE).ok_or_else(|| malformed("expected a SEQUENCE"))?;
+    if !rest.is_empty() {
+        return Err(malformed("trailing bytes after the SEQUENCE"));
+    }
+    let (r, rest) =
+        der_element(sequence, DER_INTEGER).ok_or_else(|| malformed("expected the INTEGER r"))?;
+    let (s, rest) =
+        der_element(rest, DER_INTEGER).ok_or_else(|| malformed("expected the INTEGER s"))?;
+    if !rest.is_empty() {
+        return Err(malformed("trailing bytes after the INTEGER s"));
+    }
+
+    let half_length = ED25519_SIGNATURE_LENGTH / 2;
+    let mut raw = Vec::with_capacity(ED25519_SIGNATURE_LENGTH);
+    for (name, integer) in [("r", r), ("s", s)] {
+        // Leading zeros are padding e.g. to keep the integer positive, and are added back
+        // to make up the length
+        let value = &integer[integer.iter().take_while(|byte| **byte == 0).count()..];
+        if integer.is_empty() || value.len() > half_length {
+            return Err(malformed(&format!(
+                "{} isn't a {} byte integer",
+                name, half_length
+            )));
+        }
+        raw.resize(raw.len() + half_length - value.len(), 0);
+        raw.extend_from_slice(value);
+    }
+    Ok(raw)
+}
+
+/// Splits a DER element with the tag off the front, returning its contents and the bytes
+/// after it
+///
+/// Only short form lengths are supported, which are enough for two 32 byte integers.
+fn der_element(bytes: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    match bytes {
+        [actual_tag, length, rest @ ..]
+            if *actual_tag == tag && *length < 0x80 && rest.len() >= *length as usize =>
+        {
+            Some(rest.split_at(*length as usize))
+        },
+        _ => None,
+    }
+}
+
+/// Authentication key of a public key of the authentication scheme
+pub fn auth_key_for_scheme(
+    public_key: &PublicKey,
+    auth_scheme: AuthScheme,
+) -> ApiResult<AuthenticationKey> {
+    Ok(match auth_scheme {
+        AuthScheme::Ed25519 => AuthenticationKey::ed25519(&decode_for_scheme::<Ed25519PublicKey>(
+            &public_key.hex_bytes,
+            "public key",
+            auth_scheme,
+        )?),
+        AuthScheme::MultiEd25519 => {
+            AuthenticationKey::multi_ed25519(&decode_for_scheme::<MultiEd25519PublicKey>(
+                &public_key.hex_bytes,
+                "public key",
+                auth_scheme,
+            )?)
+        },
+    })
+}
+
+/// Verifies both keys signed the rotation challenge for the sequence number
+///
+/// Without this, a bad proof would only be caught on chain, after paying for gas.
+fn verify_rotation_proofs(rotate_auth_key: &RotateAuthKey, sequence_number: u64) -> ApiResult<()> {
+    let challenge = rotate_auth_key.challenge(sequence_number)?;
+    for (name, auth_scheme, public_key, proof) in [
+        (
+            "current",
+            rotate_auth_key.current_auth_scheme,
+            &rotate_auth_key.current_public_key,
+            &rotate_auth_key.current_key_proof,
+        ),
+        (
+            "new",
+            rotate_auth_key.new_auth_scheme,
+            &rotate_auth_key.new_public_key,
+            &rotate_auth_key.new_key_proof,
+        ),
+    ] {
+        let verified = match auth_scheme {
+            AuthScheme::Ed25519 => {
+                decode_for_scheme::<Ed25519Signature>(proof, "rotation proof", auth_scheme)?
+                    .verify_arbitrary_msg(
+                        &challenge,
+                        &decode_for_scheme::<Ed25519PublicKey>(
+                            &public_key.hex_bytes,
+                            "public key",
+                            auth_scheme,
+                        )?,
+                    )
+            },
+            AuthScheme::MultiEd25519 => {
+                decode_for_scheme::<MultiEd25519Signature>(proof, "rotation proof", auth_scheme)?
+                    .verify_arbitrary_msg(
+                        &challenge,
+                        &decode_for_scheme::<MultiEd25519PublicKey>(
+                            &public_key.hex_bytes,
+                            "public key",
+                            auth_scheme,
+                        )?,
+                    )
+            },
+        };
+        if verified.is_err() {
+            return Err(ApiError::InvalidSignature(Some(format!(
+                "The {} key's rotation proof isn't a signature of the challenge for sequence number {}",
+                name, sequence_number
+            ))));
+        }
+    }
+    Ok(())
+}
+
 /// Construction hash command (OFFLINE)
 ///
 /// Hash a transaction to get it's identifier for lookup in mempool
@@ -217,11 +442,61 @@ async fn construction_hash(
     let signed_transaction: SignedTransaction =
         decode_bcs(&request.signed_transaction, "SignedTransaction")?;
 
+    if server_context.verify_transaction_hashes {
+        verify_hash_with_node(&server_context, &signed_transaction).await?;
+    }
+
     Ok(TransactionIdentifierResponse {
         transaction_identifier: signed_transaction.committed_hash().into(),
     })
 }
 
+/// Checks that the fullnode hashes transactions the same way as we do
+///
+/// Simulation requires an invalid signature, so this compares the hash of the transaction
+/// with a zeroed signature, which is hashed the same way as the signed transaction.
+///
+/// Only single signer ed25519 transactions are verified.  Other authenticators are hashed
+/// the same way, but can't be simulated with a zeroed signature, so they're skipped.
+async fn verify_hash_with_node(
+    server_context: &RosettaContext,
+    signed_transaction: &SignedTransaction,
+) -> ApiResult<()> {
+    let public_key = match signed_transaction.authenticator() {
+        TransactionAuthenticator::Ed25519 { public_key, .. } => public_key.clone(),
+        _ => {
+            debug!("Skipping hash verification of a transaction that isn't single signer");
+            return Ok(());
+        },
+    };
+    let rest_client = server_context.rest_client()?;
+    let zero_signature = Ed25519Signature::try_from([0u8; 64].as_ref())
+        .map_err(|err| ApiError::InternalError(Some(err.to_string())))?;
+    let simulated_transaction = SignedTransaction::new(
+        signed_transaction.clone().into_raw_transaction(),
+        public_key,
+        zero_signature,
+    );
+    let local_hash = simulated_transaction.clone().committed_hash();
+
+    let response = rest_client.simulate(&simulated_transaction).await?;
+    let node_hash = response
+        .into_inner()
+        .first()
+        .map(|txn| HashValue::from(txn.info.hash))
+        .ok_or_else(|| {
+            ApiError::InternalError(Some("Simulation returned no transactions".to_string()))
+        })?;
+
+    if local_hash != node_hash {
+        return Err(ApiError::TransactionHashMismatch(Some(format!(
+            "Locally computed hash {} doesn't match the node's hash {}",
+            local_hash, node_hash
+        ))));
+    }
+    Ok(())
+}
+
 /// Fills in the operator for actions that require it but don't have one
 async fn fill_in_operator(
     rest_client: &aptos_rest_client::Client,
@@ -284,48 +559,195 @@ async fn fill_in_operator(
     Ok(internal_operation)
 }
 
+/// The gas price to use for a priority, with the multiplier applied if there is one
+fn gas_price_for_priority(
+    gas_estimation: &GasEstimation,
+    gas_price_priority: GasPricePriority,
+    gas_price_multiplier: Option<u32>,
+) -> ApiResult<u64> {
+    // Get the priorities, for backwards compatibility, if the API doesn't have the prioritized ones, use the normal one
+    let gas_price = match gas_price_priority {
+        GasPricePriority::Low => gas_estimation
+            .deprioritized_gas_estimate
+            .unwrap_or(gas_estimation.gas_estimate),
+        GasPricePriority::Normal => gas_estimation.gas_estimate,
+        GasPricePriority::High => gas_estimation
+            .prioritized_gas_estimate
+            .unwrap_or(gas_estimation.gas_estimate),
+    };
+
+    // We can also provide the multiplier at this point, we mulitply times it, and divide by 100
+    if let Some(gas_multiplier) = gas_price_multiplier {
+        let gas_multiplier = gas_multiplier as u64;
+        if let Some(multiplied_price) = gas_price.checked_mul(gas_multiplier) {
+            Ok(multiplied_price.saturating_div(100))
+        } else {
+            Err(ApiError::InvalidInput(Some(format!(
+                "Gas price multiplier {} causes overflow on the price",
+                gas_multiplier
+            ))))
+        }
+    } else {
+        Ok(gas_price)
+    }
+}
+
+/// Checks that gas can be paid in the currency
+///
+/// The framework only charges gas in the native coin, so it's the only supported currency.
+fn check_gas_currency(currency: &Currency) -> ApiResult<()> {
+    if currency == &native_coin() {
+        Ok(())
+    } else {
+        Err(ApiError::UnsupportedCurrency(Some(format!(
+            "Gas can only be paid in {}, not {}",
+            native_coin().symbol,
+            currency.symbol
+        ))))
+    }
+}
+
+/// Suggested fees for the transaction, the first of which is for the gas price used
+///
+/// If the gas price was estimated, and the node has estimates for every priority, there's a fee
+/// for each priority, each with the gas price to use it.  Otherwise, there's only the one fee.
+fn suggested_fees(
+    gas_estimation: Option<&GasEstimation>,
+    options: &ConstructionOptions,
+    gas_unit_price: u64,
+    max_gas_amount: u64,
+) -> ApiResult<Vec<Amount>> {
+    let gas_estimation = match gas_estimation {
+        Some(gas_estimation)
+            if gas_estimation.deprioritized_gas_estimate.is_some()
+                && gas_estimation.prioritized_gas_estimate.is_some() =>
+        {
+            gas_estimation
+        },
+        _ => {
+            return Ok(vec![Amount::suggested_gas_fee(
+                gas_unit_price,
+                max_gas_amount,
+            )])
+        },
+    };
+
+    let selected = options.gas_price_priority.unwrap_or_default();
+    let mut fees = vec![Amount::suggested_gas_fee_for_priority(
+        selected,
+        gas_unit_price,
+        max_gas_amount,
+    )];
+    for priority in [
+        GasPricePriority::Low,
+        GasPricePriority::Normal,
+        GasPricePriority::High,
+    ] {
+        if priority != selected {
+            let gas_price =
+                gas_price_for_priority(gas_estimation, priority, options.gas_price_multiplier)?;
+            fees.push(Amount::suggested_gas_fee_for_priority(
+                priority,
+                gas_price,
+                max_gas_amount,
+            ));
+        }
+    }
+    Ok(fees)
+}
+
+/// Checks an entry function call against the function's on-chain ABI
+async fn check_entry_function_abi(
+    rest_client: &aptos_rest_client::Client,
+    call: &EntryFunctionCall,
+) -> ApiResult<()> {
+    let module = rest_client
+        .get_account_module(*call.module.address(), call.module.name().as_str())
+        .await?
+        .into_inner()
+        .try_parse_abi()?;
+    let function = module
+        .abi
+        .as_ref()
+        .and_then(|abi| {
+            abi.exposed_functions
+                .iter()
+                .find(|function| function.name.0 == call.function)
+        })
+        .ok_or_else(|| {
+            ApiError::InvalidInput(Some(format!(
+                "Function {} doesn't exist",
+                call.function_id()
+            )))
+        })?;
+    if !function.is_entry {
+        return Err(ApiError::InvalidInput(Some(format!(
+            "Function {} is not an entry function",
+            call.function_id()
+        ))));
+    }
+    check_entry_function_arity(call, function)
+}
+
+/// Checks the number of type arguments and arguments match the function, signers aren't passed
+fn check_entry_function_arity(call: &EntryFunctionCall, function: &MoveFunction) -> ApiResult<()> {
+    if call.type_arguments.len() != function.generic_type_params.len() {
+        return Err(ApiError::InvalidInput(Some(format!(
+            "Function {} expects {} type arguments, but got {}",
+            call.function_id(),
+            function.generic_type_params.len(),
+            call.type_arguments.len()
+        ))));
+    }
+
+    let num_params = function
+        .params
+        .iter()
+        .filter(|param| !is_signer(param))
+        .count();
+    if call.arguments.len() != num_params {
+        return Err(ApiError::InvalidInput(Some(format!(
+            "Function {} expects {} arguments, but got {}",
+            call.function_id(),
+            num_params,
+            call.arguments.len()
+        ))));
+    }
+    Ok(())
+}
+
+fn is_signer(move_type: &MoveType) -> bool {
+    match move_type {
+        MoveType::Signer => true,
+        MoveType::Reference { to, .. } => is_signer(to),
+        _ => false,
+    }
+}
+
 async fn simulate_transaction(
     rest_client: &aptos_rest_client::Client,
     chain_id: ChainId,
-    options: &MetadataOptions,
+    options: &ConstructionOptions,
     internal_operation: &InternalOperation,
     sequence_number: u64,
-) -> ApiResult<(Amount, u64, u64)> {
+) -> ApiResult<(Vec<Amount>, u64, u64)> {
     // If we have any missing fields, let's simulate!
     let mut transaction_factory = TransactionFactory::new(chain_id);
 
     // If we have a gas unit price, let's not estimate
-    if let Some(gas_unit_price) = options.gas_price_per_unit.as_ref() {
+    let gas_estimation = if let Some(gas_unit_price) = options.gas_price_per_unit.as_ref() {
         transaction_factory = transaction_factory.with_gas_unit_price(gas_unit_price.0);
+        None
     } else {
         let gas_estimation = rest_client.estimate_gas_price().await?.into_inner();
-
-        // Get the priorities, for backwards compatibility, if the API doesn't have the prioritized ones, use the normal one
-        let mut gas_price = match options.gas_price_priority.unwrap_or_default() {
-            GasPricePriority::Low => gas_estimation
-                .deprioritized_gas_estimate
-                .unwrap_or(gas_estimation.gas_estimate),
-            GasPricePriority::Normal => gas_estimation.gas_estimate,
-            GasPricePriority::High => gas_estimation
-                .prioritized_gas_estimate
-                .unwrap_or(gas_estimation.gas_estimate),
-        };
-
-        // We can also provide the multiplier at this point, we mulitply times it, and divide by 100
-        if let Some(gas_multiplier) = options.gas_price_multiplier {
-            let gas_multiplier = gas_multiplier as u64;
-            if let Some(multiplied_price) = gas_price.checked_mul(gas_multiplier) {
-                gas_price = multiplied_price.saturating_div(100)
-            } else {
-                return Err(ApiError::InvalidInput(Some(format!(
-                    "Gas price multiplier {} causes overflow on the price",
-                    gas_multiplier
-                ))));
-            }
-        }
-
+        let gas_price = gas_price_for_priority(
+            &gas_estimation,
+            options.gas_price_priority.unwrap_or_default(),
+            options.gas_price_multiplier,
+        )?;
         transaction_factory = transaction_factory.with_gas_unit_price(gas_price);
-    }
+        Some(gas_estimation)
+    };
 
     // Build up the transaction
     let (txn_payload, sender) = internal_operation.payload()?;
@@ -339,24 +761,48 @@ async fn simulate_transaction(
     // TODO: Only single signer supported
     let public_key =
         if let Some(public_key) = options.public_keys.as_ref().and_then(|inner| inner.first()) {
-            Ed25519PublicKey::from_encoded_string(&public_key.hex_bytes).map_err(|err| {
-                ApiError::InvalidInput(Some(format!(
-                    "Public key provided is not parsable {:?}",
-                    err
-                )))
-            })?
+            public_key
         } else {
             return Err(ApiError::InvalidInput(Some(
                 "Must provide public_keys for simulation otherwise it can't simulate!".to_string(),
             )));
         };
 
-    // Sign the transaction with a dummy signature of all zeros as required by the API
-    let signed_transaction = SignedTransaction::new(
-        unsigned_transaction,
-        public_key,
-        Ed25519Signature::try_from([0u8; 64].as_ref()).expect("Zero signature should always work"),
-    );
+    // Sign the transaction with dummy signatures of all zeros as required by the API
+    let zero_signature =
+        Ed25519Signature::try_from([0u8; 64].as_ref()).expect("Zero signature should always work");
+    let auth_scheme = options.auth_scheme.unwrap_or_default();
+    let signed_transaction = match auth_scheme {
+        AuthScheme::Ed25519 => SignedTransaction::new(
+            unsigned_transaction,
+            decode_for_scheme::<Ed25519PublicKey>(
+                &public_key.hex_bytes,
+                "public key",
+                auth_scheme,
+            )?,
+            zero_signature,
+        ),
+        AuthScheme::MultiEd25519 => {
+            let public_key = decode_for_scheme::<MultiEd25519PublicKey>(
+                &public_key.hex_bytes,
+                "public key",
+                auth_scheme,
+            )?;
+            // As many signatures as the threshold needs
+            let signature = MultiEd25519Signature::new(
+                (0..*public_key.threshold())
+                    .map(|index| (zero_signature.clone(), index))
+                    .collect(),
+            )
+            .map_err(|err| {
+                ApiError::InvalidInput(Some(format!(
+                    "Can't build a signature for simulation with the public key {:?}",
+                    err
+                )))
+            })?;
+            SignedTransaction::new_multisig(unsigned_transaction, public_key, signature)
+        },
+    };
 
     // Simulate, filling in the fields that aren't being currently handled
     // This API will always succeed unless 2 conditions
@@ -404,9 +850,14 @@ async fn simulate_transaction(
         };
 
         // Multiply the gas price times the max gas amount to use
-        let suggested_fee = Amount::suggested_gas_fee(simulated_gas_unit_price, max_gas_amount);
+        let suggested_fees = suggested_fees(
+            gas_estimation.as_ref(),
+            options,
+            simulated_gas_unit_price,
+            max_gas_amount,
+        )?;
 
-        Ok((suggested_fee, simulated_gas_unit_price, max_gas_amount))
+        Ok((suggested_fees, simulated_gas_unit_price, max_gas_amount))
     } else {
         // This should never happen, because the underlying API can't run a non-user transaction
         Err(ApiError::InternalError(Some(format!(
@@ -427,6 +878,10 @@ async fn construction_metadata(
 ) -> ApiResult<ConstructionMetadataResponse> {
     debug!("/construction/metadata {:?}", request);
     check_network(request.network_identifier, &server_context)?;
+    server_context.check_online()?;
+    if let Some(ref gas_currency) = request.options.gas_currency {
+        check_gas_currency(gas_currency)?;
+    }
 
     let rest_client = server_context.rest_client()?;
     let address = request.options.internal_operation.sender();
@@ -437,12 +892,19 @@ async fn construction_metadata(
         return Err(ApiError::ChainIdMismatch);
     }
 
-    let sequence_number = if let Some(sequence_number) = request.options.sequence_number {
-        sequence_number.0
-    } else {
-        // Retrieve the sequence number from the rest server if one wasn't provided
-        response.inner().sequence_number
-    };
+    let (sequence_number, simulated_sequence_number) =
+        if let Some(sequence_number) = request.options.sequence_number {
+            (sequence_number.0, sequence_number.0)
+        } else {
+            // Reserve the next sequence number if one wasn't provided, so concurrent requests
+            // for the same account don't get the same one.  Simulation has to use the on-chain
+            // sequence number, as it can't run ahead of the account.
+            let on_chain = response.inner().sequence_number;
+            (
+                server_context.sequence_numbers.reserve(address, on_chain),
+                on_chain,
+            )
+        };
 
     // We have to cheat the set operator and set voter operations right here
     let internal_operation = fill_in_operator(
@@ -450,14 +912,17 @@ async fn construction_metadata(
         request.options.internal_operation.clone(),
     )
     .await?;
+    if let InternalOperation::EntryFunction(ref call) = internal_operation {
+        check_entry_function_abi(rest_client.as_ref(), call).await?;
+    }
 
-    // If both are present, we skip simulation
+    // Simulate even if both gas values are present, to check the transaction would succeed
     let (suggested_fee, gas_unit_price, max_gas_amount) = simulate_transaction(
         rest_client.as_ref(),
         server_context.chain_id,
         &request.options,
         &internal_operation,
-        sequence_number,
+        simulated_sequence_number,
     )
     .await?;
 
@@ -467,9 +932,10 @@ async fn construction_metadata(
             max_gas_amount: max_gas_amount.into(),
             gas_price_per_unit: gas_unit_price.into(),
             expiry_time_secs: request.options.expiry_time_secs,
+            expiry_offset_secs: request.options.expiry_offset_secs,
             internal_operation,
         },
-        suggested_fee: vec![suggested_fee],
+        suggested_fee,
     })
 }
 
@@ -487,6 +953,13 @@ async fn construction_parse(
     let metadata;
     let (account_identifier_signers, unsigned_txn) = if request.signed {
         let signed_txn: SignedTransaction = decode_bcs(&request.transaction, "SignedTransaction")?;
+
+        // Verify every signature against its public key, so signers are only returned if valid
+        signed_txn
+            .clone()
+            .check_signature()
+            .map_err(|err| ApiError::InvalidSignature(Some(err.to_string())))?;
+
         metadata = Some(ConstructionParseMetadata {
             unsigned_transaction: None,
             signed_transaction: Some(signed_txn.clone()),
@@ -528,6 +1001,9 @@ async fn construction_parse(
                 (AccountAddress::ONE, APTOS_ACCOUNT_MODULE, TRANSFER_FUNCTION) => {
                     parse_account_transfer_operation(sender, &type_args, &args)?
                 },
+                (AccountAddress::ONE, APTOS_ACCOUNT_MODULE, BATCH_TRANSFER_FUNCTION) => {
+                    parse_batch_transfer_operation(sender, &type_args, &args)?
+                },
                 (AccountAddress::ONE, APTOS_ACCOUNT_MODULE, CREATE_ACCOUNT_FUNCTION) => {
                     parse_create_account_operation(sender, &type_args, &args)?
                 },
@@ -547,14 +1023,17 @@ async fn construction_parse(
                 (AccountAddress::ONE, STAKING_CONTRACT_MODULE, RESET_LOCKUP_FUNCTION) => {
                     parse_reset_lockup_operation(sender, &type_args, &args)?
                 },
-                _ => {
-                    return Err(ApiError::TransactionParseError(Some(format!(
-                        "Unsupported entry function type {:x}::{}::{}",
-                        module.address(),
-                        module.name(),
-                        function_name
-                    ))));
+                (AccountAddress::ONE, ACCOUNT_MODULE, ROTATE_AUTHENTICATION_KEY_FUNCTION) => {
+                    parse_rotate_auth_key_operation(sender, &type_args, &args)?
                 },
+                // Anything else can only be represented as a call to the entry function
+                _ => vec![Operation::entry_function(0, None, &EntryFunctionCall {
+                    sender,
+                    module,
+                    function: function_name,
+                    type_arguments: type_args,
+                    arguments: args,
+                })],
             }
         },
         payload => {
@@ -565,6 +1044,11 @@ async fn construction_parse(
         },
     };
 
+    if request.strict {
+        check_fully_modeled(&operations)?;
+    }
+
+    record_operations("parse", &operations);
     Ok(ConstructionParseResponse {
         operations,
         account_identifier_signers,
@@ -572,6 +1056,32 @@ async fn construction_parse(
     })
 }
 
+/// Checks that every operation is fully modeled, for clients that can't accept an operation
+/// they can't interpret
+///
+/// Any entry function without its own operation type is parsed as an
+/// [`OperationType::EntryFunction`] call, which says nothing about what it does.
+fn check_fully_modeled(operations: &[Operation]) -> ApiResult<()> {
+    let unmodeled: Vec<u64> = operations
+        .iter()
+        .filter(|operation| {
+            !matches!(
+                operation.operation_type.parse::<OperationType>(),
+                Ok(operation_type) if operation_type != OperationType::EntryFunction
+            )
+        })
+        .map(|operation| operation.operation_identifier.index)
+        .collect();
+    if unmodeled.is_empty() {
+        Ok(())
+    } else {
+        Err(ApiError::TransactionParseError(Some(format!(
+            "Operations {:?} aren't fully modeled, and strict parsing is enabled",
+            unmodeled
+        ))))
+    }
+}
+
 fn parse_create_account_operation(
     sender: AccountAddress,
     type_args: &[TypeTag],
@@ -711,6 +1221,51 @@ fn parse_account_transfer_operation(
     Ok(operations)
 }
 
+fn parse_batch_transfer_operation(
+    sender: AccountAddress,
+    type_args: &[TypeTag],
+    args: &[Vec<u8>],
+) -> ApiResult<Vec<Operation>> {
+    // There are no typeargs for batch transfer
+    if !type_args.is_empty() {
+        return Err(ApiError::TransactionParseError(Some(format!(
+            "Batch transfer should not have type arguments: {:?}",
+            type_args
+        ))));
+    }
+
+    let receivers: Vec<AccountAddress> = if let Some(receivers) = args.first() {
+        bcs::from_bytes(receivers)?
+    } else {
+        return Err(ApiError::TransactionParseError(Some(
+            "No receivers in batch transfer".to_string(),
+        )));
+    };
+    let amounts: Vec<u64> = if let Some(amounts) = args.get(1) {
+        bcs::from_bytes(amounts)?
+    } else {
+        return Err(ApiError::TransactionParseError(Some(
+            "No amounts in batch transfer".to_string(),
+        )));
+    };
+    if receivers.len() != amounts.len() {
+        return Err(ApiError::TransactionParseError(Some(format!(
+            "Batch transfer has {} receivers but {} amounts",
+            receivers.len(),
+            amounts.len()
+        ))));
+    }
+
+    let batch_transfer = BatchTransfer::new(
+        sender,
+        receivers,
+        amounts.into_iter().map(U64::from).collect(),
+        native_coin(),
+    )
+    .map_err(|err| ApiError::TransactionParseError(Some(format!("{:?}", err))))?;
+    Ok(batch_transfer.operations())
+}
+
 pub fn parse_function_arg<T: DeserializeOwned>(
     name: &str,
     args: &[Vec<u8>],
@@ -823,6 +1378,42 @@ pub fn parse_reset_lockup_operation(
     )])
 }
 
+pub fn parse_rotate_auth_key_operation(
+    sender: AccountAddress,
+    type_args: &[TypeTag],
+    args: &[Vec<u8>],
+) -> ApiResult<Vec<Operation>> {
+    if !type_args.is_empty() {
+        return Err(ApiError::TransactionParseError(Some(format!(
+            "Rotate auth key should not have type arguments: {:?}",
+            type_args
+        ))));
+    }
+
+    let current_scheme: u8 = parse_function_arg("rotate_authentication_key", args, 0)?;
+    let current_public_key: Vec<u8> = parse_function_arg("rotate_authentication_key", args, 1)?;
+    let new_scheme: u8 = parse_function_arg("rotate_authentication_key", args, 2)?;
+    let new_public_key: Vec<u8> = parse_function_arg("rotate_authentication_key", args, 3)?;
+    let current_key_proof: Vec<u8> = parse_function_arg("rotate_authentication_key", args, 4)?;
+    let new_key_proof: Vec<u8> = parse_function_arg("rotate_authentication_key", args, 5)?;
+    let rotate_auth_key = RotateAuthKey::new(
+        sender,
+        AuthScheme::from_scheme_id(current_scheme)?,
+        PublicKey {
+            hex_bytes: hex::encode(current_public_key),
+            curve_type: CurveType::Edwards25519,
+        },
+        AuthScheme::from_scheme_id(new_scheme)?,
+        PublicKey {
+            hex_bytes: hex::encode(new_public_key),
+            curve_type: CurveType::Edwards25519,
+        },
+        hex::encode(current_key_proof),
+        hex::encode(new_key_proof),
+    )?;
+    Ok(vec![Operation::rotate_auth_key(0, None, &rotate_auth_key)])
+}
+
 /// Construction payloads command (OFFLINE)
 ///
 /// Constructs payloads for given known operations
@@ -835,8 +1426,16 @@ async fn construction_payloads(
     debug!("/construction/payloads {:?}", request);
     check_network(request.network_identifier, &server_context)?;
 
+    // Any public keys must be of the scheme, so the signatures can be combined later
+    let auth_scheme = request.auth_scheme.unwrap_or_default();
+    for public_key in request.public_keys.iter().flatten() {
+        auth_key_for_scheme(public_key, auth_scheme)?;
+    }
+
     // Retrieve the real operation we're doing
     let mut operation = InternalOperation::extract(&request.operations)?;
+    check_self_transfer(&server_context, &operation)?;
+    check_transfer_amount(&server_context, &operation)?;
     let metadata = if let Some(ref metadata) = request.metadata {
         metadata
     } else {
@@ -861,6 +1460,14 @@ async fn construction_payloads(
                 ))));
             }
         },
+        InternalOperation::BatchTransfer(_) => {
+            if operation != metadata.internal_operation {
+                return Err(ApiError::InvalidInput(Some(format!(
+                    "Batch transfer operation doesn't match metadata {:?} vs {:?}",
+                    operation, metadata.internal_operation
+                ))));
+            }
+        },
         InternalOperation::SetOperator(inner) => {
             if let InternalOperation::SetOperator(ref metadata_op) = metadata.internal_operation {
                 if inner.owner == metadata_op.owner
@@ -924,6 +1531,23 @@ async fn construction_payloads(
                 ))));
             }
         },
+        InternalOperation::RotateAuthKey(inner) => {
+            if operation != metadata.internal_operation {
+                return Err(ApiError::InvalidInput(Some(format!(
+                    "Rotate auth key operation doesn't match metadata {:?} vs {:?}",
+                    operation, metadata.internal_operation
+                ))));
+            }
+            verify_rotation_proofs(inner, metadata.sequence_number.0)?;
+        },
+        InternalOperation::EntryFunction(_) => {
+            if operation != metadata.internal_operation {
+                return Err(ApiError::InvalidInput(Some(format!(
+                    "Entry function operation doesn't match metadata {:?} vs {:?}",
+                    operation, metadata.internal_operation
+                ))));
+            }
+        },
     }
 
     // Encode operation
@@ -940,11 +1564,26 @@ async fn construction_payloads(
         .sequence_number(metadata.sequence_number.0);
 
     // Default expiry is 30 seconds from right now
-    if let Some(expiry_time_secs) = metadata.expiry_time_secs {
-        txn_builder = txn_builder.expiration_timestamp_secs(expiry_time_secs.0)
+    let now = now_secs()?;
+    if let Some(expiry_time_secs) =
+        expiry_time_secs(metadata.expiry_time_secs, metadata.expiry_offset_secs, now)?
+    {
+        txn_builder = txn_builder.expiration_timestamp_secs(expiry_time_secs)
     }
     let unsigned_transaction = txn_builder.build();
 
+    // The sequence number was reserved at metadata, but has to stay reserved for as long as
+    // this transaction can be committed
+    server_context.sequence_numbers.extend(
+        sender,
+        metadata.sequence_number.0,
+        Duration::from_secs(
+            unsigned_transaction
+                .expiration_timestamp_secs()
+                .saturating_sub(now),
+        ),
+    );
+
     let signing_message = hex::encode(signing_message(&unsigned_transaction).map_err(|err| {
         ApiError::InvalidInput(Some(format!(
             "Invalid transaction, can't build into a signing message {}",
@@ -957,58 +1596,230 @@ async fn construction_payloads(
         signature_type: Some(SignatureType::Ed25519),
     };
 
+    record_operations("payloads", &request.operations);
+
     // Transaction is both the unsigned transaction and the payload
     Ok(ConstructionPayloadsResponse {
         unsigned_transaction: encode_bcs(&unsigned_transaction)?,
         payloads: vec![payload],
+        metadata: Some(PayloadsMetadata {
+            expiration_timestamp_secs: unsigned_transaction.expiration_timestamp_secs().into(),
+        }),
     })
 }
 
-/// Construction preprocess command (OFFLINE)
+/// Counts the operations handled by a construction endpoint, by operation type
 ///
-/// This creates the request needed to fetch metadata
+/// Operations of unknown types are skipped, so only the supported types become labels.
+fn record_operations(endpoint: &'static str, operations: &[Operation]) {
+    for operation in operations {
+        if let Ok(operation_type) = operation.operation_type.parse::<OperationType>() {
+            CONSTRUCTION_OPERATIONS
+                .with_label_values(&[endpoint, &operation_type.to_string()])
+                .inc();
+        }
+    }
+}
+
+/// Furthest after now that a transaction can expire, so a transaction that can't be committed
+/// promptly expires rather than lingering
 ///
-/// [API Spec](https://www.rosetta-api.org/docs/ConstructionApi.html#constructionpreprocess)
-async fn construction_preprocess(
-    request: ConstructionPreprocessRequest,
-    server_context: RosettaContext,
-) -> ApiResult<ConstructionPreprocessResponse> {
-    debug!("/construction/preprocess {:?}", request);
-    check_network(request.network_identifier, &server_context)?;
+/// Payloads keeps the transaction's sequence number reserved until it expires, so this also
+/// bounds how long a flow that never submits holds on to its sequence number.  It's the same as
+/// the [`RESERVATION_TTL`] and the default expiry.
+pub const MAX_EXPIRY_WINDOW_SECS: u64 = RESERVATION_TTL.as_secs();
 
-    let internal_operation = InternalOperation::extract(&request.operations)?;
-    let required_public_keys = vec![AccountIdentifier::base_account(internal_operation.sender())];
+fn now_secs() -> ApiResult<u64> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|err| {
+            ApiError::InternalError(Some(format!("Failed to get current time {}", err)))
+        })?
+        .as_secs())
+}
 
-    if let Some(max_gas) = request
-        .metadata
-        .as_ref()
-        .and_then(|inner| inner.max_gas_amount)
-    {
-        if max_gas.0 < 1 {
+/// Determines the expiry time of a transaction built at `now_secs`, from either an expiry time
+/// or an offset from now.  Returns `None` for the default expiry.
+///
+/// The expiry must be in the future, and within [`MAX_EXPIRY_WINDOW_SECS`] of now.
+fn expiry_time_secs(
+    expiry_time_secs: Option<U64>,
+    expiry_offset_secs: Option<U64>,
+    now_secs: u64,
+) -> ApiResult<Option<u64>> {
+    let expiry_time_secs = match (expiry_time_secs, expiry_offset_secs) {
+        (None, None) => return Ok(None),
+        (Some(_), Some(_)) => {
             return Err(ApiError::InvalidInput(Some(
-                "Cannot have a max gas amount less than 1".to_string(),
-            )));
-        }
+                "Cannot provide both an expiry time and an expiry offset".to_string(),
+            )))
+        },
+        (Some(expiry_time_secs), None) => expiry_time_secs.0,
+        (None, Some(expiry_offset_secs)) => now_secs.saturating_add(expiry_offset_secs.0),
+    };
+
+    if expiry_time_secs <= now_secs {
+        return Err(ApiError::InvalidInput(Some(
+            "Expiry time secs is in the past, please provide a Unix timestamp in the future"
+                .to_string(),
+        )));
     }
-    if let Some(expiry_time_secs) = request
+    if expiry_time_secs - now_secs > MAX_EXPIRY_WINDOW_SECS {
+        return Err(ApiError::InvalidInput(Some(format!(
+            "Expiry time secs {} is more than {} seconds in the future",
+            expiry_time_secs, MAX_EXPIRY_WINDOW_SECS
+        ))));
+    }
+    Ok(Some(expiry_time_secs))
+}
+
+/// The receivers and amounts of the transfers made by an operation
+///
+/// Entry function calls to the framework's transfer functions are included, so the checks on
+/// transfers can't be bypassed by constructing a transfer as an entry function call.
+fn transfers_of(operation: &InternalOperation) -> ApiResult<Vec<(AccountAddress, u64)>> {
+    Ok(match operation {
+        InternalOperation::Transfer(transfer) => vec![(transfer.receiver, transfer.amount.0)],
+        InternalOperation::BatchTransfer(batch_transfer) => batch_transfer
+            .receivers
+            .iter()
+            .copied()
+            .zip(batch_transfer.amounts.iter().map(|amount| amount.0))
+            .collect(),
+        InternalOperation::EntryFunction(call) => entry_function_transfers(call)?,
+        _ => vec![],
+    })
+}
+
+/// The receivers and amounts of the transfers made by an entry function call, which is empty
+/// unless it calls one of the framework's transfer functions
+fn entry_function_transfers(call: &EntryFunctionCall) -> ApiResult<Vec<(AccountAddress, u64)>> {
+    let name = format!(
+        "{}::{}::{}",
+        call.module.address(),
+        call.module.name(),
+        call.function
+    );
+    match (
+        *call.module.address(),
+        call.module.name().as_str(),
+        call.function.as_str(),
+    ) {
+        (AccountAddress::ONE, COIN_MODULE, TRANSFER_FUNCTION)
+        | (AccountAddress::ONE, APTOS_ACCOUNT_MODULE, TRANSFER_FUNCTION)
+        | (AccountAddress::ONE, APTOS_ACCOUNT_MODULE, TRANSFER_COINS_FUNCTION) => Ok(vec![(
+            parse_function_arg(&name, &call.arguments, 0)?,
+            parse_function_arg(&name, &call.arguments, 1)?,
+        )]),
+        (AccountAddress::ONE, APTOS_ACCOUNT_MODULE, BATCH_TRANSFER_FUNCTION)
+        | (AccountAddress::ONE, APTOS_ACCOUNT_MODULE, BATCH_TRANSFER_COINS_FUNCTION) => {
+            let receivers: Vec<AccountAddress> = parse_function_arg(&name, &call.arguments, 0)?;
+            let amounts: Vec<u64> = parse_function_arg(&name, &call.arguments, 1)?;
+            Ok(receivers.into_iter().zip(amounts).collect())
+        },
+        _ => Ok(vec![]),
+    }
+}
+
+/// Rejects a transfer to the sender, if the server forbids them
+fn check_self_transfer(
+    server_context: &RosettaContext,
+    operation: &InternalOperation,
+) -> ApiResult<()> {
+    if !server_context.forbid_self_transfer {
+        return Ok(());
+    }
+    let sender = operation.sender();
+    let is_self_transfer = transfers_of(operation)?
+        .iter()
+        .any(|(receiver, _)| *receiver == sender);
+    if is_self_transfer {
+        Err(ApiError::InvalidTransferOperations(Some(
+            "Transfers to the sender are forbidden by this server",
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// Rejects a transfer of more than the server allows, if it's limited
+///
+/// A batch transfer is limited by the total it transfers, as it's a single transaction.
+fn check_transfer_amount(
+    server_context: &RosettaContext,
+    operation: &InternalOperation,
+) -> ApiResult<()> {
+    let max_transfer_amount = match server_context.max_transfer_amount {
+        Some(max_transfer_amount) => max_transfer_amount,
+        None => return Ok(()),
+    };
+    let amount: u128 = transfers_of(operation)?
+        .iter()
+        .map(|(_, amount)| *amount as u128)
+        .sum();
+    if amount > max_transfer_amount as u128 {
+        Err(ApiError::TransferAmountTooLarge(Some(format!(
+            "Transfer of {} is more than the maximum of {}",
+            amount, max_transfer_amount
+        ))))
+    } else {
+        Ok(())
+    }
+}
+
+/// Construction preprocess command (OFFLINE)
+///
+/// This creates the request needed to fetch metadata
+///
+/// [API Spec](https://www.rosetta-api.org/docs/ConstructionApi.html#constructionpreprocess)
+async fn construction_preprocess(
+    request: ConstructionPreprocessRequest,
+    server_context: RosettaContext,
+) -> ApiResult<ConstructionPreprocessResponse> {
+    debug!("/construction/preprocess {:?}", request);
+    check_network(request.network_identifier, &server_context)?;
+
+    let internal_operation = InternalOperation::extract(&request.operations)?;
+    check_self_transfer(&server_context, &internal_operation)?;
+    check_transfer_amount(&server_context, &internal_operation)?;
+    let required_public_keys = internal_operation
+        .signers()
+        .into_iter()
+        .map(AccountIdentifier::base_account)
+        .collect();
+
+    if let Some(max_gas) = request
         .metadata
         .as_ref()
-        .and_then(|inner| inner.expiry_time_secs)
+        .and_then(|inner| inner.max_gas_amount)
     {
-        if expiry_time_secs.0
-            <= SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .map_err(|err| {
-                    ApiError::InternalError(Some(format!("Failed to get current time {}", err)))
-                })?
-                .as_secs()
-        {
+        if max_gas.0 < 1 {
             return Err(ApiError::InvalidInput(Some(
-                "Expiry time secs is in the past, please provide a Unix timestamp in the future"
-                    .to_string(),
+                "Cannot have a max gas amount less than 1".to_string(),
             )));
         }
     }
+    if let Some(ref metadata) = request.metadata {
+        expiry_time_secs(
+            metadata.expiry_time_secs,
+            metadata.expiry_offset_secs,
+            now_secs()?,
+        )?;
+    }
+
+    // The rotation proofs sign the sequence number, so it can't be looked up later
+    if let InternalOperation::RotateAuthKey(ref rotate_auth_key) = internal_operation {
+        let sequence_number = request
+            .metadata
+            .as_ref()
+            .and_then(|inner| inner.sequence_number)
+            .ok_or_else(|| {
+                ApiError::InvalidInput(Some(
+                    "Must provide the sequence number signed by the rotation proofs".to_string(),
+                ))
+            })?;
+        verify_rotation_proofs(rotate_auth_key, sequence_number.0)?;
+    }
 
     // Check gas input options
 
@@ -1017,6 +1828,15 @@ async fn construction_preprocess(
         .as_ref()
         .and_then(|inner| inner.public_keys.as_ref());
 
+    // Any public keys must be of the scheme, so the transaction can be simulated with them
+    let auth_scheme = request
+        .metadata
+        .as_ref()
+        .and_then(|inner| inner.auth_scheme);
+    for public_key in public_keys.into_iter().flatten() {
+        auth_key_for_scheme(public_key, auth_scheme.unwrap_or_default())?;
+    }
+
     if request
         .metadata
         .as_ref()
@@ -1034,7 +1854,7 @@ async fn construction_preprocess(
     }
 
     Ok(ConstructionPreprocessResponse {
-        options: MetadataOptions {
+        options: ConstructionOptions {
             internal_operation,
             max_gas_amount: request
                 .metadata
@@ -1045,6 +1865,10 @@ async fn construction_preprocess(
                 .metadata
                 .as_ref()
                 .and_then(|inner| inner.expiry_time_secs),
+            expiry_offset_secs: request
+                .metadata
+                .as_ref()
+                .and_then(|inner| inner.expiry_offset_secs),
             sequence_number: request
                 .metadata
                 .as_ref()
@@ -1053,6 +1877,7 @@ async fn construction_preprocess(
                 .metadata
                 .as_ref()
                 .and_then(|inner| inner.public_keys.clone()),
+            auth_scheme,
             gas_price_multiplier: request
                 .metadata
                 .as_ref()
@@ -1061,6 +1886,10 @@ async fn construction_preprocess(
                 .metadata
                 .as_ref()
                 .and_then(|inner| inner.gas_price_priority),
+            gas_currency: request
+                .metadata
+                .as_ref()
+                .and_then(|inner| inner.gas_currency.clone()),
         },
         required_public_keys,
     })
@@ -1077,13 +1906,2264 @@ async fn construction_submit(
 ) -> ApiResult<ConstructionSubmitResponse> {
     debug!("/construction/submit {:?}", request);
     check_network(request.network_identifier, &server_context)?;
+    server_context.check_online()?;
 
     let rest_client = server_context.rest_client()?;
 
+    // The wait is bounded by the submit timeout, so a submit can't hold its connection for
+    // arbitrarily long
+    let wait_for_commit = request
+        .wait_for_commit_secs
+        .map(|secs| Duration::from_secs(secs.0));
+    let submit_timeout = server_context.upstream_timeouts.get(EndpointClass::Submit);
+    if let Some(wait) = wait_for_commit {
+        if wait >= submit_timeout {
+            return Err(ApiError::InvalidInput(Some(format!(
+                "Wait for commit of {}s must be less than the submit timeout of {}s",
+                wait.as_secs(),
+                submit_timeout.as_secs()
+            ))));
+        }
+    }
+
     let txn: SignedTransaction = decode_bcs(&request.signed_transaction, "SignedTransaction")?;
     let hash = txn.clone().committed_hash();
-    rest_client.submit_bcs(&txn).await?;
+    let started = Instant::now();
+    // Only the upstream submission is timed, not the wait for the submit queue.  The upstream
+    // may have accepted the transaction by the time it times out, so a timeout isn't retriable
+    let submit = || async {
+        server_context
+            .upstream_timeouts
+            .run(EndpointClass::Submit, async {
+                rest_client.submit_bcs(&txn).await?;
+                Ok::<_, ApiError>(())
+            })
+            .await
+            .map_err(|err| match err {
+                ApiError::UpstreamTimeout(_) => ApiError::SubmitOutcomeUnknown(Some(format!(
+                    "Upstream didn't respond to the submit within {}s, check whether transaction {} was committed before resubmitting",
+                    submit_timeout.as_secs(),
+                    hash
+                ))),
+                err => err,
+            })
+    };
+    if let Some(ref submit_queue) = server_context.submit_queue {
+        submit_queue.submit(txn.sender(), submit).await?;
+    } else {
+        submit().await?;
+    }
+
+    let metadata = match wait_for_commit {
+        // The time spent queued and submitting comes out of the wait
+        Some(wait) => Some(
+            wait_for_commit_outcome(&rest_client, hash, wait.saturating_sub(started.elapsed()))
+                .await,
+        ),
+        None => None,
+    };
     Ok(ConstructionSubmitResponse {
         transaction_identifier: hash.into(),
+        metadata,
     })
 }
+
+/// Interval between checks of whether a submitted transaction has been committed
+const COMMIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Waits up to `wait` for the transaction to be committed, returning its outcome
+///
+/// If it isn't committed in time, the outcome is unconfirmed, as it may still be committed.
+async fn wait_for_commit_outcome(
+    rest_client: &aptos_rest_client::Client,
+    hash: HashValue,
+    wait: Duration,
+) -> SubmitMetadata {
+    let poll = async {
+        loop {
+            // Until it's committed, the transaction is pending or not found, and failures to
+            // fetch it are retried in case they're transient
+            if let Ok(response) = rest_client.get_transaction_by_hash_bcs(hash).await {
+                if let TransactionData::OnChain(txn) = response.into_inner() {
+                    return SubmitMetadata {
+                        confirmed: true,
+                        version: Some(txn.version.into()),
+                        failed: Some(!txn.info.status().is_success()),
+                        vm_status: Some(format!("{:?}", txn.info.status())),
+                        gas_used: Some(txn.info.gas_used().into()),
+                    };
+                }
+            }
+            tokio::time::sleep(COMMIT_POLL_INTERVAL).await;
+        }
+    };
+    tokio::time::timeout(wait, poll)
+        .await
+        .unwrap_or_else(|_| SubmitMetadata {
+            confirmed: false,
+            ..Default::default()
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        check_entry_function_arity, check_gas_currency, construction_combine, construction_derive,
+        construction_hash, construction_metadata, construction_parse, construction_payloads,
+        construction_preprocess, construction_submit, der_signature_to_raw, expiry_time_secs,
+        now_secs, record_operations, suggested_fees, MAX_EXPIRY_WINDOW_SECS,
+    };
+    use crate::{
+        block::BlockRetriever,
+        common::{decode_bcs, encode_bcs, native_coin},
+        counters::CONSTRUCTION_OPERATIONS,
+        error::{ApiError, ApiResult},
+        sequence_numbers::SequenceNumberReservations,
+        submit_queue::SubmitQueueConfig,
+        test_utils::{self, MockLedger},
+        timeouts::{EndpointClass, UpstreamTimeouts},
+        types::{
+            AccountIdentifier, AuthScheme, BatchTransfer, ConstructionCombineRequest,
+            ConstructionDeriveRequest, ConstructionHashRequest, ConstructionMetadata,
+            ConstructionMetadataRequest, ConstructionOptions, ConstructionParseRequest,
+            ConstructionPayloadsRequest, ConstructionPayloadsResponse,
+            ConstructionPreprocessRequest, ConstructionSubmitRequest, CreateAccount, Currency,
+            CurrencyMetadata, CurveType, EntryFunctionCall, GasPricePriority, InitializeStakePool,
+            InternalOperation, NetworkIdentifier, Operation, OperationType, PreprocessMetadata,
+            PublicKey, ResetLockup, RotateAuthKey, SetOperator, SetVoter, Signature,
+            SignatureEncoding, SignatureType, SigningPayload, SubmitMetadata,
+            TransactionIdentifier, Transfer,
+        },
+        upstream::UpstreamClients,
+        RosettaConfig, RosettaContext, RosettaMode, DEFAULT_SLOW_BLOCK_FETCH_THRESHOLD,
+    };
+    use aptos_cached_packages::aptos_stdlib;
+    use aptos_crypto::{
+        ed25519::Ed25519PrivateKey, multi_ed25519::MultiEd25519PrivateKey, signing_message,
+        HashValue, PrivateKey, SigningKey, ValidCryptoMaterialStringExt,
+    };
+    use aptos_rest_client::aptos_api_types::{
+        GasEstimation, MoveFunction, MoveFunctionGenericTypeParam, MoveFunctionVisibility,
+        MoveType, TransactionData, TransactionOnChainData, U64,
+    };
+    use aptos_types::{
+        account_address::AccountAddress,
+        chain_id::ChainId,
+        transaction::{
+            authenticator::AuthenticationKey, ExecutionStatus, RawTransaction, SignedTransaction,
+            Transaction, TransactionInfo, TransactionPayload,
+        },
+        write_set::WriteSet,
+    };
+    use std::{
+        collections::BTreeSet,
+        convert::{Infallible, TryFrom},
+        str::FromStr,
+        sync::Arc,
+        time::Duration,
+    };
+    use warp::{http::StatusCode, Filter, Reply};
+
+    fn test_key(seed: u8) -> (Ed25519PrivateKey, AccountAddress) {
+        let private_key = Ed25519PrivateKey::try_from([seed; 32].as_slice()).unwrap();
+        let address = AuthenticationKey::ed25519(&private_key.public_key()).derived_address();
+        (private_key, address)
+    }
+
+    fn transfer_txn(sender: AccountAddress, receiver: AccountAddress) -> RawTransaction {
+        RawTransaction::new(
+            sender,
+            0,
+            aptos_stdlib::aptos_account_transfer(receiver, 100),
+            1000,
+            100,
+            u64::MAX,
+            ChainId::test(),
+        )
+    }
+
+    async fn offline_context() -> RosettaContext {
+        RosettaContext::new(
+            RosettaMode::Offline,
+            None,
+            ChainId::test(),
+            None,
+            vec![],
+            &RosettaConfig::default(),
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn parse_signed(
+        signed_txn: &SignedTransaction,
+    ) -> Result<Vec<AccountIdentifier>, ApiError> {
+        let server_context = offline_context().await;
+        let response = construction_parse(
+            ConstructionParseRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                signed: true,
+                transaction: encode_bcs(signed_txn).unwrap(),
+                strict: false,
+            },
+            server_context,
+        )
+        .await?;
+
+        // Operations should still be reconstructed from the signed transaction
+        assert_eq!(2, response.operations.len());
+        Ok(response.account_identifier_signers.unwrap())
+    }
+
+    #[tokio::test]
+    async fn parse_signed_transaction_with_valid_signature() {
+        let (sender_key, sender) = test_key(1);
+        let (_, receiver) = test_key(2);
+        let signed_txn = transfer_txn(sender, receiver)
+            .sign(&sender_key, sender_key.public_key())
+            .unwrap()
+            .into_inner();
+
+        let signers = parse_signed(&signed_txn).await.unwrap();
+        assert_eq!(vec![AccountIdentifier::base_account(sender)], signers);
+    }
+
+    #[tokio::test]
+    async fn parse_signed_transaction_with_invalid_signature() {
+        let (sender_key, sender) = test_key(1);
+        let (_, receiver) = test_key(2);
+
+        // Sign a different transaction, so the signature doesn't match
+        let signature = sender_key.sign(&transfer_txn(sender, sender)).unwrap();
+        let signed_txn = SignedTransaction::new(
+            transfer_txn(sender, receiver),
+            sender_key.public_key(),
+            signature,
+        );
+
+        let err = parse_signed(&signed_txn).await.unwrap_err();
+        assert!(matches!(err, ApiError::InvalidSignature(_)));
+    }
+
+    #[tokio::test]
+    async fn parse_signed_transaction_with_multiple_signers() {
+        let (sender_key, sender) = test_key(1);
+        let (secondary_key, secondary) = test_key(2);
+        let (_, receiver) = test_key(3);
+
+        let signed_txn = transfer_txn(sender, receiver)
+            .sign_multi_agent(&sender_key, vec![secondary], vec![&secondary_key])
+            .unwrap()
+            .into_inner();
+        let signers = parse_signed(&signed_txn).await.unwrap();
+        assert_eq!(
+            vec![
+                AccountIdentifier::base_account(sender),
+                AccountIdentifier::base_account(secondary)
+            ],
+            signers
+        );
+
+        // A bad secondary signature should fail the whole transaction
+        let (wrong_key, _) = test_key(4);
+        let signed_txn = transfer_txn(sender, receiver)
+            .sign_multi_agent(&sender_key, vec![secondary], vec![&wrong_key])
+            .unwrap()
+            .into_inner();
+        let err = parse_signed(&signed_txn).await.unwrap_err();
+        assert!(matches!(err, ApiError::InvalidSignature(_)));
+    }
+
+    #[tokio::test]
+    async fn hash_verification_skips_multiple_signers() {
+        // Offline, so single signer transactions fail to be verified
+        let server_context = RosettaContext::new(
+            RosettaMode::Offline,
+            None,
+            ChainId::test(),
+            None,
+            vec![],
+            &RosettaConfig {
+                verify_transaction_hashes: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let hash = |signed_txn: &SignedTransaction| {
+            construction_hash(
+                ConstructionHashRequest {
+                    network_identifier: NetworkIdentifier::from(ChainId::test()),
+                    signed_transaction: encode_bcs(signed_txn).unwrap(),
+                },
+                server_context.clone(),
+            )
+        };
+        let (sender_key, sender) = test_key(1);
+        let (secondary_key, secondary) = test_key(2);
+        let (_, receiver) = test_key(3);
+
+        let signed_txn = transfer_txn(sender, receiver)
+            .sign(&sender_key, sender_key.public_key())
+            .unwrap()
+            .into_inner();
+        let err = hash(&signed_txn).await.unwrap_err();
+        assert!(matches!(err, ApiError::NodeIsOffline), "{:?}", err);
+
+        let signed_txn = transfer_txn(sender, receiver)
+            .sign_multi_agent(&sender_key, vec![secondary], vec![&secondary_key])
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            TransactionIdentifier::from(signed_txn.clone().committed_hash()),
+            hash(&signed_txn).await.unwrap().transaction_identifier
+        );
+    }
+
+    #[tokio::test]
+    async fn required_public_keys_match_signers() {
+        let (sender_key, sender) = test_key(1);
+        let (_, receiver) = test_key(2);
+        let operations = vec![
+            Operation::withdraw(
+                0,
+                None,
+                AccountIdentifier::base_account(sender),
+                native_coin(),
+                100,
+            ),
+            Operation::deposit(
+                1,
+                None,
+                AccountIdentifier::base_account(receiver),
+                native_coin(),
+                100,
+            ),
+        ];
+
+        let preprocess = construction_preprocess(
+            ConstructionPreprocessRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                operations: operations.clone(),
+                metadata: None,
+            },
+            offline_context().await,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            vec![AccountIdentifier::base_account(sender)],
+            preprocess.required_public_keys
+        );
+
+        let payloads = construction_payloads(
+            ConstructionPayloadsRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                operations,
+                metadata: Some(ConstructionMetadata {
+                    sequence_number: U64(0),
+                    max_gas_amount: U64(1000),
+                    gas_price_per_unit: U64(100),
+                    expiry_time_secs: None,
+                    expiry_offset_secs: None,
+                    internal_operation: preprocess.options.internal_operation,
+                }),
+                public_keys: None,
+                auth_scheme: None,
+            },
+            offline_context().await,
+        )
+        .await
+        .unwrap();
+        let payload_signers: Vec<_> = payloads
+            .payloads
+            .iter()
+            .map(|payload| payload.account_identifier.clone())
+            .collect();
+        assert_eq!(preprocess.required_public_keys, payload_signers);
+
+        // Sign each payload with the requested signer, and combine should accept them
+        let signatures = payloads
+            .payloads
+            .into_iter()
+            .map(|payload| {
+                let message = hex::decode(&payload.hex_bytes).unwrap();
+                Signature {
+                    signing_payload: payload,
+                    public_key: PublicKey::try_from(sender_key.public_key()).unwrap(),
+                    signature_type: SignatureType::Ed25519,
+                    hex_bytes: sender_key
+                        .sign_arbitrary_message(&message)
+                        .to_encoded_string()
+                        .unwrap(),
+                }
+            })
+            .collect();
+        let combined = construction_combine(
+            ConstructionCombineRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                unsigned_transaction: payloads.unsigned_transaction,
+                signatures,
+                auth_scheme: None,
+                signature_encoding: None,
+            },
+            offline_context().await,
+        )
+        .await
+        .unwrap();
+
+        let signed_txn: SignedTransaction =
+            decode_bcs(&combined.signed_transaction, "SignedTransaction").unwrap();
+        let signers = parse_signed(&signed_txn).await.unwrap();
+        assert_eq!(preprocess.required_public_keys, signers);
+    }
+
+    /// Derives the sender from the key, then builds, simulates, signs, combines and parses a
+    /// transfer from it
+    async fn auth_scheme_round_trip(
+        auth_scheme: AuthScheme,
+        public_key: PublicKey,
+        sign: impl Fn(&[u8]) -> String,
+    ) -> ApiResult<(AccountAddress, Vec<AccountIdentifier>)> {
+        let sender = construction_derive(
+            ConstructionDeriveRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                public_key: public_key.clone(),
+                auth_scheme: Some(auth_scheme),
+            },
+            offline_context().await,
+        )
+        .await?
+        .account_identifier
+        .account_address()?;
+        let (_, receiver) = test_key(9);
+        let operations = vec![
+            Operation::withdraw(
+                0,
+                None,
+                AccountIdentifier::base_account(sender),
+                native_coin(),
+                100,
+            ),
+            Operation::deposit(
+                1,
+                None,
+                AccountIdentifier::base_account(receiver),
+                native_coin(),
+                100,
+            ),
+        ];
+
+        let preprocess = construction_preprocess(
+            ConstructionPreprocessRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                operations: operations.clone(),
+                metadata: Some(PreprocessMetadata {
+                    expiry_time_secs: None,
+                    expiry_offset_secs: None,
+                    sequence_number: None,
+                    max_gas_amount: Some(U64(1000)),
+                    gas_price: Some(U64(100)),
+                    public_keys: Some(vec![public_key.clone()]),
+                    auth_scheme: Some(auth_scheme),
+                    gas_price_multiplier: None,
+                    gas_price_priority: None,
+                    gas_currency: None,
+                }),
+            },
+            offline_context().await,
+        )
+        .await?;
+
+        // The transaction is simulated with an authenticator of the scheme
+        let metadata = construction_metadata(
+            ConstructionMetadataRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                options: preprocess.options,
+            },
+            online_context(account_upstream(0), &RosettaConfig::default()).await,
+        )
+        .await?;
+
+        let payloads = construction_payloads(
+            ConstructionPayloadsRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                operations,
+                metadata: Some(metadata.metadata),
+                public_keys: Some(vec![public_key.clone()]),
+                auth_scheme: Some(auth_scheme),
+            },
+            offline_context().await,
+        )
+        .await?;
+
+        let signatures = payloads
+            .payloads
+            .into_iter()
+            .map(|payload| {
+                let message = hex::decode(&payload.hex_bytes).unwrap();
+                Signature {
+                    signing_payload: payload,
+                    public_key: public_key.clone(),
+                    signature_type: SignatureType::Ed25519,
+                    hex_bytes: sign(&message),
+                }
+            })
+            .collect();
+        let combined = construction_combine(
+            ConstructionCombineRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                unsigned_transaction: payloads.unsigned_transaction,
+                signatures,
+                auth_scheme: Some(auth_scheme),
+                signature_encoding: None,
+            },
+            offline_context().await,
+        )
+        .await?;
+
+        let signed_txn: SignedTransaction =
+            decode_bcs(&combined.signed_transaction, "SignedTransaction")?;
+        Ok((sender, parse_signed(&signed_txn).await?))
+    }
+
+    #[tokio::test]
+    async fn each_auth_scheme_round_trips() {
+        let (private_key, address) = test_key(1);
+        let (sender, signers) = auth_scheme_round_trip(
+            AuthScheme::Ed25519,
+            PublicKey::try_from(private_key.public_key()).unwrap(),
+            |message| {
+                private_key
+                    .sign_arbitrary_message(message)
+                    .to_encoded_string()
+                    .unwrap()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(address, sender);
+        assert_eq!(vec![AccountIdentifier::base_account(sender)], signers);
+
+        // A 2 of 2 multi-ed25519 account
+        let multi_key = MultiEd25519PrivateKey::new(vec![test_key(2).0, test_key(3).0], 2).unwrap();
+        let multi_public_key = multi_key.public_key();
+        let (sender, signers) = auth_scheme_round_trip(
+            AuthScheme::MultiEd25519,
+            PublicKey {
+                hex_bytes: multi_public_key.to_encoded_string().unwrap(),
+                curve_type: CurveType::Edwards25519,
+            },
+            |message| {
+                multi_key
+                    .sign_arbitrary_message(message)
+                    .to_encoded_string()
+                    .unwrap()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            AuthenticationKey::multi_ed25519(&multi_public_key).derived_address(),
+            sender
+        );
+        assert_eq!(vec![AccountIdentifier::base_account(sender)], signers);
+    }
+
+    #[tokio::test]
+    async fn keys_of_another_auth_scheme_are_rejected() {
+        let (private_key, _) = test_key(1);
+        let multi_key = MultiEd25519PrivateKey::new(vec![test_key(2).0, test_key(3).0], 2).unwrap();
+        let keys = [
+            (
+                AuthScheme::MultiEd25519,
+                PublicKey::try_from(private_key.public_key()).unwrap(),
+            ),
+            (AuthScheme::Ed25519, PublicKey {
+                hex_bytes: multi_key.public_key().to_encoded_string().unwrap(),
+                curve_type: CurveType::Edwards25519,
+            }),
+        ];
+        for (auth_scheme, public_key) in keys {
+            let err = construction_derive(
+                ConstructionDeriveRequest {
+                    network_identifier: NetworkIdentifier::from(ChainId::test()),
+                    public_key,
+                    auth_scheme: Some(auth_scheme),
+                },
+                offline_context().await,
+            )
+            .await
+            .unwrap_err();
+            assert!(matches!(err, ApiError::InvalidInput(Some(_))), "{:?}", err);
+        }
+    }
+
+    /// DER encodes the halves of a raw signature as the INTEGERs r and s
+    fn to_der(raw: &[u8]) -> Vec<u8> {
+        let mut sequence = vec![];
+        for half in raw.chunks(raw.len() / 2) {
+            let mut value: Vec<u8> = half.iter().copied().skip_while(|byte| *byte == 0).collect();
+            // Keep the integer positive
+            if value.first().map_or(true, |byte| *byte >= 0x80) {
+                value.insert(0, 0);
+            }
+            sequence.extend([0x02, value.len() as u8]);
+            sequence.extend(value);
+        }
+        let mut der = vec![0x30, sequence.len() as u8];
+        der.extend(sequence);
+        der
+    }
+
+    async fn combine_with_encoding(
+        raw_txn: &RawTransaction,
+        public_key: PublicKey,
+        signature: Vec<u8>,
+        auth_scheme: AuthScheme,
+        signature_encoding: SignatureEncoding,
+    ) -> ApiResult<SignedTransaction> {
+        let combined = construction_combine(
+            ConstructionCombineRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                unsigned_transaction: encode_bcs(raw_txn).unwrap(),
+                signatures: vec![Signature {
+                    signing_payload: SigningPayload {
+                        account_identifier: AccountIdentifier::base_account(raw_txn.sender()),
+                        hex_bytes: hex::encode(signing_message(raw_txn).unwrap()),
+                        signature_type: Some(SignatureType::Ed25519),
+                    },
+                    public_key,
+                    signature_type: SignatureType::Ed25519,
+                    hex_bytes: hex::encode(signature),
+                }],
+                auth_scheme: Some(auth_scheme),
+                signature_encoding: Some(signature_encoding),
+            },
+            offline_context().await,
+        )
+        .await?;
+        decode_bcs(&combined.signed_transaction, "SignedTransaction")
+    }
+
+    #[tokio::test]
+    async fn hsm_signature_encodings_are_normalized() {
+        let (private_key, sender) = test_key(1);
+        let (_, receiver) = test_key(2);
+        let raw_txn = transfer_txn(sender, receiver);
+        let public_key = PublicKey::try_from(private_key.public_key()).unwrap();
+        let expected = raw_txn
+            .clone()
+            .sign(&private_key, private_key.public_key())
+            .unwrap()
+            .into_inner();
+        let raw = private_key
+            .sign_arbitrary_message(&signing_message(&raw_txn).unwrap())
+            .to_bytes()
+            .to_vec();
+
+        for (signature_encoding, signature) in [
+            (SignatureEncoding::Rosetta, raw.clone()),
+            (SignatureEncoding::Raw, raw.clone()),
+            (SignatureEncoding::Der, to_der(&raw)),
+        ] {
+            let signed_txn = combine_with_encoding(
+                &raw_txn,
+                public_key.clone(),
+                signature,
+                AuthScheme::Ed25519,
+                signature_encoding,
+            )
+            .await
+            .unwrap();
+            assert_eq!(expected, signed_txn, "{}", signature_encoding);
+        }
+
+        // Padding is stripped from DER integers, and short integers are padded to 32 bytes
+        let mut padded = raw;
+        padded[0] = 0;
+        padded[32] = 0x80;
+        assert_eq!(padded, der_signature_to_raw(&to_der(&padded)).unwrap());
+    }
+
+    #[tokio::test]
+    async fn malformed_hsm_signatures_are_rejected() {
+        let (private_key, sender) = test_key(1);
+        let (_, receiver) = test_key(2);
+        let raw_txn = transfer_txn(sender, receiver);
+        let public_key = PublicKey::try_from(private_key.public_key()).unwrap();
+        let raw = private_key
+            .sign_arbitrary_message(&signing_message(&raw_txn).unwrap())
+            .to_bytes()
+            .to_vec();
+        let der = to_der(&raw);
+
+        let mut wrong_tag = der.clone();
+        wrong_tag[0] = 0x31;
+        let mut trailing = der.clone();
+        trailing.push(0);
+        let mut truncated = der.clone();
+        truncated.pop();
+        // A 33 byte r
+        let mut oversized = vec![0x30, 0x45, 0x02, 0x21, 0x01];
+        oversized.extend(&raw[..32]);
+        oversized.extend([0x02, 0x20]);
+        oversized.extend(&raw[32..]);
+
+        for (signature_encoding, signature) in [
+            (SignatureEncoding::Raw, raw[..63].to_vec()),
+            (SignatureEncoding::Raw, [&raw[..], &[0u8][..]].concat()),
+            (SignatureEncoding::Der, raw.clone()),
+            (SignatureEncoding::Der, wrong_tag),
+            (SignatureEncoding::Der, trailing),
+            (SignatureEncoding::Der, truncated),
+            (SignatureEncoding::Der, oversized),
+        ] {
+            let err = combine_with_encoding(
+                &raw_txn,
+                public_key.clone(),
+                signature,
+                AuthScheme::Ed25519,
+                signature_encoding,
+            )
+            .await
+            .unwrap_err();
+            assert!(matches!(err, ApiError::InvalidInput(Some(_))), "{:?}", err);
+        }
+
+        // Only single key signatures are accepted from an HSM
+        let err = combine_with_encoding(
+            &raw_txn,
+            public_key,
+            raw,
+            AuthScheme::MultiEd25519,
+            SignatureEncoding::Raw,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, ApiError::InvalidInput(Some(_))), "{:?}", err);
+    }
+
+    #[test]
+    fn operation_types_round_trip() {
+        for operation_type in OperationType::all() {
+            assert_eq!(
+                operation_type,
+                OperationType::from_str(&operation_type.to_string()).unwrap()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn every_operation_type_is_parsed() {
+        let (_, owner) = test_key(1);
+        let (_, operator) = test_key(2);
+        let (_, other) = test_key(3);
+        let internal_operations = vec![
+            InternalOperation::CreateAccount(CreateAccount {
+                sender: owner,
+                new_account: other,
+            }),
+            InternalOperation::Transfer(Transfer {
+                sender: owner,
+                receiver: other,
+                amount: U64(100),
+                currency: native_coin(),
+            }),
+            InternalOperation::SetOperator(SetOperator {
+                owner,
+                old_operator: Some(operator),
+                new_operator: other,
+            }),
+            InternalOperation::SetVoter(SetVoter {
+                owner,
+                operator: Some(operator),
+                new_voter: other,
+            }),
+            InternalOperation::InitializeStakePool(InitializeStakePool {
+                owner,
+                operator,
+                voter: other,
+                amount: 100,
+                commission_percentage: 10,
+                seed: vec![],
+            }),
+            InternalOperation::ResetLockup(ResetLockup { owner, operator }),
+            InternalOperation::RotateAuthKey(ed25519_rotation(0)),
+            InternalOperation::EntryFunction(message_call(owner)),
+        ];
+
+        let mut parsed = BTreeSet::new();
+        for internal_operation in internal_operations {
+            let (payload, sender) = internal_operation.payload().unwrap();
+            let txn = RawTransaction::new(sender, 0, payload, 1000, 100, u64::MAX, ChainId::test());
+            let response = construction_parse(
+                ConstructionParseRequest {
+                    network_identifier: NetworkIdentifier::from(ChainId::test()),
+                    signed: false,
+                    transaction: encode_bcs(&txn).unwrap(),
+                    strict: false,
+                },
+                offline_context().await,
+            )
+            .await
+            .unwrap();
+            for operation in response.operations {
+                parsed.insert(OperationType::from_str(&operation.operation_type).unwrap());
+            }
+        }
+
+        // Fees and staking rewards only come from committed transactions, not construction
+        for operation_type in OperationType::all() {
+            match operation_type {
+                OperationType::Fee | OperationType::StakingReward => {},
+                _ => assert!(
+                    parsed.contains(&operation_type),
+                    "{} is advertised but never parsed",
+                    operation_type
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn suggested_fees_by_priority() {
+        let mut options = ConstructionOptions {
+            internal_operation: InternalOperation::CreateAccount(CreateAccount {
+                sender: AccountAddress::ONE,
+                new_account: AccountAddress::ONE,
+            }),
+            max_gas_amount: None,
+            gas_price_per_unit: None,
+            expiry_time_secs: None,
+            expiry_offset_secs: None,
+            sequence_number: None,
+            public_keys: None,
+            auth_scheme: None,
+            gas_price_multiplier: None,
+            gas_price_priority: Some(GasPricePriority::High),
+            gas_currency: None,
+        };
+        let gas_estimation = GasEstimation {
+            deprioritized_gas_estimate: Some(100),
+            gas_estimate: 150,
+            prioritized_gas_estimate: Some(200),
+        };
+
+        // The requested priority comes first, then the rest from low to high
+        let fees = suggested_fees(Some(&gas_estimation), &options, 200, 10).unwrap();
+        let fees: Vec<_> = fees
+            .into_iter()
+            .map(|fee| {
+                let metadata = fee.metadata.unwrap();
+                (
+                    metadata.gas_price_priority,
+                    metadata.gas_price_per_unit.0,
+                    fee.value,
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (GasPricePriority::High, 200, "2000".to_string()),
+                (GasPricePriority::Low, 100, "1000".to_string()),
+                (GasPricePriority::Normal, 150, "1500".to_string()),
+            ],
+            fees
+        );
+
+        // The multiplier applies to every priority
+        options.gas_price_multiplier = Some(200);
+        let fees = suggested_fees(Some(&gas_estimation), &options, 400, 10).unwrap();
+        assert_eq!(
+            vec!["4000", "2000", "3000"],
+            fees.iter()
+                .map(|fee| fee.value.as_str())
+                .collect::<Vec<_>>()
+        );
+
+        // Without estimates by priority, or without estimating, there's a single fee
+        let gas_estimation = GasEstimation {
+            deprioritized_gas_estimate: None,
+            gas_estimate: 150,
+            prioritized_gas_estimate: None,
+        };
+        for gas_estimation in [Some(&gas_estimation), None] {
+            let fees = suggested_fees(gas_estimation, &options, 150, 10).unwrap();
+            assert_eq!(1, fees.len());
+            assert_eq!("1500", fees[0].value);
+            assert!(fees[0].metadata.is_none());
+        }
+    }
+
+    #[test]
+    fn transfer_creates_missing_receiver() {
+        let (_, sender) = test_key(1);
+        let (_, receiver) = test_key(2);
+        let (payload, payload_sender) = InternalOperation::Transfer(Transfer {
+            sender,
+            receiver,
+            amount: U64(100),
+            currency: native_coin(),
+        })
+        .payload()
+        .unwrap();
+        assert_eq!(sender, payload_sender);
+
+        // The account-creating transfer is used, so the receiver doesn't need to exist
+        match payload {
+            TransactionPayload::EntryFunction(entry_function) => {
+                assert_eq!(AccountAddress::ONE, *entry_function.module().address());
+                assert_eq!("aptos_account", entry_function.module().name().as_str());
+                assert_eq!("transfer", entry_function.function().as_str());
+            },
+            payload => panic!("Unexpected transfer payload {:?}", payload),
+        }
+    }
+
+    /// A call to an entry function that isn't modeled as any other operation
+    fn message_call(sender: AccountAddress) -> EntryFunctionCall {
+        EntryFunctionCall::from_metadata(sender, "0xcafe::message::set_message", &[], &[
+            hex::encode(bcs::to_bytes("hello").unwrap()),
+        ])
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn entry_function_round_trip() {
+        let (_, sender) = test_key(1);
+        let operation = Operation::entry_function(0, None, &message_call(sender));
+        assert_eq!(
+            "0xcafe::message::set_message",
+            operation
+                .metadata
+                .as_ref()
+                .unwrap()
+                .function
+                .as_ref()
+                .unwrap()
+        );
+
+        let internal_operation = InternalOperation::extract(&vec![operation.clone()]).unwrap();
+        let (payload, _) = internal_operation.payload().unwrap();
+        let txn = RawTransaction::new(sender, 0, payload, 1000, 100, u64::MAX, ChainId::test());
+        let response = construction_parse(
+            ConstructionParseRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                signed: false,
+                transaction: encode_bcs(&txn).unwrap(),
+                strict: false,
+            },
+            offline_context().await,
+        )
+        .await
+        .unwrap();
+        assert_eq!(vec![operation], response.operations);
+
+        // The function must be fully qualified
+        for function in [
+            "message::set_message",
+            "0xcafe::message::",
+            "0xcafe::a::b::c",
+        ] {
+            let err = EntryFunctionCall::from_metadata(sender, function, &[], &[]).unwrap_err();
+            assert!(matches!(err, ApiError::InvalidInput(_)));
+        }
+    }
+
+    #[tokio::test]
+    async fn strict_parse_rejects_unmodeled_operations() {
+        let (_, sender) = test_key(1);
+        let (_, receiver) = test_key(2);
+        let (payload, _) = InternalOperation::EntryFunction(message_call(sender))
+            .payload()
+            .unwrap();
+        let call_txn =
+            RawTransaction::new(sender, 0, payload, 1000, 100, u64::MAX, ChainId::test());
+        let parse = |txn: RawTransaction, strict: bool| async move {
+            construction_parse(
+                ConstructionParseRequest {
+                    network_identifier: NetworkIdentifier::from(ChainId::test()),
+                    signed: false,
+                    transaction: encode_bcs(&txn).unwrap(),
+                    strict,
+                },
+                offline_context().await,
+            )
+            .await
+        };
+
+        // Modeled operations parse either way
+        assert_eq!(
+            2,
+            parse(transfer_txn(sender, receiver), true)
+                .await
+                .unwrap()
+                .operations
+                .len()
+        );
+
+        // A call to an arbitrary entry function is only rejected when strict
+        parse(call_txn.clone(), false).await.unwrap();
+        match parse(call_txn, true).await.unwrap_err() {
+            ApiError::TransactionParseError(Some(details)) => {
+                assert!(details.contains("[0]"), "{}", details)
+            },
+            err => panic!("Unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn entry_function_arity() {
+        let function = MoveFunction {
+            name: "set_message".parse().unwrap(),
+            visibility: MoveFunctionVisibility::Public,
+            is_entry: true,
+            generic_type_params: vec![],
+            params: vec![
+                MoveType::Reference {
+                    mutable: false,
+                    to: Box::new(MoveType::Signer),
+                },
+                MoveType::Vector {
+                    items: Box::new(MoveType::U8),
+                },
+            ],
+            return_: vec![],
+        };
+
+        // Signers aren't passed as arguments
+        let (_, sender) = test_key(1);
+        let mut call = message_call(sender);
+        check_entry_function_arity(&call, &function).unwrap();
+
+        call.arguments.push(vec![]);
+        check_entry_function_arity(&call, &function).unwrap_err();
+
+        let call = message_call(sender);
+        let generic_function = MoveFunction {
+            generic_type_params: vec![MoveFunctionGenericTypeParam {
+                constraints: vec![],
+            }],
+            ..function
+        };
+        check_entry_function_arity(&call, &generic_function).unwrap_err();
+    }
+
+    /// Rotates the account of `test_key(1)` to the new key, with proofs for the sequence number
+    fn rotation(
+        new_auth_scheme: AuthScheme,
+        new_public_key: PublicKey,
+        sign_new: impl Fn(&[u8]) -> String,
+        sequence_number: u64,
+    ) -> RotateAuthKey {
+        let (current_key, account) = test_key(1);
+        let mut rotate_auth_key = RotateAuthKey {
+            account,
+            current_auth_scheme: AuthScheme::Ed25519,
+            current_public_key: PublicKey::try_from(current_key.public_key()).unwrap(),
+            new_auth_scheme,
+            new_public_key,
+            current_key_proof: String::new(),
+            new_key_proof: String::new(),
+        };
+        let challenge = rotate_auth_key.challenge(sequence_number).unwrap();
+        rotate_auth_key.current_key_proof = current_key
+            .sign_arbitrary_message(&challenge)
+            .to_encoded_string()
+            .unwrap();
+        rotate_auth_key.new_key_proof = sign_new(&challenge);
+        rotate_auth_key
+    }
+
+    fn ed25519_rotation(sequence_number: u64) -> RotateAuthKey {
+        let (new_key, _) = test_key(2);
+        rotation(
+            AuthScheme::Ed25519,
+            PublicKey::try_from(new_key.public_key()).unwrap(),
+            |challenge| {
+                new_key
+                    .sign_arbitrary_message(challenge)
+                    .to_encoded_string()
+                    .unwrap()
+            },
+            sequence_number,
+        )
+    }
+
+    async fn preprocess_rotation(
+        rotate_auth_key: &RotateAuthKey,
+        sequence_number: Option<U64>,
+    ) -> ApiResult<ConstructionOptions> {
+        Ok(construction_preprocess(
+            ConstructionPreprocessRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                operations: vec![Operation::rotate_auth_key(0, None, rotate_auth_key)],
+                metadata: Some(PreprocessMetadata {
+                    expiry_time_secs: None,
+                    expiry_offset_secs: None,
+                    sequence_number,
+                    max_gas_amount: Some(U64(1000)),
+                    gas_price: None,
+                    public_keys: None,
+                    auth_scheme: None,
+                    gas_price_multiplier: None,
+                    gas_price_priority: None,
+                    gas_currency: None,
+                }),
+            },
+            offline_context().await,
+        )
+        .await?
+        .options)
+    }
+
+    async fn rotation_payloads(
+        rotate_auth_key: &RotateAuthKey,
+        sequence_number: u64,
+    ) -> ApiResult<ConstructionPayloadsResponse> {
+        construction_payloads(
+            ConstructionPayloadsRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                operations: vec![Operation::rotate_auth_key(0, None, rotate_auth_key)],
+                metadata: Some(ConstructionMetadata {
+                    sequence_number: U64(sequence_number),
+                    max_gas_amount: U64(1000),
+                    gas_price_per_unit: U64(100),
+                    expiry_time_secs: None,
+                    expiry_offset_secs: None,
+                    internal_operation: InternalOperation::RotateAuthKey(rotate_auth_key.clone()),
+                }),
+                public_keys: None,
+                auth_scheme: None,
+            },
+            offline_context().await,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn rotate_auth_key_round_trip() {
+        let multi_key = MultiEd25519PrivateKey::new(vec![test_key(2).0, test_key(3).0], 2).unwrap();
+        let multi_rotation = rotation(
+            AuthScheme::MultiEd25519,
+            PublicKey {
+                hex_bytes: multi_key.public_key().to_encoded_string().unwrap(),
+                curve_type: CurveType::Edwards25519,
+            },
+            |challenge| {
+                multi_key
+                    .sign_arbitrary_message(challenge)
+                    .to_encoded_string()
+                    .unwrap()
+            },
+            5,
+        );
+
+        for rotate_auth_key in [ed25519_rotation(5), multi_rotation] {
+            let options = preprocess_rotation(&rotate_auth_key, Some(U64(5)))
+                .await
+                .unwrap();
+            assert_eq!(Some(U64(5)), options.sequence_number);
+            assert_eq!(
+                InternalOperation::RotateAuthKey(rotate_auth_key.clone()),
+                options.internal_operation
+            );
+
+            let payloads = rotation_payloads(&rotate_auth_key, 5).await.unwrap();
+            let response = construction_parse(
+                ConstructionParseRequest {
+                    network_identifier: NetworkIdentifier::from(ChainId::test()),
+                    signed: false,
+                    transaction: payloads.unsigned_transaction,
+                    strict: true,
+                },
+                offline_context().await,
+            )
+            .await
+            .unwrap();
+            assert_eq!(
+                vec![Operation::rotate_auth_key(0, None, &rotate_auth_key)],
+                response.operations
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn bad_rotation_proofs_are_rejected() {
+        let rotate_auth_key = ed25519_rotation(5);
+
+        // The sequence number is signed, so it must be given up front
+        let err = preprocess_rotation(&rotate_auth_key, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ApiError::InvalidInput(Some(_))), "{:?}", err);
+
+        // Proofs for another sequence number
+        let err = preprocess_rotation(&rotate_auth_key, Some(U64(6)))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, ApiError::InvalidSignature(Some(_))),
+            "{:?}",
+            err
+        );
+        let err = rotation_payloads(&rotate_auth_key, 6).await.unwrap_err();
+        assert!(
+            matches!(err, ApiError::InvalidSignature(Some(_))),
+            "{:?}",
+            err
+        );
+
+        // Each key's proof signed by the other key
+        let mut swapped = rotate_auth_key.clone();
+        std::mem::swap(&mut swapped.current_key_proof, &mut swapped.new_key_proof);
+        let err = rotation_payloads(&swapped, 5).await.unwrap_err();
+        assert!(
+            matches!(err, ApiError::InvalidSignature(Some(_))),
+            "{:?}",
+            err
+        );
+
+        // A new key that isn't of its scheme
+        let mut mismatched = rotate_auth_key;
+        mismatched.new_auth_scheme = AuthScheme::MultiEd25519;
+        let err = preprocess_rotation(&mismatched, Some(U64(5)))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ApiError::InvalidInput(Some(_))), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn batch_transfer_round_trip() {
+        let (_, sender) = test_key(1);
+        let receivers: Vec<_> = (2..5).map(|seed| test_key(seed).1).collect();
+        let batch_transfer = BatchTransfer::new(
+            sender,
+            receivers.clone(),
+            vec![U64(100), U64(200), U64(300)],
+            native_coin(),
+        )
+        .unwrap();
+        let operations = batch_transfer.operations();
+        assert_eq!(6, operations.len());
+
+        let preprocess = construction_preprocess(
+            ConstructionPreprocessRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                operations: operations.clone(),
+                metadata: None,
+            },
+            offline_context().await,
+        )
+        .await
+        .unwrap();
+        let internal_operation = InternalOperation::BatchTransfer(batch_transfer);
+        assert_eq!(internal_operation, preprocess.options.internal_operation);
+
+        let payloads = construction_payloads(
+            ConstructionPayloadsRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                operations: operations.clone(),
+                metadata: Some(ConstructionMetadata {
+                    sequence_number: U64(0),
+                    max_gas_amount: U64(1000),
+                    gas_price_per_unit: U64(100),
+                    expiry_time_secs: None,
+                    expiry_offset_secs: None,
+                    internal_operation,
+                }),
+                public_keys: None,
+                auth_scheme: None,
+            },
+            offline_context().await,
+        )
+        .await
+        .unwrap();
+        let response = construction_parse(
+            ConstructionParseRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                signed: false,
+                transaction: payloads.unsigned_transaction,
+                strict: true,
+            },
+            offline_context().await,
+        )
+        .await
+        .unwrap();
+        assert_eq!(operations, response.operations);
+
+        // Withdraws from more than one sender can't be batched
+        let mut other_sender = operations.clone();
+        other_sender[2].account = Some(AccountIdentifier::base_account(receivers[0]));
+        InternalOperation::extract(&other_sender).unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn empty_operations_are_invalid() {
+        let err = construction_preprocess(
+            ConstructionPreprocessRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                operations: vec![],
+                metadata: None,
+            },
+            offline_context().await,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, ApiError::InvalidOperations(_)), "{:?}", err);
+        BatchTransfer::extract_batch_transfer(&[]).unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn batch_transfer_lengths_must_match() {
+        let (_, sender) = test_key(1);
+        let (_, receiver) = test_key(2);
+        let err = BatchTransfer::new(
+            sender,
+            vec![receiver, receiver],
+            vec![U64(100)],
+            native_coin(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ApiError::InvalidInput(Some(_))), "{:?}", err);
+
+        // A transaction built elsewhere with mismatched arguments can't be parsed
+        let unsigned_txn = RawTransaction::new(
+            sender,
+            0,
+            aptos_stdlib::aptos_account_batch_transfer(vec![receiver, receiver], vec![100]),
+            1000,
+            100,
+            u64::MAX,
+            ChainId::test(),
+        );
+        let err = construction_parse(
+            ConstructionParseRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                signed: false,
+                transaction: encode_bcs(&unsigned_txn).unwrap(),
+                strict: false,
+            },
+            offline_context().await,
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            matches!(err, ApiError::TransactionParseError(Some(_))),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn self_transfers_can_be_forbidden() {
+        let (_, sender) = test_key(1);
+        let (_, receiver) = test_key(2);
+        let transfer = |receiver: AccountAddress, server_context: RosettaContext| async move {
+            let operations = vec![
+                Operation::withdraw(
+                    0,
+                    None,
+                    AccountIdentifier::base_account(sender),
+                    native_coin(),
+                    100,
+                ),
+                Operation::deposit(
+                    1,
+                    None,
+                    AccountIdentifier::base_account(receiver),
+                    native_coin(),
+                    100,
+                ),
+            ];
+            let preprocess = construction_preprocess(
+                ConstructionPreprocessRequest {
+                    network_identifier: NetworkIdentifier::from(ChainId::test()),
+                    operations: operations.clone(),
+                    metadata: None,
+                },
+                server_context.clone(),
+            )
+            .await
+            .map(|_| ());
+            let payloads = construction_payloads(
+                ConstructionPayloadsRequest {
+                    network_identifier: NetworkIdentifier::from(ChainId::test()),
+                    operations,
+                    metadata: Some(ConstructionMetadata {
+                        sequence_number: U64(0),
+                        max_gas_amount: U64(1000),
+                        gas_price_per_unit: U64(100),
+                        expiry_time_secs: None,
+                        expiry_offset_secs: None,
+                        internal_operation: InternalOperation::Transfer(Transfer {
+                            sender,
+                            receiver,
+                            amount: U64(100),
+                            currency: native_coin(),
+                        }),
+                    }),
+                    public_keys: None,
+                    auth_scheme: None,
+                },
+                server_context,
+            )
+            .await
+            .map(|_| ());
+            (preprocess, payloads)
+        };
+
+        // Allowed by default
+        let (preprocess, payloads) = transfer(sender, offline_context().await).await;
+        preprocess.unwrap();
+        payloads.unwrap();
+
+        let mut server_context = offline_context().await;
+        server_context.forbid_self_transfer = true;
+        let (preprocess, payloads) = transfer(sender, server_context.clone()).await;
+        for result in [preprocess, payloads] {
+            let err = result.unwrap_err();
+            assert!(
+                matches!(err, ApiError::InvalidTransferOperations(Some(_))),
+                "{:?}",
+                err
+            );
+        }
+
+        // Transfers to other accounts are still allowed
+        let (preprocess, payloads) = transfer(receiver, server_context).await;
+        preprocess.unwrap();
+        payloads.unwrap();
+    }
+
+    /// A call to one of the framework's transfer functions, as an entry function operation
+    fn transfer_call(
+        sender: AccountAddress,
+        function: &str,
+        type_arguments: &[String],
+        receivers: &[AccountAddress],
+        amounts: &[u64],
+    ) -> Operation {
+        let arguments = if function.contains("batch") {
+            vec![
+                hex::encode(bcs::to_bytes(receivers).unwrap()),
+                hex::encode(bcs::to_bytes(amounts).unwrap()),
+            ]
+        } else {
+            vec![
+                hex::encode(bcs::to_bytes(&receivers[0]).unwrap()),
+                hex::encode(bcs::to_bytes(&amounts[0]).unwrap()),
+            ]
+        };
+        Operation::entry_function(
+            0,
+            None,
+            &EntryFunctionCall::from_metadata(sender, function, type_arguments, &arguments)
+                .unwrap(),
+        )
+    }
+
+    /// Preprocesses the operation, which checks it like any other construction endpoint
+    async fn preprocess_operation(
+        operation: Operation,
+        server_context: RosettaContext,
+    ) -> ApiResult<()> {
+        construction_preprocess(
+            ConstructionPreprocessRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                operations: vec![operation],
+                metadata: None,
+            },
+            server_context,
+        )
+        .await
+        .map(|_| ())
+    }
+
+    #[tokio::test]
+    async fn entry_function_self_transfers_can_be_forbidden() {
+        let (_, sender) = test_key(1);
+        let (_, receiver) = test_key(2);
+        let apt = vec!["0x1::aptos_coin::AptosCoin".to_string()];
+        let mut server_context = offline_context().await;
+        server_context.forbid_self_transfer = true;
+
+        for (function, type_arguments) in [
+            ("0x1::aptos_account::transfer", vec![]),
+            ("0x1::coin::transfer", apt.clone()),
+            ("0x1::aptos_account::transfer_coins", apt.clone()),
+            ("0x1::aptos_account::batch_transfer", vec![]),
+            ("0x1::aptos_account::batch_transfer_coins", apt.clone()),
+        ] {
+            let err = preprocess_operation(
+                transfer_call(sender, function, &type_arguments, &[receiver, sender], &[
+                    1, 1,
+                ]),
+                server_context.clone(),
+            )
+            .await
+            .unwrap_err();
+            assert!(
+                matches!(err, ApiError::InvalidTransferOperations(Some(_))),
+                "{}: {:?}",
+                function,
+                err
+            );
+
+            preprocess_operation(
+                transfer_call(sender, function, &type_arguments, &[receiver], &[1]),
+                server_context.clone(),
+            )
+            .await
+            .unwrap();
+        }
+
+        // Other entry functions aren't transfers
+        preprocess_operation(
+            Operation::entry_function(0, None, &message_call(sender)),
+            server_context,
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn entry_function_transfer_amounts_can_be_limited() {
+        let (_, sender) = test_key(1);
+        let (_, receiver) = test_key(2);
+        let apt = vec!["0x1::aptos_coin::AptosCoin".to_string()];
+        let mut server_context = offline_context().await;
+        server_context.max_transfer_amount = Some(1000);
+
+        for (function, type_arguments) in [
+            ("0x1::aptos_account::transfer", vec![]),
+            ("0x1::coin::transfer", apt.clone()),
+            ("0x1::aptos_account::transfer_coins", apt.clone()),
+            ("0x1::aptos_account::batch_transfer", vec![]),
+            ("0x1::aptos_account::batch_transfer_coins", apt.clone()),
+        ] {
+            let err = preprocess_operation(
+                transfer_call(sender, function, &type_arguments, &[receiver], &[1001]),
+                server_context.clone(),
+            )
+            .await
+            .unwrap_err();
+            assert!(
+                matches!(err, ApiError::TransferAmountTooLarge(Some(_))),
+                "{}: {:?}",
+                function,
+                err
+            );
+
+            preprocess_operation(
+                transfer_call(sender, function, &type_arguments, &[receiver], &[1000]),
+                server_context.clone(),
+            )
+            .await
+            .unwrap();
+        }
+
+        // A batch is limited by its total
+        let err = preprocess_operation(
+            transfer_call(
+                sender,
+                "0x1::aptos_account::batch_transfer",
+                &[],
+                &[receiver, receiver],
+                &[600, 600],
+            ),
+            server_context.clone(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, ApiError::TransferAmountTooLarge(Some(_))));
+
+        // Arguments that can't be checked are rejected
+        let call =
+            EntryFunctionCall::from_metadata(sender, "0x1::aptos_account::transfer", &[], &[
+                hex::encode(bcs::to_bytes(&receiver).unwrap()),
+            ])
+            .unwrap();
+        let err = preprocess_operation(Operation::entry_function(0, None, &call), server_context)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ApiError::InvalidInput(Some(_))), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn transfer_amounts_can_be_limited() {
+        let (_, sender) = test_key(1);
+        let (_, receiver) = test_key(2);
+        let transfer = |amount: u64| async move {
+            let mut server_context = offline_context().await;
+            server_context.max_transfer_amount = Some(1000);
+            let operations = vec![
+                Operation::withdraw(
+                    0,
+                    None,
+                    AccountIdentifier::base_account(sender),
+                    native_coin(),
+                    amount,
+                ),
+                Operation::deposit(
+                    1,
+                    None,
+                    AccountIdentifier::base_account(receiver),
+                    native_coin(),
+                    amount,
+                ),
+            ];
+            let preprocess = construction_preprocess(
+                ConstructionPreprocessRequest {
+                    network_identifier: NetworkIdentifier::from(ChainId::test()),
+                    operations: operations.clone(),
+                    metadata: None,
+                },
+                server_context.clone(),
+            )
+            .await
+            .map(|_| ());
+            let payloads = construction_payloads(
+                ConstructionPayloadsRequest {
+                    network_identifier: NetworkIdentifier::from(ChainId::test()),
+                    operations,
+                    metadata: Some(ConstructionMetadata {
+                        sequence_number: U64(0),
+                        max_gas_amount: U64(1000),
+                        gas_price_per_unit: U64(100),
+                        expiry_time_secs: None,
+                        expiry_offset_secs: None,
+                        internal_operation: InternalOperation::Transfer(Transfer {
+                            sender,
+                            receiver,
+                            amount: U64(amount),
+                            currency: native_coin(),
+                        }),
+                    }),
+                    public_keys: None,
+                    auth_scheme: None,
+                },
+                server_context,
+            )
+            .await
+            .map(|_| ());
+            (preprocess, payloads)
+        };
+
+        // Up to the limit is allowed
+        for amount in [999, 1000] {
+            let (preprocess, payloads) = transfer(amount).await;
+            preprocess.unwrap();
+            payloads.unwrap();
+        }
+
+        // Over it, the limit and the amount are both in the error
+        let (preprocess, payloads) = transfer(1001).await;
+        for result in [preprocess, payloads] {
+            match result.unwrap_err() {
+                ApiError::TransferAmountTooLarge(Some(details)) => {
+                    assert!(details.contains("1001"), "{}", details);
+                    assert!(details.contains("1000"), "{}", details);
+                },
+                err => panic!("Unexpected error {:?}", err),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn gas_currency_round_trip() {
+        let (_, sender) = test_key(1);
+        let (_, receiver) = test_key(2);
+        let other_coin = Currency {
+            symbol: "USDC".to_string(),
+            decimals: 6,
+            metadata: Some(CurrencyMetadata {
+                move_type: "0xcafe::usdc::USDC".to_string(),
+            }),
+        };
+        let preprocess = |gas_currency: Option<Currency>| async move {
+            construction_preprocess(
+                ConstructionPreprocessRequest {
+                    network_identifier: NetworkIdentifier::from(ChainId::test()),
+                    operations: vec![
+                        Operation::withdraw(
+                            0,
+                            None,
+                            AccountIdentifier::base_account(sender),
+                            native_coin(),
+                            100,
+                        ),
+                        Operation::deposit(
+                            1,
+                            None,
+                            AccountIdentifier::base_account(receiver),
+                            native_coin(),
+                            100,
+                        ),
+                    ],
+                    metadata: Some(PreprocessMetadata {
+                        expiry_time_secs: None,
+                        expiry_offset_secs: None,
+                        sequence_number: None,
+                        max_gas_amount: Some(U64(1000)),
+                        gas_price: None,
+                        public_keys: None,
+                        auth_scheme: None,
+                        gas_price_multiplier: None,
+                        gas_price_priority: None,
+                        gas_currency,
+                    }),
+                },
+                offline_context().await,
+            )
+            .await
+            .unwrap()
+            .options
+        };
+
+        // Offline, the gas currency is trusted and passed through to metadata
+        for gas_currency in [None, Some(native_coin()), Some(other_coin.clone())] {
+            let options = preprocess(gas_currency.clone()).await;
+            assert_eq!(gas_currency, options.gas_currency);
+            let options: ConstructionOptions =
+                serde_json::from_value(serde_json::to_value(&options).unwrap()).unwrap();
+            assert_eq!(gas_currency, options.gas_currency);
+        }
+
+        // Online, only the native coin can pay for gas
+        check_gas_currency(&native_coin()).unwrap();
+        let err = check_gas_currency(&other_coin).unwrap_err();
+        assert!(matches!(err, ApiError::UnsupportedCurrency(Some(_))));
+    }
+
+    /// An upstream which accepts every submit after `submit_delay`, and has committed
+    /// `committed` if there is one
+    fn mock_upstream(committed: Option<SignedTransaction>, submit_delay: Duration) -> url::Url {
+        let route = warp::any().and(warp::method()).and_then(move |method| {
+            let committed = committed.clone();
+            async move {
+                if method == warp::http::Method::POST {
+                    tokio::time::sleep(submit_delay).await;
+                    return Ok::<_, Infallible>(Vec::<u8>::new().into_response());
+                }
+                Ok(transaction_response(committed.as_ref()))
+            }
+        });
+        test_utils::mock_upstream(MockLedger::default(), route)
+    }
+
+    /// The upstream's response for a transaction by hash, not found unless it's `committed`
+    fn transaction_response(committed: Option<&SignedTransaction>) -> warp::reply::Response {
+        let txn = match committed {
+            Some(txn) => txn,
+            None => return StatusCode::NOT_FOUND.into_response(),
+        };
+        bcs::to_bytes(&TransactionData::OnChain(TransactionOnChainData {
+            version: 7,
+            transaction: Transaction::UserTransaction(txn.clone()),
+            info: TransactionInfo::new(
+                txn.clone().committed_hash(),
+                HashValue::zero(),
+                HashValue::zero(),
+                None,
+                150,
+                ExecutionStatus::Success,
+            ),
+            events: vec![],
+            accumulator_root_hash: HashValue::zero(),
+            changes: WriteSet::default(),
+        }))
+        .unwrap()
+        .into_response()
+    }
+
+    async fn online_context(upstream: url::Url, config: &RosettaConfig) -> RosettaContext {
+        let rest_clients = Arc::new(UpstreamClients::new(
+            ChainId::test(),
+            vec![aptos_rest_client::Client::new(upstream)],
+            None,
+        ));
+        let block_cache = BlockRetriever::new(
+            100,
+            rest_clients.clone(),
+            DEFAULT_SLOW_BLOCK_FETCH_THRESHOLD,
+        );
+        RosettaContext::new(
+            RosettaMode::Online,
+            Some(rest_clients),
+            ChainId::test(),
+            Some(Arc::new(block_cache)),
+            vec![],
+            config,
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn submit(
+        signed_txn: &SignedTransaction,
+        committed: bool,
+        wait_for_commit_secs: Option<u64>,
+    ) -> ApiResult<Option<SubmitMetadata>> {
+        let upstream = mock_upstream(committed.then(|| signed_txn.clone()), Duration::ZERO);
+        let server_context = online_context(upstream, &RosettaConfig {
+            upstream_timeouts: UpstreamTimeouts::new(Duration::from_secs(10)),
+            ..Default::default()
+        })
+        .await;
+        submit_to(server_context, signed_txn, wait_for_commit_secs).await
+    }
+
+    async fn submit_to(
+        server_context: RosettaContext,
+        signed_txn: &SignedTransaction,
+        wait_for_commit_secs: Option<u64>,
+    ) -> ApiResult<Option<SubmitMetadata>> {
+        let response = construction_submit(
+            ConstructionSubmitRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                signed_transaction: encode_bcs(signed_txn).unwrap(),
+                wait_for_commit_secs: wait_for_commit_secs.map(U64),
+            },
+            server_context,
+        )
+        .await?;
+        assert_eq!(
+            TransactionIdentifier::from(signed_txn.clone().committed_hash()),
+            response.transaction_identifier
+        );
+        Ok(response.metadata)
+    }
+
+    #[tokio::test]
+    async fn submit_waits_for_commit() {
+        let (private_key, sender) = test_key(1);
+        let (_, receiver) = test_key(2);
+        let signed_txn = transfer_txn(sender, receiver)
+            .sign(&private_key, private_key.public_key())
+            .unwrap()
+            .into_inner();
+
+        // Fire and forget by default
+        assert_eq!(None, submit(&signed_txn, true, None).await.unwrap());
+
+        assert_eq!(
+            Some(SubmitMetadata {
+                confirmed: true,
+                version: Some(U64(7)),
+                failed: Some(false),
+                vm_status: Some("Success".to_string()),
+                gas_used: Some(U64(150)),
+            }),
+            submit(&signed_txn, true, Some(5)).await.unwrap()
+        );
+
+        // The wait must finish before the submit times out
+        let err = submit(&signed_txn, true, Some(10)).await.unwrap_err();
+        assert!(matches!(err, ApiError::InvalidInput(Some(_))), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn submit_timeout_excludes_queue_wait() {
+        let (private_key, sender) = test_key(1);
+        let (_, receiver) = test_key(2);
+        let signed_txn = transfer_txn(sender, receiver)
+            .sign(&private_key, private_key.public_key())
+            .unwrap()
+            .into_inner();
+        let config = RosettaConfig {
+            upstream_timeouts: UpstreamTimeouts::new(Duration::from_secs(10))
+                .with_timeout(EndpointClass::Submit, Duration::from_millis(200)),
+            submit_queue: Some(SubmitQueueConfig {
+                max_concurrency: 1,
+                max_queued: 10,
+            }),
+            ..Default::default()
+        };
+
+        // Waiting in the queue for longer than the timeout is fine
+        let server_context = online_context(mock_upstream(None, Duration::ZERO), &config).await;
+        let submit_queue = server_context.submit_queue.clone().unwrap();
+        let (started, waiting) = tokio::sync::oneshot::channel();
+        let busy = tokio::spawn(async move {
+            submit_queue
+                .submit(AccountAddress::ONE, || async {
+                    started.send(()).unwrap();
+                    tokio::time::sleep(Duration::from_millis(500)).await;
+                    Ok(())
+                })
+                .await
+        });
+        waiting.await.unwrap();
+        assert_eq!(
+            None,
+            submit_to(server_context, &signed_txn, None).await.unwrap()
+        );
+        busy.await.unwrap().unwrap();
+
+        // The upstream may have accepted a submit that timed out, so it isn't retriable
+        let server_context =
+            online_context(mock_upstream(None, Duration::from_secs(1)), &config).await;
+        let err = submit_to(server_context, &signed_txn, None)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, ApiError::SubmitOutcomeUnknown(Some(_))),
+            "{:?}",
+            err
+        );
+        assert!(!err.retriable());
+    }
+
+    #[tokio::test]
+    async fn submit_wait_times_out_unconfirmed() {
+        let (private_key, sender) = test_key(1);
+        let (_, receiver) = test_key(2);
+        let signed_txn = transfer_txn(sender, receiver)
+            .sign(&private_key, private_key.public_key())
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(
+            Some(SubmitMetadata {
+                confirmed: false,
+                ..Default::default()
+            }),
+            submit(&signed_txn, false, Some(1)).await.unwrap()
+        );
+
+        // A slow submission comes out of the wait, rather than adding to it
+        let server_context = online_context(
+            mock_upstream(None, Duration::from_millis(900)),
+            &RosettaConfig {
+                upstream_timeouts: UpstreamTimeouts::new(Duration::from_secs(10)),
+                ..Default::default()
+            },
+        )
+        .await;
+        let start = std::time::Instant::now();
+        assert_eq!(
+            Some(SubmitMetadata {
+                confirmed: false,
+                ..Default::default()
+            }),
+            submit_to(server_context, &signed_txn, Some(1))
+                .await
+                .unwrap()
+        );
+        assert!(
+            start.elapsed() < Duration::from_millis(1500),
+            "{:?}",
+            start.elapsed()
+        );
+    }
+
+    /// An upstream with accounts at `sequence_number`, on which every transaction simulates
+    /// successfully as itself
+    fn account_upstream(sequence_number: u64) -> url::Url {
+        let route = warp::any()
+            .and(warp::method())
+            .and(warp::path::full())
+            .and(warp::body::bytes())
+            .map(
+                move |method: warp::http::Method,
+                      path: warp::path::FullPath,
+                      body: warp::hyper::body::Bytes| {
+                    if method == warp::http::Method::POST && path.as_str().ends_with("/simulate") {
+                        let simulated: SignedTransaction = bcs::from_bytes(&body).unwrap();
+                        bcs::to_bytes(&TransactionOnChainData {
+                            version: 7,
+                            transaction: Transaction::UserTransaction(simulated),
+                            info: TransactionInfo::new(
+                                HashValue::zero(),
+                                HashValue::zero(),
+                                HashValue::zero(),
+                                None,
+                                150,
+                                ExecutionStatus::Success,
+                            ),
+                            events: vec![],
+                            accumulator_root_hash: HashValue::zero(),
+                            changes: WriteSet::default(),
+                        })
+                        .unwrap()
+                        .into_response()
+                    } else if path.as_str().contains("/accounts/") {
+                        warp::reply::json(&serde_json::json!({
+                            "sequence_number": sequence_number.to_string(),
+                            "authentication_key": AuthenticationKey::zero().to_string(),
+                        }))
+                        .into_response()
+                    } else {
+                        StatusCode::NOT_FOUND.into_response()
+                    }
+                },
+            )
+            .recover(|_| async { Ok::<_, Infallible>(StatusCode::BAD_REQUEST) });
+        test_utils::mock_upstream(MockLedger::default(), route)
+    }
+
+    #[tokio::test]
+    async fn payloads_keep_the_sequence_number_reserved() {
+        let (private_key, sender) = test_key(1);
+        let (_, receiver) = test_key(2);
+        let mut server_context =
+            online_context(account_upstream(5), &RosettaConfig::default()).await;
+        server_context.sequence_numbers =
+            Arc::new(SequenceNumberReservations::new(Duration::from_secs(1)));
+
+        let operations = vec![
+            Operation::withdraw(
+                0,
+                None,
+                AccountIdentifier::base_account(sender),
+                native_coin(),
+                100,
+            ),
+            Operation::deposit(
+                1,
+                None,
+                AccountIdentifier::base_account(receiver),
+                native_coin(),
+                100,
+            ),
+        ];
+        let options = construction_preprocess(
+            ConstructionPreprocessRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                operations: operations.clone(),
+                metadata: Some(PreprocessMetadata {
+                    expiry_time_secs: None,
+                    expiry_offset_secs: None,
+                    sequence_number: None,
+                    max_gas_amount: Some(U64(1000)),
+                    gas_price: Some(U64(100)),
+                    public_keys: Some(vec![PublicKey::try_from(private_key.public_key()).unwrap()]),
+                    auth_scheme: None,
+                    gas_price_multiplier: None,
+                    gas_price_priority: None,
+                    gas_currency: None,
+                }),
+            },
+            offline_context().await,
+        )
+        .await
+        .unwrap()
+        .options;
+        let metadata = |server_context: RosettaContext| {
+            let options = options.clone();
+            async move {
+                construction_metadata(
+                    ConstructionMetadataRequest {
+                        network_identifier: NetworkIdentifier::from(ChainId::test()),
+                        options,
+                    },
+                    server_context,
+                )
+                .await
+                .unwrap()
+                .metadata
+            }
+        };
+
+        let reserved = metadata(server_context.clone()).await;
+        assert_eq!(U64(5), reserved.sequence_number);
+
+        // The transaction is built part way through the reservation
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let payloads = construction_payloads(
+            ConstructionPayloadsRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                operations,
+                metadata: Some(reserved),
+                public_keys: None,
+                auth_scheme: None,
+            },
+            server_context.clone(),
+        )
+        .await
+        .unwrap();
+        let expiration_timestamp_secs = payloads.metadata.unwrap().expiration_timestamp_secs.0;
+        assert!(expiration_timestamp_secs > now_secs().unwrap() + 1);
+
+        // So the sequence number isn't handed out again until the transaction expires
+        tokio::time::sleep(Duration::from_millis(700)).await;
+        assert_eq!(U64(6), metadata(server_context).await.sequence_number);
+    }
+
+    #[test]
+    fn unknown_options_are_rejected() {
+        let with_typo = |mut value: serde_json::Value| {
+            value
+                .as_object_mut()
+                .unwrap()
+                .insert("gas_price_multipler".to_string(), serde_json::json!(120));
+            value
+        };
+
+        // Preprocess metadata, where options are first given
+        let metadata = serde_json::json!({ "gas_price_multiplier": 120 });
+        serde_json::from_value::<PreprocessMetadata>(metadata.clone()).unwrap();
+        serde_json::from_value::<PreprocessMetadata>(with_typo(metadata)).unwrap_err();
+
+        // Options passed from preprocess to metadata
+        let internal_operation = InternalOperation::CreateAccount(CreateAccount {
+            sender: AccountAddress::ONE,
+            new_account: AccountAddress::ONE,
+        });
+        let options = serde_json::to_value(ConstructionOptions {
+            internal_operation: internal_operation.clone(),
+            max_gas_amount: Some(U64(1000)),
+            gas_price_per_unit: None,
+            expiry_time_secs: None,
+            expiry_offset_secs: None,
+            sequence_number: None,
+            public_keys: None,
+            auth_scheme: None,
+            gas_price_multiplier: None,
+            gas_price_priority: None,
+            gas_currency: None,
+        })
+        .unwrap();
+        serde_json::from_value::<ConstructionOptions>(options.clone()).unwrap();
+        serde_json::from_value::<ConstructionOptions>(with_typo(options)).unwrap_err();
+
+        // Metadata passed from metadata to payloads
+        let metadata = serde_json::to_value(ConstructionMetadata {
+            sequence_number: U64(0),
+            max_gas_amount: U64(1000),
+            gas_price_per_unit: U64(100),
+            expiry_time_secs: None,
+            expiry_offset_secs: None,
+            internal_operation,
+        })
+        .unwrap();
+        serde_json::from_value::<ConstructionMetadata>(metadata.clone()).unwrap();
+        serde_json::from_value::<ConstructionMetadata>(with_typo(metadata)).unwrap_err();
+    }
+
+    #[test]
+    fn expiry_time_bounds() {
+        let now = 1_000_000;
+        let expiry = |time: Option<u64>, offset: Option<u64>| {
+            expiry_time_secs(time.map(U64), offset.map(U64), now)
+        };
+
+        assert_eq!(None, expiry(None, None).unwrap());
+        // Past
+        expiry(Some(now - 1), None).unwrap_err();
+        expiry(Some(now), None).unwrap_err();
+        expiry(None, Some(0)).unwrap_err();
+        // Near
+        assert_eq!(Some(now + 10), expiry(Some(now + 10), None).unwrap());
+        assert_eq!(Some(now + 10), expiry(None, Some(10)).unwrap());
+        assert_eq!(
+            Some(now + MAX_EXPIRY_WINDOW_SECS),
+            expiry(None, Some(MAX_EXPIRY_WINDOW_SECS)).unwrap()
+        );
+        // Far
+        expiry(Some(now + MAX_EXPIRY_WINDOW_SECS + 1), None).unwrap_err();
+        expiry(None, Some(u64::MAX)).unwrap_err();
+        // Only one of them can be provided
+        let err = expiry(Some(now + 10), Some(10)).unwrap_err();
+        assert!(matches!(err, ApiError::InvalidInput(_)));
+    }
+
+    #[tokio::test]
+    async fn payloads_expiry_offset() {
+        let (_, sender) = test_key(1);
+        let (_, receiver) = test_key(2);
+        let internal_operation = InternalOperation::Transfer(Transfer {
+            sender,
+            receiver,
+            amount: 100.into(),
+            currency: native_coin(),
+        });
+        let payloads = construction_payloads(
+            ConstructionPayloadsRequest {
+                network_identifier: NetworkIdentifier::from(ChainId::test()),
+                operations: vec![
+                    Operation::withdraw(
+                        0,
+                        None,
+                        AccountIdentifier::base_account(sender),
+                        native_coin(),
+                        100,
+                    ),
+                    Operation::deposit(
+                        1,
+                        None,
+                        AccountIdentifier::base_account(receiver),
+                        native_coin(),
+                        100,
+                    ),
+                ],
+                metadata: Some(ConstructionMetadata {
+                    sequence_number: U64(0),
+                    max_gas_amount: U64(1000),
+                    gas_price_per_unit: U64(100),
+                    expiry_time_secs: None,
+                    expiry_offset_secs: Some(U64(120)),
+                    internal_operation,
+                }),
+                public_keys: None,
+                auth_scheme: None,
+            },
+            offline_context().await,
+        )
+        .await
+        .unwrap();
+
+        // The effective expiry is the one in the transaction
+        let txn: RawTransaction =
+            decode_bcs(&payloads.unsigned_transaction, "RawTransaction").unwrap();
+        let expiration_timestamp_secs = payloads.metadata.unwrap().expiration_timestamp_secs.0;
+        assert_eq!(txn.expiration_timestamp_secs(), expiration_timestamp_secs);
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        assert!(expiration_timestamp_secs > now && expiration_timestamp_secs <= now + 120);
+    }
+
+    #[test]
+    fn operations_are_counted_by_type() {
+        // A label only used here, so other tests can't change the counts
+        let count = |operation_type: OperationType| {
+            CONSTRUCTION_OPERATIONS
+                .with_label_values(&["test", &operation_type.to_string()])
+                .get()
+        };
+        let (_, sender) = test_key(1);
+        let (_, receiver) = test_key(2);
+        let mut unknown = Operation::create_account(2, None, receiver, sender);
+        unknown.operation_type = "mint".to_string();
+
+        record_operations("test", &[
+            Operation::withdraw(
+                0,
+                None,
+                AccountIdentifier::base_account(sender),
+                native_coin(),
+                100,
+            ),
+            Operation::deposit(
+                1,
+                None,
+                AccountIdentifier::base_account(receiver),
+                native_coin(),
+                100,
+            ),
+            unknown,
+        ]);
+        record_operations("test", &[Operation::create_account(
+            0, None, receiver, sender,
+        )]);
+
+        assert_eq!(1, count(OperationType::Withdraw));
+        assert_eq!(1, count(OperationType::Deposit));
+        assert_eq!(1, count(OperationType::CreateAccount));
+        assert_eq!(0, count(OperationType::Fee));
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use aptos_metrics_core::{
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge_vec,
+    HistogramVec, IntCounter, IntCounterVec, IntGaugeVec,
+};
+use once_cell::sync::Lazy;
+
+/// State of the upstream circuit breaker, 0 is closed, 1 is open, and 2 is half open, by chain
+/// and the upstreams the breaker guards
+pub static UPSTREAM_CIRCUIT_BREAKER_STATE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "aptos_rosetta_upstream_circuit_breaker_state",
+        "State of the upstream circuit breaker, 0 is closed, 1 is open, and 2 is half open",
+        &["chain_id", "upstreams"]
+    )
+    .unwrap()
+});
+
+/// Lookups in the block timestamp cache, by whether they were a `hit` or `miss`
+pub static BLOCK_TIMESTAMP_CACHE_LOOKUPS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_rosetta_block_timestamp_cache_lookups",
+        "Lookups in the block timestamp cache, by whether they were a hit or miss",
+        &["result"]
+    )
+    .unwrap()
+});
+
+/// Times the upstream chain was inconsistent with the cached blocks, and the cache was flushed
+pub static BLOCK_REORGS_DETECTED: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "aptos_rosetta_block_reorgs_detected",
+        "Times the upstream chain was inconsistent with the cached blocks"
+    )
+    .unwrap()
+});
+
+/// Block fetches from the upstream fullnode slower than the slow fetch threshold
+pub static SLOW_BLOCK_FETCHES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "aptos_rosetta_slow_block_fetches",
+        "Block fetches from the upstream fullnode slower than the slow fetch threshold"
+    )
+    .unwrap()
+});
+
+/// Timeout for waiting on the upstream fullnode in seconds, by endpoint class
+pub static UPSTREAM_TIMEOUT_SECONDS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "aptos_rosetta_upstream_timeout_seconds",
+        "Timeout for waiting on the upstream fullnode in seconds, by endpoint class",
+        &["endpoint_class"]
+    )
+    .unwrap()
+});
+
+/// Operations handled by the construction API, by endpoint and operation type
+///
+/// Only the supported operation types are used as labels, so the cardinality is bounded.
+pub static CONSTRUCTION_OPERATIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_rosetta_construction_operations",
+        "Operations handled by the construction API, by endpoint and operation type",
+        &["endpoint", "operation_type"]
+    )
+    .unwrap()
+});
+
+/// Requests handled by the server, by HTTP status code, including those not logged
+pub static REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_rosetta_requests",
+        "Requests handled by the server, by HTTP status code",
+        &["status"]
+    )
+    .unwrap()
+});
+
+/// Requests handled by an endpoint, by the upstream fullnode serving them and HTTP status code
+///
+/// The upstream is the host of one of the configured fullnodes, or `none` if there isn't one,
+/// so the cardinality is bounded by the configured upstreams.
+pub static UPSTREAM_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_rosetta_upstream_requests",
+        "Requests handled by an endpoint, by upstream fullnode and HTTP status code",
+        &["upstream", "status"]
+    )
+    .unwrap()
+});
+
+/// Latency of requests handled by an endpoint in seconds, by the upstream fullnode serving them
+pub static UPSTREAM_REQUEST_LATENCY: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "aptos_rosetta_upstream_request_latency_seconds",
+        "Latency of requests handled by an endpoint in seconds, by upstream fullnode",
+        &["upstream"]
+    )
+    .unwrap()
+});
@@ -31,6 +31,17 @@ pub enum ApiError {
     TransactionParseError(Option<String>),
     InternalError(Option<String>),
     CoinTypeFailedToBeFetched(Option<String>),
+    InvalidSignature(Option<String>),
+    RateLimited(Option<String>),
+    TransactionHashMismatch(Option<String>),
+    UnsupportedRosettaVersion(Option<String>),
+    Overloaded(Option<String>),
+    BlockTooOld(Option<String>),
+    UpstreamTimeout(Option<String>),
+    BlockTooLarge(Option<String>),
+    TransferAmountTooLarge(Option<String>),
+    UpstreamUnavailable(Option<String>),
+    SubmitOutcomeUnknown(Option<String>),
 
     // Below here are codes directly from the REST API
     AccountNotFound(Option<String>),
@@ -80,6 +91,17 @@ impl ApiError {
             TransactionParseError(None),
             InternalError(None),
             CoinTypeFailedToBeFetched(None),
+            InvalidSignature(None),
+            RateLimited(None),
+            TransactionHashMismatch(None),
+            UnsupportedRosettaVersion(None),
+            Overloaded(None),
+            BlockTooOld(None),
+            UpstreamTimeout(None),
+            BlockTooLarge(None),
+            TransferAmountTooLarge(None),
+            UpstreamUnavailable(None),
+            SubmitOutcomeUnknown(None),
             AccountNotFound(None),
             ResourceNotFound(None),
             ModuleNotFound(None),
@@ -134,6 +156,17 @@ impl ApiError {
             VmError(_) => 31,
             MempoolIsFull(_) => 32,
             CoinTypeFailedToBeFetched(_) => 33,
+            InvalidSignature(_) => 34,
+            RateLimited(_) => 35,
+            TransactionHashMismatch(_) => 36,
+            UnsupportedRosettaVersion(_) => 37,
+            Overloaded(_) => 38,
+            BlockTooOld(_) => 39,
+            UpstreamTimeout(_) => 40,
+            BlockTooLarge(_) => 41,
+            TransferAmountTooLarge(_) => 42,
+            UpstreamUnavailable(_) => 43,
+            SubmitOutcomeUnknown(_) => 44,
         }
     }
 
@@ -146,12 +179,22 @@ impl ApiError {
                 | MempoolIsFull(_)
                 | GasEstimationFailed(_)
                 | CoinTypeFailedToBeFetched(_)
+                | RateLimited(_)
+                | Overloaded(_)
+                | UpstreamTimeout(_)
+                | UpstreamUnavailable(_)
         )
     }
 
     pub fn status_code(&self) -> StatusCode {
-        // Per Rosetta guidelines, all errors are 500s
-        StatusCode::INTERNAL_SERVER_ERROR
+        match self {
+            // Rate limiting is expected to be handled by clients as a 429
+            ApiError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            // Similarly, clients should back off when the server is overloaded
+            ApiError::Overloaded(_) => StatusCode::SERVICE_UNAVAILABLE,
+            // Per Rosetta guidelines, all other errors are 500s
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
     }
 
     /// This value must be fixed, so it's all static strings
@@ -176,6 +219,17 @@ impl ApiError {
             ApiError::TransactionParseError(_) => "Transaction failed to parse",
             ApiError::InternalError(_) => "Internal error",
             ApiError::CoinTypeFailedToBeFetched(_) => "Faileed to retrieve the coin type information, please retry",
+            ApiError::InvalidSignature(_) => "Signature verification failed",
+            ApiError::RateLimited(_) => "Too many requests, please retry later",
+            ApiError::TransactionHashMismatch(_) => "Transaction hash doesn't match the node's",
+            ApiError::UnsupportedRosettaVersion(_) => "Requested Rosetta version is not supported",
+            ApiError::Overloaded(_) => "Server is overloaded, please retry later",
+            ApiError::BlockTooOld(_) => "Block is older than the server allows querying",
+            ApiError::UpstreamTimeout(_) => "Upstream fullnode took too long to respond, please retry",
+            ApiError::BlockTooLarge(_) => "Block has more operations than the server allows",
+            ApiError::TransferAmountTooLarge(_) => "Transfer amount is more than the server allows",
+            ApiError::UpstreamUnavailable(_) => "Upstream fullnode couldn't be reached, please retry",
+            ApiError::SubmitOutcomeUnknown(_) => "Upstream fullnode took too long to accept the transaction, which may still be committed",
             ApiError::ResourceNotFound(_) => "Resource not found",
             ApiError::ModuleNotFound(_) => "Module not found",
             ApiError::StructFieldNotFound(_) => "Struct field not found",
@@ -203,6 +257,17 @@ impl ApiError {
             ApiError::InvalidOperations(inner) => inner,
             ApiError::InternalError(inner) => inner,
             ApiError::CoinTypeFailedToBeFetched(inner) => inner,
+            ApiError::InvalidSignature(inner) => inner,
+            ApiError::RateLimited(inner) => inner,
+            ApiError::TransactionHashMismatch(inner) => inner,
+            ApiError::UnsupportedRosettaVersion(inner) => inner,
+            ApiError::Overloaded(inner) => inner,
+            ApiError::BlockTooOld(inner) => inner,
+            ApiError::UpstreamTimeout(inner) => inner,
+            ApiError::BlockTooLarge(inner) => inner,
+            ApiError::TransferAmountTooLarge(inner) => inner,
+            ApiError::UpstreamUnavailable(inner) => inner,
+            ApiError::SubmitOutcomeUnknown(inner) => inner,
             ApiError::AccountNotFound(inner) => inner,
             ApiError::ResourceNotFound(inner) => inner,
             ApiError::ModuleNotFound(inner) => inner,
@@ -296,13 +361,22 @@ impl From<RestError> for ApiError {
             },
             RestError::Bcs(_) => ApiError::DeserializationFailed(None),
             RestError::Json(_) => ApiError::DeserializationFailed(None),
+            // A server error without an API error body comes from in front of the fullnode
+            // e.g. a load balancer, so the fullnode is treated as unreachable
+            RestError::Http(status_code, err) if status_code.is_server_error() => {
+                ApiError::UpstreamUnavailable(Some(format!(
+                    "Failed internal API call with HTTP code {}: {:#}",
+                    status_code, err
+                )))
+            },
             RestError::Http(status_code, err) => ApiError::InternalError(Some(format!(
                 "Failed internal API call with HTTP code {}: {:#}",
                 status_code, err
             ))),
             RestError::UrlParse(err) => ApiError::InternalError(Some(err.to_string())),
             RestError::Timeout(err) => ApiError::InternalError(Some(err.to_string())),
-            RestError::Unknown(err) => ApiError::InternalError(Some(err.to_string())),
+            // Errors without an HTTP response are failures to connect or transfer
+            RestError::Unknown(err) => ApiError::UpstreamUnavailable(Some(err.to_string())),
         }
     }
 }
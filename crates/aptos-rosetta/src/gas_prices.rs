@@ -0,0 +1,109 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Gas price estimates of the upstream fullnode, as reported by `/network/gas_prices`
+//!
+//! Estimates only change as blocks are committed, so they're cached for
+//! [`GAS_PRICE_CACHE_TTL`] rather than fetched on every request.  That keeps clients polling
+//! for congestion from each costing an upstream call.
+
+use crate::error::ApiResult;
+use aptos_infallible::RwLock;
+use aptos_rest_client::{aptos_api_types::GasEstimation, Client};
+use std::time::{Duration, Instant};
+
+/// How long a gas price estimate is served from the cache before it's fetched again
+pub const GAS_PRICE_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// The last gas price estimate of the upstream fullnode, and when it was fetched
+#[derive(Debug)]
+pub struct GasPriceCache {
+    ttl: Duration,
+    cached: RwLock<Option<(Instant, GasEstimation)>>,
+}
+
+impl Default for GasPriceCache {
+    fn default() -> Self {
+        GasPriceCache::new(GAS_PRICE_CACHE_TTL)
+    }
+}
+
+impl GasPriceCache {
+    pub fn new(ttl: Duration) -> Self {
+        GasPriceCache {
+            ttl,
+            cached: RwLock::new(None),
+        }
+    }
+
+    /// Returns the cached estimate, or fetches it from the upstream fullnode if it's expired
+    ///
+    /// On failure, the expired estimate isn't served, as it may be arbitrarily stale.
+    pub async fn get(&self, rest_client: &Client) -> ApiResult<GasEstimation> {
+        let cached = self.cached.read().clone();
+        if let Some((fetched_at, gas_estimation)) = cached {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(gas_estimation);
+            }
+        }
+
+        let gas_estimation = rest_client.estimate_gas_price().await?.into_inner();
+        *self.cached.write() = Some((Instant::now(), gas_estimation.clone()));
+        Ok(gas_estimation)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::GasPriceCache;
+    use crate::test_utils::{self, MockLedger};
+    use aptos_rest_client::aptos_api_types::GasEstimation;
+    use std::{
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+    use warp::Filter;
+
+    /// Serves a gas estimation on every path, which goes up by one on every call
+    fn mock_upstream(calls: Arc<AtomicU64>) -> url::Url {
+        let route = warp::any().map(move || {
+            let call = calls.fetch_add(1, Ordering::SeqCst);
+            warp::reply::json(&GasEstimation {
+                deprioritized_gas_estimate: Some(100 + call),
+                gas_estimate: 150 + call,
+                prioritized_gas_estimate: Some(200 + call),
+            })
+        });
+        test_utils::mock_upstream(MockLedger::default(), route)
+    }
+
+    #[tokio::test]
+    async fn estimates_are_cached_until_expiry() {
+        let calls = Arc::new(AtomicU64::new(0));
+        let rest_client = aptos_rest_client::Client::new(mock_upstream(calls.clone()));
+        let cache = GasPriceCache::new(Duration::from_millis(200));
+
+        let first = cache.get(&rest_client).await.unwrap();
+        assert_eq!(
+            GasEstimation {
+                deprioritized_gas_estimate: Some(100),
+                gas_estimate: 150,
+                prioritized_gas_estimate: Some(200),
+            },
+            first
+        );
+
+        // Within the TTL, the upstream isn't called again
+        assert_eq!(first, cache.get(&rest_client).await.unwrap());
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+
+        // After it, the new estimate is fetched
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let second = cache.get(&rest_client).await.unwrap();
+        assert_eq!(151, second.gas_estimate);
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+}
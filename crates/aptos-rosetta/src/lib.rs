@@ -6,57 +6,234 @@
 //! [Rosetta API Spec](https://www.rosetta-api.org/docs/Reference.html)
 
 use crate::{
+    access_log::{access_logger, DEFAULT_ACCESS_LOG_SAMPLE_RATE},
+    balance_check::BalanceCheckMode,
     block::BlockRetriever,
-    common::{handle_request, with_context},
+    common::{handle_request, with_context, with_empty_request, with_upstream_timeout},
     error::{ApiError, ApiResult},
-    types::Store,
+    gas_prices::GasPriceCache,
+    node_version::{NodeVersion, DEFAULT_NODE_VERSION_REFRESH_INTERVAL},
+    rate_limit::{RateLimitConfig, RateLimited, RateLimiter},
+    sequence_numbers::SequenceNumberReservations,
+    submit_queue::{SubmitQueue, SubmitQueueConfig},
+    timeouts::{EndpointClass, UpstreamTimeouts},
+    types::{MetadataRequest, NetworkIdentifier, Store},
+    upstream::{
+        CircuitBreakerConfig, UpstreamClients, UpstreamUsage, NO_UPSTREAM_LABEL,
+        UPSTREAM_HEALTH_CHECK_INTERVAL,
+    },
+    version::VersionMismatch,
 };
 use aptos_config::config::ApiConfig;
-use aptos_logger::{debug, warn};
+use aptos_logger::{debug, info, warn};
 use aptos_types::{account_address::AccountAddress, chain_id::ChainId};
-use aptos_warp_webserver::{logger, Error, WebServer};
-use std::{collections::BTreeMap, convert::Infallible, sync::Arc};
+use aptos_warp_webserver::{Error, WebServer};
+use once_cell::sync::OnceCell;
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 use tokio::task::JoinHandle;
 use warp::{
-    http::{HeaderValue, Method, StatusCode},
+    http::{header::RETRY_AFTER, HeaderValue, Method, StatusCode},
     reply, Filter, Rejection, Reply,
 };
 
 mod account;
 mod block;
 mod construction;
+mod counters;
 mod network;
+mod search;
+#[cfg(test)]
+mod test_utils;
 
+pub mod access_log;
+pub mod balance_check;
 pub mod client;
 pub mod common;
 pub mod error;
+pub mod gas_prices;
+pub mod multi_chain;
+pub mod node_version;
+pub mod rate_limit;
+pub mod sequence_numbers;
+pub mod submit_queue;
+pub mod timeouts;
 pub mod types;
+pub mod upstream;
+pub mod version;
 
 pub const NODE_VERSION: &str = "0.1";
 pub const ROSETTA_VERSION: &str = "1.4.12";
+/// Default maximum number of accounts in one `/account/balances` request
+pub const DEFAULT_MAX_BALANCE_BATCH_SIZE: usize = 100;
+
+/// Default latency above which a block fetch from the upstream fullnode is logged as slow
+pub const DEFAULT_SLOW_BLOCK_FETCH_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// Permissions of the Unix domain socket, only its owner and group can connect
+pub const UNIX_SOCKET_MODE: u32 = 0o660;
+
+/// Whether a server has upstream fullnodes, and so which endpoints it serves
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RosettaMode {
+    /// Serves every endpoint from the upstream fullnodes
+    Online,
+    /// Serves only the endpoints that don't need a fullnode, and the others fail with
+    /// [`ApiError::NodeIsOffline`]
+    Offline,
+}
+
+/// Options for a Rosetta server, shared by every chain it serves
+///
+/// The defaults serve every endpoint without limits, except where a default constant is used.
+#[derive(Clone, Debug)]
+pub struct RosettaConfig {
+    /// Address to listen on instead of the one in the `ApiConfig`, e.g. `127.0.0.1:8082`
+    pub bind_address: Option<String>,
+    /// Path of a Unix domain socket to listen on instead of any TCP address
+    ///
+    /// This is only supported on Unix, other platforms log an error and fall back to TCP.
+    /// Connections over the socket have no client IP, so the rate limit only applies to
+    /// requests with a trusted `X-Forwarded-For` header.
+    pub unix_socket_path: Option<PathBuf>,
+    /// Rate limit by client IP, if enabled
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Whether `/construction/hash` also checks its hashing against the upstream fullnode
+    pub verify_transaction_hashes: bool,
+    /// Whether constructing a transfer to the sender fails, as it's often a sign of a bug in
+    /// the caller
+    pub forbid_self_transfer: bool,
+    /// How `/block` handles blocks whose coin operations don't balance, if checking is enabled
+    pub balance_check: Option<BalanceCheckMode>,
+    /// Fails requests fast while the upstream fullnodes are unhealthy, if enabled
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    /// Limits the submits sent to the upstream fullnode at once, if enabled
+    pub submit_queue: Option<SubmitQueueConfig>,
+    /// Maximum number of blocks before the latest that can be queried, if limited
+    pub max_historical_depth: Option<u64>,
+    /// Timeouts for waiting on the upstream fullnodes, by class of endpoint
+    pub upstream_timeouts: UpstreamTimeouts,
+    /// Maximum number of accounts in one `/account/balances` request
+    pub max_balance_batch_size: usize,
+    /// Only 1 in this many successful requests are logged, errors are always logged
+    pub access_log_sample_rate: u64,
+    /// Maximum number of operations in a `/block` response, larger blocks fail with
+    /// `BlockTooLarge`, if limited
+    pub max_operations_per_block: Option<usize>,
+    /// Maximum amount of a constructed transfer, larger transfers fail with
+    /// `TransferAmountTooLarge`, if limited
+    pub max_transfer_amount: Option<u64>,
+    /// How often the upstream fullnode version reported by `/network/options` is refreshed
+    pub node_version_refresh_interval: Duration,
+    /// Block fetches from the upstream fullnode slower than this are logged and counted
+    pub slow_block_fetch_threshold: Duration,
+    /// File the reserved sequence numbers are loaded from on startup, and dumped to by the
+    /// server's [`AccountsDump`] on shutdown, if set
+    pub accounts_path: Option<PathBuf>,
+}
+
+impl Default for RosettaConfig {
+    fn default() -> Self {
+        RosettaConfig {
+            bind_address: None,
+            unix_socket_path: None,
+            rate_limit: None,
+            verify_transaction_hashes: false,
+            forbid_self_transfer: false,
+            balance_check: None,
+            circuit_breaker: None,
+            submit_queue: None,
+            max_historical_depth: None,
+            upstream_timeouts: UpstreamTimeouts::default(),
+            max_balance_batch_size: DEFAULT_MAX_BALANCE_BATCH_SIZE,
+            access_log_sample_rate: DEFAULT_ACCESS_LOG_SAMPLE_RATE,
+            max_operations_per_block: None,
+            max_transfer_amount: None,
+            node_version_refresh_interval: DEFAULT_NODE_VERSION_REFRESH_INTERVAL,
+            slow_block_fetch_threshold: DEFAULT_SLOW_BLOCK_FETCH_THRESHOLD,
+            accounts_path: None,
+        }
+    }
+}
 
 /// Rosetta API context for use on all APIs
 #[derive(Clone, Debug)]
 pub struct RosettaContext {
-    /// A rest client to connect to a fullnode
-    rest_client: Option<Arc<aptos_rest_client::Client>>,
+    /// Whether the server is online, online contexts always have rest clients and a block cache
+    mode: RosettaMode,
+    /// Rest clients to connect to fullnodes, in order of preference
+    rest_clients: Option<Arc<UpstreamClients>>,
     /// ChainId of the chain to connect to
     pub chain_id: ChainId,
     /// Block index cache
-    pub block_cache: Option<Arc<BlockRetriever>>,
+    block_cache: Option<Arc<BlockRetriever>>,
     pub owner_addresses: Vec<AccountAddress>,
     pub pool_address_to_owner: BTreeMap<AccountAddress, AccountAddress>,
+    /// Rate limiter by client IP, if rate limiting is enabled
+    rate_limiter: Option<Arc<RateLimiter>>,
+    /// Whether to verify transaction hashes against the upstream fullnode
+    pub verify_transaction_hashes: bool,
+    /// Whether to reject transfers to the sender in `/construction/preprocess` and
+    /// `/construction/payloads`
+    pub forbid_self_transfer: bool,
+    /// How to handle blocks whose coin operations don't balance, if checking is enabled
+    pub balance_check: Option<BalanceCheckMode>,
+    /// Sequence numbers handed out for transaction construction, by account
+    pub sequence_numbers: Arc<SequenceNumberReservations>,
+    /// Queue limiting concurrent submits to the upstream fullnode, if enabled
+    pub submit_queue: Option<Arc<SubmitQueue>>,
+    /// Maximum number of blocks before the latest that can be queried, if limited
+    pub max_historical_depth: Option<u64>,
+    /// Timeouts for waiting on the upstream fullnodes, by class of endpoint
+    pub upstream_timeouts: UpstreamTimeouts,
+    /// Maximum number of accounts in one `/account/balances` request
+    pub max_balance_batch_size: usize,
+    /// Only 1 in this many successful requests are logged, errors are always logged
+    pub access_log_sample_rate: u64,
+    /// Maximum number of operations in a `/block` response, if limited
+    pub max_operations_per_block: Option<usize>,
+    /// Maximum amount of a transfer built by `/construction/preprocess` and
+    /// `/construction/payloads`, if limited
+    pub max_transfer_amount: Option<u64>,
+    /// Last known version of the upstream fullnode, refreshed in the background if online
+    pub node_version: Arc<NodeVersion>,
+    /// Gas price estimates of the upstream fullnode, cached for `/network/gas_prices`
+    pub gas_prices: Arc<GasPriceCache>,
+    /// The upstream fullnode the current request was sent to, see [`RosettaContext::for_request`]
+    upstream_usage: UpstreamUsage,
 }
 
 impl RosettaContext {
+    /// Creates the context, failing if the rest clients and block cache don't match the mode
     pub async fn new(
-        rest_client: Option<Arc<aptos_rest_client::Client>>,
+        mode: RosettaMode,
+        rest_clients: Option<Arc<UpstreamClients>>,
         chain_id: ChainId,
         block_cache: Option<Arc<BlockRetriever>>,
         owner_addresses: Vec<AccountAddress>,
-    ) -> Self {
+        config: &RosettaConfig,
+    ) -> anyhow::Result<Self> {
+        match mode {
+            RosettaMode::Online => anyhow::ensure!(
+                rest_clients.is_some() && block_cache.is_some(),
+                "Online mode requires rest clients and a block cache"
+            ),
+            RosettaMode::Offline => anyhow::ensure!(
+                rest_clients.is_none() && block_cache.is_none(),
+                "Offline mode can't have rest clients or a block cache"
+            ),
+        }
+
         let mut pool_address_to_owner = BTreeMap::new();
-        if let Some(ref rest_client) = rest_client {
+        if let Some(ref rest_clients) = rest_clients {
+            let rest_client = rest_clients.current();
             // We have to now fill in all of the mappings of owner to pool address
             for owner_address in owner_addresses.iter() {
                 if let Ok(store) = rest_client
@@ -81,123 +258,615 @@ impl RosettaContext {
             }
         }
 
-        RosettaContext {
-            rest_client,
+        Ok(RosettaContext {
+            mode,
+            rest_clients,
             chain_id,
             block_cache,
             owner_addresses,
             pool_address_to_owner,
+            rate_limiter: config
+                .rate_limit
+                .map(|config| Arc::new(RateLimiter::new(config))),
+            verify_transaction_hashes: config.verify_transaction_hashes,
+            forbid_self_transfer: config.forbid_self_transfer,
+            balance_check: config.balance_check,
+            sequence_numbers: Arc::new(SequenceNumberReservations::default()),
+            submit_queue: config
+                .submit_queue
+                .map(|config| Arc::new(SubmitQueue::new(config))),
+            max_historical_depth: config.max_historical_depth,
+            upstream_timeouts: config.upstream_timeouts.clone(),
+            max_balance_batch_size: config.max_balance_batch_size,
+            access_log_sample_rate: config.access_log_sample_rate,
+            max_operations_per_block: config.max_operations_per_block,
+            max_transfer_amount: config.max_transfer_amount,
+            node_version: Arc::new(NodeVersion::default()),
+            gas_prices: Arc::new(GasPriceCache::default()),
+            upstream_usage: UpstreamUsage::default(),
+        })
+    }
+
+    /// A copy of the context for handling one request, which records the upstream fullnode
+    /// the request is sent to
+    pub fn for_request(&self) -> Self {
+        RosettaContext {
+            upstream_usage: UpstreamUsage::default(),
+            ..self.clone()
         }
     }
 
-    fn rest_client(&self) -> ApiResult<Arc<aptos_rest_client::Client>> {
-        if let Some(ref client) = self.rest_client {
-            Ok(client.clone())
-        } else {
-            Err(ApiError::NodeIsOffline)
+    /// Records the outcome of the request this context was created for, see
+    /// [`UpstreamClients::record_request`]
+    pub fn record_request(&self, error: Option<&ApiError>) {
+        if let Some(ref rest_clients) = self.rest_clients {
+            rest_clients.record_request(&self.upstream_usage, error);
         }
     }
 
-    fn block_cache(&self) -> ApiResult<Arc<BlockRetriever>> {
-        if let Some(ref block_cache) = self.block_cache {
-            Ok(block_cache.clone())
-        } else {
-            Err(ApiError::NodeIsOffline)
+    /// Whether the server is online, which is fixed when the context is created
+    pub fn mode(&self) -> RosettaMode {
+        self.mode
+    }
+
+    /// Fails with [`ApiError::NodeIsOffline`] unless the server is online, for endpoints which
+    /// need a fullnode
+    pub fn check_online(&self) -> ApiResult<()> {
+        match self.mode {
+            RosettaMode::Online => Ok(()),
+            RosettaMode::Offline => Err(ApiError::NodeIsOffline),
         }
     }
+
+    /// Metrics label of the upstream fullnode currently serving requests
+    pub fn upstream_label(&self) -> &str {
+        self.rest_clients
+            .as_ref()
+            .map(|rest_clients| rest_clients.current_label())
+            .unwrap_or(NO_UPSTREAM_LABEL)
+    }
+
+    /// Retrieves a rest client for the currently healthy fullnode
+    fn rest_client(&self) -> ApiResult<Arc<aptos_rest_client::Client>> {
+        self.check_online()?;
+        self.rest_clients
+            .as_ref()
+            .ok_or(ApiError::NodeIsOffline)?
+            .available_for(&self.upstream_usage)
+    }
+
+    fn block_cache(&self) -> ApiResult<Arc<BlockRetriever>> {
+        // The block cache fetches from the current upstream, so record it as used
+        self.rest_client()?;
+        self.block_cache.clone().ok_or(ApiError::NodeIsOffline)
+    }
+
+    /// Writes the reserved sequence numbers of each account to `path` as JSON, ordered by
+    /// account, so they can be loaded after a restart
+    pub fn dump_accounts(&self, path: &Path) -> anyhow::Result<()> {
+        let snapshot = serde_json::to_vec_pretty(&self.sequence_numbers.snapshot())?;
+
+        // Write to a temporary file first, so a failed dump doesn't clobber the last one.  The
+        // suffix is appended, as replacing the extension could clobber an unrelated file.
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        std::fs::write(&tmp_path, snapshot)?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Loads reserved sequence numbers written by [`RosettaContext::dump_accounts`]
+    ///
+    /// Loaded sequence numbers are still bounded below by the on-chain sequence numbers, and
+    /// expire like any other reservation, so a stale dump is safe to load.
+    pub fn load_accounts(&self, path: &Path) -> anyhow::Result<()> {
+        let snapshot = serde_json::from_slice(&std::fs::read(path)?)?;
+        self.sequence_numbers.restore(snapshot);
+        Ok(())
+    }
 }
 
 /// Creates HTTP server (warp-based) for Rosetta
+///
+/// The `rest_clients` are the upstream fullnodes in order of preference, and if there are
+/// none, the server runs in offline mode.  See [`RosettaConfig`] for the other options.
+///
+/// The returned [`AccountsDump`] saves the reserved sequence numbers, and is left to the
+/// caller to use on shutdown.
 pub fn bootstrap(
     chain_id: ChainId,
     api_config: ApiConfig,
-    rest_client: Option<aptos_rest_client::Client>,
+    rest_clients: Vec<aptos_rest_client::Client>,
     owner_addresses: Vec<AccountAddress>,
-) -> anyhow::Result<tokio::runtime::Runtime> {
+    config: RosettaConfig,
+) -> anyhow::Result<(tokio::runtime::Runtime, AccountsDump)> {
     let runtime = aptos_runtimes::spawn_named_runtime("rosetta".into(), None);
 
-    debug!("Starting up Rosetta server with {:?}", api_config);
+    // Bind up front, as errors from the spawned server would be lost.  The Unix socket is
+    // registered with the runtime it's served on.
+    let (api_config, unix_listener) = {
+        let _guard = runtime.enter();
+        bind(api_config, &config)?
+    };
 
-    runtime.spawn(bootstrap_async(
+    let accounts_dump = AccountsDump::default();
+    runtime.spawn(serve(
         chain_id,
         api_config,
-        rest_client,
+        unix_listener,
+        rest_clients,
         owner_addresses,
+        config,
+        accounts_dump.clone(),
     ));
-    Ok(runtime)
+    Ok((runtime, accounts_dump))
 }
 
 /// Creates HTTP server for Rosetta in an async context
+///
+/// See [`bootstrap`] for the options.
 pub async fn bootstrap_async(
     chain_id: ChainId,
     api_config: ApiConfig,
-    rest_client: Option<aptos_rest_client::Client>,
+    rest_clients: Vec<aptos_rest_client::Client>,
     owner_addresses: Vec<AccountAddress>,
-) -> anyhow::Result<JoinHandle<()>> {
+    config: RosettaConfig,
+) -> anyhow::Result<(JoinHandle<()>, AccountsDump)> {
+    let (api_config, unix_listener) = bind(api_config, &config)?;
+    let accounts_dump = AccountsDump::default();
+    let handle = serve(
+        chain_id,
+        api_config,
+        unix_listener,
+        rest_clients,
+        owner_addresses,
+        config,
+        accounts_dump.clone(),
+    )
+    .await;
+    Ok((handle, accounts_dump))
+}
+
+/// The Unix domain socket the server is served on, which is never bound off Unix
+#[cfg(unix)]
+type UnixListener = tokio::net::UnixListener;
+#[cfg(not(unix))]
+type UnixListener = ();
+
+/// Applies the bind address to the `api_config`, and binds the Unix socket if there is one
+fn bind(
+    api_config: ApiConfig,
+    config: &RosettaConfig,
+) -> anyhow::Result<(ApiConfig, Option<UnixListener>)> {
+    let api_config = with_bind_address(api_config, config.bind_address.as_deref())?;
     debug!("Starting up Rosetta server with {:?}", api_config);
 
-    if let Some(ref client) = rest_client {
-        assert_eq!(
-            chain_id.id(),
-            client
-                .get_ledger_information()
-                .await
-                .expect("Should successfully get ledger information from Rest API on bootstap")
-                .into_inner()
-                .chain_id,
-            "Failed to match Rosetta chain Id to upstream server"
+    let unix_listener = match config.unix_socket_path {
+        Some(ref path) => {
+            if let Some(rate_limit) = config.rate_limit {
+                if !rate_limit.trust_forwarded_for {
+                    warn!(
+                        "Clients of Unix socket {:?} have no IP, so aren't rate limited unless X-Forwarded-For is trusted",
+                        path
+                    );
+                }
+            }
+            bind_unix_socket(path, &api_config)?
+        },
+        None => None,
+    };
+    Ok((api_config, unix_listener))
+}
+
+/// Starts the server for a single chain, setting up the `accounts_dump` once it's running
+///
+/// It's served on the `unix_listener` if there is one, otherwise on the `api_config` address.
+async fn serve(
+    chain_id: ChainId,
+    api_config: ApiConfig,
+    #[cfg_attr(not(unix), allow(unused_variables))] unix_listener: Option<UnixListener>,
+    rest_clients: Vec<aptos_rest_client::Client>,
+    owner_addresses: Vec<AccountAddress>,
+    config: RosettaConfig,
+    accounts_dump: AccountsDump,
+) -> JoinHandle<()> {
+    let rest_clients = connect_upstream(chain_id, rest_clients, config.circuit_breaker).await;
+
+    let api = WebServer::from(api_config.clone());
+    tokio::spawn(async move {
+        let context = create_context(
+            chain_id,
+            api_config.max_transactions_page_size,
+            rest_clients,
+            owner_addresses,
+            &config,
+            &accounts_dump,
+        )
+        .await;
+
+        #[cfg(unix)]
+        if let Some(listener) = unix_listener {
+            let incoming = futures::stream::poll_fn(move |cx| {
+                listener
+                    .poll_accept(cx)
+                    .map(|result| Some(result.map(|(stream, _)| stream)))
+            });
+            warp::serve(routes(context)).run_incoming(incoming).await;
+            return;
+        }
+        api.serve(routes(context)).await;
+    })
+}
+
+/// The upstream fullnodes for one chain served by a multi-chain server
+#[derive(Clone, Debug)]
+pub struct ChainConfig {
+    pub chain_id: ChainId,
+    /// Rest clients for the upstream fullnodes in order of preference, empty if offline
+    pub rest_clients: Vec<aptos_rest_client::Client>,
+    pub owner_addresses: Vec<AccountAddress>,
+}
+
+impl ChainConfig {
+    /// Groups the upstreams by chain, keeping the order of the chains and of each chain's
+    /// upstreams, so the first upstream of a chain is its primary
+    pub fn from_upstreams(upstreams: Vec<ChainUpstream>) -> Vec<ChainConfig> {
+        let mut chains: Vec<ChainConfig> = Vec::new();
+        for upstream in upstreams {
+            let client = aptos_rest_client::Client::new(upstream.rest_api_url);
+            match chains
+                .iter_mut()
+                .find(|chain| chain.chain_id == upstream.chain_id)
+            {
+                Some(chain) => chain.rest_clients.push(client),
+                None => chains.push(ChainConfig {
+                    chain_id: upstream.chain_id,
+                    rest_clients: vec![client],
+                    owner_addresses: vec![],
+                }),
+            }
+        }
+        chains
+    }
+}
+
+/// An upstream fullnode for a chain, parsed from `<chain_id>=<rest_api_url>` e.g.
+/// `testnet=https://fullnode.testnet.aptoslabs.com`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChainUpstream {
+    pub chain_id: ChainId,
+    pub rest_api_url: url::Url,
+}
+
+impl FromStr for ChainUpstream {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (chain_id, rest_api_url) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Expected <chain_id>=<rest_api_url>, got {}", s))?;
+        Ok(ChainUpstream {
+            chain_id: chain_id.parse()?,
+            rest_api_url: rest_api_url
+                .parse()
+                .map_err(|err| anyhow::anyhow!("Invalid REST API URL {}: {}", rest_api_url, err))?,
+        })
+    }
+}
+
+/// Creates HTTP server for Rosetta serving multiple chains in an async context
+///
+/// Requests are dispatched to a chain by their network identifier, see [`multi_chain`].
+/// The rate limit is shared across all of the chains, and the other options apply to each.
+/// Only TCP is supported, so the `unix_socket_path` is ignored, and the `accounts_path` is
+/// ignored as it can only hold the accounts of one chain.
+pub async fn bootstrap_multi_chain_async(
+    api_config: ApiConfig,
+    chains: Vec<ChainConfig>,
+    config: RosettaConfig,
+) -> anyhow::Result<JoinHandle<()>> {
+    let api_config = with_bind_address(api_config, config.bind_address.as_deref())?;
+    debug!(
+        "Starting up multi-chain Rosetta server with {:?}",
+        api_config
+    );
+    anyhow::ensure!(!chains.is_empty(), "Must provide at least one chain");
+
+    let mut upstreams = Vec::new();
+    for chain in chains {
+        anyhow::ensure!(
+            upstreams
+                .iter()
+                .all(|(chain_id, _, _)| *chain_id != chain.chain_id),
+            "Chain {} is configured more than once",
+            chain.chain_id
         );
+        let rest_clients =
+            connect_upstream(chain.chain_id, chain.rest_clients, config.circuit_breaker).await;
+        upstreams.push((chain.chain_id, rest_clients, chain.owner_addresses));
     }
 
     let api = WebServer::from(api_config.clone());
     let handle = tokio::spawn(async move {
-        // If it's Online mode, add the block cache
-        let rest_client = rest_client.map(Arc::new);
-        let block_cache = rest_client.as_ref().map(|rest_client| {
-            Arc::new(BlockRetriever::new(
+        let mut contexts = HashMap::new();
+        for (chain_id, rest_clients, owner_addresses) in upstreams {
+            let context = create_context(
+                chain_id,
                 api_config.max_transactions_page_size,
-                rest_client.clone(),
-            ))
-        });
+                rest_clients,
+                owner_addresses,
+                // Rate limiting is done once for all chains
+                &RosettaConfig {
+                    rate_limit: None,
+                    accounts_path: None,
+                    ..config.clone()
+                },
+                &AccountsDump::default(),
+            )
+            .await;
+            contexts.insert(chain_id, context);
+        }
 
-        let context =
-            RosettaContext::new(rest_client.clone(), chain_id, block_cache, owner_addresses).await;
-        api.serve(routes(context)).await;
+        let rate_limiter = config
+            .rate_limit
+            .map(|config| Arc::new(RateLimiter::new(config)));
+        api.serve(multi_chain::routes(contexts, rate_limiter)).await;
     });
     Ok(handle)
 }
 
+/// Overrides the address in the `api_config` with the `bind_address`, if there is one
+fn with_bind_address(
+    mut api_config: ApiConfig,
+    bind_address: Option<&str>,
+) -> anyhow::Result<ApiConfig> {
+    if let Some(bind_address) = bind_address {
+        api_config.address = SocketAddr::from_str(bind_address)
+            .map_err(|err| anyhow::anyhow!("Invalid bind address '{}': {}", bind_address, err))?;
+    }
+    Ok(api_config)
+}
+
+/// Connects to the upstream fullnodes, checking that they're all for the expected chain
+///
+/// Returns `None` if there are no upstreams, as the server is offline.
+async fn connect_upstream(
+    chain_id: ChainId,
+    rest_clients: Vec<aptos_rest_client::Client>,
+    circuit_breaker: Option<CircuitBreakerConfig>,
+) -> Option<Arc<UpstreamClients>> {
+    if rest_clients.is_empty() {
+        return None;
+    }
+
+    let rest_clients = Arc::new(UpstreamClients::new(
+        chain_id,
+        rest_clients,
+        circuit_breaker,
+    ));
+    rest_clients
+        .check_chain_ids()
+        .await
+        .expect("Failed to match Rosetta chain Id to upstream servers");
+    rest_clients
+        .check_health()
+        .await
+        .expect("At least one upstream fullnode should be healthy on bootstrap");
+    Some(rest_clients)
+}
+
+/// Creates the context for a chain
+async fn create_context(
+    chain_id: ChainId,
+    page_size: u16,
+    rest_clients: Option<Arc<UpstreamClients>>,
+    owner_addresses: Vec<AccountAddress>,
+    config: &RosettaConfig,
+    accounts_dump: &AccountsDump,
+) -> RosettaContext {
+    let mode = if rest_clients.is_some() {
+        RosettaMode::Online
+    } else {
+        RosettaMode::Offline
+    };
+
+    // If it's Online mode, add the block cache, and keep checking the upstream fullnodes
+    let block_cache = rest_clients.as_ref().map(|rest_clients| {
+        tokio::spawn(
+            rest_clients
+                .clone()
+                .run_health_checks(UPSTREAM_HEALTH_CHECK_INTERVAL),
+        );
+        Arc::new(BlockRetriever::new(
+            page_size,
+            rest_clients.clone(),
+            config.slow_block_fetch_threshold,
+        ))
+    });
+    if mode == RosettaMode::Online {
+        config.upstream_timeouts.record_metrics();
+    }
+
+    let context = RosettaContext::new(
+        mode,
+        rest_clients.clone(),
+        chain_id,
+        block_cache,
+        owner_addresses,
+        config,
+    )
+    .await
+    .expect("The block cache is only created with the rest clients, so matches the mode");
+
+    // Keep the upstream fullnode version up to date, so upgrades show in `/network/options`
+    if let Some(rest_clients) = rest_clients {
+        tokio::spawn(
+            context
+                .node_version
+                .clone()
+                .run_refreshes(rest_clients, config.node_version_refresh_interval),
+        );
+    }
+
+    if let Some(ref path) = config.accounts_path {
+        if path.exists() {
+            match context.load_accounts(path) {
+                Ok(()) => info!("Loaded reserved sequence numbers from {:?}", path),
+                Err(err) => warn!(
+                    "Failed to load reserved sequence numbers from {:?}: {:#}",
+                    path, err
+                ),
+            }
+        }
+        accounts_dump.set(context.clone(), path.clone());
+    }
+    context
+}
+
+/// Handle to dump the reserved sequence numbers of a running server to its
+/// [`RosettaConfig::accounts_path`]
+///
+/// Dumping is left to the caller, e.g. when it's interrupted, rather than the server taking
+/// over the process' signal handling.
+#[derive(Clone, Debug, Default)]
+pub struct AccountsDump {
+    inner: Arc<OnceCell<(RosettaContext, PathBuf)>>,
+}
+
+impl AccountsDump {
+    fn set(&self, context: RosettaContext, path: PathBuf) {
+        // Each handle is only set up by the one server it's created for
+        let _ = self.inner.set((context, path));
+    }
+
+    /// Writes the reserved sequence numbers, returning the path they're written to, or `None`
+    /// if there's no `accounts_path` or the server hasn't started yet
+    pub fn dump(&self) -> anyhow::Result<Option<&Path>> {
+        match self.inner.get() {
+            Some((context, path)) => {
+                context.dump_accounts(path)?;
+                Ok(Some(path.as_path()))
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+/// Binds the Unix domain socket for the server, replacing a stale socket left at the path
+///
+/// The socket's permissions are set to [`UNIX_SOCKET_MODE`] rather than depending on the
+/// umask.  TLS isn't supported over the socket, so any TLS config is ignored.
+#[cfg(unix)]
+fn bind_unix_socket(path: &Path, api_config: &ApiConfig) -> anyhow::Result<Option<UnixListener>> {
+    use anyhow::Context;
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    if api_config.tls_cert_path.is_some() {
+        warn!(
+            "TLS isn't supported over a Unix domain socket, serving {:?} without TLS",
+            path
+        );
+    }
+    if let Ok(metadata) = std::fs::metadata(path) {
+        if metadata.file_type().is_socket() {
+            std::fs::remove_file(path)
+                .with_context(|| format!("Failed to remove stale Unix socket {:?}", path))?;
+        }
+    }
+
+    let listener = tokio::net::UnixListener::bind(path)
+        .with_context(|| format!("Failed to bind Unix socket {:?}", path))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(UNIX_SOCKET_MODE))
+        .with_context(|| format!("Failed to set permissions of Unix socket {:?}", path))?;
+    Ok(Some(listener))
+}
+
+/// Unix domain sockets aren't available, so the server falls back to TCP
+#[cfg(not(unix))]
+fn bind_unix_socket(path: &Path, api_config: &ApiConfig) -> anyhow::Result<Option<UnixListener>> {
+    aptos_logger::error!(
+        "Unix domain sockets aren't supported on this platform, serving on {} instead of {:?}",
+        api_config.address,
+        path
+    );
+    Ok(None)
+}
+
 /// Collection of all routes for the server
 pub fn routes(
     context: RosettaContext,
 ) -> impl Filter<Extract = (impl Reply,), Error = Infallible> + Clone {
-    account::routes(context.clone())
-        .or(block::block_route(context.clone()))
-        .or(construction::combine_route(context.clone()))
-        .or(construction::derive_route(context.clone()))
-        .or(construction::hash_route(context.clone()))
-        .or(construction::metadata_route(context.clone()))
-        .or(construction::parse_route(context.clone()))
-        .or(construction::payloads_route(context.clone()))
-        .or(construction::preprocess_route(context.clone()))
-        .or(construction::submit_route(context.clone()))
-        .or(network::list_route(context.clone()))
-        .or(network::options_route(context.clone()))
-        .or(network::status_route(context.clone()))
-        .or(health_check_route(context))
-        .with(
-            warp::cors()
-                .allow_any_origin()
-                .allow_methods(vec![Method::GET, Method::POST])
-                .allow_headers(vec![warp::http::header::CONTENT_TYPE]),
-        )
-        .with(logger())
+    let access_log_sample_rate = context.access_log_sample_rate;
+    rate_limit::rate_limit(context.clone())
+        .and(chain_routes(context))
+        .with(cors())
+        .with(access_logger(access_log_sample_rate))
         .recover(handle_rejection)
 }
 
+/// Routes for a single chain, without rate limiting or CORS
+fn chain_routes(
+    context: RosettaContext,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    version::negotiate_version().and(
+        account::routes(context.clone())
+            .or(block::block_route(context.clone()))
+            .or(block::events_route(context.clone()))
+            .or(construction::combine_route(context.clone()))
+            .or(construction::derive_route(context.clone()))
+            .or(construction::hash_route(context.clone()))
+            .or(construction::metadata_route(context.clone()))
+            .or(construction::parse_route(context.clone()))
+            .or(construction::payloads_route(context.clone()))
+            .or(construction::preprocess_route(context.clone()))
+            .or(construction::submit_route(context.clone()))
+            .or(network::gas_prices_route(context.clone()))
+            .or(network::list_route(context.clone()))
+            .or(network::options_route(context.clone()))
+            .or(network::status_route(context.clone()))
+            .or(search::search_transactions_route(context.clone()))
+            .or(sync_lag_route(context.clone()))
+            .or(health_check_route(context)),
+    )
+}
+
+fn cors() -> warp::cors::Builder {
+    warp::cors()
+        .allow_any_origin()
+        .allow_methods(vec![Method::GET, Method::POST])
+        .allow_headers(vec![
+            warp::http::header::CONTENT_TYPE.as_str(),
+            version::ROSETTA_VERSION_HEADER,
+        ])
+}
+
 /// Handle error codes from warp
 async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
     debug!("Failed with: {:?}", err);
+    if let Some(RateLimited { retry_after }) = err.find::<RateLimited>() {
+        let api_error = ApiError::RateLimited(None);
+        let status = api_error.status_code();
+        let mut rep =
+            reply::with_status(reply::json(&api_error.into_error()), status).into_response();
+        // Retry-After is in whole seconds, so round up
+        let retry_after_secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+        rep.headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from(retry_after_secs));
+        rep.headers_mut()
+            .insert("access-control-allow-origin", HeaderValue::from_static("*"));
+        return Ok(rep);
+    }
+
+    if let Some(VersionMismatch(api_error)) = err.find::<VersionMismatch>() {
+        let status = api_error.status_code();
+        let mut rep = reply::with_status(reply::json(&api_error.clone().into_error()), status)
+            .into_response();
+        rep.headers_mut()
+            .insert("access-control-allow-origin", HeaderValue::from_static("*"));
+        return Ok(rep);
+    }
+
     let body = reply::json(&Error::new(
         StatusCode::INTERNAL_SERVER_ERROR,
         format!("unexpected error: {:?}", err),
@@ -209,9 +878,15 @@ async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
 }
 
 /// These parameters are directly passed onto the underlying rest server for a healthcheck
-#[derive(serde::Deserialize)]
+#[derive(Clone, Copy, serde::Deserialize)]
 struct HealthCheckParams {
     pub duration_secs: Option<u64>,
+    /// Re-probe the upstream fullnodes rather than use the last known healthy one
+    ///
+    /// This is heavier, as it checks every upstream until one is healthy, so it shouldn't be
+    /// used by load balancer probes.
+    #[serde(default)]
+    pub force: bool,
 }
 
 /// Default amount of time the fullnode is accepted to be behind (arbitrarily it's 5 minutes)
@@ -232,9 +907,438 @@ async fn health_check(
     params: HealthCheckParams,
     server_context: RosettaContext,
 ) -> ApiResult<&'static str> {
-    let rest_client = server_context.rest_client()?;
+    server_context.check_online()?;
+    let rest_client = match server_context.rest_clients {
+        Some(ref rest_clients) if params.force => rest_clients.check_health().await?,
+        _ => server_context.rest_client()?,
+    };
     let duration_secs = params.duration_secs.unwrap_or(HEALTH_CHECK_DEFAULT_SECS);
     rest_client.health_check(duration_secs).await?;
 
     Ok("aptos-node:ok")
 }
+
+/// How far behind the upstream fullnodes are, for graphing rather than health checks
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct SyncLagResponse {
+    pub chains: Vec<ChainSyncLag>,
+}
+
+/// How far behind the upstream fullnode of a chain is
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct ChainSyncLag {
+    pub network_identifier: NetworkIdentifier,
+    /// Latest ledger version of the upstream fullnode
+    pub ledger_version: u64,
+    /// Latest block height of the upstream fullnode
+    pub block_height: u64,
+    /// Timestamp of the latest ledger version, in milliseconds since the Unix epoch
+    pub ledger_timestamp: u64,
+    /// How far the latest ledger timestamp is behind the wall clock, in milliseconds
+    pub lag_ms: u64,
+}
+
+/// Reports the upstream fullnode's sync lag as numbers, unlike `/-/healthy`
+pub fn sync_lag_route(
+    server_context: RosettaContext,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("-" / "sync_lag")
+        .and(warp::path::end())
+        .and(with_empty_request())
+        .and(with_context(server_context))
+        .and_then(handle_request(with_upstream_timeout(
+            EndpointClass::Network,
+            sync_lag,
+        )))
+}
+
+async fn sync_lag(
+    _empty: MetadataRequest,
+    server_context: RosettaContext,
+) -> ApiResult<SyncLagResponse> {
+    Ok(SyncLagResponse {
+        chains: vec![chain_sync_lag(&server_context).await?],
+    })
+}
+
+/// Retrieves the latest ledger info of the upstream fullnode, and compares it to the wall clock
+pub(crate) async fn chain_sync_lag(server_context: &RosettaContext) -> ApiResult<ChainSyncLag> {
+    server_context.check_online()?;
+    let state = server_context
+        .rest_client()?
+        .get_ledger_information()
+        .await?
+        .into_inner();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|err| ApiError::InternalError(Some(err.to_string())))?
+        .as_millis() as u64;
+    let ledger_timestamp = state.timestamp_usecs / 1000;
+
+    Ok(ChainSyncLag {
+        network_identifier: server_context.chain_id.into(),
+        ledger_version: state.version,
+        block_height: state.block_height,
+        ledger_timestamp,
+        lag_ms: now.saturating_sub(ledger_timestamp),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        routes, ChainConfig, ChainUpstream, RosettaConfig, RosettaContext, RosettaMode,
+        SyncLagResponse, DEFAULT_SLOW_BLOCK_FETCH_THRESHOLD,
+    };
+    use crate::{
+        block::BlockRetriever,
+        error::ApiError,
+        test_utils::{self, MockLedger},
+        types::{ConstructionDeriveRequest, NetworkIdentifier, PublicKey},
+        upstream::UpstreamClients,
+    };
+    use aptos_crypto::{ed25519::Ed25519PrivateKey, PrivateKey};
+    use aptos_types::{account_address::AccountAddress, chain_id::ChainId};
+    use std::{
+        convert::TryFrom,
+        sync::Arc,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+    use warp::{http::StatusCode, Filter};
+
+    async fn context(
+        mode: RosettaMode,
+        rest_clients: Option<Arc<UpstreamClients>>,
+        block_cache: Option<Arc<BlockRetriever>>,
+    ) -> anyhow::Result<RosettaContext> {
+        RosettaContext::new(
+            mode,
+            rest_clients,
+            ChainId::test(),
+            block_cache,
+            vec![],
+            &RosettaConfig::default(),
+        )
+        .await
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket_bind_errors_are_returned() {
+        use aptos_config::config::ApiConfig;
+
+        let path = std::env::temp_dir()
+            .join(format!("rosetta-missing-{}", std::process::id()))
+            .join("rosetta.sock");
+        let err = super::bootstrap(
+            ChainId::test(),
+            ApiConfig::default(),
+            vec![],
+            vec![],
+            RosettaConfig {
+                unix_socket_path: Some(path),
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("Failed to bind Unix socket"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn serves_on_unix_socket() {
+        use crate::UNIX_SOCKET_MODE;
+        use aptos_config::config::ApiConfig;
+        use std::os::unix::{fs::PermissionsExt, net::UnixListener};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // A stale socket left by a previous server is replaced
+        let path = std::env::temp_dir().join(format!("rosetta-{}.sock", std::process::id()));
+        drop(UnixListener::bind(&path).unwrap());
+
+        let _server = super::bootstrap_async(
+            ChainId::test(),
+            ApiConfig::default(),
+            vec![],
+            vec![],
+            RosettaConfig {
+                unix_socket_path: Some(path.clone()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            UNIX_SOCKET_MODE,
+            std::fs::metadata(&path).unwrap().permissions().mode() & 0o777
+        );
+
+        let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        stream
+            .write_all(
+                b"POST /network/list HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}",
+            )
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.contains("network_identifiers"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn accounts_are_loaded_on_startup() {
+        let dir = std::env::temp_dir().join(format!("rosetta-accounts-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("accounts.json");
+        // Only the dump's own suffix is used for its temporary file
+        let unrelated = dir.join("accounts.tmp");
+        std::fs::write(&unrelated, "unrelated").unwrap();
+
+        let context = context(RosettaMode::Offline, None, None).await.unwrap();
+        assert_eq!(5, context.sequence_numbers.reserve(AccountAddress::ONE, 5));
+        context.dump_accounts(&path).unwrap();
+        assert_eq!("unrelated", std::fs::read_to_string(&unrelated).unwrap());
+        assert!(!dir.join("accounts.json.tmp").exists());
+
+        let accounts_dump = super::AccountsDump::default();
+        assert_eq!(None, accounts_dump.dump().unwrap());
+        let restarted = super::create_context(
+            ChainId::test(),
+            100,
+            None,
+            vec![],
+            &RosettaConfig {
+                accounts_path: Some(path.clone()),
+                ..Default::default()
+            },
+            &accounts_dump,
+        )
+        .await;
+        assert_eq!(
+            6,
+            restarted.sequence_numbers.reserve(AccountAddress::ONE, 5)
+        );
+
+        // The handle dumps the restarted server's reservations
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(Some(path.as_path()), accounts_dump.dump().unwrap());
+        let reloaded = context(RosettaMode::Offline, None, None).await.unwrap();
+        reloaded.load_accounts(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(7, reloaded.sequence_numbers.reserve(AccountAddress::ONE, 5));
+    }
+
+    #[test]
+    fn upstreams_are_grouped_by_chain() {
+        let upstreams: Vec<ChainUpstream> = [
+            "testnet=http://testnet-1:8080",
+            "testing=http://localhost:8080",
+            "testnet=http://testnet-2:8080",
+        ]
+        .iter()
+        .map(|upstream| upstream.parse().unwrap())
+        .collect();
+        let chains = ChainConfig::from_upstreams(upstreams);
+        let urls: Vec<_> = chains
+            .iter()
+            .map(|chain| {
+                let urls: Vec<_> = chain
+                    .rest_clients
+                    .iter()
+                    .map(|client| client.path_prefix_string())
+                    .collect();
+                (chain.chain_id, urls)
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (ChainId::testnet(), vec![
+                    "http://testnet-1:8080/v1/".to_string(),
+                    "http://testnet-2:8080/v1/".to_string()
+                ]),
+                (ChainId::test(), vec![
+                    "http://localhost:8080/v1/".to_string()
+                ]),
+            ],
+            urls
+        );
+
+        "testnet".parse::<ChainUpstream>().unwrap_err();
+        "testnet=not a url".parse::<ChainUpstream>().unwrap_err();
+        "not a chain=http://localhost:8080"
+            .parse::<ChainUpstream>()
+            .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn mode_must_match_upstream() {
+        // Nothing listens on the upstream, but it's never called
+        let rest_clients = Arc::new(UpstreamClients::new(
+            ChainId::test(),
+            vec![aptos_rest_client::Client::new(
+                url::Url::parse("http://127.0.0.1:1").unwrap(),
+            )],
+            None,
+        ));
+        let block_cache = Arc::new(BlockRetriever::new(
+            100,
+            rest_clients.clone(),
+            DEFAULT_SLOW_BLOCK_FETCH_THRESHOLD,
+        ));
+
+        context(RosettaMode::Online, None, None).await.unwrap_err();
+        context(RosettaMode::Online, Some(rest_clients.clone()), None)
+            .await
+            .unwrap_err();
+        context(
+            RosettaMode::Offline,
+            Some(rest_clients.clone()),
+            Some(block_cache.clone()),
+        )
+        .await
+        .unwrap_err();
+
+        let online = context(RosettaMode::Online, Some(rest_clients), Some(block_cache))
+            .await
+            .unwrap();
+        assert_eq!(RosettaMode::Online, online.mode());
+        online.check_online().unwrap();
+    }
+
+    #[tokio::test]
+    async fn offline_rejects_online_endpoints() {
+        let routes = routes(context(RosettaMode::Offline, None, None).await.unwrap());
+        let network_identifier =
+            serde_json::to_value(NetworkIdentifier::from(ChainId::test())).unwrap();
+
+        // Endpoints which don't need a fullnode still work
+        let private_key = Ed25519PrivateKey::try_from([1u8; 32].as_slice()).unwrap();
+        let derive = serde_json::to_value(ConstructionDeriveRequest {
+            network_identifier: NetworkIdentifier::from(ChainId::test()),
+            public_key: PublicKey::try_from(private_key.public_key()).unwrap(),
+            auth_scheme: None,
+        })
+        .unwrap();
+        let network = serde_json::json!({ "network_identifier": network_identifier });
+        for (path, body) in [
+            ("/construction/derive", derive),
+            ("/network/options", network.clone()),
+        ] {
+            let response = warp::test::request()
+                .method("POST")
+                .path(path)
+                .json(&body)
+                .reply(&routes)
+                .await;
+            assert_eq!(StatusCode::OK, response.status(), "{}", path);
+        }
+
+        // Endpoints which do are consistently rejected
+        for (path, body) in [
+            ("/-/sync_lag", network.clone()),
+            ("/network/gas_prices", network.clone()),
+            ("/network/status", network),
+            (
+                "/search/transactions",
+                serde_json::json!({
+                    "network_identifier": network_identifier,
+                    "account_identifier": { "address": "0x1" },
+                }),
+            ),
+            (
+                "/account/balance",
+                serde_json::json!({
+                    "network_identifier": network_identifier,
+                    "account_identifier": { "address": "0x1" },
+                }),
+            ),
+            (
+                "/block",
+                serde_json::json!({
+                    "network_identifier": network_identifier,
+                    "block_identifier": { "index": 1 },
+                }),
+            ),
+            (
+                "/construction/submit",
+                serde_json::json!({
+                    "network_identifier": network_identifier,
+                    "signed_transaction": "00",
+                }),
+            ),
+        ] {
+            let response = warp::test::request()
+                .method("POST")
+                .path(path)
+                .json(&body)
+                .reply(&routes)
+                .await;
+            let error: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(
+                ApiError::NodeIsOffline.code(),
+                error["code"].as_u64().unwrap() as u32,
+                "{}",
+                path
+            );
+        }
+    }
+
+    /// Serves the ledger info of a fullnode whose latest ledger version is `lag` old
+    fn lagging_upstream(lag: Duration) -> url::Url {
+        let ledger = MockLedger {
+            ledger_timestamp_usecs: (SystemTime::now().duration_since(UNIX_EPOCH).unwrap() - lag)
+                .as_micros() as u64,
+            ..Default::default()
+        };
+        test_utils::mock_upstream(ledger, warp::any().map(move || ledger.index_response()))
+    }
+
+    #[tokio::test]
+    async fn sync_lag_is_reported() {
+        let rest_clients = Arc::new(UpstreamClients::new(
+            ChainId::test(),
+            vec![aptos_rest_client::Client::new(lagging_upstream(
+                Duration::from_secs(60),
+            ))],
+            None,
+        ));
+        let block_cache = Arc::new(BlockRetriever::new(
+            100,
+            rest_clients.clone(),
+            DEFAULT_SLOW_BLOCK_FETCH_THRESHOLD,
+        ));
+        let routes = routes(
+            context(RosettaMode::Online, Some(rest_clients), Some(block_cache))
+                .await
+                .unwrap(),
+        );
+
+        let response = warp::test::request()
+            .method("GET")
+            .path("/-/sync_lag")
+            .reply(&routes)
+            .await;
+        assert_eq!(StatusCode::OK, response.status());
+        let response: SyncLagResponse = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(1, response.chains.len());
+        let chain = &response.chains[0];
+        assert_eq!(
+            NetworkIdentifier::from(ChainId::test()),
+            chain.network_identifier
+        );
+        assert_eq!(100, chain.ledger_version);
+        assert_eq!(10, chain.block_height);
+        // Allow for the time taken by the request itself
+        assert!(
+            (60_000..65_000).contains(&chain.lag_ms),
+            "Unexpected lag of {}ms",
+            chain.lag_ms
+        );
+    }
+}
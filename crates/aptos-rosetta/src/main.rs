@@ -6,21 +6,22 @@
 use aptos_config::config::{ApiConfig, DEFAULT_MAX_PAGE_SIZE};
 use aptos_logger::prelude::*;
 use aptos_node::AptosNodeArgs;
-use aptos_rosetta::bootstrap;
+use aptos_rosetta::{
+    access_log::DEFAULT_ACCESS_LOG_SAMPLE_RATE,
+    balance_check::BalanceCheckMode,
+    bootstrap, bootstrap_multi_chain_async,
+    node_version::DEFAULT_NODE_VERSION_REFRESH_INTERVAL,
+    rate_limit::RateLimitConfig,
+    submit_queue::SubmitQueueConfig,
+    timeouts::{EndpointTimeout, UpstreamTimeouts},
+    upstream::CircuitBreakerConfig,
+    AccountsDump, ChainConfig, ChainUpstream, RosettaConfig, DEFAULT_MAX_BALANCE_BATCH_SIZE,
+    DEFAULT_SLOW_BLOCK_FETCH_THRESHOLD,
+};
 use aptos_sdk::move_types::account_address::AccountAddress;
 use aptos_types::chain_id::ChainId;
 use clap::Parser;
-use std::{
-    fs::read_to_string,
-    net::SocketAddr,
-    path::PathBuf,
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    },
-    thread,
-    time::Duration,
-};
+use std::{fs::read_to_string, net::SocketAddr, path::PathBuf, thread, time::Duration};
 use tokio::time::Instant;
 
 /// Poll every 100 ms
@@ -40,6 +41,9 @@ async fn main() {
             println!("aptos-rosetta: Starting Rosetta in Online (with local full node) mode")
         },
         CommandArgs::Offline(_) => println!("aptos-rosetta: Starting Rosetta in Offline mode"),
+        CommandArgs::MultiChain(_) => {
+            println!("aptos-rosetta: Starting Rosetta in Online multi-chain mode")
+        },
     }
 
     // If we're in online mode, we run a full node side by side, the fullnode sets up the logger
@@ -84,21 +88,64 @@ async fn main() {
     };
 
     println!("aptos-rosetta: Starting rosetta");
-    // Ensure runtime for Rosetta is up and running
-    let _rosetta = bootstrap(
+    // Ensure runtime for Rosetta is up and running, a multi-chain server runs on this one
+    let rosetta = match args {
+        CommandArgs::OnlineRemote(ref args) => Some(bootstrap_server(args)),
+        CommandArgs::Online(ref args) => Some(bootstrap_server(args)),
+        CommandArgs::Offline(ref args) => Some(bootstrap_server(args)),
+        CommandArgs::MultiChain(ref args) => {
+            bootstrap_multi_chain_async(
+                args.offline_args.api_config(),
+                ChainConfig::from_upstreams(args.chains.clone()),
+                args.offline_args.rosetta_config(),
+            )
+            .await
+            .expect("aptos-rosetta: Should bootstrap multi-chain rosetta server");
+            None
+        },
+    };
+
+    println!("aptos-rosetta: Rosetta started");
+    // Run until there is an interrupt, then save the reserved sequence numbers
+    shutdown_signal().await;
+    println!("aptos-rosetta: Shutting down");
+    if let Some((runtime, accounts_dump)) = rosetta {
+        match accounts_dump.dump() {
+            Ok(Some(path)) => info!("Dumped reserved sequence numbers to {:?}", path),
+            Ok(None) => {},
+            Err(err) => error!("Failed to dump reserved sequence numbers: {:#}", err),
+        }
+        // The runtime can't be dropped from within this one
+        runtime.shutdown_background();
+    }
+}
+
+/// Waits for an interrupt, or on Unix for a terminate signal
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate =
+            signal(SignalKind::terminate()).expect("Should be able to listen for SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {},
+            _ = terminate.recv() => {},
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Bootstraps a server for a single chain
+fn bootstrap_server(args: &impl ServerArgs) -> (tokio::runtime::Runtime, AccountsDump) {
+    bootstrap(
         args.chain_id(),
         args.api_config(),
-        args.rest_client(),
+        args.rest_clients(),
         args.owner_addresses(),
+        args.rosetta_config(),
     )
-    .expect("aptos-rosetta: Should bootstrap rosetta server");
-
-    println!("aptos-rosetta: Rosetta started");
-    // Run until there is an interrupt
-    let term = Arc::new(AtomicBool::new(false));
-    while !term.load(Ordering::Acquire) {
-        std::thread::park();
-    }
+    .expect("aptos-rosetta: Should bootstrap rosetta server")
 }
 
 /// A trait to provide common values from both online and offline mode
@@ -106,14 +153,19 @@ trait ServerArgs {
     /// Retrieve the API config for the local server
     fn api_config(&self) -> ApiConfig;
 
-    /// Retrieve the optional rest client for the local server
-    fn rest_client(&self) -> Option<aptos_rest_client::Client>;
+    /// Retrieve the rest clients for the upstream fullnodes, in order of preference
+    ///
+    /// Empty if the server is offline
+    fn rest_clients(&self) -> Vec<aptos_rest_client::Client>;
 
     /// Retrieve the chain id
     fn chain_id(&self) -> ChainId;
 
     /// Retrieve owner addresses
     fn owner_addresses(&self) -> Vec<AccountAddress>;
+
+    /// Retrieve the options for the server
+    fn rosetta_config(&self) -> RosettaConfig;
 }
 
 /// Aptos Rosetta API Server
@@ -126,42 +178,8 @@ pub enum CommandArgs {
     OnlineRemote(OnlineRemoteArgs),
     /// Run a local full node in tandem with Rosetta
     Online(OnlineLocalArgs),
-    /// Run a local online server that doesn't connect to a fullnode endpoint
-    Offline(OfflineArgs),
-}
-
-impl ServerArgs for CommandArgs {
-    fn api_config(&self) -> ApiConfig {
-        match self {
-            CommandArgs::OnlineRemote(args) => args.api_config(),
-            CommandArgs::Offline(args) => args.api_config(),
-            CommandArgs::Online(args) => args.api_config(),
-        }
-    }
-
-    fn rest_client(&self) -> Option<aptos_rest_client::Client> {
-        match self {
-            CommandArgs::OnlineRemote(args) => args.rest_client(),
-            CommandArgs::Offline(args) => args.rest_client(),
-            CommandArgs::Online(args) => args.rest_client(),
-        }
-    }
-
-    fn chain_id(&self) -> ChainId {
-        match self {
-            CommandArgs::OnlineRemote(args) => args.chain_id(),
-            CommandArgs::Offline(args) => args.chain_id(),
-            CommandArgs::Online(args) => args.chain_id(),
-        }
-    }
-
-    fn owner_addresses(&self) -> Vec<AccountAddress> {
-        match self {
-            CommandArgs::OnlineRemote(args) => args.owner_addresses(),
-            CommandArgs::Offline(args) => args.owner_addresses(),
-            CommandArgs::Online(args) => args.owner_addresses(),
-        }
-    }
+    /// Run a local online server for multiple chains, that connects to fullnode endpoints
+    MultiChain(MultiChainArgs),
 }
 
 #[derive(Debug, Parser)]
@@ -186,6 +204,46 @@ pub struct OfflineArgs {
     /// This can be configured to change performance characteristics
     #[clap(long, default_value_t = DEFAULT_MAX_PAGE_SIZE)]
     transactions_page_size: u16,
+    /// Requests per second allowed for each client IP, rate limiting is disabled if not set
+    #[clap(long)]
+    rate_limit_per_second: Option<f64>,
+    /// Number of requests each client IP can burst before being rate limited
+    #[clap(long, default_value_t = 10)]
+    rate_limit_burst: u32,
+    /// Use the X-Forwarded-For header for the client IP when rate limiting
+    ///
+    /// Only enable this when running behind a trusted proxy, as clients can set the header
+    #[clap(long)]
+    rate_limit_trust_forwarded_for: bool,
+    /// Path of a Unix domain socket to listen on instead of the listen address
+    ///
+    /// Only supported on Unix platforms, TLS is not supported over the socket.  The socket is
+    /// only accessible to its owner and group, and its clients are only rate limited by a
+    /// trusted X-Forwarded-For header, as they have no IP
+    #[clap(long, parse(from_os_str))]
+    unix_socket_path: Option<PathBuf>,
+    /// Log only 1 in this many successful requests, errors are always logged
+    ///
+    /// Every request is still counted in the metrics
+    #[clap(long, default_value_t = DEFAULT_ACCESS_LOG_SAMPLE_RATE)]
+    access_log_sample_rate: u64,
+    /// Reject constructing transfers where the sender is also the receiver
+    ///
+    /// These are often a sign of a bug in the caller
+    #[clap(long)]
+    forbid_self_transfer: bool,
+    /// Maximum amount, in the currency's smallest unit, of a transfer to construct
+    ///
+    /// Larger transfers fail with `TransferAmountTooLarge`, as a last line of defense against
+    /// a bug in the caller
+    #[clap(long)]
+    max_transfer_amount: Option<u64>,
+    /// File to keep the reserved sequence numbers of accounts in across restarts
+    ///
+    /// They're loaded from it on startup, and dumped to it when interrupted or terminated.
+    /// Ignored in multi-chain mode.
+    #[clap(long, parse(from_os_str))]
+    accounts_path: Option<PathBuf>,
 }
 
 impl ServerArgs for OfflineArgs {
@@ -201,8 +259,8 @@ impl ServerArgs for OfflineArgs {
         }
     }
 
-    fn rest_client(&self) -> Option<aptos_rest_client::Client> {
-        None
+    fn rest_clients(&self) -> Vec<aptos_rest_client::Client> {
+        vec![]
     }
 
     fn chain_id(&self) -> ChainId {
@@ -212,6 +270,24 @@ impl ServerArgs for OfflineArgs {
     fn owner_addresses(&self) -> Vec<AccountAddress> {
         vec![]
     }
+
+    fn rosetta_config(&self) -> RosettaConfig {
+        RosettaConfig {
+            unix_socket_path: self.unix_socket_path.clone(),
+            rate_limit: self
+                .rate_limit_per_second
+                .map(|requests_per_second| RateLimitConfig {
+                    requests_per_second,
+                    burst: self.rate_limit_burst,
+                    trust_forwarded_for: self.rate_limit_trust_forwarded_for,
+                }),
+            forbid_self_transfer: self.forbid_self_transfer,
+            access_log_sample_rate: self.access_log_sample_rate,
+            max_transfer_amount: self.max_transfer_amount,
+            accounts_path: self.accounts_path.clone(),
+            ..Default::default()
+        }
+    }
 }
 
 #[derive(Debug, Parser)]
@@ -221,9 +297,67 @@ pub struct OnlineRemoteArgs {
     /// URL for the Aptos REST API. e.g. https://fullnode.devnet.aptoslabs.com
     #[clap(long, default_value = "http://localhost:8080")]
     rest_api_url: url::Url,
+    /// URLs for fallback Aptos REST APIs, in order of preference, used if the primary is unhealthy
+    #[clap(long)]
+    failover_rest_api_urls: Vec<url::Url>,
     /// Owner addresses file as a YAML file with a list
     #[clap(long, parse(from_os_str))]
     owner_address_file: Option<PathBuf>,
+    /// Verify transaction hashes from /construction/hash against the fullnode
+    ///
+    /// This catches any drift between how Rosetta and the fullnode hash transactions
+    #[clap(long)]
+    verify_transaction_hashes: bool,
+    /// Check that coin operations in each block balance, either `log` or `error` on imbalance
+    ///
+    /// Useful for validating the mapping of events to operations.  Deposits minted by the
+    /// framework's mint entry function add to the supply, so they don't need a withdrawal.
+    #[clap(long)]
+    check_block_balances: Option<BalanceCheckMode>,
+    /// Consecutive failed upstream requests before failing requests fast
+    ///
+    /// The circuit breaker is disabled if not set
+    #[clap(long)]
+    circuit_breaker_failure_threshold: Option<u32>,
+    /// Seconds to fail requests fast before trying the upstream fullnodes again
+    #[clap(long, default_value_t = 30)]
+    circuit_breaker_cooldown_secs: u64,
+    /// Maximum number of transactions submitted to the fullnode at once
+    ///
+    /// Submits are unlimited if not set
+    #[clap(long)]
+    submit_max_concurrency: Option<usize>,
+    /// Maximum number of submits waiting for the fullnode before failing with `Overloaded`
+    #[clap(long, default_value_t = 100)]
+    submit_max_queued: usize,
+    /// Maximum number of blocks before the latest that can be queried
+    ///
+    /// Older blocks fail with `BlockTooOld`, and all history can be queried if not set
+    #[clap(long)]
+    max_historical_depth: Option<u64>,
+    /// Maximum number of operations in a `/block` response
+    ///
+    /// Larger blocks fail with `BlockTooLarge`, and blocks of any size are served if not set
+    #[clap(long)]
+    max_operations_per_block: Option<usize>,
+    /// Seconds to wait on the upstream fullnode before failing with `UpstreamTimeout`
+    #[clap(long, default_value_t = 30)]
+    upstream_timeout_secs: u64,
+    /// Timeout for a class of endpoint instead of the default, as `<class>=<seconds>`
+    ///
+    /// Classes are `account`, `block`, `construction`, `submit`, and `network` e.g.
+    /// `--endpoint-timeout submit=60`
+    #[clap(long)]
+    endpoint_timeout: Vec<EndpointTimeout>,
+    /// Maximum number of accounts in one `/account/balances` request
+    #[clap(long, default_value_t = DEFAULT_MAX_BALANCE_BATCH_SIZE)]
+    max_balance_batch_size: usize,
+    /// Seconds between refreshes of the upstream fullnode version in `/network/options`
+    #[clap(long, default_value_t = DEFAULT_NODE_VERSION_REFRESH_INTERVAL.as_secs())]
+    node_version_refresh_interval_secs: u64,
+    /// Milliseconds an upstream block fetch can take before it's logged and counted as slow
+    #[clap(long, default_value_t = DEFAULT_SLOW_BLOCK_FETCH_THRESHOLD.as_millis() as u64)]
+    slow_block_fetch_threshold_ms: u64,
 }
 
 impl ServerArgs for OnlineRemoteArgs {
@@ -231,8 +365,11 @@ impl ServerArgs for OnlineRemoteArgs {
         self.offline_args.api_config()
     }
 
-    fn rest_client(&self) -> Option<aptos_rest_client::Client> {
-        Some(aptos_rest_client::Client::new(self.rest_api_url.clone()))
+    fn rest_clients(&self) -> Vec<aptos_rest_client::Client> {
+        std::iter::once(&self.rest_api_url)
+            .chain(self.failover_rest_api_urls.iter())
+            .map(|url| aptos_rest_client::Client::new(url.clone()))
+            .collect()
     }
 
     fn chain_id(&self) -> ChainId {
@@ -249,6 +386,56 @@ impl ServerArgs for OnlineRemoteArgs {
             vec![]
         }
     }
+
+    fn rosetta_config(&self) -> RosettaConfig {
+        let upstream_timeouts = self.endpoint_timeout.iter().fold(
+            UpstreamTimeouts::new(Duration::from_secs(self.upstream_timeout_secs)),
+            |timeouts, endpoint_timeout| {
+                timeouts.with_timeout(endpoint_timeout.class, endpoint_timeout.timeout)
+            },
+        );
+        RosettaConfig {
+            verify_transaction_hashes: self.verify_transaction_hashes,
+            balance_check: self.check_block_balances,
+            circuit_breaker: self
+                .circuit_breaker_failure_threshold
+                .map(|failure_threshold| CircuitBreakerConfig {
+                    failure_threshold,
+                    cooldown: Duration::from_secs(self.circuit_breaker_cooldown_secs),
+                }),
+            submit_queue: self
+                .submit_max_concurrency
+                .map(|max_concurrency| SubmitQueueConfig {
+                    max_concurrency,
+                    max_queued: self.submit_max_queued,
+                }),
+            max_historical_depth: self.max_historical_depth,
+            upstream_timeouts,
+            max_balance_batch_size: self.max_balance_batch_size,
+            max_operations_per_block: self.max_operations_per_block,
+            node_version_refresh_interval: Duration::from_secs(
+                self.node_version_refresh_interval_secs,
+            ),
+            slow_block_fetch_threshold: Duration::from_millis(self.slow_block_fetch_threshold_ms),
+            ..self.offline_args.rosetta_config()
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct MultiChainArgs {
+    /// Options of the server, which apply to every chain
+    ///
+    /// The chain id, Unix socket path and accounts path are ignored, as each chain has its own
+    /// id and accounts, and only TCP is supported for multiple chains
+    #[clap(flatten)]
+    offline_args: OfflineArgs,
+    /// Upstream fullnode of a chain as `<chain_id>=<rest_api_url>`, repeated for each chain
+    ///
+    /// A chain given more than once has failover upstreams, in the order given e.g.
+    /// `--chain testnet=https://fullnode.testnet.aptoslabs.com --chain mainnet=https://fullnode.mainnet.aptoslabs.com`
+    #[clap(long = "chain", required = true)]
+    chains: Vec<ChainUpstream>,
 }
 
 #[derive(Debug, Parser)]
@@ -264,10 +451,8 @@ impl ServerArgs for OnlineLocalArgs {
         self.online_args.offline_args.api_config()
     }
 
-    fn rest_client(&self) -> Option<aptos_rest_client::Client> {
-        Some(aptos_rest_client::Client::new(
-            self.online_args.rest_api_url.clone(),
-        ))
+    fn rest_clients(&self) -> Vec<aptos_rest_client::Client> {
+        self.online_args.rest_clients()
     }
 
     fn chain_id(&self) -> ChainId {
@@ -277,4 +462,8 @@ impl ServerArgs for OnlineLocalArgs {
     fn owner_addresses(&self) -> Vec<AccountAddress> {
         self.online_args.owner_addresses()
     }
+
+    fn rosetta_config(&self) -> RosettaConfig {
+        self.online_args.rosetta_config()
+    }
 }
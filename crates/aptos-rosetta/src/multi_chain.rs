@@ -0,0 +1,312 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Serving multiple chains from a single Rosetta server
+//!
+//! Each chain has its own [`RosettaContext`], and requests are dispatched to the chain in
+//! their `network_identifier`.  Requests for a chain that isn't configured fail with
+//! [`ApiError::NetworkIdentifierMismatch`].
+//!
+//! `/network/list`, `/-/healthy`, and `/-/sync_lag` aren't for a single chain, so they cover
+//! every chain: the list has all of the chains, the server is only healthy if every chain is,
+//! and the sync lag of every chain is reported.
+
+use crate::{
+    access_log::{access_logger, DEFAULT_ACCESS_LOG_SAMPLE_RATE},
+    chain_routes, chain_sync_lag,
+    common::BLOCKCHAIN,
+    cors,
+    error::{ApiError, ApiResult},
+    handle_rejection, health_check,
+    rate_limit::{rate_limit_with, RateLimiter},
+    types::{NetworkIdentifier, NetworkListResponse},
+    HealthCheckParams, RosettaContext, SyncLagResponse,
+};
+use aptos_logger::debug;
+use aptos_types::chain_id::ChainId;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, convert::Infallible, sync::Arc};
+use warp::{
+    http::{HeaderMap, Method},
+    hyper::{body::Bytes, service::Service, Body, Request},
+    path::FullPath,
+    reply::{self, Response},
+    Filter, Reply,
+};
+
+/// Only the network identifier of a request, to pick the chain to dispatch it to
+#[derive(Deserialize)]
+struct NetworkScopedRequest {
+    network_identifier: NetworkIdentifier,
+}
+
+/// Collection of all routes for a server serving the chains in `contexts`
+///
+/// Requests are logged at the most frequent access log sample rate of the chains.
+pub fn routes(
+    contexts: HashMap<ChainId, RosettaContext>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+) -> impl Filter<Extract = (impl Reply,), Error = Infallible> + Clone {
+    let services: HashMap<_, _> = contexts
+        .iter()
+        .map(|(chain_id, context)| {
+            let service = warp::service(chain_routes(context.clone()).recover(handle_rejection));
+            (*chain_id, service)
+        })
+        .collect();
+    let access_log_sample_rate = contexts
+        .values()
+        .map(|context| context.access_log_sample_rate)
+        .min()
+        .unwrap_or(DEFAULT_ACCESS_LOG_SAMPLE_RATE);
+    let contexts = Arc::new(contexts);
+
+    rate_limit_with(rate_limiter)
+        .and(
+            list_route(contexts.clone())
+                .or(health_check_route(contexts.clone()))
+                .or(sync_lag_route(contexts))
+                .or(dispatch_route(Arc::new(services))),
+        )
+        .with(cors())
+        .with(access_logger(access_log_sample_rate))
+        .recover(handle_rejection)
+}
+
+/// Lists every chain served
+fn list_route(
+    contexts: Arc<HashMap<ChainId, RosettaContext>>,
+) -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("network" / "list")
+        .and(warp::post())
+        .map(move || {
+            debug!("/network/list");
+            let mut network_identifiers: Vec<NetworkIdentifier> =
+                contexts.keys().map(|chain_id| (*chain_id).into()).collect();
+            network_identifiers.sort_by(|first, second| first.network.cmp(&second.network));
+            api_reply(Ok(NetworkListResponse {
+                network_identifiers,
+            }))
+        })
+}
+
+/// Checks the health of every chain served
+fn health_check_route(
+    contexts: Arc<HashMap<ChainId, RosettaContext>>,
+) -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("-" / "healthy")
+        .and(warp::path::end())
+        .and(warp::query::<HealthCheckParams>())
+        .and_then(move |params: HealthCheckParams| {
+            let contexts = contexts.clone();
+            async move {
+                let mut result = Ok("aptos-node:ok");
+                for context in contexts.values() {
+                    result = health_check(params, context.clone()).await;
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                Ok::<_, Infallible>(api_reply(result))
+            }
+        })
+}
+
+/// Reports the sync lag of every chain served, failing if any chain's can't be retrieved
+fn sync_lag_route(
+    contexts: Arc<HashMap<ChainId, RosettaContext>>,
+) -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("-" / "sync_lag")
+        .and(warp::path::end())
+        .and_then(move || {
+            let contexts = contexts.clone();
+            async move {
+                let mut chains = Vec::new();
+                for context in contexts.values() {
+                    match chain_sync_lag(context).await {
+                        Ok(chain) => chains.push(chain),
+                        Err(err) => return Ok::<_, Infallible>(api_reply::<()>(Err(err))),
+                    }
+                }
+                chains.sort_by(|first, second| {
+                    first
+                        .network_identifier
+                        .network
+                        .cmp(&second.network_identifier.network)
+                });
+                Ok(api_reply(Ok(SyncLagResponse { chains })))
+            }
+        })
+}
+
+/// Dispatches any other request to the chain in its network identifier
+fn dispatch_route<S>(
+    services: Arc<HashMap<ChainId, S>>,
+) -> impl Filter<Extract = (Response,), Error = warp::Rejection> + Clone
+where
+    S: Service<Request<Body>, Response = Response, Error = Infallible> + Clone + Send + Sync,
+    S::Future: Send,
+{
+    warp::method()
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .and_then(
+            move |method: Method, path: FullPath, headers: HeaderMap, body: Bytes| {
+                let services = services.clone();
+                async move {
+                    let service = request_chain_id(&body).and_then(|chain_id| {
+                        services
+                            .get(&chain_id)
+                            .cloned()
+                            .ok_or(ApiError::NetworkIdentifierMismatch)
+                    });
+                    let mut service = match service {
+                        Ok(service) => service,
+                        Err(err) => return Ok::<_, Infallible>(api_reply::<()>(Err(err))),
+                    };
+
+                    let mut request = Request::new(Body::from(body));
+                    *request.method_mut() = method;
+                    *request.headers_mut() = headers;
+                    match path.as_str().parse() {
+                        Ok(uri) => *request.uri_mut() = uri,
+                        Err(err) => {
+                            return Ok(api_reply::<()>(Err(ApiError::InternalError(Some(
+                                format!("Invalid request path: {}", err),
+                            )))))
+                        },
+                    }
+
+                    match service.call(request).await {
+                        Ok(response) => Ok(response),
+                        Err(never) => match never {},
+                    }
+                }
+            },
+        )
+}
+
+/// The chain that a request is for, from its network identifier
+fn request_chain_id(body: &[u8]) -> ApiResult<ChainId> {
+    let request: NetworkScopedRequest = serde_json::from_slice(body).map_err(|err| {
+        ApiError::DeserializationFailed(Some(format!(
+            "Request must have a network_identifier: {}",
+            err
+        )))
+    })?;
+    if request.network_identifier.blockchain != BLOCKCHAIN {
+        return Err(ApiError::NetworkIdentifierMismatch);
+    }
+    request
+        .network_identifier
+        .chain_id()
+        .map_err(|_| ApiError::NetworkIdentifierMismatch)
+}
+
+/// Converts a result to a reply, the same way as [`crate::common::handle_request`]
+fn api_reply<T: Serialize>(result: ApiResult<T>) -> Response {
+    match result {
+        Ok(response) => {
+            reply::with_status(reply::json(&response), warp::http::StatusCode::OK).into_response()
+        },
+        Err(api_error) => {
+            debug!("Error: {:?}", api_error);
+            let status = api_error.status_code();
+            reply::with_status(reply::json(&api_error.into_error()), status).into_response()
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::routes;
+    use crate::{
+        error::ApiError,
+        types::{
+            ConstructionDeriveRequest, MetadataRequest, NetworkIdentifier, NetworkListResponse,
+            PublicKey,
+        },
+        RosettaConfig, RosettaContext, RosettaMode,
+    };
+    use aptos_crypto::{ed25519::Ed25519PrivateKey, PrivateKey};
+    use aptos_types::chain_id::ChainId;
+    use std::collections::HashMap;
+    use warp::http::StatusCode;
+
+    async fn contexts(chain_ids: &[ChainId]) -> HashMap<ChainId, RosettaContext> {
+        let mut contexts = HashMap::new();
+        for chain_id in chain_ids {
+            let context = RosettaContext::new(
+                RosettaMode::Offline,
+                None,
+                *chain_id,
+                None,
+                vec![],
+                &RosettaConfig::default(),
+            )
+            .await
+            .unwrap();
+            contexts.insert(*chain_id, context);
+        }
+        contexts
+    }
+
+    fn derive_request(chain_id: ChainId) -> ConstructionDeriveRequest {
+        let private_key = Ed25519PrivateKey::try_from([1u8; 32].as_slice()).unwrap();
+        ConstructionDeriveRequest {
+            network_identifier: NetworkIdentifier::from(chain_id),
+            public_key: PublicKey::try_from(private_key.public_key()).unwrap(),
+            auth_scheme: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn lists_every_chain() {
+        let chain_ids = [ChainId::test(), ChainId::new(5)];
+        let routes = routes(contexts(&chain_ids).await, None);
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/network/list")
+            .json(&MetadataRequest {})
+            .reply(&routes)
+            .await;
+        assert_eq!(StatusCode::OK, response.status());
+        let response: NetworkListResponse = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(2, response.network_identifiers.len());
+        for chain_id in chain_ids {
+            assert!(response
+                .network_identifiers
+                .contains(&NetworkIdentifier::from(chain_id)));
+        }
+    }
+
+    #[tokio::test]
+    async fn dispatches_by_network_identifier() {
+        let routes = routes(contexts(&[ChainId::test(), ChainId::new(5)]).await, None);
+
+        for chain_id in [ChainId::test(), ChainId::new(5)] {
+            let response = warp::test::request()
+                .method("POST")
+                .path("/construction/derive")
+                .json(&derive_request(chain_id))
+                .reply(&routes)
+                .await;
+            assert_eq!(StatusCode::OK, response.status());
+        }
+
+        // A chain that isn't configured is rejected
+        let response = warp::test::request()
+            .method("POST")
+            .path("/construction/derive")
+            .json(&derive_request(ChainId::new(6)))
+            .reply(&routes)
+            .await;
+        let error: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(
+            ApiError::NetworkIdentifierMismatch.code(),
+            error["code"].as_u64().unwrap() as u32
+        );
+    }
+}
@@ -2,13 +2,18 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    common::{check_network, handle_request, with_context, with_empty_request},
+    common::{
+        check_network, handle_request, oldest_queryable_block, with_context, with_empty_request,
+        with_upstream_timeout,
+    },
     error::ApiError,
+    timeouts::EndpointClass,
     types::{
-        Allow, MetadataRequest, NetworkListResponse, NetworkOptionsResponse, NetworkRequest,
-        NetworkStatusResponse, OperationStatusType, OperationType, Version,
+        Allow, GasPricePriority, MetadataRequest, NetworkGasPricesResponse, NetworkListResponse,
+        NetworkOptionsResponse, NetworkRequest, NetworkStatusResponse, OperationStatusType,
+        OperationType, Version,
     },
-    RosettaContext, NODE_VERSION, ROSETTA_VERSION,
+    RosettaContext, ROSETTA_VERSION,
 };
 use aptos_logger::{debug, trace};
 use warp::Filter;
@@ -40,7 +45,23 @@ pub fn status_route(
         .and(warp::post())
         .and(warp::body::json())
         .and(with_context(server_context))
-        .and_then(handle_request(network_status))
+        .and_then(handle_request(with_upstream_timeout(
+            EndpointClass::Network,
+            network_status,
+        )))
+}
+
+pub fn gas_prices_route(
+    server_context: RosettaContext,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("network" / "gas_prices")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_context(server_context))
+        .and_then(handle_request(with_upstream_timeout(
+            EndpointClass::Network,
+            network_gas_prices,
+        )))
 }
 
 /// List [`NetworkIdentifier`]s supported by this proxy aka [`ChainId`]s
@@ -86,8 +107,7 @@ async fn network_options(
 
     let version = Version {
         rosetta_version: ROSETTA_VERSION.to_string(),
-        // TODO: Get from node via REST API
-        node_version: NODE_VERSION.to_string(),
+        node_version: server_context.node_version.get(),
         middleware_version: "0.1.0".to_string(),
     };
 
@@ -137,6 +157,7 @@ async fn network_status(
     );
 
     check_network(request.network_identifier, &server_context)?;
+    server_context.check_online()?;
     let chain_id = server_context.chain_id;
     let rest_client = server_context.rest_client()?;
     let block_cache = server_context.block_cache()?;
@@ -147,9 +168,14 @@ async fn network_status(
     let response = rest_client.get_ledger_information().await?;
     let state = response.state();
 
-    // Get the oldest block
+    // Get the oldest block that can be queried
+    let oldest_block_height = oldest_queryable_block(
+        state.block_height,
+        state.oldest_block_height,
+        server_context.max_historical_depth,
+    );
     let oldest_block_identifier = block_cache
-        .get_block_info_by_height(state.oldest_block_height, chain_id)
+        .get_block_info_by_height(oldest_block_height, chain_id)
         .await?
         .block_id;
 
@@ -170,3 +196,40 @@ async fn network_status(
 
     Ok(response)
 }
+
+/// Get the gas unit prices the upstream fullnode estimates, by priority
+///
+/// This is an Aptos specific extension, and not part of the Rosetta spec.  Estimates are cached
+/// for [`GAS_PRICE_CACHE_TTL`](crate::gas_prices::GAS_PRICE_CACHE_TTL).  If the fullnode doesn't
+/// give a low or high estimate, the normal one is used, as in `/construction/metadata`.
+async fn network_gas_prices(
+    request: NetworkRequest,
+    server_context: RosettaContext,
+) -> Result<NetworkGasPricesResponse, ApiError> {
+    debug!("/network/gas_prices");
+    trace!(
+        request = ?request,
+        server_context = ?server_context,
+        "network_gas_prices",
+    );
+
+    check_network(request.network_identifier, &server_context)?;
+    server_context.check_online()?;
+    let rest_client = server_context.rest_client()?;
+    let gas_estimation = server_context.gas_prices.get(&rest_client).await?;
+
+    let price = |priority| {
+        let price = match priority {
+            GasPricePriority::Low => gas_estimation.deprioritized_gas_estimate,
+            GasPricePriority::Normal => None,
+            GasPricePriority::High => gas_estimation.prioritized_gas_estimate,
+        };
+        price.unwrap_or(gas_estimation.gas_estimate).into()
+    };
+
+    Ok(NetworkGasPricesResponse {
+        low: price(GasPricePriority::Low),
+        normal: price(GasPricePriority::Normal),
+        high: price(GasPricePriority::High),
+    })
+}
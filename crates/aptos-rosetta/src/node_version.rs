@@ -0,0 +1,141 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Version of the upstream fullnode, as reported by `/network/options`
+//!
+//! The version is refreshed periodically in the background, so `/network/options` reflects
+//! fullnode upgrades without restarting Rosetta.  If the upstream can't be reached, the last
+//! known version is kept rather than cleared.
+
+use crate::{error::ApiResult, upstream::UpstreamClients, NODE_VERSION};
+use aptos_infallible::RwLock;
+use aptos_logger::{debug, warn};
+use std::{sync::Arc, time::Duration};
+
+/// Default interval between refreshes of the upstream fullnode version
+pub const DEFAULT_NODE_VERSION_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The last known version of the upstream fullnode
+///
+/// Until the first successful refresh, or if the server is offline, this is [`NODE_VERSION`].
+#[derive(Debug)]
+pub struct NodeVersion {
+    version: RwLock<String>,
+}
+
+impl Default for NodeVersion {
+    fn default() -> Self {
+        NodeVersion {
+            version: RwLock::new(NODE_VERSION.to_string()),
+        }
+    }
+}
+
+impl NodeVersion {
+    pub fn get(&self) -> String {
+        self.version.read().clone()
+    }
+
+    /// Fetches the version from the upstream fullnode
+    ///
+    /// On failure, or if the fullnode doesn't report its version, the last version is kept.
+    pub async fn refresh(&self, rest_clients: &UpstreamClients) -> ApiResult<()> {
+        let index = rest_clients.available()?.get_index().await?.into_inner();
+        if let Some(git_hash) = index.git_hash {
+            let mut version = self.version.write();
+            if *version != git_hash {
+                debug!(
+                    "Upstream fullnode version changed from {} to {}",
+                    version, git_hash
+                );
+                *version = git_hash;
+            }
+        }
+        Ok(())
+    }
+
+    /// Periodically refreshes the version from the upstream fullnodes
+    pub async fn run_refreshes(
+        self: Arc<Self>,
+        rest_clients: Arc<UpstreamClients>,
+        interval: Duration,
+    ) {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            if let Err(err) = self.refresh(&rest_clients).await {
+                warn!(
+                    "Failed to refresh upstream fullnode version, keeping {}: {:?}",
+                    self.get(),
+                    err
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::NodeVersion;
+    use crate::{
+        test_utils::{self, MockLedger},
+        upstream::UpstreamClients,
+        NODE_VERSION,
+    };
+    use aptos_config::config::RoleType;
+    use aptos_infallible::Mutex;
+    use aptos_rest_client::aptos_api_types::{IndexResponse, U64};
+    use aptos_types::chain_id::ChainId;
+    use std::sync::Arc;
+    use warp::{http::StatusCode, Filter, Reply};
+
+    /// Serves the index on every path with the git hash, or a 503 if there isn't one
+    fn mock_upstream(git_hash: Arc<Mutex<Option<String>>>) -> url::Url {
+        let route = warp::any().map(move || {
+            let git_hash = match git_hash.lock().clone() {
+                Some(git_hash) => git_hash,
+                None => return StatusCode::SERVICE_UNAVAILABLE.into_response(),
+            };
+            warp::reply::json(&IndexResponse {
+                chain_id: 4,
+                epoch: U64(1),
+                ledger_version: U64(100),
+                oldest_ledger_version: U64(0),
+                ledger_timestamp: U64(1000),
+                node_role: RoleType::FullNode,
+                oldest_block_height: U64(0),
+                block_height: U64(10),
+                git_hash: Some(git_hash),
+            })
+            .into_response()
+        });
+        test_utils::mock_upstream(MockLedger::default(), route)
+    }
+
+    #[tokio::test]
+    async fn refresh_follows_upgrades_and_keeps_last_version() {
+        let git_hash = Arc::new(Mutex::new(Some("abc123".to_string())));
+        let rest_clients = UpstreamClients::new(
+            ChainId::test(),
+            vec![aptos_rest_client::Client::new(mock_upstream(
+                git_hash.clone(),
+            ))],
+            None,
+        );
+        let node_version = NodeVersion::default();
+        assert_eq!(NODE_VERSION, node_version.get());
+
+        node_version.refresh(&rest_clients).await.unwrap();
+        assert_eq!("abc123", node_version.get());
+
+        // The fullnode is upgraded
+        *git_hash.lock() = Some("def456".to_string());
+        node_version.refresh(&rest_clients).await.unwrap();
+        assert_eq!("def456", node_version.get());
+
+        // The fullnode is briefly unreachable, and the last version is kept
+        *git_hash.lock() = None;
+        node_version.refresh(&rest_clients).await.unwrap_err();
+        assert_eq!("def456", node_version.get());
+    }
+}
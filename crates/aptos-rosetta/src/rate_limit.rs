@@ -0,0 +1,258 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Per client IP rate limiting
+//!
+//! Each client IP gets a token bucket, which refills at a fixed rate up to a maximum burst.
+//! Buckets that have been idle long enough to be full again are evicted, so the state
+//! doesn't grow with the number of clients ever seen.
+
+use crate::RosettaContext;
+use aptos_infallible::Mutex;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use warp::{reject::Reject, Filter, Rejection};
+
+/// How often idle buckets are evicted
+const EVICTION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Configuration for rate limiting by client IP
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimitConfig {
+    /// Number of requests per second allowed for a single client
+    pub requests_per_second: f64,
+    /// Number of requests a client can make at once before being limited
+    pub burst: u32,
+    /// Use the `X-Forwarded-For` header as the client IP, only use this behind a trusted proxy
+    pub trust_forwarded_for: bool,
+}
+
+/// The client has made too many requests, and can retry after the given duration
+#[derive(Debug)]
+pub struct RateLimited {
+    pub retry_after: Duration,
+}
+
+impl Reject for RateLimited {}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+#[derive(Debug)]
+struct RateLimiterState {
+    buckets: HashMap<IpAddr, TokenBucket>,
+    last_eviction: Instant,
+}
+
+/// A token bucket rate limiter keyed by client IP
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    state: Mutex<RateLimiterState>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        assert!(
+            config.requests_per_second > 0.0,
+            "Rate limit must be greater than 0"
+        );
+        assert!(config.burst > 0, "Rate limit burst must be greater than 0");
+        RateLimiter {
+            config,
+            state: Mutex::new(RateLimiterState {
+                buckets: HashMap::new(),
+                last_eviction: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn config(&self) -> &RateLimitConfig {
+        &self.config
+    }
+
+    /// Takes a token for the client, or returns how long until one is available
+    pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        self.check_at(ip, Instant::now())
+    }
+
+    fn check_at(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let burst = self.config.burst as f64;
+        let rate = self.config.requests_per_second;
+        let mut state = self.state.lock();
+
+        if now.saturating_duration_since(state.last_eviction) >= EVICTION_INTERVAL {
+            self.evict_idle(&mut state, now);
+        }
+
+        let bucket = state.buckets.entry(ip).or_insert(TokenBucket {
+            tokens: burst,
+            last_refill: now,
+        });
+
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * rate).min(burst);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+
+    /// Removes any buckets that would have refilled completely, as they're the same as new ones
+    fn evict_idle(&self, state: &mut RateLimiterState, now: Instant) {
+        let refill_time =
+            Duration::from_secs_f64(self.config.burst as f64 / self.config.requests_per_second);
+        state
+            .buckets
+            .retain(|_, bucket| now.saturating_duration_since(bucket.last_refill) < refill_time);
+        state.last_eviction = now;
+    }
+
+    #[cfg(test)]
+    fn num_buckets(&self) -> usize {
+        self.state.lock().buckets.len()
+    }
+}
+
+/// Determines the client IP, preferring the `X-Forwarded-For` header if it's trusted
+///
+/// The last entry of the header is used, as that's the one added by the trusted proxy.
+fn client_ip(
+    trust_forwarded_for: bool,
+    remote: Option<SocketAddr>,
+    forwarded_for: Option<String>,
+) -> Option<IpAddr> {
+    if trust_forwarded_for {
+        let forwarded_ip = forwarded_for.and_then(|header| {
+            header
+                .rsplit(',')
+                .next()
+                .and_then(|ip| ip.trim().parse::<IpAddr>().ok())
+        });
+        if forwarded_ip.is_some() {
+            return forwarded_ip;
+        }
+    }
+
+    remote.map(|addr| addr.ip())
+}
+
+/// Rejects requests with [`RateLimited`] if the client is over its rate limit
+pub fn rate_limit(
+    server_context: RosettaContext,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    rate_limit_with(server_context.rate_limiter)
+}
+
+/// Rejects requests with [`RateLimited`] if the client is over the given rate limiter's limit
+pub fn rate_limit_with(
+    rate_limiter: Option<Arc<RateLimiter>>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::addr::remote()
+        .and(warp::header::optional::<String>("x-forwarded-for"))
+        .and_then(
+            move |remote: Option<SocketAddr>, forwarded_for: Option<String>| {
+                let rate_limiter = rate_limiter.clone();
+                async move {
+                    if let Some(rate_limiter) = rate_limiter {
+                        let ip = client_ip(
+                            rate_limiter.config().trust_forwarded_for,
+                            remote,
+                            forwarded_for,
+                        );
+                        // Without a client IP, there's nothing to limit by
+                        if let Some(ip) = ip {
+                            if let Err(retry_after) = rate_limiter.check(ip) {
+                                return Err(warp::reject::custom(RateLimited { retry_after }));
+                            }
+                        }
+                    }
+                    Ok(())
+                }
+            },
+        )
+        .untuple_one()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{client_ip, RateLimitConfig, RateLimiter, EVICTION_INTERVAL};
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        time::{Duration, Instant},
+    };
+
+    const CLIENT_1: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const CLIENT_2: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+    fn rate_limiter() -> RateLimiter {
+        RateLimiter::new(RateLimitConfig {
+            requests_per_second: 1.0,
+            burst: 2,
+            trust_forwarded_for: false,
+        })
+    }
+
+    #[test]
+    fn bucket_limits_and_refills() {
+        let rate_limiter = rate_limiter();
+        let start = Instant::now();
+
+        rate_limiter.check_at(CLIENT_1, start).unwrap();
+        rate_limiter.check_at(CLIENT_1, start).unwrap();
+        let retry_after = rate_limiter.check_at(CLIENT_1, start).unwrap_err();
+        assert_eq!(Duration::from_secs(1), retry_after);
+
+        // Other clients have their own buckets
+        rate_limiter.check_at(CLIENT_2, start).unwrap();
+
+        // After refilling, it should allow another request
+        rate_limiter
+            .check_at(CLIENT_1, start + Duration::from_secs(1))
+            .unwrap();
+    }
+
+    #[test]
+    fn idle_buckets_are_evicted() {
+        let rate_limiter = rate_limiter();
+        let start = Instant::now();
+        rate_limiter.check_at(CLIENT_1, start).unwrap();
+        rate_limiter
+            .check_at(CLIENT_2, start + EVICTION_INTERVAL - Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(2, rate_limiter.num_buckets());
+
+        // Client 1 has been idle long enough, but client 2 hasn't
+        rate_limiter
+            .check_at(CLIENT_2, start + EVICTION_INTERVAL)
+            .unwrap();
+        assert_eq!(1, rate_limiter.num_buckets());
+    }
+
+    #[test]
+    fn forwarded_for_only_when_trusted() {
+        let remote = Some("127.0.0.1:1234".parse().unwrap());
+        let header = Some("1.1.1.1, 10.0.0.1".to_string());
+
+        assert_eq!(Some(CLIENT_1), client_ip(true, remote, header.clone()));
+        assert_eq!(
+            Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            client_ip(false, remote, header)
+        );
+        assert_eq!(
+            Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            client_ip(true, remote, Some("garbage".to_string()))
+        );
+    }
+}
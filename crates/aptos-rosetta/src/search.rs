@@ -0,0 +1,852 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Rosetta Search API
+//!
+//! Transactions can be searched by the account that sent them, or by their hash, optionally
+//! filtered by status and block.  Searches by account page through the account's transactions
+//! in order of sequence number, so `offset` is the sequence number of the first transaction.
+//!
+//! Only the transactions an account sent are found by searching by account.  The upstream
+//! fullnode only indexes transactions by sender, so transactions that deposit to the account
+//! without it sending them, e.g. transfers from other accounts, aren't included.  They can be
+//! found through the account's deposit events instead.
+//!
+//! See: [Search API Spec](https://www.rosetta-api.org/docs/SearchApi.html)
+
+use crate::{
+    block::BlockInfo,
+    common::{check_network, handle_request, with_context, with_upstream_timeout},
+    error::{ApiError, ApiResult},
+    timeouts::EndpointClass,
+    types::{
+        BlockTransaction, OperationStatusType, SearchOperator, SearchTransactionsRequest,
+        SearchTransactionsResponse, Transaction,
+    },
+    RosettaContext,
+};
+use aptos_crypto::HashValue;
+use aptos_logger::{debug, trace};
+use aptos_rest_client::aptos_api_types::{TransactionData, TransactionOnChainData};
+use aptos_types::account_address::AccountAddress;
+use std::str::FromStr;
+use warp::Filter;
+
+/// Maximum number of transactions searched by one request, and the default limit
+pub const MAX_SEARCH_LIMIT: u64 = 100;
+
+/// Maximum number of an account's transactions scanned by one filtered search
+pub const MAX_FILTERED_SEARCH_SCAN: u64 = 10 * MAX_SEARCH_LIMIT;
+
+pub fn search_transactions_route(
+    server_context: RosettaContext,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("search" / "transactions")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_context(server_context))
+        .and_then(handle_request(with_upstream_timeout(
+            EndpointClass::Block,
+            search_transactions,
+        )))
+}
+
+/// Search for committed transactions by account or hash
+///
+/// Filters other than the account, hash, status, success, and max block fail with
+/// [`ApiError::InvalidInput`], as does combining an account and hash with `or`.  The filters
+/// are applied before counting, so `total_count` is the number of matching transactions.
+///
+/// A filtered search by account scans at most [`MAX_FILTERED_SEARCH_SCAN`] of the account's
+/// transactions from `offset`, so its `total_count` only counts the matches from `offset` up
+/// to where the scan stopped, and the search continues from `next_offset`.
+///
+/// [API Spec](https://www.rosetta-api.org/docs/SearchApi.html#searchtransactions)
+async fn search_transactions(
+    request: SearchTransactionsRequest,
+    server_context: RosettaContext,
+) -> ApiResult<SearchTransactionsResponse> {
+    debug!("/search/transactions");
+    trace!(
+        request = ?request,
+        server_context = ?server_context,
+        "search_transactions",
+    );
+
+    check_network(request.network_identifier.clone(), &server_context)?;
+    server_context.check_online()?;
+    check_supported_filters(&request)?;
+    let filter = SearchFilter {
+        success: success_filter(&request)?,
+        max_version: max_version(&server_context, request.max_block).await?,
+    };
+
+    let account = match request.account_identifier {
+        Some(ref account) if !account.is_base_account() => {
+            return Err(ApiError::InvalidInput(Some(
+                "Searching by sub account is not supported".to_string(),
+            )))
+        },
+        Some(ref account) => Some(account.account_address()?),
+        None => None,
+    };
+
+    let (txns, total_count, next_offset) = match (account, &request.transaction_identifier) {
+        (account, Some(transaction_identifier)) => {
+            let hash = HashValue::from_str(
+                transaction_identifier
+                    .hash
+                    .strip_prefix("0x")
+                    .unwrap_or(&transaction_identifier.hash),
+            )
+            .map_err(|err| {
+                ApiError::InvalidInput(Some(format!(
+                    "Invalid transaction hash {}: {}",
+                    transaction_identifier.hash, err
+                )))
+            })?;
+            let mut txns = get_transaction_by_hash(&server_context, hash).await?;
+            // With `and`, the transaction must also be from the account
+            txns.retain(|txn| {
+                filter.matches(txn) && account.map_or(true, |account| sender(txn) == Some(account))
+            });
+            let total_count = txns.len() as u64;
+            (txns, total_count, None)
+        },
+        (Some(account), None) => {
+            get_account_transactions(&server_context, account, &request, &filter).await?
+        },
+        (None, None) => {
+            return Err(ApiError::InvalidInput(Some(
+                "Search must be by account identifier or transaction identifier".to_string(),
+            )))
+        },
+    };
+
+    let block_cache = server_context.block_cache()?;
+    let mut transactions = Vec::new();
+    let mut block: Option<BlockInfo> = None;
+    for txn in txns {
+        // The results are in order of version, so a block covering an earlier result covers
+        // this one too, if it ends after it
+        let block_identifier = match block {
+            Some(ref block) if txn.version <= block.last_version => block.block_id.clone(),
+            _ => {
+                let info = block_cache
+                    .get_block_info_by_version(txn.version, server_context.chain_id)
+                    .await?;
+                let block_identifier = info.block_id.clone();
+                block = Some(info);
+                block_identifier
+            },
+        };
+        transactions.push(BlockTransaction {
+            block_identifier,
+            transaction: Transaction::from_transaction(&server_context, txn).await?,
+        });
+    }
+
+    Ok(SearchTransactionsResponse {
+        transactions,
+        total_count,
+        next_offset,
+    })
+}
+
+/// The filters on the searched transactions, other than the account and hash
+struct SearchFilter {
+    /// Whether to only match successful or failed transactions
+    success: Option<bool>,
+    /// Last version of the max block, to only match transactions up to it
+    max_version: Option<u64>,
+}
+
+impl SearchFilter {
+    fn is_empty(&self) -> bool {
+        self.success.is_none() && self.max_version.is_none()
+    }
+
+    fn matches(&self, txn: &TransactionOnChainData) -> bool {
+        self.success
+            .map_or(true, |success| txn.info.status().is_success() == success)
+            && self
+                .max_version
+                .map_or(true, |max_version| txn.version <= max_version)
+    }
+}
+
+/// Rejects filters that can't be searched by, rather than ignoring them
+fn check_supported_filters(request: &SearchTransactionsRequest) -> ApiResult<()> {
+    let unsupported = [
+        ("coin identifier", request.coin_identifier.is_some()),
+        ("currency", request.currency.is_some()),
+        ("operation type", request.operation_type.is_some()),
+        ("address", request.address.is_some()),
+    ];
+    if let Some((filter, _)) = unsupported.iter().find(|(_, is_set)| *is_set) {
+        return Err(ApiError::InvalidInput(Some(format!(
+            "Searching by {} is not supported",
+            filter
+        ))));
+    }
+
+    if request.operator.unwrap_or_default() == SearchOperator::Or
+        && request.account_identifier.is_some()
+        && request.transaction_identifier.is_some()
+    {
+        return Err(ApiError::InvalidInput(Some(
+            "Searching by account or transaction identifier is not supported, use and".to_string(),
+        )));
+    }
+    Ok(())
+}
+
+/// Whether to only return successful or failed transactions, from the status and success
+/// filters, which must agree if both are given
+fn success_filter(request: &SearchTransactionsRequest) -> ApiResult<Option<bool>> {
+    let status = match request.status {
+        Some(ref status) => {
+            Some(OperationStatusType::from_str(status)? == OperationStatusType::Success)
+        },
+        None => None,
+    };
+    match (status, request.success) {
+        (Some(status), Some(success)) if status != success => {
+            Err(ApiError::InvalidInput(Some(format!(
+                "Status {:?} contradicts success {}",
+                request.status, success
+            ))))
+        },
+        (status, success) => Ok(status.or(success)),
+    }
+}
+
+fn sender(txn: &TransactionOnChainData) -> Option<AccountAddress> {
+    match txn.transaction {
+        aptos_types::transaction::Transaction::UserTransaction(ref user_txn) => {
+            Some(user_txn.sender())
+        },
+        _ => None,
+    }
+}
+
+/// The committed transaction with the hash, if there is one
+async fn get_transaction_by_hash(
+    server_context: &RosettaContext,
+    hash: HashValue,
+) -> ApiResult<Vec<TransactionOnChainData>> {
+    let response = server_context
+        .rest_client()?
+        .get_transaction_by_hash_bcs(hash)
+        .await
+        .map_err(ApiError::from);
+    match response {
+        Ok(response) => match response.into_inner() {
+            TransactionData::OnChain(txn) => Ok(vec![txn]),
+            // Pending transactions aren't committed, so can't be in a block yet
+            TransactionData::Pending(_) => Ok(vec![]),
+        },
+        Err(ApiError::TransactionNotFound(_)) => Ok(vec![]),
+        Err(err) => Err(err),
+    }
+}
+
+/// The last version of the max block, if there is one
+///
+/// A max block after the latest block doesn't exclude any committed transactions.
+async fn max_version(
+    server_context: &RosettaContext,
+    max_block: Option<u64>,
+) -> ApiResult<Option<u64>> {
+    let max_block = match max_block {
+        Some(max_block) => max_block,
+        None => return Ok(None),
+    };
+    match server_context
+        .block_cache()?
+        .get_block_info_by_height(max_block, server_context.chain_id)
+        .await
+    {
+        Ok(block) => Ok(Some(block.last_version)),
+        Err(ApiError::BlockNotFound(_)) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// A page of the account's transactions matching the filter, the number of matching
+/// transactions, and the offset of the next page
+async fn get_account_transactions(
+    server_context: &RosettaContext,
+    account: AccountAddress,
+    request: &SearchTransactionsRequest,
+    filter: &SearchFilter,
+) -> ApiResult<(Vec<TransactionOnChainData>, u64, Option<u64>)> {
+    let rest_client = server_context.rest_client()?;
+    let sequence_number = match rest_client.get_account_bcs(account).await {
+        Ok(response) => response.into_inner().sequence_number(),
+        Err(err) => match ApiError::from(err) {
+            ApiError::AccountNotFound(_) => 0,
+            err => return Err(err),
+        },
+    };
+
+    let offset = request.offset.unwrap_or_default();
+    let limit = request
+        .limit
+        .unwrap_or(MAX_SEARCH_LIMIT)
+        .min(MAX_SEARCH_LIMIT);
+
+    // Without filters, every transaction the account sent matches
+    if filter.is_empty() {
+        let total_count = sequence_number;
+        if offset >= total_count || limit == 0 {
+            return Ok((vec![], total_count, None));
+        }
+
+        let txns = rest_client
+            .get_account_transactions_bcs(account, Some(offset), Some(limit as u16))
+            .await?
+            .into_inner();
+        let next_offset = offset + txns.len() as u64;
+        let next_offset = if !txns.is_empty() && next_offset < total_count {
+            Some(next_offset)
+        } else {
+            None
+        };
+        return Ok((txns, total_count, next_offset));
+    }
+
+    // Otherwise the account's transactions are scanned from the offset for matches, a bounded
+    // number per request
+    let scan_end = sequence_number.min(offset.saturating_add(MAX_FILTERED_SEARCH_SCAN));
+    let mut txns = Vec::new();
+    let mut total_count = 0;
+    let mut next_offset = None;
+    let mut start = offset;
+    while start < scan_end {
+        let page_size = (scan_end - start).min(MAX_SEARCH_LIMIT);
+        let page = rest_client
+            .get_account_transactions_bcs(account, Some(start), Some(page_size as u16))
+            .await?
+            .into_inner();
+        if page.is_empty() {
+            break;
+        }
+
+        let page_start = start;
+        start += page.len() as u64;
+        for (txn_sequence_number, txn) in (page_start..).zip(page) {
+            // Versions increase with sequence numbers, so the rest are after the max block
+            if matches!(filter.max_version, Some(max_version) if txn.version > max_version) {
+                return Ok((txns, total_count, next_offset));
+            }
+            if !filter.matches(&txn) {
+                continue;
+            }
+            total_count += 1;
+            if (txns.len() as u64) < limit {
+                txns.push(txn);
+            } else if next_offset.is_none() && limit > 0 {
+                next_offset = Some(txn_sequence_number);
+            }
+        }
+    }
+
+    // The scan stopped before the account's last transaction, so the search continues after it
+    if next_offset.is_none() && start >= scan_end && start < sequence_number {
+        next_offset = Some(start);
+    }
+    Ok((txns, total_count, next_offset))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{search_transactions, MAX_FILTERED_SEARCH_SCAN};
+    use crate::{
+        block::BlockRetriever,
+        error::ApiError,
+        test_utils::{self, MockLedger},
+        types::{
+            AccountIdentifier, NetworkIdentifier, SearchOperator, SearchTransactionsRequest,
+            TransactionIdentifier,
+        },
+        upstream::UpstreamClients,
+        RosettaConfig, RosettaContext, RosettaMode, DEFAULT_SLOW_BLOCK_FETCH_THRESHOLD,
+    };
+    use aptos_cached_packages::aptos_stdlib;
+    use aptos_crypto::{ed25519::Ed25519PrivateKey, HashValue, PrivateKey};
+    use aptos_rest_client::aptos_api_types::{
+        AptosError, AptosErrorCode, BcsBlock, TransactionData, TransactionOnChainData,
+    };
+    use aptos_types::{
+        account_address::AccountAddress,
+        account_config::AccountResource,
+        chain_id::ChainId,
+        event::{EventHandle, EventKey},
+        transaction::{
+            authenticator::AuthenticationKey, ExecutionStatus, RawTransaction, Transaction,
+            TransactionInfo,
+        },
+        write_set::WriteSet,
+    };
+    use std::{
+        collections::HashMap,
+        convert::{Infallible, TryFrom},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+    use warp::{http::StatusCode, Filter, Rejection, Reply};
+
+    /// Version of the account's transaction with the sequence number
+    fn version(sequence_number: u64) -> u64 {
+        10 + sequence_number
+    }
+
+    /// Each block has two versions, so the height of the block with a version is half of it
+    fn mock_block(height: u64) -> BcsBlock {
+        BcsBlock {
+            block_height: height,
+            block_hash: HashValue::zero(),
+            block_timestamp: 1_000_000_000,
+            first_version: 2 * height,
+            last_version: 2 * height + 1,
+            transactions: None,
+        }
+    }
+
+    /// The account's committed transactions, every other one failed
+    fn account_txns(private_key: &Ed25519PrivateKey, count: u64) -> Vec<TransactionOnChainData> {
+        let sender = AuthenticationKey::ed25519(&private_key.public_key()).derived_address();
+        (0..count)
+            .map(|sequence_number| {
+                let raw_txn = RawTransaction::new(
+                    sender,
+                    sequence_number,
+                    aptos_stdlib::aptos_account_transfer(AccountAddress::ONE, 100),
+                    1000,
+                    100,
+                    u64::MAX,
+                    ChainId::test(),
+                );
+                let txn = raw_txn
+                    .sign(private_key, private_key.public_key())
+                    .unwrap()
+                    .into_inner();
+                let status = if sequence_number % 2 == 0 {
+                    ExecutionStatus::Success
+                } else {
+                    ExecutionStatus::OutOfGas
+                };
+                TransactionOnChainData {
+                    version: version(sequence_number),
+                    transaction: Transaction::UserTransaction(txn.clone()),
+                    info: TransactionInfo::new(
+                        txn.committed_hash(),
+                        HashValue::zero(),
+                        HashValue::zero(),
+                        None,
+                        10,
+                        status,
+                    ),
+                    events: vec![],
+                    accumulator_root_hash: HashValue::zero(),
+                    changes: WriteSet::default(),
+                }
+            })
+            .collect()
+    }
+
+    /// Serves the account, its transactions, transactions by hash, and blocks, counting the
+    /// lookups of blocks by version
+    fn mock_upstream(
+        sender: AccountAddress,
+        txns: Vec<TransactionOnChainData>,
+        block_lookups: Arc<AtomicUsize>,
+    ) -> url::Url {
+        let route = warp::path!("v1" / ..)
+            .and(warp::path::tail())
+            .and(warp::query::<HashMap<String, String>>())
+            .map(
+                move |tail: warp::path::Tail, query: HashMap<String, String>| {
+                    let parts: Vec<_> = tail.as_str().split('/').collect();
+                    let body = match parts.as_slice() {
+                        ["accounts", _] => bcs::to_bytes(&AccountResource::new(
+                            txns.len() as u64,
+                            sender.to_vec(),
+                            EventHandle::new(EventKey::new(0, sender), 0),
+                            EventHandle::new(EventKey::new(1, sender), 0),
+                        )),
+                        ["accounts", _, "transactions"] => {
+                            let start: usize = query["start"].parse().unwrap();
+                            let limit: usize = query["limit"].parse().unwrap();
+                            let page: Vec<_> =
+                                txns.iter().skip(start).take(limit).cloned().collect();
+                            bcs::to_bytes(&page)
+                        },
+                        ["transactions", "by_hash", hash] => {
+                            match txns
+                                .iter()
+                                .find(|txn| txn.info.transaction_hash().to_hex_literal() == *hash)
+                            {
+                                Some(txn) => bcs::to_bytes(&TransactionData::OnChain(txn.clone())),
+                                None => {
+                                    return warp::reply::with_status(
+                                        warp::reply::json(&AptosError::new_with_error_code(
+                                            "Transaction not found",
+                                            AptosErrorCode::TransactionNotFound,
+                                        )),
+                                        StatusCode::NOT_FOUND,
+                                    )
+                                    .into_response()
+                                },
+                            }
+                        },
+                        ["blocks", "by_version", version] => {
+                            block_lookups.fetch_add(1, Ordering::SeqCst);
+                            let version: u64 = version.parse().unwrap();
+                            bcs::to_bytes(&mock_block(version / 2))
+                        },
+                        ["blocks", "by_height", height] => {
+                            bcs::to_bytes(&mock_block(height.parse().unwrap()))
+                        },
+                        _ => return StatusCode::NOT_FOUND.into_response(),
+                    };
+                    body.unwrap().into_response()
+                },
+            )
+            .recover(|_: Rejection| async { Ok::<_, Infallible>(StatusCode::NOT_FOUND) });
+        test_utils::mock_upstream(
+            MockLedger {
+                block_height: 100,
+                ..Default::default()
+            },
+            route,
+        )
+    }
+
+    async fn context(upstream: url::Url) -> RosettaContext {
+        let rest_clients = Arc::new(UpstreamClients::new(
+            ChainId::test(),
+            vec![aptos_rest_client::Client::new(upstream)],
+            None,
+        ));
+        let block_cache = Arc::new(BlockRetriever::new(
+            100,
+            rest_clients.clone(),
+            DEFAULT_SLOW_BLOCK_FETCH_THRESHOLD,
+        ));
+        RosettaContext::new(
+            RosettaMode::Online,
+            Some(rest_clients),
+            ChainId::test(),
+            Some(block_cache),
+            vec![],
+            &RosettaConfig::default(),
+        )
+        .await
+        .unwrap()
+    }
+
+    fn request() -> SearchTransactionsRequest {
+        SearchTransactionsRequest {
+            network_identifier: NetworkIdentifier::from(ChainId::test()),
+            operator: None,
+            max_block: None,
+            offset: None,
+            limit: None,
+            transaction_identifier: None,
+            account_identifier: None,
+            coin_identifier: None,
+            currency: None,
+            status: None,
+            operation_type: None,
+            address: None,
+            success: None,
+        }
+    }
+
+    fn versions(response: &super::SearchTransactionsResponse) -> Vec<u64> {
+        response
+            .transactions
+            .iter()
+            .map(|txn| {
+                assert_eq!(
+                    txn.transaction.metadata.version.0 / 2,
+                    txn.block_identifier.index
+                );
+                txn.transaction.metadata.version.0
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn search_by_account() {
+        let private_key = Ed25519PrivateKey::try_from([1u8; 32].as_slice()).unwrap();
+        let sender = AuthenticationKey::ed25519(&private_key.public_key()).derived_address();
+        let txns = account_txns(&private_key, 5);
+        let block_lookups = Arc::new(AtomicUsize::new(0));
+        let server_context = context(mock_upstream(sender, txns, block_lookups.clone())).await;
+        let account_request = SearchTransactionsRequest {
+            account_identifier: Some(AccountIdentifier::base_account(sender)),
+            limit: Some(2),
+            ..request()
+        };
+
+        // Pages through the account's transactions
+        let first = search_transactions(account_request.clone(), server_context.clone())
+            .await
+            .unwrap();
+        assert_eq!(vec![version(0), version(1)], versions(&first));
+        assert_eq!(5, first.total_count);
+        assert_eq!(Some(2), first.next_offset);
+        let last = search_transactions(
+            SearchTransactionsRequest {
+                offset: Some(4),
+                ..account_request.clone()
+            },
+            server_context.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(vec![version(4)], versions(&last));
+        assert_eq!(None, last.next_offset);
+
+        // Results in the same block share its lookup
+        block_lookups.store(0, Ordering::SeqCst);
+        let all = search_transactions(
+            SearchTransactionsRequest {
+                limit: None,
+                ..account_request.clone()
+            },
+            server_context.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!((0..5).map(version).collect::<Vec<_>>(), versions(&all));
+        assert_eq!(3, block_lookups.load(Ordering::SeqCst));
+
+        // Filtered by status and block, which are counted and paged through
+        let failed = search_transactions(
+            SearchTransactionsRequest {
+                limit: None,
+                status: Some("failure".to_string()),
+                ..account_request.clone()
+            },
+            server_context.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(vec![version(1), version(3)], versions(&failed));
+        assert_eq!(2, failed.total_count);
+        assert_eq!(None, failed.next_offset);
+        let first_failed = search_transactions(
+            SearchTransactionsRequest {
+                limit: Some(1),
+                status: Some("failure".to_string()),
+                ..account_request.clone()
+            },
+            server_context.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(vec![version(1)], versions(&first_failed));
+        assert_eq!(2, first_failed.total_count);
+        assert_eq!(Some(3), first_failed.next_offset);
+
+        // Block 6 ends at version 13, the account's fourth transaction
+        let early = search_transactions(
+            SearchTransactionsRequest {
+                limit: None,
+                max_block: Some(6),
+                success: Some(true),
+                ..account_request.clone()
+            },
+            server_context.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(vec![version(0), version(2)], versions(&early));
+        assert_eq!(2, early.total_count);
+        let early = search_transactions(
+            SearchTransactionsRequest {
+                limit: None,
+                max_block: Some(6),
+                ..account_request.clone()
+            },
+            server_context.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(4, early.total_count);
+    }
+
+    #[tokio::test]
+    async fn filtered_search_scans_from_offset() {
+        let private_key = Ed25519PrivateKey::try_from([1u8; 32].as_slice()).unwrap();
+        let sender = AuthenticationKey::ed25519(&private_key.public_key()).derived_address();
+        let count = MAX_FILTERED_SEARCH_SCAN + 5;
+        let txns = account_txns(&private_key, count);
+        let server_context =
+            context(mock_upstream(sender, txns, Arc::new(AtomicUsize::new(0)))).await;
+        let failed_request = SearchTransactionsRequest {
+            account_identifier: Some(AccountIdentifier::base_account(sender)),
+            status: Some("failure".to_string()),
+            ..request()
+        };
+
+        // Past the scan limit, the search starts from the offset rather than failing
+        let last = search_transactions(
+            SearchTransactionsRequest {
+                offset: Some(MAX_FILTERED_SEARCH_SCAN),
+                ..failed_request.clone()
+            },
+            server_context.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            vec![
+                version(MAX_FILTERED_SEARCH_SCAN + 1),
+                version(MAX_FILTERED_SEARCH_SCAN + 3)
+            ],
+            versions(&last)
+        );
+        assert_eq!(2, last.total_count);
+        assert_eq!(None, last.next_offset);
+
+        // A search stopped by the scan limit continues where it stopped
+        let first = search_transactions(
+            SearchTransactionsRequest {
+                limit: Some(1),
+                ..failed_request.clone()
+            },
+            server_context.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(vec![version(1)], versions(&first));
+        assert_eq!(MAX_FILTERED_SEARCH_SCAN / 2, first.total_count);
+        assert_eq!(Some(3), first.next_offset);
+        let counted = search_transactions(
+            SearchTransactionsRequest {
+                limit: Some(0),
+                ..failed_request.clone()
+            },
+            server_context.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(Some(MAX_FILTERED_SEARCH_SCAN), counted.next_offset);
+        let rest = search_transactions(
+            SearchTransactionsRequest {
+                offset: Some(MAX_FILTERED_SEARCH_SCAN - 2),
+                ..failed_request.clone()
+            },
+            server_context.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            (MAX_FILTERED_SEARCH_SCAN - 2..count)
+                .filter(|sequence_number| sequence_number % 2 == 1)
+                .map(version)
+                .collect::<Vec<_>>(),
+            versions(&rest)
+        );
+    }
+
+    #[tokio::test]
+    async fn search_by_hash() {
+        let private_key = Ed25519PrivateKey::try_from([1u8; 32].as_slice()).unwrap();
+        let sender = AuthenticationKey::ed25519(&private_key.public_key()).derived_address();
+        let txns = account_txns(&private_key, 3);
+        let hash = txns[1].info.transaction_hash().to_hex_literal();
+        let server_context =
+            context(mock_upstream(sender, txns, Arc::new(AtomicUsize::new(0)))).await;
+        let hash_request = SearchTransactionsRequest {
+            transaction_identifier: Some(TransactionIdentifier { hash }),
+            ..request()
+        };
+
+        let response = search_transactions(hash_request.clone(), server_context.clone())
+            .await
+            .unwrap();
+        assert_eq!(vec![version(1)], versions(&response));
+        assert_eq!(1, response.total_count);
+
+        // Combined with the sender, or another account
+        let response = search_transactions(
+            SearchTransactionsRequest {
+                account_identifier: Some(AccountIdentifier::base_account(sender)),
+                ..hash_request.clone()
+            },
+            server_context.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(vec![version(1)], versions(&response));
+        let response = search_transactions(
+            SearchTransactionsRequest {
+                account_identifier: Some(AccountIdentifier::base_account(AccountAddress::ONE)),
+                ..hash_request.clone()
+            },
+            server_context.clone(),
+        )
+        .await
+        .unwrap();
+        assert!(response.transactions.is_empty());
+
+        // Filters apply before counting
+        let response = search_transactions(
+            SearchTransactionsRequest {
+                success: Some(true),
+                ..hash_request.clone()
+            },
+            server_context.clone(),
+        )
+        .await
+        .unwrap();
+        assert!(response.transactions.is_empty());
+        assert_eq!(0, response.total_count);
+
+        // An unknown hash has no results
+        let response = search_transactions(
+            SearchTransactionsRequest {
+                transaction_identifier: Some(TransactionIdentifier {
+                    hash: HashValue::zero().to_hex_literal(),
+                }),
+                ..request()
+            },
+            server_context.clone(),
+        )
+        .await
+        .unwrap();
+        assert!(response.transactions.is_empty());
+
+        // Unsupported filter combinations are rejected
+        for request in [
+            SearchTransactionsRequest {
+                operator: Some(SearchOperator::Or),
+                account_identifier: Some(AccountIdentifier::base_account(sender)),
+                ..hash_request.clone()
+            },
+            SearchTransactionsRequest {
+                address: Some(sender.to_hex_literal()),
+                ..hash_request.clone()
+            },
+            SearchTransactionsRequest {
+                status: Some("success".to_string()),
+                success: Some(false),
+                ..hash_request.clone()
+            },
+            request(),
+        ] {
+            let err = search_transactions(request, server_context.clone())
+                .await
+                .unwrap_err();
+            assert!(matches!(err, ApiError::InvalidInput(Some(_))), "{:?}", err);
+        }
+    }
+}
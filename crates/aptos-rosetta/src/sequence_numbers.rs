@@ -0,0 +1,255 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Sequence number reservations for transaction construction
+//!
+//! Concurrent `/construction/metadata` requests for the same account would otherwise all get
+//! the on-chain sequence number, and only one of the resulting transactions could be committed.
+//! Instead, each request reserves the next sequence number for the account.
+//!
+//! Reservations expire after [`RESERVATION_TTL`] without a new one, so sequence numbers
+//! reserved by flows that never submitted are reclaimed, and the account goes back to its
+//! on-chain sequence number.  Once `/construction/payloads` builds a transaction with a
+//! reserved sequence number, the reservation is extended until that transaction expires, so
+//! the sequence number isn't handed out again while the transaction can still be committed.
+//!
+//! Reservations can be snapshotted and restored across restarts.  A restored reservation is
+//! still bounded below by the on-chain sequence number, so a stale snapshot is safe, and it
+//! expires like any other reservation.
+
+use aptos_infallible::Mutex;
+use aptos_types::account_address::AccountAddress;
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+
+/// How long reservations for an account are kept, the same as the default transaction expiry
+pub const RESERVATION_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+struct Reservation {
+    /// The next sequence number to hand out
+    next: u64,
+    expires_at: Instant,
+}
+
+/// Sequence numbers reserved by account
+#[derive(Debug)]
+pub struct SequenceNumberReservations {
+    ttl: Duration,
+    reservations: Mutex<HashMap<AccountAddress, Reservation>>,
+}
+
+impl SequenceNumberReservations {
+    pub fn new(ttl: Duration) -> Self {
+        SequenceNumberReservations {
+            ttl,
+            reservations: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reserves the next sequence number for `address`, given its on-chain sequence number
+    pub fn reserve(&self, address: AccountAddress, on_chain: u64) -> u64 {
+        self.reserve_at(address, on_chain, Instant::now())
+    }
+
+    fn reserve_at(&self, address: AccountAddress, on_chain: u64, now: Instant) -> u64 {
+        let mut reservations = self.reservations.lock();
+
+        // Expired reservations are dropped, so they don't grow with the accounts ever seen
+        reservations.retain(|_, reservation| reservation.expires_at > now);
+
+        let reservation = reservations.entry(address).or_insert(Reservation {
+            next: on_chain,
+            expires_at: now,
+        });
+
+        // Anything committed since the last reservation can't be handed out again
+        let sequence_number = std::cmp::max(reservation.next, on_chain);
+        reservation.next = sequence_number + 1;
+        reservation.expires_at = now + self.ttl;
+        sequence_number
+    }
+
+    /// Keeps the reservation of `sequence_number` for `address` for at least `valid_for` from
+    /// now, e.g. until the transaction using it expires
+    ///
+    /// Nothing is extended if `sequence_number` wasn't reserved, as it was provided rather than
+    /// handed out.
+    pub fn extend(&self, address: AccountAddress, sequence_number: u64, valid_for: Duration) {
+        self.extend_at(address, sequence_number, valid_for, Instant::now())
+    }
+
+    fn extend_at(
+        &self,
+        address: AccountAddress,
+        sequence_number: u64,
+        valid_for: Duration,
+        now: Instant,
+    ) {
+        if let Some(reservation) = self.reservations.lock().get_mut(&address) {
+            if reservation.next > sequence_number {
+                reservation.expires_at = std::cmp::max(reservation.expires_at, now + valid_for);
+            }
+        }
+    }
+
+    /// The next sequence number to hand out for each account with a reservation, ordered by
+    /// account
+    pub fn snapshot(&self) -> BTreeMap<AccountAddress, u64> {
+        self.snapshot_at(Instant::now())
+    }
+
+    fn snapshot_at(&self, now: Instant) -> BTreeMap<AccountAddress, u64> {
+        self.reservations
+            .lock()
+            .iter()
+            .filter(|(_, reservation)| reservation.expires_at > now)
+            .map(|(address, reservation)| (*address, reservation.next))
+            .collect()
+    }
+
+    /// Restores reservations from a [`SequenceNumberReservations::snapshot`]
+    ///
+    /// Restored reservations expire after the TTL from now.  Existing reservations that are
+    /// further ahead are kept.
+    pub fn restore(&self, snapshot: BTreeMap<AccountAddress, u64>) {
+        self.restore_at(snapshot, Instant::now())
+    }
+
+    fn restore_at(&self, snapshot: BTreeMap<AccountAddress, u64>, now: Instant) {
+        let mut reservations = self.reservations.lock();
+        for (address, next) in snapshot {
+            let reservation = reservations.entry(address).or_insert(Reservation {
+                next,
+                expires_at: now,
+            });
+            if reservation.expires_at <= now || reservation.next < next {
+                reservation.next = next;
+            }
+            reservation.expires_at = now + self.ttl;
+        }
+    }
+}
+
+impl Default for SequenceNumberReservations {
+    fn default() -> Self {
+        SequenceNumberReservations::new(RESERVATION_TTL)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SequenceNumberReservations;
+    use aptos_types::account_address::AccountAddress;
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn reservations_expire() {
+        let reservations = SequenceNumberReservations::new(Duration::from_secs(30));
+        let other = AccountAddress::new([2; AccountAddress::LENGTH]);
+        let now = Instant::now();
+
+        assert_eq!(5, reservations.reserve_at(AccountAddress::ONE, 5, now));
+        assert_eq!(6, reservations.reserve_at(AccountAddress::ONE, 5, now));
+        // Other accounts are independent
+        assert_eq!(0, reservations.reserve_at(other, 0, now));
+        // Committed transactions move the reservations forward
+        assert_eq!(
+            9,
+            reservations.reserve_at(AccountAddress::ONE, 9, now + Duration::from_secs(1))
+        );
+
+        // Abandoned reservations are reclaimed after the TTL
+        assert_eq!(
+            5,
+            reservations.reserve_at(AccountAddress::ONE, 5, now + Duration::from_secs(60))
+        );
+    }
+
+    #[test]
+    fn extended_reservations_outlive_the_ttl() {
+        let reservations = SequenceNumberReservations::new(Duration::from_secs(30));
+        let now = Instant::now();
+        assert_eq!(5, reservations.reserve_at(AccountAddress::ONE, 5, now));
+
+        // Sequence numbers that weren't reserved don't extend anything
+        reservations.extend_at(AccountAddress::ONE, 6, Duration::from_secs(60), now);
+        assert_eq!(
+            5,
+            reservations.reserve_at(AccountAddress::ONE, 5, now + Duration::from_secs(40))
+        );
+
+        // The reservation is kept until the transaction using it expires
+        let later = now + Duration::from_secs(40);
+        reservations.extend_at(AccountAddress::ONE, 5, Duration::from_secs(60), later);
+        assert_eq!(
+            6,
+            reservations.reserve_at(AccountAddress::ONE, 5, later + Duration::from_secs(50))
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_reservations_are_distinct() {
+        let reservations = Arc::new(SequenceNumberReservations::default());
+        let handles: Vec<_> = (0..100)
+            .map(|_| {
+                let reservations = reservations.clone();
+                tokio::spawn(async move { reservations.reserve(AccountAddress::ONE, 10) })
+            })
+            .collect();
+
+        let mut sequence_numbers = BTreeSet::new();
+        for handle in handles {
+            assert!(sequence_numbers.insert(handle.await.unwrap()));
+        }
+        assert_eq!((10..110).collect::<BTreeSet<_>>(), sequence_numbers);
+    }
+
+    #[test]
+    fn snapshot_and_restore() {
+        let reservations = SequenceNumberReservations::new(Duration::from_secs(30));
+        let other = AccountAddress::new([2; AccountAddress::LENGTH]);
+        let now = Instant::now();
+        reservations.reserve_at(other, 7, now);
+        reservations.reserve_at(AccountAddress::ONE, 5, now);
+        reservations.reserve_at(AccountAddress::ONE, 5, now);
+
+        let snapshot = reservations.snapshot_at(now);
+        assert_eq!(
+            vec![(AccountAddress::ONE, 7), (other, 8)],
+            snapshot.clone().into_iter().collect::<Vec<_>>()
+        );
+        // Expired reservations aren't included
+        assert!(reservations
+            .snapshot_at(now + Duration::from_secs(60))
+            .is_empty());
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let snapshot: BTreeMap<AccountAddress, u64> = serde_json::from_str(&json).unwrap();
+
+        // After a restart, reservations carry on from the snapshot
+        let restored = SequenceNumberReservations::new(Duration::from_secs(30));
+        let later = now + Duration::from_secs(60);
+        restored.reserve_at(other, 10, later);
+        restored.restore_at(snapshot, later);
+        assert_eq!(7, restored.reserve_at(AccountAddress::ONE, 5, later));
+        // Reservations further ahead than the snapshot are kept
+        assert_eq!(11, restored.reserve_at(other, 7, later));
+        // And a stale snapshot is still bounded by the on-chain sequence number
+        assert_eq!(
+            20,
+            restored.reserve_at(AccountAddress::ONE, 20, later + Duration::from_secs(1))
+        );
+        // Restored reservations expire like any other
+        assert_eq!(
+            5,
+            restored.reserve_at(AccountAddress::ONE, 5, later + Duration::from_secs(120))
+        );
+    }
+}
@@ -0,0 +1,230 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Bounded queue for submitting transactions to the upstream fullnode
+//!
+//! A burst of submits would otherwise all hit the fullnode's mempool at once, and some would be
+//! rejected for a full mempool.  Instead, only a fixed number of submits are sent at once, and
+//! the rest wait in a bounded queue.  Once the queue is full, submits fail fast with
+//! [`ApiError::Overloaded`] so clients back off.
+//!
+//! Submits for the same account are sent in the order they arrived, so sequence numbers reach
+//! the mempool in order.
+
+use crate::error::{ApiError, ApiResult};
+use aptos_infallible::Mutex;
+use aptos_types::account_address::AccountAddress;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::Semaphore;
+
+/// Configuration for the submit queue
+#[derive(Clone, Copy, Debug)]
+pub struct SubmitQueueConfig {
+    /// Number of submits sent to the upstream fullnode at once
+    pub max_concurrency: usize,
+    /// Number of submits that can wait for the upstream fullnode before failing
+    pub max_queued: usize,
+}
+
+/// Decrements the number of pending submits when the submit finishes, or is dropped
+struct PendingGuard<'a>(&'a AtomicUsize);
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Limits the number of concurrent submits, preserving the order of submits per account
+#[derive(Debug)]
+pub struct SubmitQueue {
+    config: SubmitQueueConfig,
+    permits: Semaphore,
+    /// Submits that are queued or in flight
+    pending: AtomicUsize,
+    /// Submits are sent one at a time per account, in order
+    accounts: Mutex<HashMap<AccountAddress, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl SubmitQueue {
+    pub fn new(config: SubmitQueueConfig) -> Self {
+        assert!(
+            config.max_concurrency > 0,
+            "Submit concurrency must be greater than 0"
+        );
+        SubmitQueue {
+            config,
+            permits: Semaphore::new(config.max_concurrency),
+            pending: AtomicUsize::new(0),
+            accounts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &SubmitQueueConfig {
+        &self.config
+    }
+
+    /// Runs `submit` for a transaction from `sender` once there's capacity
+    pub async fn submit<F, Fut, T>(&self, sender: AccountAddress, submit: F) -> ApiResult<T>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = ApiResult<T>>,
+    {
+        let capacity = self.config.max_concurrency + self.config.max_queued;
+        let _pending = if self.pending.fetch_add(1, Ordering::SeqCst) < capacity {
+            PendingGuard(&self.pending)
+        } else {
+            self.pending.fetch_sub(1, Ordering::SeqCst);
+            return Err(ApiError::Overloaded(Some(format!(
+                "Too many transactions are being submitted, at most {} can be queued",
+                capacity
+            ))));
+        };
+
+        // The account lock is fair, so submits for the account go out in the order they arrived
+        let account_lock = self.account_lock(sender);
+        let _account_guard = account_lock.lock().await;
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("Submit queue semaphore is never closed");
+        submit().await
+    }
+
+    fn account_lock(&self, sender: AccountAddress) -> Arc<tokio::sync::Mutex<()>> {
+        let mut accounts = self.accounts.lock();
+
+        // Locks that no submit holds a reference to are done with, so they're dropped here,
+        // which also covers submits that were cancelled
+        accounts.retain(|_, lock| Arc::strong_count(lock) > 1);
+        accounts.entry(sender).or_default().clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SubmitQueue, SubmitQueueConfig};
+    use crate::error::ApiError;
+    use aptos_infallible::Mutex;
+    use aptos_types::account_address::AccountAddress;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    /// A slow upstream node, recording the order of submits and the most in flight at once
+    #[derive(Default)]
+    struct SlowNode {
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+        submitted: Mutex<Vec<(AccountAddress, u64)>>,
+    }
+
+    impl SlowNode {
+        async fn submit(&self, sender: AccountAddress, sequence_number: u64) {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            self.submitted.lock().push((sender, sequence_number));
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn burst_is_limited_and_ordered() {
+        let queue = Arc::new(SubmitQueue::new(SubmitQueueConfig {
+            max_concurrency: 2,
+            max_queued: 18,
+        }));
+        let node = Arc::new(SlowNode::default());
+        let accounts = [
+            AccountAddress::new([1; AccountAddress::LENGTH]),
+            AccountAddress::new([2; AccountAddress::LENGTH]),
+        ];
+
+        let handles: Vec<_> = (0..20u64)
+            .map(|i| {
+                let queue = queue.clone();
+                let node = node.clone();
+                let sender = accounts[i as usize % 2];
+                tokio::spawn(async move {
+                    queue
+                        .submit(sender, || async {
+                            node.submit(sender, i / 2).await;
+                            Ok(())
+                        })
+                        .await
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap().unwrap();
+        }
+
+        assert_eq!(2, node.max_in_flight.load(Ordering::SeqCst));
+        let submitted = node.submitted.lock();
+        assert_eq!(20, submitted.len());
+        for account in accounts {
+            let sequence_numbers: Vec<_> = submitted
+                .iter()
+                .filter(|(sender, _)| *sender == account)
+                .map(|(_, sequence_number)| *sequence_number)
+                .collect();
+            assert_eq!((0..10).collect::<Vec<_>>(), sequence_numbers);
+        }
+    }
+
+    #[tokio::test]
+    async fn saturated_queue_is_overloaded() {
+        let queue = Arc::new(SubmitQueue::new(SubmitQueueConfig {
+            max_concurrency: 1,
+            max_queued: 1,
+        }));
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+
+        // Hold the only slot, and fill the queue behind it
+        let in_flight = tokio::spawn({
+            let queue = queue.clone();
+            async move {
+                queue
+                    .submit(AccountAddress::ONE, || async {
+                        released.await.unwrap();
+                        Ok(())
+                    })
+                    .await
+            }
+        });
+        tokio::task::yield_now().await;
+        let queued = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.submit(AccountAddress::ONE, || async { Ok(()) }).await }
+        });
+        tokio::task::yield_now().await;
+
+        let err = queue
+            .submit(AccountAddress::ONE, || async { Ok(()) })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ApiError::Overloaded(_)));
+
+        // Once the node catches up, there's room again
+        release.send(()).unwrap();
+        in_flight.await.unwrap().unwrap();
+        queued.await.unwrap().unwrap();
+        queue
+            .submit(AccountAddress::ONE, || async { Ok(()) })
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,88 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Mock servers shared by the unit tests
+
+use aptos_config::config::RoleType;
+use aptos_rest_client::aptos_api_types::{
+    IndexResponseBcs, X_APTOS_BLOCK_HEIGHT, X_APTOS_CHAIN_ID, X_APTOS_EPOCH,
+    X_APTOS_LEDGER_OLDEST_VERSION, X_APTOS_LEDGER_TIMESTAMP, X_APTOS_LEDGER_VERSION,
+    X_APTOS_OLDEST_BLOCK_HEIGHT,
+};
+use aptos_types::chain_id::ChainId;
+use std::convert::Infallible;
+use warp::{reply::Response, Filter, Reply};
+
+/// The ledger a mock upstream fullnode reports in the headers of every response
+#[derive(Clone, Copy, Debug)]
+pub struct MockLedger {
+    pub chain_id: ChainId,
+    pub ledger_version: u64,
+    pub ledger_timestamp_usecs: u64,
+    pub block_height: u64,
+}
+
+impl Default for MockLedger {
+    fn default() -> Self {
+        MockLedger {
+            chain_id: ChainId::test(),
+            ledger_version: 100,
+            ledger_timestamp_usecs: 1000,
+            block_height: 10,
+        }
+    }
+}
+
+impl MockLedger {
+    /// The BCS index of a fullnode on this ledger, as served at the root path
+    pub fn index_response(&self) -> Response {
+        bcs::to_bytes(&IndexResponseBcs {
+            chain_id: self.chain_id.id(),
+            epoch: 1.into(),
+            ledger_version: self.ledger_version.into(),
+            oldest_ledger_version: 0.into(),
+            ledger_timestamp: self.ledger_timestamp_usecs.into(),
+            node_role: RoleType::FullNode,
+            oldest_block_height: 0.into(),
+            block_height: self.block_height.into(),
+        })
+        .unwrap()
+        .into_response()
+    }
+}
+
+/// Serves `route` on an ephemeral local port, returning the server's URL
+pub fn serve<F, R>(route: F) -> url::Url
+where
+    F: Filter<Extract = (R,), Error = Infallible> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    let (address, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    url::Url::parse(&format!("http://{}", address)).unwrap()
+}
+
+/// Serves `route` as an upstream fullnode, adding the ledger headers the REST client expects
+/// to every response
+pub fn mock_upstream<F, R>(ledger: MockLedger, route: F) -> url::Url
+where
+    F: Filter<Extract = (R,), Error = Infallible> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    serve(route.map(move |reply: R| {
+        let mut response = reply.into_response();
+        let headers = response.headers_mut();
+        for (name, value) in [
+            (X_APTOS_CHAIN_ID, ledger.chain_id.id() as u64),
+            (X_APTOS_LEDGER_VERSION, ledger.ledger_version),
+            (X_APTOS_LEDGER_TIMESTAMP, ledger.ledger_timestamp_usecs),
+            (X_APTOS_EPOCH, 1),
+            (X_APTOS_LEDGER_OLDEST_VERSION, 0),
+            (X_APTOS_BLOCK_HEIGHT, ledger.block_height),
+            (X_APTOS_OLDEST_BLOCK_HEIGHT, 0),
+        ] {
+            headers.insert(name, value.into());
+        }
+        response
+    }))
+}
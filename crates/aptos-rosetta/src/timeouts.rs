@@ -0,0 +1,212 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Timeouts for waiting on the upstream fullnode, by class of endpoint
+//!
+//! Endpoints wait on the upstream for different amounts of work, e.g. a submit may wait on a
+//! busy mempool, while a balance lookup should return quickly.  Each class of endpoint has its
+//! own timeout, falling back to a default.
+
+use crate::{
+    counters::UPSTREAM_TIMEOUT_SECONDS,
+    error::{ApiError, ApiResult},
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt::Formatter, future::Future, str::FromStr, time::Duration};
+
+/// Default timeout for waiting on the upstream fullnode
+pub const DEFAULT_UPSTREAM_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Classes of endpoints that wait on the upstream fullnode
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointClass {
+    /// `/account/*`
+    Account,
+    /// `/block/*`
+    Block,
+    /// `/construction/metadata`
+    Construction,
+    /// `/construction/submit`
+    Submit,
+    /// `/network/status`
+    Network,
+}
+
+impl EndpointClass {
+    pub fn all() -> [EndpointClass; 5] {
+        [
+            EndpointClass::Account,
+            EndpointClass::Block,
+            EndpointClass::Construction,
+            EndpointClass::Submit,
+            EndpointClass::Network,
+        ]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EndpointClass::Account => "account",
+            EndpointClass::Block => "block",
+            EndpointClass::Construction => "construction",
+            EndpointClass::Submit => "submit",
+            EndpointClass::Network => "network",
+        }
+    }
+}
+
+impl FromStr for EndpointClass {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EndpointClass::all()
+            .into_iter()
+            .find(|class| class.as_str() == s)
+            .ok_or_else(|| anyhow::anyhow!("Invalid endpoint class {}", s))
+    }
+}
+
+impl std::fmt::Display for EndpointClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A timeout for a class of endpoint, parsed from `<class>=<seconds>` e.g. `submit=60`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct EndpointTimeout {
+    pub class: EndpointClass,
+    pub timeout: Duration,
+}
+
+impl FromStr for EndpointTimeout {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (class, secs) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Expected <class>=<seconds>, got {}", s))?;
+        let secs: u64 = secs
+            .parse()
+            .map_err(|err| anyhow::anyhow!("Invalid timeout seconds {}: {}", secs, err))?;
+        anyhow::ensure!(secs > 0, "Timeout for {} must be greater than 0", class);
+        Ok(EndpointTimeout {
+            class: class.parse()?,
+            timeout: Duration::from_secs(secs),
+        })
+    }
+}
+
+/// Timeouts for waiting on the upstream fullnode by class of endpoint
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UpstreamTimeouts {
+    default: Duration,
+    overrides: BTreeMap<EndpointClass, Duration>,
+}
+
+impl UpstreamTimeouts {
+    pub fn new(default: Duration) -> Self {
+        UpstreamTimeouts {
+            default,
+            overrides: BTreeMap::new(),
+        }
+    }
+
+    /// Uses `timeout` for the class instead of the default
+    pub fn with_timeout(mut self, class: EndpointClass, timeout: Duration) -> Self {
+        self.overrides.insert(class, timeout);
+        self
+    }
+
+    /// The timeout for the class
+    pub fn get(&self, class: EndpointClass) -> Duration {
+        self.overrides.get(&class).copied().unwrap_or(self.default)
+    }
+
+    /// The timeout of every class, including those using the default
+    pub fn effective(&self) -> BTreeMap<EndpointClass, Duration> {
+        EndpointClass::all()
+            .into_iter()
+            .map(|class| (class, self.get(class)))
+            .collect()
+    }
+
+    /// Publishes the effective timeouts as metrics, so operators can audit them
+    pub fn record_metrics(&self) {
+        for (class, timeout) in self.effective() {
+            UPSTREAM_TIMEOUT_SECONDS
+                .with_label_values(&[class.as_str()])
+                .set(timeout.as_secs() as i64);
+        }
+    }
+
+    /// Runs `fut`, failing with [`ApiError::UpstreamTimeout`] if it takes longer than the
+    /// class's timeout
+    pub async fn run<T>(
+        &self,
+        class: EndpointClass,
+        fut: impl Future<Output = ApiResult<T>>,
+    ) -> ApiResult<T> {
+        let timeout = self.get(class);
+        tokio::time::timeout(timeout, fut).await.map_err(|_| {
+            ApiError::UpstreamTimeout(Some(format!(
+                "Upstream didn't respond to the {} request within {}s",
+                class,
+                timeout.as_secs()
+            )))
+        })?
+    }
+}
+
+impl Default for UpstreamTimeouts {
+    fn default() -> Self {
+        UpstreamTimeouts::new(DEFAULT_UPSTREAM_TIMEOUT)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EndpointClass, EndpointTimeout, UpstreamTimeouts};
+    use crate::error::ApiError;
+    use std::time::Duration;
+
+    #[test]
+    fn timeouts_by_class() {
+        let timeouts = UpstreamTimeouts::new(Duration::from_secs(10))
+            .with_timeout(EndpointClass::Submit, Duration::from_secs(60))
+            .with_timeout(EndpointClass::Account, Duration::from_secs(2));
+        assert_eq!(Duration::from_secs(60), timeouts.get(EndpointClass::Submit));
+        assert_eq!(Duration::from_secs(2), timeouts.get(EndpointClass::Account));
+        assert_eq!(Duration::from_secs(10), timeouts.get(EndpointClass::Block));
+        assert_eq!(EndpointClass::all().len(), timeouts.effective().len());
+
+        assert_eq!(
+            EndpointTimeout {
+                class: EndpointClass::Submit,
+                timeout: Duration::from_secs(60)
+            },
+            "submit=60".parse().unwrap()
+        );
+        for invalid in ["submit", "submit=0", "submit=soon", "balance=5"] {
+            assert!(invalid.parse::<EndpointTimeout>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn slow_upstream_times_out() {
+        let timeouts = UpstreamTimeouts::new(Duration::from_secs(10))
+            .with_timeout(EndpointClass::Account, Duration::from_millis(10));
+        let slow = || async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok::<_, ApiError>(())
+        };
+
+        let err = timeouts
+            .run(EndpointClass::Account, slow())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ApiError::UpstreamTimeout(_)));
+        assert!(err.retriable());
+        timeouts.run(EndpointClass::Submit, slow()).await.unwrap();
+    }
+}
@@ -7,6 +7,7 @@ use crate::{
     types::{AccountIdentifier, Amount},
     AccountAddress, ApiResult,
 };
+use aptos_num_variants::NumVariants;
 use aptos_types::stake_pool::StakePool;
 use serde::{Deserialize, Serialize};
 use std::{
@@ -81,7 +82,12 @@ pub struct Version {
 }
 
 /// An internal enum to support Operation typing
-#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+///
+/// This is the single source of the supported operation types, used by both the network
+/// options and the parsing of operations.
+#[derive(
+    Clone, Debug, Deserialize, Eq, Hash, NumVariants, Ord, PartialEq, PartialOrd, Serialize,
+)]
 pub enum OperationType {
     // Create must always be first for ordering
     CreateAccount,
@@ -93,6 +99,8 @@ pub enum OperationType {
     SetVoter,
     InitializeStakePool,
     ResetLockup,
+    RotateAuthKey,
+    EntryFunction,
     // Fee must always be last for ordering
     Fee,
 }
@@ -100,27 +108,32 @@ pub enum OperationType {
 impl OperationType {
     const CREATE_ACCOUNT: &'static str = "create_account";
     const DEPOSIT: &'static str = "deposit";
+    const ENTRY_FUNCTION: &'static str = "entry_function";
     const FEE: &'static str = "fee";
     const INITIALIZE_STAKE_POOL: &'static str = "initialize_stake_pool";
     const RESET_LOCKUP: &'static str = "reset_lockup";
+    const ROTATE_AUTH_KEY: &'static str = "rotate_auth_key";
     const SET_OPERATOR: &'static str = "set_operator";
     const SET_VOTER: &'static str = "set_voter";
     const STAKING_REWARD: &'static str = "staking_reward";
+    // Sized by the number of variants, so a new variant can't be left out
+    const VARIANTS: [OperationType; OperationType::NUM_VARIANTS] = [
+        OperationType::CreateAccount,
+        OperationType::Withdraw,
+        OperationType::Deposit,
+        OperationType::Fee,
+        OperationType::SetOperator,
+        OperationType::SetVoter,
+        OperationType::StakingReward,
+        OperationType::InitializeStakePool,
+        OperationType::ResetLockup,
+        OperationType::RotateAuthKey,
+        OperationType::EntryFunction,
+    ];
     const WITHDRAW: &'static str = "withdraw";
 
     pub fn all() -> Vec<OperationType> {
-        use OperationType::*;
-        vec![
-            CreateAccount,
-            Withdraw,
-            Deposit,
-            Fee,
-            SetOperator,
-            SetVoter,
-            StakingReward,
-            InitializeStakePool,
-            ResetLockup,
-        ]
+        Self::VARIANTS.to_vec()
     }
 }
 
@@ -138,6 +151,8 @@ impl FromStr for OperationType {
             Self::SET_VOTER => Ok(OperationType::SetVoter),
             Self::INITIALIZE_STAKE_POOL => Ok(OperationType::InitializeStakePool),
             Self::RESET_LOCKUP => Ok(OperationType::ResetLockup),
+            Self::ROTATE_AUTH_KEY => Ok(OperationType::RotateAuthKey),
+            Self::ENTRY_FUNCTION => Ok(OperationType::EntryFunction),
             _ => Err(ApiError::DeserializationFailed(Some(format!(
                 "Invalid OperationType: {}",
                 s
@@ -158,6 +173,8 @@ impl Display for OperationType {
             SetVoter => Self::SET_VOTER,
             InitializeStakePool => Self::INITIALIZE_STAKE_POOL,
             ResetLockup => Self::RESET_LOCKUP,
+            RotateAuthKey => Self::ROTATE_AUTH_KEY,
+            EntryFunction => Self::ENTRY_FUNCTION,
             Fee => Self::FEE,
         })
     }
@@ -271,5 +288,6 @@ fn get_stake_balance_from_stake_pool(
     Ok(Amount {
         value: stake_pool.get_total_staked_amount().to_string(),
         currency: native_coin(),
+        metadata: None,
     })
 }
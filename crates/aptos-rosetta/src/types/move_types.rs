@@ -21,14 +21,33 @@ pub const ACCOUNT_RESOURCE: &str = "Account";
 pub const APTOS_COIN_RESOURCE: &str = "AptosCoin";
 pub const COIN_INFO_RESOURCE: &str = "CoinInfo";
 pub const COIN_STORE_RESOURCE: &str = "CoinStore";
+pub const ROTATION_PROOF_CHALLENGE_STRUCT: &str = "RotationProofChallenge";
 pub const STAKE_POOL_RESOURCE: &str = "StakePool";
 pub const STAKING_CONTRACT_RESOURCE: &str = "StakingContract";
 pub const STORE_RESOURCE: &str = "Store";
 pub const VESTING_RESOURCE: &str = "Vesting";
 
+pub const ADD_DISTRIBUTION_EVENT: &str = "AddDistributionEvent";
+pub const ADD_STAKE_EVENT: &str = "AddStakeEvent";
+pub const CREATE_STAKING_CONTRACT_EVENT: &str = "CreateStakingContractEvent";
+pub const DEPOSIT_EVENT: &str = "DepositEvent";
+pub const DISTRIBUTE_EVENT: &str = "DistributeEvent";
+pub const REQUEST_COMMISSION_EVENT: &str = "RequestCommissionEvent";
+pub const RESET_LOCKUP_EVENT: &str = "ResetLockupEvent";
+pub const SET_OPERATOR_EVENT: &str = "SetOperatorEvent";
+pub const SWITCH_OPERATOR_EVENT: &str = "SwitchOperatorEvent";
+pub const UNLOCK_STAKE_EVENT: &str = "UnlockStakeEvent";
+pub const UPDATE_VOTER_EVENT: &str = "UpdateVoterEvent";
+pub const WITHDRAW_EVENT: &str = "WithdrawEvent";
+
 pub const CREATE_ACCOUNT_FUNCTION: &str = "create_account";
 pub const TRANSFER_FUNCTION: &str = "transfer";
+pub const BATCH_TRANSFER_FUNCTION: &str = "batch_transfer";
+pub const TRANSFER_COINS_FUNCTION: &str = "transfer_coins";
+pub const BATCH_TRANSFER_COINS_FUNCTION: &str = "batch_transfer_coins";
+pub const MINT_FUNCTION: &str = "mint";
 pub const RESET_LOCKUP_FUNCTION: &str = "reset_lockup";
+pub const ROTATE_AUTHENTICATION_KEY_FUNCTION: &str = "rotate_authentication_key";
 pub const CREATE_STAKING_CONTRACT_FUNCTION: &str = "create_staking_contract";
 pub const SWITCH_OPERATOR_WITH_SAME_COMMISSION_FUNCTION: &str =
     "switch_operator_with_same_commission";
@@ -8,33 +8,42 @@
 use crate::{
     common::{is_native_coin, native_coin, native_coin_tag},
     construction::{
-        parse_create_stake_pool_operation, parse_reset_lockup_operation,
-        parse_set_operator_operation, parse_set_voter_operation,
+        auth_key_for_scheme, decode_for_scheme, parse_create_stake_pool_operation,
+        parse_reset_lockup_operation, parse_set_operator_operation, parse_set_voter_operation,
     },
     error::ApiResult,
     types::{
-        move_types::*, AccountIdentifier, BlockIdentifier, Error, OperationIdentifier,
-        OperationStatus, OperationStatusType, OperationType, TransactionIdentifier,
+        move_types::*, AccountIdentifier, BlockIdentifier, Error, GasPricePriority,
+        OperationIdentifier, OperationStatus, OperationStatusType, OperationType,
+        TransactionIdentifier,
     },
     ApiError, RosettaContext,
 };
 use anyhow::anyhow;
 use aptos_cached_packages::aptos_stdlib;
-use aptos_crypto::{ed25519::Ed25519PublicKey, ValidCryptoMaterialStringExt};
+use aptos_crypto::{
+    ed25519::{Ed25519PublicKey, Ed25519Signature},
+    multi_ed25519::{MultiEd25519PublicKey, MultiEd25519Signature},
+    ValidCryptoMaterialStringExt,
+};
 use aptos_logger::warn;
 use aptos_rest_client::aptos_api_types::{TransactionOnChainData, U64};
+use aptos_sdk::move_types::{
+    identifier::Identifier,
+    language_storage::{ModuleId, TypeTag},
+};
 use aptos_types::{
     account_address::AccountAddress,
-    account_config::{AccountResource, CoinStoreResource, WithdrawEvent},
+    account_config::{AccountResource, CoinStoreResource, DepositEvent, WithdrawEvent},
     contract_event::ContractEvent,
     event::EventKey,
     stake_pool::{SetOperatorEvent, StakePool},
     state_store::state_key::StateKey,
-    transaction::{EntryFunction, TransactionPayload},
+    transaction::{authenticator::Scheme, EntryFunction, TransactionPayload},
     write_set::{WriteOp, WriteSet},
 };
 use itertools::Itertools;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
     cmp::Ordering,
     collections::{BTreeMap, HashMap},
@@ -81,6 +90,9 @@ pub struct Amount {
     pub value: String,
     /// [`Currency`]
     pub currency: Currency,
+    /// Only set on suggested fees, to say which gas price they're for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<AmountMetadata>,
 }
 
 impl Amount {
@@ -88,10 +100,33 @@ impl Amount {
         Amount {
             value: (gas_unit_price * max_gas_amount).to_string(),
             currency: native_coin(),
+            metadata: None,
+        }
+    }
+
+    /// A suggested gas fee for a gas price priority, so clients can pick between them
+    pub fn suggested_gas_fee_for_priority(
+        gas_price_priority: GasPricePriority,
+        gas_unit_price: u64,
+        max_gas_amount: u64,
+    ) -> Amount {
+        Amount {
+            metadata: Some(AmountMetadata {
+                gas_price_priority,
+                gas_price_per_unit: gas_unit_price.into(),
+            }),
+            ..Amount::suggested_gas_fee(gas_unit_price, max_gas_amount)
         }
     }
 }
 
+/// Metadata on a suggested fee, with the gas price to pass to payloads to use it
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AmountMetadata {
+    pub gas_price_priority: GasPricePriority,
+    pub gas_price_per_unit: U64,
+}
+
 /// [API Spec](https://www.rosetta-api.org/docs/models/BalanceExemption.html)
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct BalanceExemption {}
@@ -118,9 +153,9 @@ pub struct Block {
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct BlockTransaction {
     /// Block associated with transaction
-    block_identifier: BlockIdentifier,
+    pub block_identifier: BlockIdentifier,
     /// Transaction associated with block
-    transaction: Transaction,
+    pub transaction: Transaction,
 }
 
 /// Currency represented as atomic units including decimals
@@ -252,6 +287,7 @@ impl Operation {
             Some(Amount {
                 value: amount.to_string(),
                 currency,
+                metadata: None,
             }),
             None,
         )
@@ -272,6 +308,7 @@ impl Operation {
             Some(Amount {
                 value: amount.to_string(),
                 currency,
+                metadata: None,
             }),
             None,
         )
@@ -292,6 +329,7 @@ impl Operation {
             Some(Amount {
                 value: format!("-{}", amount),
                 currency,
+                metadata: None,
             }),
             None,
         )
@@ -311,6 +349,7 @@ impl Operation {
             Some(Amount {
                 value: format!("-{}", gas_used.saturating_mul(gas_price_per_unit)),
                 currency: native_coin(),
+                metadata: None,
             }),
             None,
         )
@@ -370,6 +409,36 @@ impl Operation {
             Some(OperationMetadata::reset_lockup(operator)),
         )
     }
+
+    pub fn rotate_auth_key(
+        operation_index: u64,
+        status: Option<OperationStatusType>,
+        rotate_auth_key: &RotateAuthKey,
+    ) -> Operation {
+        Operation::new(
+            OperationType::RotateAuthKey,
+            operation_index,
+            status,
+            AccountIdentifier::base_account(rotate_auth_key.account),
+            None,
+            Some(OperationMetadata::rotate_auth_key(rotate_auth_key)),
+        )
+    }
+
+    pub fn entry_function(
+        operation_index: u64,
+        status: Option<OperationStatusType>,
+        call: &EntryFunctionCall,
+    ) -> Operation {
+        Operation::new(
+            OperationType::EntryFunction,
+            operation_index,
+            status,
+            AccountIdentifier::base_account(call.sender),
+            None,
+            Some(OperationMetadata::entry_function(call)),
+        )
+    }
 }
 
 impl std::cmp::PartialOrd for Operation {
@@ -406,6 +475,12 @@ impl std::cmp::Ord for Operation {
 pub struct OperationMetadata {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sender: Option<AccountIdentifier>,
+    /// Account paying for the gas of the operation's transaction
+    ///
+    /// Set on every operation of a committed user transaction, so the gas payer can be told
+    /// apart from the counterparties of the operation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gas_payer: Option<AccountIdentifier>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub operator: Option<AccountIdentifier>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -418,6 +493,33 @@ pub struct OperationMetadata {
     pub staked_balance: Option<U64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub commission_percentage: Option<U64>,
+    /// Fully qualified entry function e.g. `0x1::coin::transfer`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function: Option<String>,
+    /// Entry function type arguments e.g. `0x1::aptos_coin::AptosCoin`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub type_arguments: Option<Vec<String>>,
+    /// Hex encoded BCS entry function arguments, not including signers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<Vec<String>>,
+    /// Scheme of the key being rotated from, defaults to [`AuthScheme::Ed25519`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_auth_scheme: Option<AuthScheme>,
+    /// Public key being rotated from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_public_key: Option<PublicKey>,
+    /// Scheme of the key being rotated to, defaults to [`AuthScheme::Ed25519`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_auth_scheme: Option<AuthScheme>,
+    /// Public key being rotated to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_public_key: Option<PublicKey>,
+    /// Hex encoded signature of the rotation challenge by the key being rotated from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_key_proof: Option<String>,
+    /// Hex encoded signature of the rotation challenge by the key being rotated to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_key_proof: Option<String>,
 }
 
 impl OperationMetadata {
@@ -470,6 +572,32 @@ impl OperationMetadata {
             ..Default::default()
         }
     }
+
+    pub fn rotate_auth_key(rotate_auth_key: &RotateAuthKey) -> Self {
+        OperationMetadata {
+            current_auth_scheme: Some(rotate_auth_key.current_auth_scheme),
+            current_public_key: Some(rotate_auth_key.current_public_key.clone()),
+            new_auth_scheme: Some(rotate_auth_key.new_auth_scheme),
+            new_public_key: Some(rotate_auth_key.new_public_key.clone()),
+            current_key_proof: Some(rotate_auth_key.current_key_proof.clone()),
+            new_key_proof: Some(rotate_auth_key.new_key_proof.clone()),
+            ..Default::default()
+        }
+    }
+
+    pub fn entry_function(call: &EntryFunctionCall) -> Self {
+        OperationMetadata {
+            function: Some(call.function_id()),
+            type_arguments: Some(
+                call.type_arguments
+                    .iter()
+                    .map(|type_argument| type_argument.to_string())
+                    .collect(),
+            ),
+            arguments: Some(call.arguments.iter().map(hex::encode).collect()),
+            ..Default::default()
+        }
+    }
 }
 
 /// Public key used for the rosetta implementation.  All private keys will never be handled
@@ -534,6 +662,78 @@ pub enum SignatureType {
     Ed25519,
 }
 
+/// Scheme of the key authenticating a transaction's sender.  Aptos specific, not part of the
+/// Rosetta spec
+///
+/// Both schemes sign with Ed25519 keys, so signatures are still [`SignatureType::Ed25519`].
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthScheme {
+    /// A single Ed25519 key
+    #[default]
+    Ed25519,
+    /// K-of-N Ed25519 keys.  The public key is the BCS encoded
+    /// [`aptos_crypto::multi_ed25519::MultiEd25519PublicKey`], and the signature is the
+    /// [`aptos_crypto::multi_ed25519::MultiEd25519Signature`] combining the signers' signatures
+    MultiEd25519,
+}
+
+impl AuthScheme {
+    /// The scheme's id in `0x1::account`, which is its authentication key scheme
+    pub fn scheme_id(&self) -> u8 {
+        match self {
+            AuthScheme::Ed25519 => Scheme::Ed25519 as u8,
+            AuthScheme::MultiEd25519 => Scheme::MultiEd25519 as u8,
+        }
+    }
+
+    pub fn from_scheme_id(scheme_id: u8) -> ApiResult<AuthScheme> {
+        [AuthScheme::Ed25519, AuthScheme::MultiEd25519]
+            .into_iter()
+            .find(|auth_scheme| auth_scheme.scheme_id() == scheme_id)
+            .ok_or_else(|| {
+                ApiError::InvalidInput(Some(format!("Unsupported auth scheme id {}", scheme_id)))
+            })
+    }
+}
+
+impl Display for AuthScheme {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AuthScheme::Ed25519 => "ed25519",
+            AuthScheme::MultiEd25519 => "multi_ed25519",
+        })
+    }
+}
+
+/// Encoding of the `hex_bytes` of an Ed25519 [`Signature`] given to combine.  Aptos specific,
+/// not part of the Rosetta spec
+///
+/// This accepts signatures as emitted by HSMs, which are normalized before the transaction is
+/// assembled.  It's always hex encoded, as it's carried in the [`Signature`].
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureEncoding {
+    /// The signature as Rosetta encodes it for the [`AuthScheme`]
+    #[default]
+    Rosetta,
+    /// Exactly 64 bytes, the 32 byte `r` followed by the 32 byte `s`
+    Raw,
+    /// A DER `SEQUENCE` of the `INTEGER`s `r` and `s`, each the big-endian value of its
+    /// 32 bytes of the signature
+    Der,
+}
+
+impl Display for SignatureEncoding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SignatureEncoding::Rosetta => "rosetta",
+            SignatureEncoding::Raw => "raw",
+            SignatureEncoding::Der => "der",
+        })
+    }
+}
+
 /// Signing payload should be signed by the client with their own private key
 ///
 /// [API Spec](https://www.rosetta-api.org/docs/models/SigningPayload.html)
@@ -547,6 +747,110 @@ pub struct SigningPayload {
     pub signature_type: Option<SignatureType>,
 }
 
+/// A raw on-chain event, as emitted by a transaction without mapping to [`Operation`]s
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RawEvent {
+    /// Version of the transaction that emitted the event
+    pub version: U64,
+    /// Event key (event handle) the event was emitted to
+    pub key: String,
+    /// Sequence number of the event on its event key
+    pub sequence_number: U64,
+    /// Move type of the event
+    pub type_tag: String,
+    /// Hex encoded, BCS encoded event data
+    pub data: String,
+    /// Decoded event data, only present if the event has a known layout
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoded_data: Option<serde_json::Value>,
+}
+
+impl RawEvent {
+    pub fn from_event(version: u64, event: &ContractEvent) -> RawEvent {
+        RawEvent {
+            version: version.into(),
+            key: event.key().to_string(),
+            sequence_number: event.sequence_number().into(),
+            type_tag: event.type_tag().to_string(),
+            data: hex::encode(event.event_data()),
+            decoded_data: decode_event_data(version, event),
+        }
+    }
+}
+
+/// Decodes the BCS event data into JSON for the framework events with a known layout
+fn decode_event_data(version: u64, event: &ContractEvent) -> Option<serde_json::Value> {
+    let struct_tag = if let TypeTag::Struct(struct_tag) = event.type_tag() {
+        struct_tag
+    } else {
+        return None;
+    };
+
+    let data = event.event_data();
+    let decoded = match (
+        struct_tag.address,
+        struct_tag.module.as_str(),
+        struct_tag.name.as_str(),
+    ) {
+        (AccountAddress::ONE, COIN_MODULE, WITHDRAW_EVENT) => {
+            decode_event_to_json::<WithdrawEvent>(data)
+        },
+        (AccountAddress::ONE, COIN_MODULE, DEPOSIT_EVENT) => {
+            decode_event_to_json::<DepositEvent>(data)
+        },
+        (AccountAddress::ONE, STAKE_MODULE, SET_OPERATOR_EVENT) => {
+            decode_event_to_json::<SetOperatorEvent>(data)
+        },
+        (AccountAddress::ONE, STAKING_CONTRACT_MODULE, CREATE_STAKING_CONTRACT_EVENT) => {
+            decode_event_to_json::<CreateStakingContractEvent>(data)
+        },
+        (AccountAddress::ONE, STAKING_CONTRACT_MODULE, UPDATE_VOTER_EVENT) => {
+            decode_event_to_json::<UpdateVoterEvent>(data)
+        },
+        (AccountAddress::ONE, STAKING_CONTRACT_MODULE, RESET_LOCKUP_EVENT) => {
+            decode_event_to_json::<ResetLockupEvent>(data)
+        },
+        (AccountAddress::ONE, STAKING_CONTRACT_MODULE, ADD_STAKE_EVENT) => {
+            decode_event_to_json::<AddStakeEvent>(data)
+        },
+        (AccountAddress::ONE, STAKING_CONTRACT_MODULE, REQUEST_COMMISSION_EVENT) => {
+            decode_event_to_json::<RequestCommissionEvent>(data)
+        },
+        (AccountAddress::ONE, STAKING_CONTRACT_MODULE, UNLOCK_STAKE_EVENT) => {
+            decode_event_to_json::<UnlockStakeEvent>(data)
+        },
+        (AccountAddress::ONE, STAKING_CONTRACT_MODULE, SWITCH_OPERATOR_EVENT) => {
+            decode_event_to_json::<SwitchOperatorEvent>(data)
+        },
+        (AccountAddress::ONE, STAKING_CONTRACT_MODULE, ADD_DISTRIBUTION_EVENT) => {
+            decode_event_to_json::<AddDistributionEvent>(data)
+        },
+        (AccountAddress::ONE, STAKING_CONTRACT_MODULE, DISTRIBUTE_EVENT) => {
+            decode_event_to_json::<DistributeEvent>(data)
+        },
+        // Unknown layouts are only returned as raw bytes
+        _ => return None,
+    };
+
+    match decoded {
+        Ok(value) => Some(value),
+        Err(err) => {
+            warn!(
+                "Failed to decode event {} at version {}: {:?}",
+                struct_tag, version, err
+            );
+            None
+        },
+    }
+}
+
+fn decode_event_to_json<T: DeserializeOwned + Serialize>(
+    data: &[u8],
+) -> anyhow::Result<serde_json::Value> {
+    let event: T = bcs::from_bytes(data)?;
+    Ok(serde_json::to_value(event)?)
+}
+
 /// A representation of a transaction by it's underlying operations (write set changes)
 ///
 /// [API Spec](https://www.rosetta-api.org/docs/models/Transaction.html)
@@ -645,14 +949,24 @@ impl Transaction {
             operation.operation_identifier.index = i as u64;
         }
 
-        // Everything committed costs gas
+        // Everything committed costs gas, which is always paid by the sender
         if let Some(txn) = maybe_user_txn {
+            let gas_payer = txn.sender();
             operations.push(Operation::gas_fee(
                 operation_index,
-                txn.sender(),
+                gas_payer,
                 txn_info.gas_used(),
                 txn.gas_unit_price(),
             ));
+
+            // Attribute every operation to the gas payer, as it may not be the operation's account
+            let gas_payer = AccountIdentifier::base_account(gas_payer);
+            for operation in operations.iter_mut() {
+                operation
+                    .metadata
+                    .get_or_insert_with(OperationMetadata::default)
+                    .gas_payer = Some(gas_payer.clone());
+            }
         }
 
         Ok(Transaction {
@@ -1362,10 +1676,13 @@ pub enum OperationDetails {
 pub enum InternalOperation {
     CreateAccount(CreateAccount),
     Transfer(Transfer),
+    BatchTransfer(BatchTransfer),
     SetOperator(SetOperator),
     SetVoter(SetVoter),
     InitializeStakePool(InitializeStakePool),
     ResetLockup(ResetLockup),
+    RotateAuthKey(RotateAuthKey),
+    EntryFunction(EntryFunctionCall),
 }
 
 impl InternalOperation {
@@ -1488,6 +1805,50 @@ impl InternalOperation {
                                 }));
                             }
                         },
+                        Ok(OperationType::RotateAuthKey) => {
+                            if let (
+                                Some(OperationMetadata {
+                                    current_auth_scheme,
+                                    current_public_key: Some(current_public_key),
+                                    new_auth_scheme,
+                                    new_public_key: Some(new_public_key),
+                                    current_key_proof: Some(current_key_proof),
+                                    new_key_proof: Some(new_key_proof),
+                                    ..
+                                }),
+                                Some(account),
+                            ) = (&operation.metadata, &operation.account)
+                            {
+                                return Ok(Self::RotateAuthKey(RotateAuthKey::new(
+                                    account.account_address()?,
+                                    current_auth_scheme.unwrap_or_default(),
+                                    current_public_key.clone(),
+                                    new_auth_scheme.unwrap_or_default(),
+                                    new_public_key.clone(),
+                                    current_key_proof.clone(),
+                                    new_key_proof.clone(),
+                                )?));
+                            }
+                        },
+                        Ok(OperationType::EntryFunction) => {
+                            if let (
+                                Some(OperationMetadata {
+                                    function: Some(function),
+                                    type_arguments,
+                                    arguments,
+                                    ..
+                                }),
+                                Some(account),
+                            ) = (&operation.metadata, &operation.account)
+                            {
+                                return Ok(Self::EntryFunction(EntryFunctionCall::from_metadata(
+                                    account.account_address()?,
+                                    function,
+                                    type_arguments.as_deref().unwrap_or_default(),
+                                    arguments.as_deref().unwrap_or_default(),
+                                )?));
+                            }
+                        },
                         _ => {},
                     }
                 }
@@ -1499,6 +1860,9 @@ impl InternalOperation {
                 ))))
             },
             2 => Ok(Self::Transfer(Transfer::extract_transfer(operations)?)),
+            len if len >= 4 && len % 2 == 0 => Ok(Self::BatchTransfer(
+                BatchTransfer::extract_batch_transfer(operations)?,
+            )),
             _ => Err(ApiError::InvalidOperations(Some(format!(
                 "Unrecognized operation combination {:?}",
                 operations
@@ -1511,13 +1875,24 @@ impl InternalOperation {
         match self {
             Self::CreateAccount(inner) => inner.sender,
             Self::Transfer(inner) => inner.sender,
+            Self::BatchTransfer(inner) => inner.sender,
             Self::SetOperator(inner) => inner.owner,
             Self::SetVoter(inner) => inner.owner,
             Self::InitializeStakePool(inner) => inner.owner,
             Self::ResetLockup(inner) => inner.owner,
+            Self::RotateAuthKey(inner) => inner.account,
+            Self::EntryFunction(inner) => inner.sender,
         }
     }
 
+    /// The accounts that must sign the transaction, starting with the sender
+    ///
+    /// Secondary signers and fee payers belong here once they're supported, so that
+    /// preprocess, payloads, and combine all agree on who signs.
+    pub fn signers(&self) -> Vec<AccountAddress> {
+        vec![self.sender()]
+    }
+
     pub fn payload(
         &self,
     ) -> ApiResult<(aptos_types::transaction::TransactionPayload, AccountAddress)> {
@@ -1533,6 +1908,20 @@ impl InternalOperation {
                     transfer.sender,
                 )
             },
+            InternalOperation::BatchTransfer(batch_transfer) => {
+                is_native_coin(&batch_transfer.currency)?;
+                (
+                    aptos_stdlib::aptos_account_batch_transfer(
+                        batch_transfer.receivers.clone(),
+                        batch_transfer
+                            .amounts
+                            .iter()
+                            .map(|amount| amount.0)
+                            .collect(),
+                    ),
+                    batch_transfer.sender,
+                )
+            },
             InternalOperation::SetOperator(set_operator) => {
                 if set_operator.old_operator.is_none() {
                     return Err(ApiError::InvalidInput(Some(
@@ -1575,6 +1964,26 @@ impl InternalOperation {
                 aptos_stdlib::staking_contract_reset_lockup(reset_lockup.operator),
                 reset_lockup.owner,
             ),
+            InternalOperation::RotateAuthKey(rotate_auth_key) => (
+                aptos_stdlib::account_rotate_authentication_key(
+                    rotate_auth_key.current_auth_scheme.scheme_id(),
+                    hex::decode(&rotate_auth_key.current_public_key.hex_bytes)?,
+                    rotate_auth_key.new_auth_scheme.scheme_id(),
+                    hex::decode(&rotate_auth_key.new_public_key.hex_bytes)?,
+                    hex::decode(&rotate_auth_key.current_key_proof)?,
+                    hex::decode(&rotate_auth_key.new_key_proof)?,
+                ),
+                rotate_auth_key.account,
+            ),
+            InternalOperation::EntryFunction(call) => (
+                TransactionPayload::EntryFunction(EntryFunction::new(
+                    call.module.clone(),
+                    call.function.clone(),
+                    call.type_arguments.clone(),
+                    call.arguments.clone(),
+                )),
+                call.sender,
+            ),
         })
     }
 }
@@ -1587,6 +1996,9 @@ pub struct CreateAccount {
 }
 
 /// Operation to transfer coins between accounts
+///
+/// Transfers use `0x1::aptos_account::transfer`, which creates the receiver if it doesn't
+/// exist, so the same transaction works for new and existing receivers.
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Transfer {
     pub sender: AccountAddress,
@@ -1703,6 +2115,110 @@ impl Transfer {
     }
 }
 
+/// Operation to transfer coins from one account to many in one transaction, with
+/// `0x1::aptos_account::batch_transfer`
+///
+/// Each transfer is a withdraw and deposit pair of operations, in the order of the receivers.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BatchTransfer {
+    pub sender: AccountAddress,
+    pub receivers: Vec<AccountAddress>,
+    pub amounts: Vec<U64>,
+    pub currency: Currency,
+}
+
+impl BatchTransfer {
+    /// Validates there's an amount for each receiver
+    pub fn new(
+        sender: AccountAddress,
+        receivers: Vec<AccountAddress>,
+        amounts: Vec<U64>,
+        currency: Currency,
+    ) -> ApiResult<BatchTransfer> {
+        if receivers.len() != amounts.len() {
+            return Err(ApiError::InvalidInput(Some(format!(
+                "Batch transfer has {} receivers but {} amounts",
+                receivers.len(),
+                amounts.len()
+            ))));
+        }
+        if receivers.is_empty() {
+            return Err(ApiError::InvalidTransferOperations(Some(
+                "Batch transfer must have at least one receiver",
+            )));
+        }
+
+        Ok(BatchTransfer {
+            sender,
+            receivers,
+            amounts,
+            currency,
+        })
+    }
+
+    /// Pulls the transfers from consecutive withdraw and deposit pairs of operations, which
+    /// must all be from the same sender in the same currency
+    pub fn extract_batch_transfer(operations: &[Operation]) -> ApiResult<BatchTransfer> {
+        if operations.is_empty() || operations.len() % 2 != 0 {
+            return Err(ApiError::InvalidTransferOperations(Some(
+                "Batch transfer must have a withdraw and a deposit for each receiver",
+            )));
+        }
+
+        let transfers = operations
+            .chunks(2)
+            .map(|pair| Transfer::extract_transfer(&pair.to_vec()))
+            .collect::<ApiResult<Vec<_>>>()?;
+        let first = &transfers[0];
+        if transfers
+            .iter()
+            .any(|transfer| transfer.sender != first.sender)
+        {
+            return Err(ApiError::InvalidTransferOperations(Some(
+                "Batch transfer must withdraw from a single sender",
+            )));
+        }
+        if transfers
+            .iter()
+            .any(|transfer| transfer.currency != first.currency)
+        {
+            return Err(ApiError::InvalidTransferOperations(Some(
+                "Batch transfer must be in a single currency",
+            )));
+        }
+
+        BatchTransfer::new(
+            first.sender,
+            transfers.iter().map(|transfer| transfer.receiver).collect(),
+            transfers.iter().map(|transfer| transfer.amount).collect(),
+            first.currency.clone(),
+        )
+    }
+
+    /// The withdraw and deposit pair of operations for each transfer
+    pub fn operations(&self) -> Vec<Operation> {
+        let mut operations = Vec::new();
+        for (receiver, amount) in self.receivers.iter().zip(&self.amounts) {
+            let index = operations.len() as u64;
+            operations.push(Operation::withdraw(
+                index,
+                None,
+                AccountIdentifier::base_account(self.sender),
+                self.currency.clone(),
+                amount.0,
+            ));
+            operations.push(Operation::deposit(
+                index + 1,
+                None,
+                AccountIdentifier::base_account(*receiver),
+                self.currency.clone(),
+                amount.0,
+            ));
+        }
+        operations
+    }
+}
+
 /// Set operator
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct SetOperator {
@@ -1733,3 +2249,170 @@ pub struct ResetLockup {
     pub owner: AccountAddress,
     pub operator: AccountAddress,
 }
+
+/// Operation to rotate an account's authentication key with `0x1::account::rotate_authentication_key`
+///
+/// Both the current and new keys must prove they agree to the rotation, by signing the
+/// [`RotateAuthKey::challenge`] for the transaction's sequence number.  The keys and proofs
+/// are hex encoded, and are validated against their schemes.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RotateAuthKey {
+    pub account: AccountAddress,
+    pub current_auth_scheme: AuthScheme,
+    pub current_public_key: PublicKey,
+    pub new_auth_scheme: AuthScheme,
+    pub new_public_key: PublicKey,
+    /// Signature of the challenge by the current key
+    pub current_key_proof: String,
+    /// Signature of the challenge by the new key
+    pub new_key_proof: String,
+}
+
+/// `0x1::account::RotationProofChallenge`, as it's signed i.e. prefixed by its type
+#[derive(Serialize)]
+struct RotationProofChallenge {
+    account_address: AccountAddress,
+    module_name: String,
+    struct_name: String,
+    sequence_number: u64,
+    originator: AccountAddress,
+    current_auth_key: AccountAddress,
+    new_public_key: Vec<u8>,
+}
+
+impl RotateAuthKey {
+    /// Validates the keys and proofs are of their schemes
+    pub fn new(
+        account: AccountAddress,
+        current_auth_scheme: AuthScheme,
+        current_public_key: PublicKey,
+        new_auth_scheme: AuthScheme,
+        new_public_key: PublicKey,
+        current_key_proof: String,
+        new_key_proof: String,
+    ) -> ApiResult<RotateAuthKey> {
+        for (auth_scheme, public_key, proof) in [
+            (current_auth_scheme, &current_public_key, &current_key_proof),
+            (new_auth_scheme, &new_public_key, &new_key_proof),
+        ] {
+            auth_key_for_scheme(public_key, auth_scheme)?;
+            match auth_scheme {
+                AuthScheme::Ed25519 => {
+                    decode_for_scheme::<Ed25519Signature>(proof, "rotation proof", auth_scheme)?;
+                },
+                AuthScheme::MultiEd25519 => {
+                    decode_for_scheme::<MultiEd25519Signature>(
+                        proof,
+                        "rotation proof",
+                        auth_scheme,
+                    )?;
+                },
+            }
+        }
+
+        Ok(RotateAuthKey {
+            account,
+            current_auth_scheme,
+            current_public_key,
+            new_auth_scheme,
+            new_public_key,
+            current_key_proof,
+            new_key_proof,
+        })
+    }
+
+    /// The message both keys sign to prove the rotation, for the transaction's sequence number
+    ///
+    /// This is the BCS encoded `0x1::account::RotationProofChallenge`, prefixed by its type
+    /// info: the address `0x1`, module `account`, and struct `RotationProofChallenge`.  The
+    /// current auth key is the one derived from the current public key, as the rotation fails
+    /// unless it's the account's auth key.
+    pub fn challenge(&self, sequence_number: u64) -> ApiResult<Vec<u8>> {
+        let current_auth_key =
+            auth_key_for_scheme(&self.current_public_key, self.current_auth_scheme)?;
+        Ok(bcs::to_bytes(&RotationProofChallenge {
+            account_address: AccountAddress::ONE,
+            module_name: ACCOUNT_MODULE.to_string(),
+            struct_name: ROTATION_PROOF_CHALLENGE_STRUCT.to_string(),
+            sequence_number,
+            originator: self.account,
+            current_auth_key: current_auth_key.derived_address(),
+            new_public_key: hex::decode(&self.new_public_key.hex_bytes)?,
+        })?)
+    }
+}
+
+/// Operation to call any entry function, for calls that aren't modeled as other operations
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct EntryFunctionCall {
+    pub sender: AccountAddress,
+    pub module: ModuleId,
+    pub function: Identifier,
+    pub type_arguments: Vec<TypeTag>,
+    /// BCS encoded arguments, not including signers
+    pub arguments: Vec<Vec<u8>>,
+}
+
+impl EntryFunctionCall {
+    /// Parses a call from its operation metadata, validating the function id format
+    pub fn from_metadata(
+        sender: AccountAddress,
+        function: &str,
+        type_arguments: &[String],
+        arguments: &[String],
+    ) -> ApiResult<EntryFunctionCall> {
+        let (module, function) = parse_function_id(function)?;
+        let type_arguments = type_arguments
+            .iter()
+            .map(|type_argument| {
+                move_core_types::parser::parse_type_tag(type_argument).map_err(|err| {
+                    ApiError::InvalidInput(Some(format!(
+                        "Invalid type argument '{}': {}",
+                        type_argument, err
+                    )))
+                })
+            })
+            .collect::<ApiResult<_>>()?;
+        let arguments = arguments
+            .iter()
+            .map(hex::decode)
+            .collect::<Result<_, _>>()?;
+
+        Ok(EntryFunctionCall {
+            sender,
+            module,
+            function,
+            type_arguments,
+            arguments,
+        })
+    }
+
+    /// The fully qualified function id e.g. `0x1::coin::transfer`
+    pub fn function_id(&self) -> String {
+        format!(
+            "{}::{}::{}",
+            self.module.address().to_hex_literal(),
+            self.module.name(),
+            self.function
+        )
+    }
+}
+
+/// Parses a fully qualified function id e.g. `0x1::coin::transfer`
+fn parse_function_id(function: &str) -> ApiResult<(ModuleId, Identifier)> {
+    let invalid = |reason: String| {
+        ApiError::InvalidInput(Some(format!(
+            "Invalid function '{}', expected address::module::function: {}",
+            function, reason
+        )))
+    };
+    let parts: Vec<_> = function.split("::").collect();
+    if parts.len() != 3 {
+        return Err(invalid(format!("found {} parts", parts.len())));
+    }
+
+    let address = AccountAddress::from_str(parts[0]).map_err(|err| invalid(err.to_string()))?;
+    let module = Identifier::new(parts[1]).map_err(|err| invalid(err.to_string()))?;
+    let function = Identifier::new(parts[2]).map_err(|err| invalid(err.to_string()))?;
+    Ok((ModuleId::new(address, module), function))
+}
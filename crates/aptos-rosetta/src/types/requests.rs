@@ -3,9 +3,10 @@
 
 use crate::{
     types::{
-        AccountIdentifier, Allow, Amount, Block, BlockIdentifier, Currency, InternalOperation,
-        NetworkIdentifier, Operation, PartialBlockIdentifier, Peer, PublicKey, Signature,
-        SigningPayload, SyncStatus, Transaction, TransactionIdentifier, Version,
+        AccountIdentifier, Allow, Amount, AuthScheme, Block, BlockIdentifier, BlockTransaction,
+        Currency, InternalOperation, NetworkIdentifier, Operation, PartialBlockIdentifier, Peer,
+        PublicKey, RawEvent, Signature, SignatureEncoding, SigningPayload, SyncStatus, Transaction,
+        TransactionIdentifier, Version,
     },
     AccountAddress, ApiError,
 };
@@ -57,6 +58,48 @@ pub struct AccountBalanceMetadata {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub operators: Option<Vec<AccountAddress>>,
 }
+
+/// Request for the balances of many accounts at once, at the same block
+///
+/// This is an Aptos specific extension, and not part of the Rosetta spec.  The number of
+/// accounts is limited by the server, 100 by default.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AccountBalancesRequest {
+    /// Network identifier describing the blockchain and the chain id
+    pub network_identifier: NetworkIdentifier,
+    /// Account identifiers describing the account addresses
+    pub account_identifiers: Vec<AccountIdentifier>,
+    /// For historical balance lookups by either hash or version
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_identifier: Option<PartialBlockIdentifier>,
+    /// For filtering which currencies to show
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currencies: Option<Vec<Currency>>,
+}
+
+/// Response with the balances of each account, in the same order as the request
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AccountBalancesResponse {
+    /// Block containing the balances
+    pub block_identifier: BlockIdentifier,
+    pub results: Vec<AccountBalanceResult>,
+}
+
+/// Balances of a single account in an [`AccountBalancesResponse`], or why they couldn't be
+/// retrieved
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AccountBalanceResult {
+    pub account_identifier: AccountIdentifier,
+    /// Balances of all known currencies, if they were retrieved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balances: Option<Vec<Amount>>,
+    /// Metadata of account, if the balances were retrieved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<AccountBalanceMetadata>,
+    /// Error retrieving the balances of this account
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<crate::types::Error>,
+}
 /// Reqyest a block (version) on the account
 ///
 /// With neither value for PartialBlockIdentifier, get the latest version
@@ -117,6 +160,27 @@ pub struct BlockResponse {
     pub block: Block,
 }
 
+/// Request for the raw events of a block
+///
+/// This is an Aptos specific extension, and is not part of the Rosetta spec
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BlockEventsRequest {
+    /// Network identifier describing the blockchain and the chain id
+    pub network_identifier: NetworkIdentifier,
+    /// A set of search parameters (latest, by hash, or by index)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_identifier: Option<PartialBlockIdentifier>,
+}
+
+/// Response with all raw events emitted in a block, in version order
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BlockEventsResponse {
+    /// Block the events are in
+    pub block_identifier: BlockIdentifier,
+    /// Events emitted by all transactions in the block
+    pub events: Vec<RawEvent>,
+}
+
 /// Request to combine signatures and an unsigned transaction for submission as a
 /// [`aptos_types::transaction::SignedTransaction`]
 ///
@@ -131,6 +195,14 @@ pub struct ConstructionCombineRequest {
     pub unsigned_transaction: String,
     /// Set of signatures with SigningPayloads to combine
     pub signatures: Vec<Signature>,
+    /// Scheme of the sender's key, which decides the authenticator built.  Aptos specific,
+    /// defaults to [`AuthScheme::Ed25519`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_scheme: Option<AuthScheme>,
+    /// Encoding of the signatures, for signatures from an HSM.  Aptos specific, defaults to
+    /// [`SignatureEncoding::Rosetta`], and only [`AuthScheme::Ed25519`] supports the others
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature_encoding: Option<SignatureEncoding>,
 }
 
 /// Response of signed transaction for submission
@@ -155,6 +227,9 @@ pub struct ConstructionDeriveRequest {
     pub network_identifier: NetworkIdentifier,
     /// Public key to derive an [`aptos_types::account_address::AccountAddress`] from
     pub public_key: PublicKey,
+    /// Scheme of the public key.  Aptos specific, defaults to [`AuthScheme::Ed25519`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_scheme: Option<AuthScheme>,
 }
 
 /// Response of derived account from a public key
@@ -191,14 +266,16 @@ pub struct ConstructionMetadataRequest {
     /// Information telling which metadata to lookup onchain
     ///
     /// This comes verbatim from a preprocess request
-    pub options: MetadataOptions,
+    pub options: ConstructionOptions,
 }
 
 /// A set of operations to tell us which metadata to lookup onchain
 ///
-/// This is built from Preprocess, and is copied verbatim to the metadata request
+/// This is built from Preprocess, and is copied verbatim to the metadata request.  Unknown
+/// options are rejected, rather than silently ignored.
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
-pub struct MetadataOptions {
+#[serde(deny_unknown_fields)]
+pub struct ConstructionOptions {
     /// The operation to run at a high level (e.g. CreateAccount/Transfer)
     pub internal_operation: InternalOperation,
     /// Maximum total gas units willing to pay for the transaction
@@ -210,12 +287,20 @@ pub struct MetadataOptions {
     /// Unix timestamp of expiry time
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expiry_time_secs: Option<U64>,
+    /// Seconds after the payloads are built that the transaction expires, instead of an expiry
+    /// time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiry_offset_secs: Option<U64>,
     /// Sequence number of the request
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sequence_number: Option<U64>,
     /// Public keys to sign simulated transaction.  Must be present if max_gas_amount is not provided
     #[serde(skip_serializing_if = "Option::is_none")]
     pub public_keys: Option<Vec<PublicKey>>,
+    /// Scheme of the public keys, which decides the authenticator of the simulated transaction.
+    /// Aptos specific, defaults to [`AuthScheme::Ed25519`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_scheme: Option<AuthScheme>,
     /// Taking the estimated gas price, and multiplying it
     /// times this number divided by 100 e.g. 120 is 120%
     /// of the estimated price
@@ -226,6 +311,10 @@ pub struct MetadataOptions {
     /// price, and if it's high, it will use the prioritized price.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub gas_price_priority: Option<GasPricePriority>,
+    /// Currency to pay gas in, the native coin if not provided.  Only checked online, as the
+    /// supported currencies depend on the chain
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gas_currency: Option<Currency>,
 }
 
 /// Response with network specific data for constructing a transaction
@@ -237,12 +326,21 @@ pub struct MetadataOptions {
 pub struct ConstructionMetadataResponse {
     /// Metadata that will be passed to Payloads to create a transaction
     pub metadata: ConstructionMetadata,
-    /// A suggested gas fee based on the current state of the network
+    /// Suggested gas fees based on the current state of the network
+    ///
+    /// The first fee is always for the gas price in the `metadata`.  If the gas price was
+    /// estimated, and the node has estimates by priority, there's a fee for each of `low`,
+    /// `normal`, and `high` priority, with the first being the requested priority.  Each has
+    /// `metadata` with its `gas_price_priority` and `gas_price_per_unit`, which can be passed
+    /// to payloads to use that fee.
     pub suggested_fee: Vec<Amount>,
 }
 
 /// Metadata required to construct a transaction
+///
+/// Unknown fields are rejected, rather than silently ignored.
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct ConstructionMetadata {
     /// Sequence number of the sending account
     pub sequence_number: U64,
@@ -253,6 +351,10 @@ pub struct ConstructionMetadata {
     /// Unix timestamp of expiry time, defaults to 30 seconds from the payload request
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expiry_time_secs: Option<U64>,
+    /// Seconds after the payload request that the transaction expires, instead of an expiry
+    /// time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiry_offset_secs: Option<U64>,
     /// Because we need information from metadata to have the real operation
     /// We don't have to parse any fields in the `Payloads` call
     pub internal_operation: InternalOperation,
@@ -273,6 +375,10 @@ pub struct ConstructionParseRequest {
     /// A hex encoded, BCS encoded [`aptos_types::transaction::SignedTransaction`]
     /// or a [`aptos_types::transaction::RawTransaction`]
     pub transaction: String,
+    /// Whether to fail if any operation isn't fully modeled, rather than only represented as a
+    /// call to its entry function.  Aptos specific, not part of the Rosetta spec
+    #[serde(default)]
+    pub strict: bool,
 }
 
 /// Response with operations in a transaction blob
@@ -314,6 +420,10 @@ pub struct ConstructionPayloadsRequest {
     /// Public keys of those who will sign the eventual [`aptos_types::transaction::SignedTransaction`]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub public_keys: Option<Vec<PublicKey>>,
+    /// Scheme of the public keys, which are checked against it.  Aptos specific, defaults to
+    /// [`AuthScheme::Ed25519`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_scheme: Option<AuthScheme>,
 }
 
 /// Response with generated payloads to be signed
@@ -326,6 +436,16 @@ pub struct ConstructionPayloadsResponse {
     pub unsigned_transaction: String,
     /// Payloads describing who and what to sign
     pub payloads: Vec<SigningPayload>,
+    /// Aptos specific details of the unsigned transaction, not part of the Rosetta spec
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<PayloadsMetadata>,
+}
+
+/// Details of the unsigned transaction built by `/construction/payloads`
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PayloadsMetadata {
+    /// Unix timestamp in seconds that the transaction expires at
+    pub expiration_timestamp_secs: U64,
 }
 
 /// Request to get options for a [`ConstructionMetadataRequest`]
@@ -344,11 +464,18 @@ pub struct ConstructionPreprocessRequest {
 }
 
 /// This object holds all the possible "changes" to payloads
+///
+/// Unknown options are rejected, rather than silently ignored.
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct PreprocessMetadata {
     /// Expiry time of the transaction in unix epoch seconds
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expiry_time_secs: Option<U64>,
+    /// Seconds after the payloads are built that the transaction expires, instead of an expiry
+    /// time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiry_offset_secs: Option<U64>,
     /// Sequence number to use for this transaction
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sequence_number: Option<U64>,
@@ -361,6 +488,10 @@ pub struct PreprocessMetadata {
     /// Public keys used for this transaction
     #[serde(skip_serializing_if = "Option::is_none")]
     pub public_keys: Option<Vec<PublicKey>>,
+    /// Scheme of the public keys, which are checked against it.  Aptos specific, defaults to
+    /// [`AuthScheme::Ed25519`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_scheme: Option<AuthScheme>,
     /// Taking the estimated gas price, and multiplying it
     /// times this number divided by 100 e.g. 120 is 120%
     /// of the estimated price
@@ -371,6 +502,10 @@ pub struct PreprocessMetadata {
     /// price, and if it's high, it will use the prioritized price.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub gas_price_priority: Option<GasPricePriority>,
+    /// Currency to pay gas in, the native coin if not provided.  Only checked online, as the
+    /// supported currencies depend on the chain
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gas_currency: Option<Currency>,
 }
 
 /// A gas price priority for what gas price to use
@@ -444,7 +579,7 @@ impl<'de> Deserialize<'de> for GasPricePriority {
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct ConstructionPreprocessResponse {
     /// Metadata to be sent verbatim to the Metadata API
-    pub options: MetadataOptions,
+    pub options: ConstructionOptions,
     /// List of who needs to be signing this transaction
     pub required_public_keys: Vec<AccountIdentifier>,
 }
@@ -460,6 +595,13 @@ pub struct ConstructionSubmitRequest {
     pub network_identifier: NetworkIdentifier,
     /// A hex encoded, BCS encoded [`aptos_types::transaction::SignedTransaction`]
     pub signed_transaction: String,
+    /// Seconds to wait for the transaction to be committed, returning its outcome
+    ///
+    /// The wait starts when the request is handled, so it includes any time spent queued and
+    /// submitting.  This must be less than the submit endpoint's upstream timeout.  If not set,
+    /// the response is returned as soon as the transaction is submitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait_for_commit_secs: Option<U64>,
 }
 
 /// Response containing transaction identifier of submitted transaction
@@ -469,6 +611,26 @@ pub struct ConstructionSubmitRequest {
 pub struct ConstructionSubmitResponse {
     /// Hash of the submitted [`aptos_types::transaction::SignedTransaction`]
     pub transaction_identifier: TransactionIdentifier,
+    /// Outcome of the transaction, only if the request waited for it to be committed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<SubmitMetadata>,
+}
+
+/// Outcome of a submitted transaction
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SubmitMetadata {
+    /// Whether the transaction was committed before the wait timed out
+    ///
+    /// If it wasn't, the transaction may still be committed later, and there's no outcome.
+    pub confirmed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<U64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vm_status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gas_used: Option<U64>,
 }
 
 /// Request for all transactions in mempool
@@ -563,6 +725,22 @@ pub struct NetworkStatusResponse {
     pub peers: Vec<Peer>,
 }
 
+/// Response with the gas unit prices the upstream fullnode estimates, by priority
+///
+/// This is an Aptos specific extension, and not part of the Rosetta spec.  The estimates are
+/// cached briefly by the server, so they may lag the latest block by a few seconds.  The
+/// fullnode doesn't expose its mempool size, so it isn't included.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct NetworkGasPricesResponse {
+    /// Gas unit price for [`GasPricePriority::Low`], for transactions which can wait
+    pub low: U64,
+    /// Gas unit price for [`GasPricePriority::Normal`]
+    pub normal: U64,
+    /// Gas unit price for [`GasPricePriority::High`], for transactions which need to be
+    /// included quickly under congestion
+    pub high: U64,
+}
+
 /// Response with a transaction that was hashed or submitted
 ///
 /// [API Spec](https://www.rosetta-api.org/docs/models/TransactionIdentifierResponse.html)
@@ -571,3 +749,81 @@ pub struct TransactionIdentifierResponse {
     /// Hash of the transaction
     pub transaction_identifier: TransactionIdentifier,
 }
+
+/// How the filters of a [`SearchTransactionsRequest`] are combined
+///
+/// [API Spec](https://www.rosetta-api.org/docs/models/Operator.html)
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchOperator {
+    Or,
+    And,
+}
+
+impl Default for SearchOperator {
+    fn default() -> Self {
+        SearchOperator::And
+    }
+}
+
+/// Request to search for committed transactions
+///
+/// Only searches by account and by transaction hash are supported, optionally filtered by
+/// status.  Setting any other filter fails rather than being ignored.
+///
+/// [API Spec](https://www.rosetta-api.org/docs/models/SearchTransactionsRequest.html)
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SearchTransactionsRequest {
+    /// Network identifier describing the blockchain and the chain id
+    pub network_identifier: NetworkIdentifier,
+    /// How the filters are combined, `and` if not provided
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operator: Option<SearchOperator>,
+    /// Only transactions in this block or earlier are returned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_block: Option<u64>,
+    /// Position of the first result, for paginating an account's transactions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
+    /// Maximum number of results
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+    /// Only the transaction with this hash
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_identifier: Option<TransactionIdentifier>,
+    /// Only transactions sent by this account
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account_identifier: Option<AccountIdentifier>,
+    /// Not supported, as there are no coin identifiers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coin_identifier: Option<serde_json::Value>,
+    /// Not supported
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<Currency>,
+    /// Only transactions with operations of this status e.g. `success`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    /// Not supported
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub operation_type: Option<String>,
+    /// Not supported, use the account identifier
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// Only successful or failed transactions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub success: Option<bool>,
+}
+
+/// Response with the transactions matching a search, and the offset of the next page
+///
+/// [API Spec](https://www.rosetta-api.org/docs/models/SearchTransactionsResponse.html)
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SearchTransactionsResponse {
+    /// Matching transactions, along with their blocks
+    pub transactions: Vec<BlockTransaction>,
+    /// Number of transactions searched, before filtering by status or block
+    pub total_count: u64,
+    /// Offset of the next page, if there are more transactions to search
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<u64>,
+}
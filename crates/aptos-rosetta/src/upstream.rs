@@ -0,0 +1,590 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Upstream fullnode REST clients
+//!
+//! Rosetta can be given an ordered list of fullnodes.  The first one is the primary, and
+//! the rest are used in order if the ones before them are unhealthy.  Every fullnode must be
+//! on the server's chain, and one that isn't is never used.
+//!
+//! Requests fail over to the next fullnode as soon as one can't reach the current fullnode,
+//! and the periodic health checks return to the primary once it's healthy again.
+//!
+//! If enough requests in a row fail to reach the upstreams or time out, an optional circuit
+//! breaker opens, and requests fail fast with [`ApiError::NodeIsOffline`] rather than piling
+//! more load onto struggling fullnodes.  After a cooldown, it half opens to let requests
+//! through again, and the next request's outcome either closes it or opens it for another
+//! cooldown.
+//!
+//! Metrics are labeled by the upstream serving the request, so a bad fullnode in the pool
+//! can be told apart from the rest.
+
+use crate::{
+    counters::UPSTREAM_CIRCUIT_BREAKER_STATE,
+    error::{ApiError, ApiResult},
+};
+use aptos_infallible::Mutex;
+use aptos_logger::{info, warn};
+use aptos_metrics_core::IntGauge;
+use aptos_types::chain_id::ChainId;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// How often the upstream fullnodes are checked for health
+pub const UPSTREAM_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Upstream metrics label for requests served without an upstream fullnode e.g. offline
+pub const NO_UPSTREAM_LABEL: &str = "none";
+
+/// Configuration for failing fast while all upstream fullnodes are unhealthy
+#[derive(Clone, Copy, Debug)]
+pub struct CircuitBreakerConfig {
+    /// Number of consecutive failed upstream requests before the breaker opens
+    pub failure_threshold: u32,
+    /// How long the breaker stays open before letting requests through again
+    pub cooldown: Duration,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CircuitState {
+    /// Requests go to the upstream as normal
+    Closed,
+    /// Requests fail fast without going to the upstream
+    Open,
+    /// Requests go to the upstream, until the next request's outcome closes or opens the breaker
+    HalfOpen,
+}
+
+impl CircuitState {
+    fn metric_value(self) -> i64 {
+        match self {
+            CircuitState::Closed => 0,
+            CircuitState::Open => 1,
+            CircuitState::HalfOpen => 2,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CircuitBreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    state: Mutex<CircuitBreakerState>,
+    /// This breaker's series of [`UPSTREAM_CIRCUIT_BREAKER_STATE`]
+    state_metric: IntGauge,
+}
+
+impl CircuitBreaker {
+    /// Creates a breaker, reporting its state under `metric_labels`, the chain and upstreams
+    fn new(config: CircuitBreakerConfig, metric_labels: &[&str]) -> Self {
+        assert!(
+            config.failure_threshold > 0,
+            "Circuit breaker failure threshold must be greater than 0"
+        );
+        let state_metric = UPSTREAM_CIRCUIT_BREAKER_STATE.with_label_values(metric_labels);
+        state_metric.set(CircuitState::Closed.metric_value());
+        CircuitBreaker {
+            config,
+            state_metric,
+            state: Mutex::new(CircuitBreakerState {
+                consecutive_failures: 0,
+                opened_at: None,
+            }),
+        }
+    }
+
+    fn state_at(&self, now: Instant) -> CircuitState {
+        Self::state_of(&self.state.lock(), &self.config, now)
+    }
+
+    fn state_of(
+        state: &CircuitBreakerState,
+        config: &CircuitBreakerConfig,
+        now: Instant,
+    ) -> CircuitState {
+        match state.opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) if now.saturating_duration_since(opened_at) < config.cooldown => {
+                CircuitState::Open
+            },
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    fn record_success(&self) {
+        let mut state = self.state.lock();
+        if state.opened_at.is_some() {
+            info!("Upstream fullnodes recovered, closing circuit breaker");
+        }
+        state.consecutive_failures = 0;
+        state.opened_at = None;
+        self.state_metric.set(CircuitState::Closed.metric_value());
+    }
+
+    fn record_failure_at(&self, now: Instant) {
+        let mut state = self.state.lock();
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        let should_open = match Self::state_of(&state, &self.config, now) {
+            CircuitState::Closed => state.consecutive_failures >= self.config.failure_threshold,
+            // A failed probe while half open starts another cooldown
+            CircuitState::HalfOpen => true,
+            CircuitState::Open => false,
+        };
+        if should_open {
+            warn!(
+                "Upstream fullnodes failed {} consecutive requests, opening circuit breaker for {:?}",
+                state.consecutive_failures, self.config.cooldown
+            );
+            state.opened_at = Some(now);
+            self.state_metric.set(CircuitState::Open.metric_value());
+        }
+    }
+}
+
+/// The upstream fullnode a request was sent to, if any, so its outcome can be recorded
+#[derive(Clone, Debug, Default)]
+pub struct UpstreamUsage(Arc<Mutex<Option<usize>>>);
+
+impl UpstreamUsage {
+    fn record(&self, index: usize) {
+        *self.0.lock() = Some(index);
+    }
+
+    fn index(&self) -> Option<usize> {
+        *self.0.lock()
+    }
+}
+
+/// An ordered list of upstream fullnode REST clients, with failover between them
+#[derive(Debug)]
+pub struct UpstreamClients {
+    /// The chain every upstream must be on
+    chain_id: ChainId,
+    clients: Vec<Arc<aptos_rest_client::Client>>,
+    /// Metrics label of each client, its host and port
+    labels: Vec<String>,
+    /// Whether each client has been checked to be on the chain, only these are failed over to
+    verified: Vec<AtomicBool>,
+    /// Index of the client currently being used for requests
+    current: AtomicUsize,
+    /// Fails requests fast while the upstreams are unhealthy, if enabled
+    circuit_breaker: Option<CircuitBreaker>,
+}
+
+impl UpstreamClients {
+    pub fn new(
+        chain_id: ChainId,
+        clients: Vec<aptos_rest_client::Client>,
+        circuit_breaker: Option<CircuitBreakerConfig>,
+    ) -> Self {
+        assert!(
+            !clients.is_empty(),
+            "Must provide at least one upstream fullnode"
+        );
+        let labels: Vec<String> = clients.iter().map(upstream_label).collect();
+        let verified = clients.iter().map(|_| AtomicBool::new(false)).collect();
+        // The breaker guards all of the chain's upstreams, so its metric is labeled by them all
+        let circuit_breaker = circuit_breaker
+            .map(|config| CircuitBreaker::new(config, &[&chain_id.to_string(), &labels.join(",")]));
+        UpstreamClients {
+            chain_id,
+            clients: clients.into_iter().map(Arc::new).collect(),
+            labels,
+            verified,
+            current: AtomicUsize::new(0),
+            circuit_breaker,
+        }
+    }
+
+    /// The client for the currently healthy upstream
+    pub fn current(&self) -> Arc<aptos_rest_client::Client> {
+        self.clients[self.current.load(Ordering::Acquire)].clone()
+    }
+
+    /// The metrics label of the currently healthy upstream
+    ///
+    /// It's always one of the configured upstreams, so the label's cardinality is bounded.
+    pub fn current_label(&self) -> &str {
+        &self.labels[self.current.load(Ordering::Acquire)]
+    }
+
+    /// The client for the currently healthy upstream, unless the circuit breaker is open
+    pub fn available(&self) -> ApiResult<Arc<aptos_rest_client::Client>> {
+        self.available_for(&UpstreamUsage::default())
+    }
+
+    /// Like [`UpstreamClients::available`], but records the upstream in `usage`, so the
+    /// request's outcome can be recorded against it with [`UpstreamClients::record_request`]
+    pub fn available_for(
+        &self,
+        usage: &UpstreamUsage,
+    ) -> ApiResult<Arc<aptos_rest_client::Client>> {
+        if self.circuit_state() == CircuitState::Open {
+            return Err(ApiError::NodeIsOffline);
+        }
+        let index = self.current.load(Ordering::Acquire);
+        usage.record(index);
+        Ok(self.clients[index].clone())
+    }
+
+    /// Records the outcome of a request, if it was sent to an upstream
+    ///
+    /// If the upstream couldn't be reached, later requests fail over to the next verified
+    /// upstream right away, rather than waiting for the next health check.  Requests which
+    /// couldn't reach the upstream or timed out count as failures for the circuit breaker,
+    /// any other outcome means the upstream answered.
+    pub fn record_request(&self, usage: &UpstreamUsage, error: Option<&ApiError>) {
+        let index = match usage.index() {
+            Some(index) => index,
+            None => return,
+        };
+        let failed = match error {
+            Some(ApiError::UpstreamUnavailable(_)) => {
+                self.fail_over(index);
+                true
+            },
+            Some(ApiError::UpstreamTimeout(_)) | Some(ApiError::SubmitOutcomeUnknown(_)) => true,
+            _ => false,
+        };
+        if let Some(ref breaker) = self.circuit_breaker {
+            if failed {
+                breaker.record_failure_at(Instant::now());
+            } else {
+                breaker.record_success();
+            }
+        }
+    }
+
+    /// Switches from the upstream at `from` to the next verified one, unless another request
+    /// already switched away from it
+    fn fail_over(&self, from: usize) {
+        let next = (1..self.clients.len())
+            .map(|offset| (from + offset) % self.clients.len())
+            .find(|index| self.verified[*index].load(Ordering::Acquire));
+        if let Some(next) = next {
+            if self
+                .current
+                .compare_exchange(from, next, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                warn!(
+                    "Upstream fullnode {} is unreachable, failing over to {}",
+                    self.clients[from].path_prefix_string(),
+                    self.clients[next].path_prefix_string()
+                );
+            }
+        }
+    }
+
+    /// Checks that every reachable upstream is on the chain, failing if any isn't
+    ///
+    /// Upstreams which can't be reached now are checked by the health checks before they're
+    /// used.
+    pub async fn check_chain_ids(&self) -> anyhow::Result<()> {
+        for (index, client) in self.clients.iter().enumerate() {
+            match client.get_index_bcs().await {
+                Ok(response) => {
+                    let chain_id = response.into_inner().chain_id;
+                    anyhow::ensure!(
+                        chain_id == self.chain_id.id(),
+                        "Upstream fullnode {} is on chain {}, not {}",
+                        client.path_prefix_string(),
+                        chain_id,
+                        self.chain_id
+                    );
+                    self.verified[index].store(true, Ordering::Release);
+                },
+                Err(err) => warn!(
+                    "Upstream fullnode {} is unreachable, its chain will be checked later: {:?}",
+                    client.path_prefix_string(),
+                    err
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    /// The current state of the circuit breaker, which is always closed if it's disabled
+    pub fn circuit_state(&self) -> CircuitState {
+        self.circuit_breaker
+            .as_ref()
+            .map(|breaker| {
+                let state = breaker.state_at(Instant::now());
+                breaker.state_metric.set(state.metric_value());
+                state
+            })
+            .unwrap_or(CircuitState::Closed)
+    }
+
+    /// Checks the upstreams in order, and switches to the first healthy one on the chain
+    ///
+    /// Since the check always starts from the primary, this will return to the primary
+    /// once it becomes healthy again.
+    pub async fn check_health(&self) -> ApiResult<Arc<aptos_rest_client::Client>> {
+        for (index, client) in self.clients.iter().enumerate() {
+            match client.get_index_bcs().await {
+                Ok(response) if response.inner().chain_id != self.chain_id.id() => {
+                    self.verified[index].store(false, Ordering::Release);
+                    warn!(
+                        "Upstream fullnode {} is on chain {}, not {}",
+                        client.path_prefix_string(),
+                        response.inner().chain_id,
+                        self.chain_id
+                    );
+                },
+                Ok(_) => {
+                    self.verified[index].store(true, Ordering::Release);
+                    let previous = self.current.swap(index, Ordering::AcqRel);
+                    if previous != index {
+                        info!(
+                            "Switching upstream fullnode from {} to {}",
+                            self.clients[previous].path_prefix_string(),
+                            client.path_prefix_string()
+                        );
+                    }
+                    return Ok(client.clone());
+                },
+                Err(err) => warn!(
+                    "Upstream fullnode {} is unhealthy: {:?}",
+                    client.path_prefix_string(),
+                    err
+                ),
+            }
+        }
+
+        Err(ApiError::NodeIsOffline)
+    }
+
+    /// Periodically checks the upstreams, failing over as necessary
+    pub async fn run_health_checks(self: Arc<Self>, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            // Failures are already logged, and the current client is left as is
+            let _ = self.check_health().await;
+        }
+    }
+}
+
+/// The host and port of an upstream, which identify it without its path or credentials
+fn upstream_label(client: &aptos_rest_client::Client) -> String {
+    let url = client.path_prefix_string();
+    url::Url::parse(&url)
+        .ok()
+        .and_then(|url| {
+            let host = url.host_str()?.to_string();
+            Some(match url.port_or_known_default() {
+                Some(port) => format!("{}:{}", host, port),
+                None => host,
+            })
+        })
+        .unwrap_or(url)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        CircuitBreaker, CircuitBreakerConfig, CircuitState, UpstreamClients, UpstreamUsage,
+    };
+    use crate::{
+        error::ApiError,
+        test_utils::{self, MockLedger},
+    };
+    use aptos_types::chain_id::ChainId;
+    use std::{
+        sync::atomic::Ordering,
+        time::{Duration, Instant},
+    };
+    use warp::Filter;
+
+    const COOLDOWN: Duration = Duration::from_secs(30);
+
+    fn circuit_breaker(upstreams: &str) -> CircuitBreaker {
+        CircuitBreaker::new(
+            CircuitBreakerConfig {
+                failure_threshold: 3,
+                cooldown: COOLDOWN,
+            },
+            &["test", upstreams],
+        )
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = circuit_breaker("opens-after-failures:8080");
+        let start = Instant::now();
+
+        breaker.record_failure_at(start);
+        breaker.record_failure_at(start);
+        // A success resets the count
+        breaker.record_success();
+        breaker.record_failure_at(start);
+        breaker.record_failure_at(start);
+        assert_eq!(CircuitState::Closed, breaker.state_at(start));
+
+        breaker.record_failure_at(start);
+        assert_eq!(CircuitState::Open, breaker.state_at(start));
+        assert_eq!(
+            CircuitState::Open,
+            breaker.state_at(start + COOLDOWN - Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn half_opens_after_cooldown() {
+        let breaker = circuit_breaker("half-opens:8080");
+        let start = Instant::now();
+        for _ in 0..3 {
+            breaker.record_failure_at(start);
+        }
+
+        // A failed probe while half open reopens for another cooldown
+        let half_open = start + COOLDOWN;
+        assert_eq!(CircuitState::HalfOpen, breaker.state_at(half_open));
+        breaker.record_failure_at(half_open);
+        assert_eq!(CircuitState::Open, breaker.state_at(half_open));
+        assert_eq!(
+            CircuitState::HalfOpen,
+            breaker.state_at(half_open + COOLDOWN)
+        );
+
+        // A successful probe closes it
+        breaker.record_success();
+        assert_eq!(CircuitState::Closed, breaker.state_at(half_open + COOLDOWN));
+    }
+
+    #[test]
+    fn breakers_report_state_separately() {
+        let open = circuit_breaker("separately-open:8080");
+        let closed = circuit_breaker("separately-closed:8080");
+        for _ in 0..3 {
+            open.record_failure_at(Instant::now());
+        }
+        assert_eq!(CircuitState::Open.metric_value(), open.state_metric.get());
+        assert_eq!(
+            CircuitState::Closed.metric_value(),
+            closed.state_metric.get()
+        );
+    }
+
+    #[test]
+    fn breaker_counts_request_outcomes() {
+        let upstreams = UpstreamClients::new(
+            ChainId::test(),
+            vec![aptos_rest_client::Client::new(
+                url::Url::parse("http://fullnode:8080").unwrap(),
+            )],
+            Some(CircuitBreakerConfig {
+                failure_threshold: 2,
+                cooldown: COOLDOWN,
+            }),
+        );
+        let request = |error: Option<ApiError>| {
+            let usage = UpstreamUsage::default();
+            upstreams.available_for(&usage).unwrap();
+            upstreams.record_request(&usage, error.as_ref());
+        };
+
+        request(Some(ApiError::UpstreamTimeout(None)));
+        // Errors from an upstream which answered are successes
+        request(Some(ApiError::TransactionIsPending));
+        request(Some(ApiError::UpstreamUnavailable(None)));
+        assert_eq!(CircuitState::Closed, upstreams.circuit_state());
+
+        // Requests which never went to an upstream aren't counted
+        upstreams.record_request(&UpstreamUsage::default(), None);
+        request(Some(ApiError::UpstreamTimeout(None)));
+        assert_eq!(CircuitState::Open, upstreams.circuit_state());
+        assert_eq!(ApiError::NodeIsOffline, upstreams.available().unwrap_err());
+    }
+
+    #[test]
+    fn labels_identify_upstreams() {
+        let client = |url: &str| aptos_rest_client::Client::new(url::Url::parse(url).unwrap());
+        let upstreams = UpstreamClients::new(
+            ChainId::test(),
+            vec![
+                client("http://fullnode-1:8080/"),
+                client("https://fullnode-2/v1"),
+            ],
+            None,
+        );
+        assert_eq!("fullnode-1:8080", upstreams.current_label());
+
+        // The label follows failover
+        upstreams.current.store(1, Ordering::Release);
+        assert_eq!("fullnode-2:443", upstreams.current_label());
+    }
+
+    fn upstream(chain_id: ChainId) -> aptos_rest_client::Client {
+        let ledger = MockLedger {
+            chain_id,
+            ..Default::default()
+        };
+        aptos_rest_client::Client::new(test_utils::mock_upstream(
+            ledger,
+            warp::any().map(move || ledger.index_response()),
+        ))
+    }
+
+    #[tokio::test]
+    async fn fails_over_when_upstream_is_unreachable() {
+        // Nothing listens on the primary
+        let upstreams = UpstreamClients::new(
+            ChainId::test(),
+            vec![
+                aptos_rest_client::Client::new(url::Url::parse("http://127.0.0.1:1").unwrap()),
+                upstream(ChainId::test()),
+            ],
+            None,
+        );
+        upstreams.check_chain_ids().await.unwrap();
+
+        let usage = UpstreamUsage::default();
+        let error: ApiError = upstreams
+            .available_for(&usage)
+            .unwrap()
+            .get_index_bcs()
+            .await
+            .unwrap_err()
+            .into();
+        assert!(matches!(error, ApiError::UpstreamUnavailable(_)));
+        upstreams.record_request(&usage, Some(&error));
+        assert_eq!(1, upstreams.current.load(Ordering::Acquire));
+
+        // Other errors don't fail over
+        let usage = UpstreamUsage::default();
+        upstreams.available_for(&usage).unwrap();
+        upstreams.record_request(&usage, Some(&ApiError::TransactionIsPending));
+        assert_eq!(1, upstreams.current.load(Ordering::Acquire));
+    }
+
+    #[tokio::test]
+    async fn upstreams_on_other_chains_are_never_used() {
+        let upstreams = UpstreamClients::new(
+            ChainId::test(),
+            vec![upstream(ChainId::new(200)), upstream(ChainId::test())],
+            None,
+        );
+        upstreams.check_chain_ids().await.unwrap_err();
+
+        upstreams.check_health().await.unwrap();
+        assert_eq!(1, upstreams.current.load(Ordering::Acquire));
+
+        // There's nowhere to fail over to
+        let usage = UpstreamUsage::default();
+        upstreams.available_for(&usage).unwrap();
+        upstreams.record_request(&usage, Some(&ApiError::UpstreamUnavailable(None)));
+        assert_eq!(1, upstreams.current.load(Ordering::Acquire));
+    }
+}
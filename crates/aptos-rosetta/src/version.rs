@@ -0,0 +1,141 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Rosetta spec version negotiation
+//!
+//! Clients can send the Rosetta spec version they're built against in the `Rosetta-Version`
+//! header, and the server checks that it's compatible with [`ROSETTA_VERSION`] before
+//! handling the request.  The compatibility matrix enforced is:
+//!
+//! | Client version            | Result                                      |
+//! |---------------------------|---------------------------------------------|
+//! | No header                 | Accepted, assumed to be the server version  |
+//! | Same major and minor      | Accepted, patch versions are only additive  |
+//! | Different major or minor  | Rejected with `UnsupportedRosettaVersion`   |
+//! | Not `major.minor.patch`   | Rejected with `UnsupportedRosettaVersion`   |
+//!
+//! Responses aren't adapted for older versions, as there are no accepted versions that
+//! would need fields omitted.
+
+use crate::{error::ApiError, ROSETTA_VERSION};
+use std::{fmt::Formatter, str::FromStr};
+use warp::{reject::Reject, Filter, Rejection};
+
+/// Header clients use to send the Rosetta spec version they expect
+pub const ROSETTA_VERSION_HEADER: &str = "rosetta-version";
+
+/// A Rosetta spec version e.g. 1.4.12
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RosettaVersion {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl RosettaVersion {
+    /// The Rosetta spec version the server implements
+    pub fn current() -> RosettaVersion {
+        RosettaVersion::from_str(ROSETTA_VERSION).expect("ROSETTA_VERSION must be valid")
+    }
+
+    /// Whether a client expecting this version can use the server's responses
+    pub fn is_compatible_with(&self, server: &RosettaVersion) -> bool {
+        self.major == server.major && self.minor == server.minor
+    }
+}
+
+impl FromStr for RosettaVersion {
+    type Err = ApiError;
+
+    fn from_str(version: &str) -> Result<Self, Self::Err> {
+        let mut parts = version.trim().split('.').map(u64::from_str);
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch)), None) => Ok(RosettaVersion {
+                major,
+                minor,
+                patch,
+            }),
+            _ => Err(ApiError::UnsupportedRosettaVersion(Some(format!(
+                "Invalid Rosetta version '{}', expected major.minor.patch",
+                version
+            )))),
+        }
+    }
+}
+
+impl std::fmt::Display for RosettaVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// The client's requested version isn't supported by the server
+#[derive(Debug)]
+pub struct VersionMismatch(pub ApiError);
+
+impl Reject for VersionMismatch {}
+
+/// Checks the client's requested version against the server's version
+fn check_version(requested: &str) -> Result<(), ApiError> {
+    let requested = RosettaVersion::from_str(requested)?;
+    let current = RosettaVersion::current();
+    if requested.is_compatible_with(&current) {
+        Ok(())
+    } else {
+        Err(ApiError::UnsupportedRosettaVersion(Some(format!(
+            "Requested Rosetta version {} is not compatible with the server's version {}",
+            requested, current
+        ))))
+    }
+}
+
+/// Rejects requests with [`VersionMismatch`] if the requested version isn't compatible
+pub fn negotiate_version() -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>(ROSETTA_VERSION_HEADER)
+        .and_then(|requested: Option<String>| async move {
+            match requested {
+                Some(requested) => check_version(&requested)
+                    .map_err(|err| warp::reject::custom(VersionMismatch(err))),
+                None => Ok(()),
+            }
+        })
+        .untuple_one()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_version, RosettaVersion};
+    use crate::ROSETTA_VERSION;
+    use std::str::FromStr;
+
+    #[test]
+    fn parse_version() {
+        assert_eq!(
+            RosettaVersion {
+                major: 1,
+                minor: 4,
+                patch: 12
+            },
+            RosettaVersion::from_str("1.4.12").unwrap()
+        );
+        assert!(RosettaVersion::from_str("1.4").is_err());
+        assert!(RosettaVersion::from_str("1.4.12.1").is_err());
+        assert!(RosettaVersion::from_str("v1.4.12").is_err());
+        assert_eq!(
+            ROSETTA_VERSION,
+            RosettaVersion::current().to_string().as_str()
+        );
+    }
+
+    #[test]
+    fn compatibility_matrix() {
+        let current = RosettaVersion::current();
+        check_version(ROSETTA_VERSION).unwrap();
+        check_version(&format!("{}.{}.0", current.major, current.minor)).unwrap();
+        check_version(&format!("{}.{}.99", current.major, current.minor)).unwrap();
+
+        check_version(&format!("{}.{}.0", current.major, current.minor + 1)).unwrap_err();
+        check_version(&format!("{}.{}.0", current.major + 1, current.minor)).unwrap_err();
+        check_version("garbage").unwrap_err();
+    }
+}
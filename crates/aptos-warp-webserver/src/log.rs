@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use aptos_logger::{
-    debug, error,
+    debug, error, info,
     prelude::{sample, SampleRate},
     Schema,
 };
@@ -13,28 +13,30 @@ use warp::{
 };
 
 pub fn logger() -> Log<impl Fn(Info) + Copy> {
-    let func = move |info: Info| {
-        let status = info.status().as_u16();
-        let log = HttpRequestLog {
-            remote_addr: info.remote_addr(),
-            method: info.method().to_string(),
-            path: info.path().to_string(),
-            status,
-            referer: info.referer(),
-            user_agent: info.user_agent(),
-            elapsed: info.elapsed(),
-            forwarded: info
-                .request_headers()
-                .get(header::FORWARDED)
-                .and_then(|v| v.to_str().ok()),
-        };
-        if status >= 500 {
-            sample!(SampleRate::Duration(Duration::from_secs(1)), error!(log));
-        } else {
-            debug!(log);
-        }
-    };
-    custom(func)
+    custom(|info: Info| log_request(&info))
+}
+
+/// Logs a request, for use in custom loggers
+pub fn log_request(info: &Info) {
+    let log = HttpRequestLog::new(info);
+    if log.status >= 500 {
+        sample!(SampleRate::Duration(Duration::from_secs(1)), error!(log));
+    } else {
+        debug!(log);
+    }
+}
+
+/// Logs a request without sampling errors, for loggers that do their own sampling
+///
+/// Errors are logged at `error` and everything else at `info`, so every request passed in
+/// is visible at the default log level.
+pub fn log_request_unsampled(info: &Info) {
+    let log = HttpRequestLog::new(info);
+    if log.status >= 500 {
+        error!(log);
+    } else {
+        info!(log);
+    }
 }
 
 #[derive(Schema)]
@@ -50,3 +52,21 @@ pub struct HttpRequestLog<'a> {
     elapsed: std::time::Duration,
     forwarded: Option<&'a str>,
 }
+
+impl<'a> HttpRequestLog<'a> {
+    fn new(info: &'a Info) -> Self {
+        HttpRequestLog {
+            remote_addr: info.remote_addr(),
+            method: info.method().to_string(),
+            path: info.path().to_string(),
+            status: info.status().as_u16(),
+            referer: info.referer(),
+            user_agent: info.user_agent(),
+            elapsed: info.elapsed(),
+            forwarded: info
+                .request_headers()
+                .get(header::FORWARDED)
+                .and_then(|v| v.to_str().ok()),
+        }
+    }
+}
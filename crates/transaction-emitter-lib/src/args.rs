@@ -81,6 +81,18 @@ pub enum TransactionType {
     PublishPackage,
 }
 
+impl TransactionType {
+    /// Name of the generator of this type in a [`crate::MixConfig`]
+    pub fn generator_name(&self) -> &'static str {
+        match self {
+            TransactionType::P2P => "p2p",
+            TransactionType::AccountGeneration => "account_generation",
+            TransactionType::NftMintAndTransfer => "nft_mint_and_transfer",
+            TransactionType::PublishPackage => "publish_package",
+        }
+    }
+}
+
 impl Default for TransactionType {
     fn default() -> Self {
         TransactionType::P2P
@@ -127,6 +139,11 @@ pub struct EmitArgs {
     #[clap(long, min_values = 0)]
     pub transaction_type_weights: Vec<usize>,
 
+    /// YAML or JSON file describing the transaction mix, used instead of --transaction-type and
+    /// --transaction-type-weights.  See `transaction_generator::mix_config` for the format.
+    #[clap(long)]
+    pub mix_file: Option<String>,
+
     #[clap(long)]
     pub expected_max_txns: Option<u64>,
 
@@ -16,9 +16,11 @@ use crate::{
     },
     transaction_generator::{
         account_generator::AccountGeneratorCreator,
+        mix_config::{BuiltinContext, GeneratorRegistry, MixConfig, SharedCreator},
         nft_mint_and_transfer::NFTMintAndTransferGeneratorCreator,
         p2p_transaction_generator::P2PTransactionGeneratorCreator,
-        publish_modules::PublishPackageCreator, transaction_mix_generator::TxnMixGeneratorCreator,
+        publish_modules::PublishPackageCreator,
+        transaction_mix_generator::TxnMixGeneratorCreator,
         TransactionGeneratorCreator,
     },
 };
@@ -119,6 +121,7 @@ pub struct EmitJobRequest {
     mint_to_root: bool,
 
     transaction_mix: Vec<(TransactionType, usize)>,
+    mix_config: Option<MixConfig>,
 
     add_created_accounts_to_pool: bool,
     max_account_working_set: usize,
@@ -145,6 +148,7 @@ impl Default for EmitJobRequest {
             reuse_accounts: false,
             mint_to_root: false,
             transaction_mix: vec![(TransactionType::P2P, 1)],
+            mix_config: None,
             add_created_accounts_to_pool: true,
             max_account_working_set: 1_000_000,
             txn_expiration_time_secs: 60,
@@ -202,6 +206,13 @@ impl EmitJobRequest {
         self
     }
 
+    /// Uses the mix from a file instead of `transaction_mix`, with generators resolved by
+    /// [`GeneratorRegistry::with_builtins`]
+    pub fn mix_config(mut self, mix_config: MixConfig) -> Self {
+        self.mix_config = Some(mix_config);
+        self
+    }
+
     pub fn mode(mut self, mode: EmitJobMode) -> Self {
         self.mode = mode;
         self
@@ -462,44 +473,78 @@ impl TxnEmitter {
         let stats = Arc::new(DynamicStatsTracking::new(stats_tracking_phases));
         let tokio_handle = Handle::current();
 
-        let mut txn_generator_creator_mix: Vec<(Box<dyn TransactionGeneratorCreator>, usize)> =
-            Vec::new();
-        for (transaction_type, weight) in req.transaction_mix {
-            let txn_generator_creator: Box<dyn TransactionGeneratorCreator> = match transaction_type
-            {
-                TransactionType::P2P => Box::new(P2PTransactionGeneratorCreator::new(
-                    txn_factory.clone(),
-                    SEND_AMOUNT,
-                    all_addresses.clone(),
-                    req.invalid_transaction_ratio,
-                )),
-                TransactionType::AccountGeneration => Box::new(AccountGeneratorCreator::new(
-                    txn_factory.clone(),
-                    all_addresses.clone(),
-                    req.add_created_accounts_to_pool,
-                    req.max_account_working_set,
-                    req.gas_price,
-                )),
-                TransactionType::NftMintAndTransfer => Box::new(
-                    NFTMintAndTransferGeneratorCreator::new(
-                        txn_factory.clone(),
-                        root_account,
-                        &txn_executor,
-                        num_workers,
-                    )
-                    .await,
-                ),
-                TransactionType::PublishPackage => {
-                    Box::new(PublishPackageCreator::new(txn_factory.clone()))
-                },
-            };
-            txn_generator_creator_mix.push((txn_generator_creator, weight));
-        }
         let mut txn_generator_creator: Box<dyn TransactionGeneratorCreator> =
-            if txn_generator_creator_mix.len() > 1 {
-                Box::new(TxnMixGeneratorCreator::new(txn_generator_creator_mix))
+            if let Some(mix_config) = &req.mix_config {
+                let nft_mint_and_transfer = if mix_config
+                    .generator_names()
+                    .contains(TransactionType::NftMintAndTransfer.generator_name())
+                {
+                    Some(SharedCreator::new(Box::new(
+                        NFTMintAndTransferGeneratorCreator::new(
+                            txn_factory.clone(),
+                            root_account,
+                            &txn_executor,
+                            num_workers,
+                        )
+                        .await,
+                    )))
+                } else {
+                    None
+                };
+                let registry = GeneratorRegistry::with_builtins(BuiltinContext {
+                    txn_factory: txn_factory.clone(),
+                    all_addresses: all_addresses.clone(),
+                    send_amount: SEND_AMOUNT,
+                    invalid_transaction_ratio: req.invalid_transaction_ratio,
+                    add_created_accounts_to_pool: req.add_created_accounts_to_pool,
+                    max_account_working_set: req.max_account_working_set,
+                    gas_price: req.gas_price,
+                    nft_mint_and_transfer,
+                });
+                Box::new(registry.build(mix_config)?)
             } else {
-                txn_generator_creator_mix.into_iter().next().unwrap().0
+                let mut txn_generator_creator_mix: Vec<(
+                    Box<dyn TransactionGeneratorCreator>,
+                    usize,
+                )> = Vec::new();
+                for (transaction_type, weight) in req.transaction_mix {
+                    let txn_generator_creator: Box<dyn TransactionGeneratorCreator> =
+                        match transaction_type {
+                            TransactionType::P2P => Box::new(P2PTransactionGeneratorCreator::new(
+                                txn_factory.clone(),
+                                SEND_AMOUNT,
+                                all_addresses.clone(),
+                                req.invalid_transaction_ratio,
+                            )),
+                            TransactionType::AccountGeneration => {
+                                Box::new(AccountGeneratorCreator::new(
+                                    txn_factory.clone(),
+                                    all_addresses.clone(),
+                                    req.add_created_accounts_to_pool,
+                                    req.max_account_working_set,
+                                    req.gas_price,
+                                ))
+                            },
+                            TransactionType::NftMintAndTransfer => Box::new(
+                                NFTMintAndTransferGeneratorCreator::new(
+                                    txn_factory.clone(),
+                                    root_account,
+                                    &txn_executor,
+                                    num_workers,
+                                )
+                                .await,
+                            ),
+                            TransactionType::PublishPackage => {
+                                Box::new(PublishPackageCreator::new(txn_factory.clone()))
+                            },
+                        };
+                    txn_generator_creator_mix.push((txn_generator_creator, weight));
+                }
+                if txn_generator_creator_mix.len() > 1 {
+                    Box::new(TxnMixGeneratorCreator::new(txn_generator_creator_mix))
+                } else {
+                    txn_generator_creator_mix.into_iter().next().unwrap().0
+                }
             };
 
         let total_workers = req.rest_clients.len() * workers_per_endpoint;
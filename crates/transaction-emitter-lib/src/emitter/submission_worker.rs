@@ -51,6 +51,9 @@ impl SubmissionWorker {
         skip_latency_stats: bool,
         rng: ::rand::rngs::StdRng,
     ) -> Self {
+        // Generators which keep state about the accounts need to know the ones they're given
+        let mut txn_generator = txn_generator;
+        txn_generator.on_accounts_changed(&accounts);
         Self {
             accounts,
             client,
@@ -97,7 +100,7 @@ impl SubmissionWorker {
             // always add expected cycle duration, to not drift from expected pace.
             wait_until += wait_duration;
 
-            let requests = self.gen_requests();
+            let requests = self.gen_requests().await;
 
             let txn_expiration_time = requests
                 .iter()
@@ -260,7 +263,7 @@ impl SubmissionWorker {
         }
     }
 
-    fn gen_requests(&mut self) -> Vec<SignedTransaction> {
+    async fn gen_requests(&mut self) -> Vec<SignedTransaction> {
         let batch_size = max(
             1,
             min(
@@ -273,7 +276,8 @@ impl SubmissionWorker {
             .iter_mut()
             .choose_multiple(&mut self.rng, batch_size);
         self.txn_generator
-            .generate_transactions(accounts, self.params.transactions_per_account)
+            .generate_transactions_async(accounts, self.params.transactions_per_account)
+            .await
     }
 }
 
@@ -358,3 +362,71 @@ pub async fn submit_transactions(
         },
     };
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        emitter::{stats::DynamicStatsTracking, submission_worker::SubmissionWorker},
+        transaction_generator::TransactionGenerator,
+        EmitModeParams,
+    };
+    use aptos_infallible::Mutex;
+    use aptos_rest_client::Client as RestClient;
+    use aptos_sdk::{
+        move_types::account_address::AccountAddress,
+        types::{transaction::SignedTransaction, LocalAccount},
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::sync::{atomic::AtomicBool, Arc};
+
+    /// Records the accounts it was last told about
+    struct RecordingGenerator(Arc<Mutex<Vec<AccountAddress>>>);
+
+    impl TransactionGenerator for RecordingGenerator {
+        fn generate_transactions(
+            &mut self,
+            _accounts: Vec<&mut LocalAccount>,
+            _transactions_per_account: usize,
+        ) -> Vec<SignedTransaction> {
+            vec![]
+        }
+
+        fn on_accounts_changed(&mut self, accounts: &[LocalAccount]) {
+            *self.0.lock() = accounts.iter().map(LocalAccount::address).collect();
+        }
+    }
+
+    #[test]
+    fn generator_is_told_the_worker_accounts() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let accounts: Vec<_> = (0..5).map(|_| LocalAccount::generate(&mut rng)).collect();
+        let addresses: Vec<_> = accounts.iter().map(LocalAccount::address).collect();
+        let recorded = Arc::new(Mutex::new(vec![]));
+
+        let worker = SubmissionWorker::new(
+            accounts,
+            RestClient::new(url::Url::parse("http://127.0.0.1:1").unwrap()),
+            Arc::new(AtomicBool::new(false)),
+            EmitModeParams {
+                txn_expiration_time_secs: 60,
+                workers_per_endpoint: 1,
+                accounts_per_worker: 5,
+                transactions_per_account: 1,
+                max_submit_batch_size: 100,
+                start_offset_multiplier_millis: 0.0,
+                start_jitter_millis: 0,
+                wait_millis: 0,
+                check_account_sequence_only_once_fraction: 0.0,
+                check_account_sequence_sleep_millis: 100,
+            },
+            Arc::new(DynamicStatsTracking::new(1)),
+            Box::new(RecordingGenerator(recorded.clone())),
+            0,
+            false,
+            rng,
+        );
+
+        assert_eq!(addresses, *recorded.lock());
+        assert_eq!(addresses.len(), worker.accounts.len());
+    }
+}
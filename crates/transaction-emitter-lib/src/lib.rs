@@ -19,4 +19,5 @@ pub use emitter::{
     stats::{TxnStats, TxnStatsRate},
     EmitJob, EmitJobMode, EmitJobRequest, EmitModeParams, TxnEmitter,
 };
+pub use transaction_generator::mix_config::MixConfig;
 pub use wrappers::{emit_transactions, emit_transactions_with_cluster};
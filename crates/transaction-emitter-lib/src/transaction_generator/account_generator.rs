@@ -138,4 +138,18 @@ impl TransactionGeneratorCreator for AccountGeneratorCreator {
             self.gas_price,
         ))
     }
+
+    async fn create_seeded_transaction_generator(
+        &mut self,
+        seed: [u8; 32],
+    ) -> Box<dyn TransactionGenerator> {
+        Box::new(AccountGenerator::new(
+            StdRng::from_seed(seed),
+            self.txn_factory.clone(),
+            self.all_addresses.clone(),
+            self.add_created_accounts_to_pool,
+            self.max_working_set,
+            self.gas_price,
+        ))
+    }
 }
@@ -144,4 +144,17 @@ impl TransactionGeneratorCreator for CallCustomModulesCreator {
             self.entry_point,
         ))
     }
+
+    async fn create_seeded_transaction_generator(
+        &mut self,
+        seed: [u8; 32],
+    ) -> Box<dyn TransactionGenerator> {
+        Box::new(CallCustomModulesGenerator::new(
+            StdRng::from_seed(seed),
+            self.txn_factory.clone(),
+            self.packages.clone(),
+            self.accounts_pool.clone(),
+            self.entry_point,
+        ))
+    }
 }
@@ -0,0 +1,36 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use aptos_metrics_core::{
+    exponential_buckets, register_histogram, register_int_counter_vec, Histogram, IntCounterVec,
+};
+use once_cell::sync::Lazy;
+
+/// Histogram of the wall-clock time spent generating each batch of transactions
+pub static TXN_GENERATION_DURATION_S: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        "aptos_emitter_txn_generation_duration_s",
+        "Histogram of the wall-clock time spent generating each batch of transactions"
+    )
+    .unwrap()
+});
+
+/// Histogram of the number of transactions generated in each batch
+pub static TXN_GENERATION_BATCH_SIZE: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        "aptos_emitter_txn_generation_batch_size",
+        "Histogram of the number of transactions generated in each batch",
+        exponential_buckets(1.0, 2.0, 16).unwrap()
+    )
+    .unwrap()
+});
+
+/// Count of batches generated by the fallback in place of each generator of the mix
+pub static TXN_GENERATION_FALLBACK_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_emitter_txn_generation_fallback_count",
+        "Count of batches generated by the fallback in place of each generator of the mix",
+        &["generator_index"]
+    )
+    .unwrap()
+});
@@ -0,0 +1,241 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Calls an entry function of an already deployed module, with arguments sampled per transaction
+use crate::transaction_generator::{TransactionGenerator, TransactionGeneratorCreator};
+use anyhow::{ensure, Result};
+use aptos_sdk::{
+    bcs,
+    move_types::{
+        account_address::AccountAddress,
+        identifier::Identifier,
+        language_storage::{ModuleId, TypeTag},
+    },
+    transaction_builder::TransactionFactory,
+    types::{
+        transaction::{EntryFunction, SignedTransaction},
+        LocalAccount,
+    },
+};
+use async_trait::async_trait;
+use rand::{prelude::SliceRandom, rngs::StdRng, Rng, SeedableRng};
+use std::ops::Range;
+
+/// How to sample an argument of the entry function
+#[derive(Clone, Debug)]
+pub enum ArgSampler {
+    /// A `u64` uniformly sampled from the range
+    U64Range(Range<u64>),
+    /// One of the values, each already BCS encoded
+    OneOf(Vec<Vec<u8>>),
+    /// The address of the account sending the transaction
+    Sender,
+}
+
+impl ArgSampler {
+    fn check(&self) -> Result<()> {
+        match self {
+            ArgSampler::U64Range(range) => {
+                ensure!(!range.is_empty(), "Empty argument range {:?}", range)
+            },
+            ArgSampler::OneOf(values) => ensure!(!values.is_empty(), "No argument values"),
+            ArgSampler::Sender => {},
+        }
+        Ok(())
+    }
+
+    /// Samples a BCS encoded argument
+    fn sample(&self, rng: &mut StdRng, sender: AccountAddress) -> Vec<u8> {
+        match self {
+            ArgSampler::U64Range(range) => {
+                bcs::to_bytes(&rng.gen_range(range.start, range.end)).unwrap()
+            },
+            ArgSampler::OneOf(values) => values.choose(rng).unwrap().clone(),
+            ArgSampler::Sender => bcs::to_bytes(&sender).unwrap(),
+        }
+    }
+}
+
+/// The entry function to call, and how to sample each of its arguments
+#[derive(Clone, Debug)]
+pub struct EntryFunctionTarget {
+    pub module: ModuleId,
+    pub function: Identifier,
+    pub type_args: Vec<TypeTag>,
+    pub args: Vec<ArgSampler>,
+}
+
+pub struct EntryFunctionGenerator {
+    rng: StdRng,
+    txn_factory: TransactionFactory,
+    target: EntryFunctionTarget,
+}
+
+impl EntryFunctionGenerator {
+    pub fn new(rng: StdRng, txn_factory: TransactionFactory, target: EntryFunctionTarget) -> Self {
+        Self {
+            rng,
+            txn_factory,
+            target,
+        }
+    }
+
+    fn gen_single_txn(&mut self, sender: &mut LocalAccount) -> SignedTransaction {
+        let address = sender.address();
+        let args = self
+            .target
+            .args
+            .iter()
+            .map(|arg| arg.sample(&mut self.rng, address))
+            .collect();
+        sender.sign_with_transaction_builder(self.txn_factory.entry_function(EntryFunction::new(
+            self.target.module.clone(),
+            self.target.function.clone(),
+            self.target.type_args.clone(),
+            args,
+        )))
+    }
+}
+
+impl TransactionGenerator for EntryFunctionGenerator {
+    fn generate_transactions(
+        &mut self,
+        accounts: Vec<&mut LocalAccount>,
+        transactions_per_account: usize,
+    ) -> Vec<SignedTransaction> {
+        let mut requests = Vec::with_capacity(accounts.len() * transactions_per_account);
+        for sender in accounts {
+            for _ in 0..transactions_per_account {
+                requests.push(self.gen_single_txn(sender));
+            }
+        }
+        requests
+    }
+}
+
+pub struct EntryFunctionGeneratorCreator {
+    txn_factory: TransactionFactory,
+    target: EntryFunctionTarget,
+}
+
+impl EntryFunctionGeneratorCreator {
+    /// Fails if any argument can't be sampled e.g. an empty range
+    pub fn new(txn_factory: TransactionFactory, target: EntryFunctionTarget) -> Result<Self> {
+        for arg in &target.args {
+            arg.check()?;
+        }
+        Ok(Self {
+            txn_factory,
+            target,
+        })
+    }
+}
+
+#[async_trait]
+impl TransactionGeneratorCreator for EntryFunctionGeneratorCreator {
+    async fn create_transaction_generator(&mut self) -> Box<dyn TransactionGenerator> {
+        Box::new(EntryFunctionGenerator::new(
+            StdRng::from_entropy(),
+            self.txn_factory.clone(),
+            self.target.clone(),
+        ))
+    }
+
+    async fn create_seeded_transaction_generator(
+        &mut self,
+        seed: [u8; 32],
+    ) -> Box<dyn TransactionGenerator> {
+        Box::new(EntryFunctionGenerator::new(
+            StdRng::from_seed(seed),
+            self.txn_factory.clone(),
+            self.target.clone(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ArgSampler, EntryFunctionGeneratorCreator, EntryFunctionTarget};
+    use crate::transaction_generator::{
+        transaction_mix_generator::TxnMixGeneratorCreator, TransactionGenerator,
+        TransactionGeneratorCreator,
+    };
+    use aptos_sdk::{
+        bcs,
+        move_types::{
+            account_address::AccountAddress,
+            identifier::Identifier,
+            language_storage::{ModuleId, TypeTag},
+        },
+        transaction_builder::TransactionFactory,
+        types::{chain_id::ChainId, transaction::TransactionPayload, LocalAccount},
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn target(args: Vec<ArgSampler>) -> EntryFunctionTarget {
+        EntryFunctionTarget {
+            module: ModuleId::new(
+                AccountAddress::from_hex_literal("0xcafe").unwrap(),
+                Identifier::new("market").unwrap(),
+            ),
+            function: Identifier::new("place_order").unwrap(),
+            type_args: vec![TypeTag::U64],
+            args,
+        }
+    }
+
+    #[tokio::test]
+    async fn calls_target_with_sampled_args() {
+        let target = target(vec![
+            ArgSampler::U64Range(10..20),
+            ArgSampler::OneOf(vec![bcs::to_bytes(&true).unwrap()]),
+            ArgSampler::Sender,
+        ]);
+        let creator = EntryFunctionGeneratorCreator::new(
+            TransactionFactory::new(ChainId::test()),
+            target.clone(),
+        )
+        .unwrap();
+
+        // Generated through a mix, as it's used in load tests
+        let mut mix = TxnMixGeneratorCreator::new(vec![(
+            Box::new(creator) as Box<dyn TransactionGeneratorCreator>,
+            1,
+        )]);
+        let mut generator = mix.create_seeded_transaction_generator([0; 32]).await;
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut accounts: Vec<_> = (0..2).map(|_| LocalAccount::generate(&mut rng)).collect();
+        let txns = generator.generate_transactions(accounts.iter_mut().collect(), 3);
+        assert_eq!(6, txns.len());
+
+        for txn in txns {
+            let entry_function = match txn.payload() {
+                TransactionPayload::EntryFunction(entry_function) => entry_function,
+                payload => panic!("Unexpected payload {:?}", payload),
+            };
+            assert_eq!(&target.module, entry_function.module());
+            assert_eq!("place_order", entry_function.function().as_str());
+            assert_eq!(&[TypeTag::U64], entry_function.ty_args());
+
+            let args = entry_function.args();
+            let amount: u64 = bcs::from_bytes(&args[0]).unwrap();
+            assert!((10..20).contains(&amount), "{}", amount);
+            assert!(bcs::from_bytes::<bool>(&args[1]).unwrap());
+            assert_eq!(
+                txn.sender(),
+                bcs::from_bytes::<AccountAddress>(&args[2]).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn unsampleable_args_are_rejected() {
+        for arg in [ArgSampler::U64Range(5..5), ArgSampler::OneOf(vec![])] {
+            assert!(EntryFunctionGeneratorCreator::new(
+                TransactionFactory::new(ChainId::test()),
+                target(vec![arg])
+            )
+            .is_err());
+        }
+    }
+}
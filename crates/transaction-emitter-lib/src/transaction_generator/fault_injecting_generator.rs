@@ -0,0 +1,452 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Corrupts transactions from another generator, so they are rejected by the target
+//!
+//! This is for negative testing only, to exercise the rejection paths of the target node.
+//! Corrupted transactions never commit, so the sender's later transactions in the same
+//! batch will be stuck behind them until the sequence numbers are resynced.
+
+use crate::transaction_generator::{TransactionGenerator, TransactionGeneratorCreator};
+use aptos_crypto::SigningKey;
+use aptos_sdk::types::{
+    transaction::{RawTransaction, SignedTransaction},
+    LocalAccount,
+};
+use async_trait::async_trait;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// The ways a transaction can be corrupted
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionCorruption {
+    /// Signature from a different key than the sender's public key
+    BadSignature,
+    /// Sequence number of a transaction that has already been committed
+    StaleSequenceNumber,
+    /// Max gas amount over the maximum allowed
+    OverMaxGas,
+}
+
+pub struct FaultInjectingGenerator {
+    rng: StdRng,
+    generator: Box<dyn TransactionGenerator>,
+    corruptions: Vec<(TransactionCorruption, usize)>,
+    total_weight: usize,
+    corruption_ratio: usize,
+}
+
+impl FaultInjectingGenerator {
+    /// Corrupts `corruption_ratio` percent of the transactions, with the corruption picked
+    /// by weight
+    pub fn new(
+        rng: StdRng,
+        generator: Box<dyn TransactionGenerator>,
+        corruptions: Vec<(TransactionCorruption, usize)>,
+        corruption_ratio: usize,
+    ) -> Self {
+        let total_weight = corruptions.iter().map(|(_, weight)| weight).sum();
+        assert!(total_weight > 0, "Must have at least one corruption");
+        assert!(
+            corruption_ratio <= 100,
+            "Corruption ratio is a percentage, got {}",
+            corruption_ratio
+        );
+        Self {
+            rng,
+            generator,
+            corruptions,
+            total_weight,
+            corruption_ratio,
+        }
+    }
+
+    fn pick_corruption(&mut self) -> TransactionCorruption {
+        let mut picked = self.rng.gen_range(0, self.total_weight);
+        for (corruption, weight) in &self.corruptions {
+            if picked < *weight {
+                return *corruption;
+            }
+            picked -= *weight;
+        }
+        panic!(
+            "Picked {} out of {}, couldn't find correct corruption",
+            picked, self.total_weight
+        );
+    }
+}
+
+/// Applies the corruption to a transaction sent by `sender`
+///
+/// `batch_start_sequence_number` is the sender's sequence number before the batch was
+/// generated.  A stale sequence number can't be made if the sender has never sent a
+/// transaction, in which case `None` is returned.
+pub fn corrupt_transaction(
+    rng: &mut StdRng,
+    sender: &LocalAccount,
+    batch_start_sequence_number: u64,
+    txn: &SignedTransaction,
+    corruption: TransactionCorruption,
+) -> Option<SignedTransaction> {
+    let raw_txn = |sequence_number: u64, max_gas_amount: u64| {
+        RawTransaction::new(
+            txn.sender(),
+            sequence_number,
+            txn.payload().clone(),
+            max_gas_amount,
+            txn.gas_unit_price(),
+            txn.expiration_timestamp_secs(),
+            txn.chain_id(),
+        )
+    };
+
+    match corruption {
+        TransactionCorruption::BadSignature => {
+            let raw_txn = raw_txn(txn.sequence_number(), txn.max_gas_amount());
+            let signature = LocalAccount::generate(rng)
+                .private_key()
+                .sign(&raw_txn)
+                .expect("Signing a raw transaction should succeed");
+            Some(SignedTransaction::new(
+                raw_txn,
+                sender.public_key().clone(),
+                signature,
+            ))
+        },
+        TransactionCorruption::StaleSequenceNumber => batch_start_sequence_number
+            .checked_sub(1)
+            .map(|sequence_number| {
+                sender.sign_transaction(raw_txn(sequence_number, txn.max_gas_amount()))
+            }),
+        TransactionCorruption::OverMaxGas => {
+            Some(sender.sign_transaction(raw_txn(txn.sequence_number(), u64::MAX)))
+        },
+    }
+}
+
+impl FaultInjectingGenerator {
+    /// Corrupts `corruption_ratio` percent of a batch generated for `accounts`, whose sequence
+    /// numbers were `start_sequence_numbers` before the batch
+    fn corrupt_batch(
+        &mut self,
+        accounts: &[&mut LocalAccount],
+        start_sequence_numbers: &[u64],
+        txns: &mut [SignedTransaction],
+    ) {
+        for txn in txns.iter_mut() {
+            if self.rng.gen_range(0, 100) >= self.corruption_ratio {
+                continue;
+            }
+            // Only transactions from the given accounts can be re-signed
+            let sender = accounts
+                .iter()
+                .zip(start_sequence_numbers.iter())
+                .find(|(account, _)| account.address() == txn.sender());
+            if let Some((sender, start_sequence_number)) = sender {
+                let corruption = self.pick_corruption();
+                if let Some(corrupted) = corrupt_transaction(
+                    &mut self.rng,
+                    sender,
+                    *start_sequence_number,
+                    txn,
+                    corruption,
+                ) {
+                    *txn = corrupted;
+                }
+            }
+        }
+    }
+}
+
+fn sequence_numbers(accounts: &[&mut LocalAccount]) -> Vec<u64> {
+    accounts
+        .iter()
+        .map(|account| account.sequence_number())
+        .collect()
+}
+
+#[async_trait]
+impl TransactionGenerator for FaultInjectingGenerator {
+    fn generate_transactions(
+        &mut self,
+        mut accounts: Vec<&mut LocalAccount>,
+        transactions_per_account: usize,
+    ) -> Vec<SignedTransaction> {
+        let start_sequence_numbers = sequence_numbers(&accounts);
+        let mut txns = self.generator.generate_transactions(
+            accounts.iter_mut().map(|account| &mut **account).collect(),
+            transactions_per_account,
+        );
+        self.corrupt_batch(&accounts, &start_sequence_numbers, &mut txns);
+        txns
+    }
+
+    async fn generate_transactions_async(
+        &mut self,
+        mut accounts: Vec<&mut LocalAccount>,
+        transactions_per_account: usize,
+    ) -> Vec<SignedTransaction> {
+        let start_sequence_numbers = sequence_numbers(&accounts);
+        let mut txns = self
+            .generator
+            .generate_transactions_async(
+                accounts.iter_mut().map(|account| &mut **account).collect(),
+                transactions_per_account,
+            )
+            .await;
+        self.corrupt_batch(&accounts, &start_sequence_numbers, &mut txns);
+        txns
+    }
+
+    fn record_generation(&self, duration: Duration, num_txns: usize) {
+        self.generator.record_generation(duration, num_txns);
+    }
+
+    fn on_accounts_changed(&mut self, accounts: &[LocalAccount]) {
+        self.generator.on_accounts_changed(accounts);
+    }
+}
+
+pub struct FaultInjectingGeneratorCreator {
+    creator: Box<dyn TransactionGeneratorCreator>,
+    corruptions: Vec<(TransactionCorruption, usize)>,
+    corruption_ratio: usize,
+}
+
+impl FaultInjectingGeneratorCreator {
+    pub fn new(
+        creator: Box<dyn TransactionGeneratorCreator>,
+        corruptions: Vec<(TransactionCorruption, usize)>,
+        corruption_ratio: usize,
+    ) -> Self {
+        Self {
+            creator,
+            corruptions,
+            corruption_ratio,
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionGeneratorCreator for FaultInjectingGeneratorCreator {
+    async fn create_transaction_generator(&mut self) -> Box<dyn TransactionGenerator> {
+        Box::new(FaultInjectingGenerator::new(
+            StdRng::from_entropy(),
+            self.creator.create_transaction_generator().await,
+            self.corruptions.clone(),
+            self.corruption_ratio,
+        ))
+    }
+
+    async fn create_seeded_transaction_generator(
+        &mut self,
+        seed: [u8; 32],
+    ) -> Box<dyn TransactionGenerator> {
+        Box::new(FaultInjectingGenerator::new(
+            StdRng::from_seed(seed),
+            self.creator.create_seeded_transaction_generator(seed).await,
+            self.corruptions.clone(),
+            self.corruption_ratio,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        corrupt_transaction, FaultInjectingGenerator, FaultInjectingGeneratorCreator,
+        TransactionCorruption,
+    };
+    use crate::transaction_generator::{TransactionGenerator, TransactionGeneratorCreator};
+    use aptos_infallible::Mutex;
+    use aptos_sdk::{
+        move_types::account_address::AccountAddress,
+        transaction_builder::TransactionFactory,
+        types::{chain_id::ChainId, transaction::SignedTransaction, LocalAccount},
+    };
+    use async_trait::async_trait;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::{sync::Arc, time::Duration};
+
+    /// Signs a transfer from each account
+    struct TransferGenerator;
+
+    impl TransactionGenerator for TransferGenerator {
+        fn generate_transactions(
+            &mut self,
+            accounts: Vec<&mut LocalAccount>,
+            transactions_per_account: usize,
+        ) -> Vec<SignedTransaction> {
+            let factory = TransactionFactory::new(ChainId::test());
+            let mut txns = vec![];
+            for account in accounts {
+                for _ in 0..transactions_per_account {
+                    txns.push(
+                        account.sign_with_transaction_builder(
+                            factory.transfer(AccountAddress::ONE, 1),
+                        ),
+                    );
+                }
+            }
+            txns
+        }
+    }
+
+    struct TransferGeneratorCreator;
+
+    #[async_trait]
+    impl TransactionGeneratorCreator for TransferGeneratorCreator {
+        async fn create_transaction_generator(&mut self) -> Box<dyn TransactionGenerator> {
+            Box::new(TransferGenerator)
+        }
+    }
+
+    /// Only generates through the async path, and records the generations it's told about
+    struct AsyncGenerator(Arc<Mutex<Vec<usize>>>);
+
+    #[async_trait]
+    impl TransactionGenerator for AsyncGenerator {
+        fn generate_transactions(
+            &mut self,
+            _accounts: Vec<&mut LocalAccount>,
+            _transactions_per_account: usize,
+        ) -> Vec<SignedTransaction> {
+            panic!("Should generate through the async path");
+        }
+
+        async fn generate_transactions_async(
+            &mut self,
+            accounts: Vec<&mut LocalAccount>,
+            transactions_per_account: usize,
+        ) -> Vec<SignedTransaction> {
+            TransferGenerator.generate_transactions(accounts, transactions_per_account)
+        }
+
+        fn record_generation(&self, _duration: Duration, num_txns: usize) {
+            self.0.lock().push(num_txns);
+        }
+    }
+
+    #[tokio::test]
+    async fn async_generation_is_forwarded() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut accounts: Vec<_> = (0..2).map(|_| LocalAccount::generate(&mut rng)).collect();
+        let recorded = Arc::new(Mutex::new(vec![]));
+        let mut generator = FaultInjectingGenerator::new(
+            StdRng::seed_from_u64(0),
+            Box::new(AsyncGenerator(recorded.clone())),
+            vec![(TransactionCorruption::OverMaxGas, 1)],
+            100,
+        );
+
+        let txns = generator
+            .generate_transactions_async(accounts.iter_mut().collect(), 3)
+            .await;
+        assert_eq!(6, txns.len());
+        assert!(txns.iter().all(|txn| txn.max_gas_amount() == u64::MAX));
+        generator.record_generation(Duration::from_millis(1), txns.len());
+        assert_eq!(vec![6], *recorded.lock());
+    }
+
+    #[test]
+    fn corruptions_are_applied() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut account = LocalAccount::generate(&mut rng);
+        let txn = TransferGenerator.generate_transactions(vec![&mut account], 1)[0].clone();
+
+        let corrupted = corrupt_transaction(
+            &mut rng,
+            &account,
+            0,
+            &txn,
+            TransactionCorruption::BadSignature,
+        )
+        .unwrap();
+        assert_eq!(txn.sequence_number(), corrupted.sequence_number());
+        assert!(corrupted.check_signature().is_err());
+
+        // The sender must have sent a transaction for its sequence number to be stale
+        assert_eq!(
+            None,
+            corrupt_transaction(
+                &mut rng,
+                &account,
+                0,
+                &txn,
+                TransactionCorruption::StaleSequenceNumber
+            )
+        );
+        let corrupted = corrupt_transaction(
+            &mut rng,
+            &account,
+            5,
+            &txn,
+            TransactionCorruption::StaleSequenceNumber,
+        )
+        .unwrap();
+        assert_eq!(4, corrupted.sequence_number());
+        assert!(corrupted.check_signature().is_ok());
+
+        let corrupted = corrupt_transaction(
+            &mut rng,
+            &account,
+            0,
+            &txn,
+            TransactionCorruption::OverMaxGas,
+        )
+        .unwrap();
+        assert_eq!(u64::MAX, corrupted.max_gas_amount());
+        assert!(corrupted.check_signature().is_ok());
+    }
+
+    #[test]
+    fn corrupts_the_configured_ratio() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut accounts: Vec<_> = (0..10).map(|_| LocalAccount::generate(&mut rng)).collect();
+        let generate = |ratio: usize, accounts: &mut Vec<LocalAccount>| {
+            FaultInjectingGenerator::new(
+                StdRng::seed_from_u64(0),
+                Box::new(TransferGenerator),
+                vec![(TransactionCorruption::OverMaxGas, 1)],
+                ratio,
+            )
+            .generate_transactions(accounts.iter_mut().collect(), 10)
+            .into_iter()
+            .filter(|txn| txn.max_gas_amount() == u64::MAX)
+            .count()
+        };
+
+        assert_eq!(0, generate(0, &mut accounts));
+        assert_eq!(100, generate(100, &mut accounts));
+        let corrupted = generate(30, &mut accounts);
+        assert!(
+            (15..45).contains(&corrupted),
+            "Expected about 30 corrupted transactions, got {}",
+            corrupted
+        );
+    }
+
+    #[tokio::test]
+    async fn seeded_corruptions_are_reproducible() {
+        let generate = |seed: u8| async move {
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut accounts: Vec<_> = (0..10).map(|_| LocalAccount::generate(&mut rng)).collect();
+            FaultInjectingGeneratorCreator::new(
+                Box::new(TransferGeneratorCreator),
+                vec![(TransactionCorruption::OverMaxGas, 1)],
+                50,
+            )
+            .create_seeded_transaction_generator([seed; 32])
+            .await
+            .generate_transactions(accounts.iter_mut().collect(), 10)
+            .iter()
+            .map(|txn| txn.max_gas_amount() == u64::MAX)
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(generate(1).await, generate(1).await);
+        assert_ne!(generate(1).await, generate(2).await);
+    }
+}
@@ -0,0 +1,808 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Builds a [`TxnMixGeneratorCreator`] from a mix described in a file
+//!
+//! The mix is a list of generators by name, each with a weight and its own parameters, e.g.
+//!
+//! ```yaml
+//! generators:
+//!   - name: p2p
+//!     weight: 3
+//!   - name: account_generation
+//!     weight: 1
+//!     params:
+//!       max_working_set: 1000
+//! ```
+//!
+//! Names are resolved with a [`GeneratorRegistry`], which maps each name to a factory for its
+//! creator.  YAML is a superset of JSON, so the mix can be written in either.
+//!
+//! [`GeneratorRegistry::with_builtins`] registers the built-in generators:
+//!
+//! * `p2p`, with optional params `amount` and `invalid_transaction_ratio`
+//! * `account_generation`, with optional params `max_working_set` and
+//!   `add_created_accounts_to_pool`
+//! * `publish_package`, without params
+//! * `nft_mint_and_transfer`, without params.  Its collection is set up on chain before the
+//!   mix is built, see [`BuiltinContext::nft_mint_and_transfer`].
+//! * `replay`, with param `path` of the recorded transactions
+//! * `entry_function`, with params `address`, `module`, `function`, and optional `type_args`
+//!   and `args`.  Each argument is sampled from `{u64_range: {start, end}}`,
+//!   `{one_of: [<hex BCS value>, ...]}`, or is the `sender`.
+//! * `zipf`, sending the transactions of the generator under `generator` from a few hot
+//!   accounts, with param `exponent` and optional `max_transactions_per_account`
+//! * `fault_injecting`, corrupting the transactions of the generator under `generator`, with
+//!   params `corruptions`, the weight of each corruption, and `corruption_ratio`
+//!
+//! Generators wrapped by `zipf` and `fault_injecting` are given by `name` and `params`, e.g.
+//!
+//! ```yaml
+//! generators:
+//!   - name: zipf
+//!     weight: 1
+//!     params:
+//!       exponent: 1.0
+//!       generator:
+//!         name: p2p
+//! ```
+//!
+//! Params that are not given default to the values of the emit job.
+
+use crate::transaction_generator::{
+    account_generator::AccountGeneratorCreator,
+    entry_function_generator::{ArgSampler, EntryFunctionGeneratorCreator, EntryFunctionTarget},
+    fault_injecting_generator::{FaultInjectingGeneratorCreator, TransactionCorruption},
+    p2p_transaction_generator::P2PTransactionGeneratorCreator,
+    publish_modules::PublishPackageCreator,
+    replay_generator::ReplayGeneratorCreator,
+    transaction_mix_generator::TxnMixGeneratorCreator,
+    zipf_account_selector::ZipfAccountSelectorCreator,
+    TransactionGenerator, TransactionGeneratorCreator,
+};
+use anyhow::{bail, ensure, format_err, Context, Result};
+use aptos_infallible::RwLock;
+use aptos_rest_client::aptos_api_types::HexEncodedBytes;
+use aptos_sdk::{
+    move_types::{
+        account_address::AccountAddress,
+        identifier::Identifier,
+        language_storage::{ModuleId, TypeTag},
+        parser::parse_type_tag,
+    },
+    transaction_builder::TransactionFactory,
+};
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::Mutex;
+
+/// A mix of generators, see the module documentation for the format
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct MixConfig {
+    pub generators: Vec<GeneratorConfig>,
+}
+
+/// A generator in a [`MixConfig`]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct GeneratorConfig {
+    /// Name of the generator in the [`GeneratorRegistry`]
+    pub name: String,
+    pub weight: usize,
+    /// Parameters passed to the generator's factory, interpreted by the factory
+    #[serde(default)]
+    pub params: serde_yaml::Value,
+}
+
+impl MixConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read mix file {}", path.display()))?;
+        Self::parse(&contents)
+            .with_context(|| format!("Failed to parse mix file {}", path.display()))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        Ok(serde_yaml::from_str(contents)?)
+    }
+
+    /// Names of the generators in the mix, including those wrapped by other generators
+    pub fn generator_names(&self) -> BTreeSet<String> {
+        let mut names = BTreeSet::new();
+        for generator in &self.generators {
+            collect_generator_names(&generator.name, &generator.params, &mut names);
+        }
+        names
+    }
+}
+
+fn collect_generator_names(name: &str, params: &serde_yaml::Value, names: &mut BTreeSet<String>) {
+    names.insert(name.to_string());
+    if let Some(Ok(inner)) = params
+        .get("generator")
+        .map(|inner| serde_yaml::from_value::<InnerGeneratorConfig>(inner.clone()))
+    {
+        collect_generator_names(&inner.name, &inner.params, names);
+    }
+}
+
+/// A generator wrapped by another generator
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InnerGeneratorConfig {
+    name: String,
+    #[serde(default)]
+    params: serde_yaml::Value,
+}
+
+/// Creates a generator's creator from its parameters, building any generators it wraps with
+/// the registry
+pub type CreatorFactory = Box<
+    dyn Fn(&GeneratorRegistry, &serde_yaml::Value) -> Result<Box<dyn TransactionGeneratorCreator>>
+        + Send
+        + Sync,
+>;
+
+/// A creator which is set up once, and shared by every generator of the mix using it
+#[derive(Clone)]
+pub struct SharedCreator(Arc<Mutex<Box<dyn TransactionGeneratorCreator>>>);
+
+impl SharedCreator {
+    pub fn new(creator: Box<dyn TransactionGeneratorCreator>) -> Self {
+        Self(Arc::new(Mutex::new(creator)))
+    }
+}
+
+#[async_trait]
+impl TransactionGeneratorCreator for SharedCreator {
+    async fn create_transaction_generator(&mut self) -> Box<dyn TransactionGenerator> {
+        self.0.lock().await.create_transaction_generator().await
+    }
+
+    async fn create_seeded_transaction_generator(
+        &mut self,
+        seed: [u8; 32],
+    ) -> Box<dyn TransactionGenerator> {
+        self.0
+            .lock()
+            .await
+            .create_seeded_transaction_generator(seed)
+            .await
+    }
+}
+
+/// State of the emit job shared by the built-in generators
+#[derive(Clone)]
+pub struct BuiltinContext {
+    pub txn_factory: TransactionFactory,
+    pub all_addresses: Arc<RwLock<Vec<AccountAddress>>>,
+    pub send_amount: u64,
+    pub invalid_transaction_ratio: usize,
+    pub add_created_accounts_to_pool: bool,
+    pub max_account_working_set: usize,
+    pub gas_price: u64,
+    /// The NFT collection, which has to be set up on chain before the mix is built, so only if
+    /// the mix uses `nft_mint_and_transfer`
+    pub nft_mint_and_transfer: Option<SharedCreator>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct P2PParams {
+    amount: Option<u64>,
+    invalid_transaction_ratio: Option<usize>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccountGenerationParams {
+    max_working_set: Option<usize>,
+    add_created_accounts_to_pool: Option<bool>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoParams {}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReplayParams {
+    path: PathBuf,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EntryFunctionParams {
+    address: AccountAddress,
+    module: Identifier,
+    function: Identifier,
+    #[serde(default)]
+    type_args: Vec<String>,
+    #[serde(default)]
+    args: Vec<ArgParams>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+enum ArgParams {
+    U64Range { start: u64, end: u64 },
+    OneOf(Vec<HexEncodedBytes>),
+    Sender,
+}
+
+impl From<ArgParams> for ArgSampler {
+    fn from(params: ArgParams) -> Self {
+        match params {
+            ArgParams::U64Range { start, end } => ArgSampler::U64Range(start..end),
+            ArgParams::OneOf(values) => {
+                ArgSampler::OneOf(values.into_iter().map(|value| value.0).collect())
+            },
+            ArgParams::Sender => ArgSampler::Sender,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ZipfParams {
+    generator: InnerGeneratorConfig,
+    exponent: f64,
+    max_transactions_per_account: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FaultInjectingParams {
+    generator: InnerGeneratorConfig,
+    corruptions: BTreeMap<TransactionCorruption, usize>,
+    corruption_ratio: usize,
+}
+
+/// Parses a generator's params, treating missing params as all defaults
+fn parse_params<T: DeserializeOwned + Default>(params: &serde_yaml::Value) -> Result<T> {
+    if params.is_null() {
+        Ok(T::default())
+    } else {
+        parse_required_params(params)
+    }
+}
+
+/// Parses a generator's params, which has some without defaults
+fn parse_required_params<T: DeserializeOwned>(params: &serde_yaml::Value) -> Result<T> {
+    Ok(serde_yaml::from_value(params.clone())?)
+}
+
+/// Maps generator names to factories for their creators
+#[derive(Default)]
+pub struct GeneratorRegistry {
+    factories: BTreeMap<String, CreatorFactory>,
+}
+
+impl GeneratorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry with the built-in generators, see the module documentation for their params
+    pub fn with_builtins(context: BuiltinContext) -> Self {
+        let mut registry = Self::new();
+        let p2p_context = context.clone();
+        let account_generation_context = context.clone();
+        let publish_package_context = context.clone();
+        let entry_function_context = context.clone();
+        registry
+            .register(
+                "p2p",
+                Box::new(
+                    move |_: &GeneratorRegistry,
+                          params: &serde_yaml::Value|
+                          -> Result<Box<dyn TransactionGeneratorCreator>> {
+                        let params: P2PParams = parse_params(params)?;
+                        Ok(Box::new(P2PTransactionGeneratorCreator::new(
+                            p2p_context.txn_factory.clone(),
+                            params.amount.unwrap_or(p2p_context.send_amount),
+                            p2p_context.all_addresses.clone(),
+                            params
+                                .invalid_transaction_ratio
+                                .unwrap_or(p2p_context.invalid_transaction_ratio),
+                        )))
+                    },
+                ),
+            )
+            .register(
+                "account_generation",
+                Box::new(
+                    move |_: &GeneratorRegistry,
+                          params: &serde_yaml::Value|
+                          -> Result<Box<dyn TransactionGeneratorCreator>> {
+                        let params: AccountGenerationParams = parse_params(params)?;
+                        let context = &account_generation_context;
+                        Ok(Box::new(AccountGeneratorCreator::new(
+                            context.txn_factory.clone(),
+                            context.all_addresses.clone(),
+                            params
+                                .add_created_accounts_to_pool
+                                .unwrap_or(context.add_created_accounts_to_pool),
+                            params
+                                .max_working_set
+                                .unwrap_or(context.max_account_working_set),
+                            context.gas_price,
+                        )))
+                    },
+                ),
+            )
+            .register(
+                "publish_package",
+                Box::new(
+                    move |_: &GeneratorRegistry,
+                          params: &serde_yaml::Value|
+                          -> Result<Box<dyn TransactionGeneratorCreator>> {
+                        let _: NoParams = parse_params(params)?;
+                        Ok(Box::new(PublishPackageCreator::new(
+                            publish_package_context.txn_factory.clone(),
+                        )))
+                    },
+                ),
+            )
+            .register(
+                "nft_mint_and_transfer",
+                Box::new(
+                    move |_: &GeneratorRegistry,
+                          params: &serde_yaml::Value|
+                          -> Result<Box<dyn TransactionGeneratorCreator>> {
+                        let _: NoParams = parse_params(params)?;
+                        let creator = context.nft_mint_and_transfer.clone().ok_or_else(|| {
+                            format_err!(
+                                "The NFT collection wasn't set up for nft_mint_and_transfer"
+                            )
+                        })?;
+                        Ok(Box::new(creator))
+                    },
+                ),
+            )
+            .register(
+                "replay",
+                Box::new(
+                    |_: &GeneratorRegistry,
+                     params: &serde_yaml::Value|
+                     -> Result<Box<dyn TransactionGeneratorCreator>> {
+                        let params: ReplayParams = parse_required_params(params)?;
+                        Ok(Box::new(ReplayGeneratorCreator::from_file(&params.path)?))
+                    },
+                ),
+            )
+            .register(
+                "entry_function",
+                Box::new(
+                    move |_: &GeneratorRegistry,
+                          params: &serde_yaml::Value|
+                          -> Result<Box<dyn TransactionGeneratorCreator>> {
+                        let params: EntryFunctionParams = parse_required_params(params)?;
+                        let type_args = params
+                            .type_args
+                            .iter()
+                            .map(|type_arg| parse_type_tag(type_arg))
+                            .collect::<Result<Vec<TypeTag>>>()?;
+                        Ok(Box::new(EntryFunctionGeneratorCreator::new(
+                            entry_function_context.txn_factory.clone(),
+                            EntryFunctionTarget {
+                                module: ModuleId::new(params.address, params.module),
+                                function: params.function,
+                                type_args,
+                                args: params.args.into_iter().map(ArgSampler::from).collect(),
+                            },
+                        )?))
+                    },
+                ),
+            )
+            .register(
+                "zipf",
+                Box::new(
+                    |registry: &GeneratorRegistry,
+                     params: &serde_yaml::Value|
+                     -> Result<Box<dyn TransactionGeneratorCreator>> {
+                        let params: ZipfParams = parse_required_params(params)?;
+                        ensure!(
+                            params.exponent.is_finite() && params.exponent >= 0.0,
+                            "Zipf exponent must be non-negative, got {}",
+                            params.exponent
+                        );
+                        Ok(Box::new(ZipfAccountSelectorCreator::new(
+                            registry.build_generator(
+                                &params.generator.name,
+                                &params.generator.params,
+                            )?,
+                            params.exponent,
+                            params.max_transactions_per_account.unwrap_or(usize::MAX),
+                        )))
+                    },
+                ),
+            )
+            .register(
+                "fault_injecting",
+                Box::new(
+                    |registry: &GeneratorRegistry,
+                     params: &serde_yaml::Value|
+                     -> Result<Box<dyn TransactionGeneratorCreator>> {
+                        let params: FaultInjectingParams = parse_required_params(params)?;
+                        ensure!(
+                            params.corruptions.values().sum::<usize>() > 0,
+                            "Must have at least one corruption"
+                        );
+                        ensure!(
+                            params.corruption_ratio <= 100,
+                            "Corruption ratio is a percentage, got {}",
+                            params.corruption_ratio
+                        );
+                        Ok(Box::new(FaultInjectingGeneratorCreator::new(
+                            registry.build_generator(
+                                &params.generator.name,
+                                &params.generator.params,
+                            )?,
+                            params.corruptions.into_iter().collect(),
+                            params.corruption_ratio,
+                        )))
+                    },
+                ),
+            );
+        registry
+    }
+
+    /// Registers `factory` under `name`, replacing any factory already registered under it
+    pub fn register(&mut self, name: impl Into<String>, factory: CreatorFactory) -> &mut Self {
+        self.factories.insert(name.into(), factory);
+        self
+    }
+
+    /// Names of the registered generators, in sorted order
+    pub fn names(&self) -> Vec<&str> {
+        self.factories.keys().map(String::as_str).collect()
+    }
+
+    /// Builds the mix, failing on unknown generator names or invalid parameters
+    pub fn build(&self, config: &MixConfig) -> Result<TxnMixGeneratorCreator> {
+        if config.generators.is_empty() {
+            bail!("Mix must have at least one generator");
+        }
+        if config
+            .generators
+            .iter()
+            .all(|generator| generator.weight == 0)
+        {
+            bail!("Mix must have at least one generator with a non-zero weight");
+        }
+
+        let mut txn_mix_creators = Vec::with_capacity(config.generators.len());
+        for generator in &config.generators {
+            let creator = self.build_generator(&generator.name, &generator.params)?;
+            txn_mix_creators.push((creator, generator.weight));
+        }
+        Ok(TxnMixGeneratorCreator::new(txn_mix_creators))
+    }
+
+    /// Builds a single generator, failing on an unknown name or invalid parameters
+    pub fn build_generator(
+        &self,
+        name: &str,
+        params: &serde_yaml::Value,
+    ) -> Result<Box<dyn TransactionGeneratorCreator>> {
+        let factory = self.factories.get(name).ok_or_else(|| {
+            format_err!(
+                "Unknown generator {}, expected one of {:?}",
+                name,
+                self.names()
+            )
+        })?;
+        factory(self, params).with_context(|| format!("Invalid parameters for generator {}", name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BuiltinContext, GeneratorRegistry, MixConfig, SharedCreator};
+    use crate::{
+        transaction_generator::{TransactionGenerator, TransactionGeneratorCreator},
+        TransactionType,
+    };
+    use anyhow::Result;
+    use aptos_infallible::{Mutex, RwLock};
+    use aptos_sdk::{
+        bcs,
+        transaction_builder::TransactionFactory,
+        types::{chain_id::ChainId, transaction::SignedTransaction, LocalAccount},
+    };
+    use aptos_temppath::TempPath;
+    use async_trait::async_trait;
+    use clap::ArgEnum;
+    use serde::Deserialize;
+    use std::{collections::BTreeSet, sync::Arc};
+
+    struct EmptyGenerator;
+
+    impl TransactionGenerator for EmptyGenerator {
+        fn generate_transactions(
+            &mut self,
+            _accounts: Vec<&mut LocalAccount>,
+            _transactions_per_account: usize,
+        ) -> Vec<SignedTransaction> {
+            vec![]
+        }
+    }
+
+    struct EmptyGeneratorCreator;
+
+    #[async_trait]
+    impl TransactionGeneratorCreator for EmptyGeneratorCreator {
+        async fn create_transaction_generator(&mut self) -> Box<dyn TransactionGenerator> {
+            Box::new(EmptyGenerator)
+        }
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct AmountParams {
+        amount: u64,
+    }
+
+    /// A registry of empty generators, recording the parameters given to `transfer`
+    fn registry(transfer_params: Arc<Mutex<Vec<AmountParams>>>) -> GeneratorRegistry {
+        let mut registry = GeneratorRegistry::new();
+        registry
+            .register(
+                "noop",
+                Box::new(
+                    |_: &GeneratorRegistry,
+                     _: &serde_yaml::Value|
+                     -> Result<Box<dyn TransactionGeneratorCreator>> {
+                        Ok(Box::new(EmptyGeneratorCreator))
+                    },
+                ),
+            )
+            .register(
+                "transfer",
+                Box::new(
+                    move |_: &GeneratorRegistry,
+                          params: &serde_yaml::Value|
+                          -> Result<Box<dyn TransactionGeneratorCreator>> {
+                        transfer_params
+                            .lock()
+                            .push(serde_yaml::from_value(params.clone())?);
+                        Ok(Box::new(EmptyGeneratorCreator))
+                    },
+                ),
+            );
+        registry
+    }
+
+    #[tokio::test]
+    async fn build_mix_from_config() {
+        let transfer_params = Arc::new(Mutex::new(vec![]));
+        let registry = registry(transfer_params.clone());
+        let config = MixConfig::parse(
+            r#"
+generators:
+  - name: transfer
+    weight: 3
+    params:
+      amount: 10
+  - name: noop
+    weight: 1
+"#,
+        )
+        .unwrap();
+
+        let mut creator = registry.build(&config).unwrap();
+        assert_eq!(vec![3, 1], creator.weights());
+        assert_eq!(vec![AmountParams { amount: 10 }], *transfer_params.lock());
+        assert!(creator
+            .create_transaction_generator()
+            .await
+            .generate_transactions(vec![], 1)
+            .is_empty());
+
+        // JSON works too
+        let json = MixConfig::parse(
+            r#"{"generators": [{"name": "transfer", "weight": 3, "params": {"amount": 10}},
+                {"name": "noop", "weight": 1}]}"#,
+        )
+        .unwrap();
+        assert_eq!(config, json);
+    }
+
+    #[test]
+    fn invalid_mix_is_rejected() {
+        let registry = registry(Arc::new(Mutex::new(vec![])));
+        let build = |contents: &str| {
+            registry
+                .build(&MixConfig::parse(contents).unwrap())
+                .err()
+                .map(|err| format!("{:#}", err))
+        };
+
+        let err = build("generators: [{name: mint, weight: 1}]").unwrap();
+        assert!(err.contains("Unknown generator mint"), "{}", err);
+        assert!(err.contains(r#"["noop", "transfer"]"#), "{}", err);
+
+        let err = build("generators: [{name: transfer, weight: 1}]").unwrap();
+        assert!(
+            err.contains("Invalid parameters for generator transfer"),
+            "{}",
+            err
+        );
+
+        assert!(build("generators: []").is_some());
+        assert!(build("generators: [{name: noop, weight: 0}]").is_some());
+        assert!(MixConfig::parse("generators: [{name: noop, weigth: 1}]").is_err());
+    }
+
+    fn builtins() -> GeneratorRegistry {
+        GeneratorRegistry::with_builtins(BuiltinContext {
+            txn_factory: TransactionFactory::new(ChainId::test()),
+            all_addresses: Arc::new(RwLock::new(vec![])),
+            send_amount: 1,
+            invalid_transaction_ratio: 0,
+            add_created_accounts_to_pool: true,
+            max_account_working_set: 1_000_000,
+            gas_price: 100,
+            nft_mint_and_transfer: Some(SharedCreator::new(Box::new(EmptyGeneratorCreator))),
+        })
+    }
+
+    #[test]
+    fn builtins_resolve_documented_mix() {
+        let registry = builtins();
+        assert_eq!(
+            vec![
+                "account_generation",
+                "entry_function",
+                "fault_injecting",
+                "nft_mint_and_transfer",
+                "p2p",
+                "publish_package",
+                "replay",
+                "zipf"
+            ],
+            registry.names()
+        );
+
+        // The example from the module documentation
+        let config = MixConfig::parse(
+            r#"
+generators:
+  - name: p2p
+    weight: 3
+  - name: account_generation
+    weight: 1
+    params:
+      max_working_set: 1000
+"#,
+        )
+        .unwrap();
+        assert_eq!(vec![3, 1], registry.build(&config).unwrap().weights());
+
+        let config = MixConfig::parse(
+            "generators: [{name: p2p, weight: 1, params: {amount: 5, invalid_transaction_ratio: 10}}, {name: publish_package, weight: 1}]",
+        )
+        .unwrap();
+        assert!(registry.build(&config).is_ok());
+    }
+
+    #[test]
+    fn every_transaction_type_resolves() {
+        let registry = builtins();
+        for transaction_type in TransactionType::value_variants() {
+            let config = MixConfig::parse(&format!(
+                "generators: [{{name: {}, weight: 1}}]",
+                transaction_type.generator_name()
+            ))
+            .unwrap();
+            assert!(registry.build(&config).is_ok(), "{:?}", transaction_type);
+        }
+
+        // The NFT collection has to be set up before the mix is built
+        let context = BuiltinContext {
+            txn_factory: TransactionFactory::new(ChainId::test()),
+            all_addresses: Arc::new(RwLock::new(vec![])),
+            send_amount: 1,
+            invalid_transaction_ratio: 0,
+            add_created_accounts_to_pool: true,
+            max_account_working_set: 1_000_000,
+            gas_price: 100,
+            nft_mint_and_transfer: None,
+        };
+        let config =
+            MixConfig::parse("generators: [{name: nft_mint_and_transfer, weight: 1}]").unwrap();
+        assert!(GeneratorRegistry::with_builtins(context)
+            .build(&config)
+            .is_err());
+    }
+
+    #[test]
+    fn builtins_resolve_generators_of_the_series() {
+        let registry = builtins();
+        let recorded = TempPath::new();
+        recorded.create_as_file().unwrap();
+        std::fs::write(
+            recorded.path(),
+            bcs::to_bytes(&Vec::<SignedTransaction>::new()).unwrap(),
+        )
+        .unwrap();
+
+        let config = MixConfig::parse(&format!(
+            r#"
+generators:
+  - name: replay
+    weight: 1
+    params:
+      path: {}
+  - name: entry_function
+    weight: 1
+    params:
+      address: "0x1"
+      module: coin
+      function: transfer
+      type_args: ["0x1::aptos_coin::AptosCoin"]
+      args: [sender, {{u64_range: {{start: 1, end: 100}}}}, {{one_of: ["0x01", "0x02"]}}]
+  - name: zipf
+    weight: 1
+    params:
+      exponent: 1.0
+      generator:
+        name: fault_injecting
+        params:
+          corruptions: {{bad_signature: 1, over_max_gas: 2}}
+          corruption_ratio: 10
+          generator:
+            name: p2p
+            params:
+              amount: 5
+"#,
+            recorded.path().display()
+        ))
+        .unwrap();
+        assert_eq!(vec![1, 1, 1], registry.build(&config).unwrap().weights());
+        assert_eq!(
+            ["entry_function", "fault_injecting", "p2p", "replay", "zipf"]
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<BTreeSet<_>>(),
+            config.generator_names()
+        );
+
+        // Wrapped generators are checked like any other
+        for contents in [
+            "generators: [{name: zipf, weight: 1, params: {exponent: 1.0, generator: {name: mint}}}]",
+            "generators: [{name: zipf, weight: 1, params: {exponent: -1.0, generator: {name: p2p}}}]",
+            "generators: [{name: fault_injecting, weight: 1, params: {corruptions: {bad_signature: 1}, corruption_ratio: 101, generator: {name: p2p}}}]",
+            "generators: [{name: entry_function, weight: 1, params: {address: \"0x1\", module: coin, function: transfer, args: [{u64_range: {start: 1, end: 1}}]}}]",
+        ] {
+            assert!(
+                registry.build(&MixConfig::parse(contents).unwrap()).is_err(),
+                "{}",
+                contents
+            );
+        }
+    }
+
+    #[test]
+    fn builtins_reject_unknown_params() {
+        let registry = builtins();
+        for contents in [
+            "generators: [{name: p2p, weight: 1, params: {amout: 5}}]",
+            "generators: [{name: account_generation, weight: 1, params: {max_working_set: lots}}]",
+            "generators: [{name: publish_package, weight: 1, params: {modules: 2}}]",
+        ] {
+            let err = registry
+                .build(&MixConfig::parse(contents).unwrap())
+                .err()
+                .map(|err| format!("{:#}", err))
+                .unwrap();
+            assert!(err.contains("Invalid parameters for generator"), "{}", err);
+        }
+    }
+}
@@ -7,28 +7,64 @@ use aptos_sdk::{
     types::{transaction::SignedTransaction, LocalAccount},
 };
 use async_trait::async_trait;
-use std::sync::atomic::AtomicUsize;
+use std::{sync::atomic::AtomicUsize, time::Duration};
 
 pub mod account_generator;
 pub mod call_custom_modules;
+mod counters;
+pub mod entry_function_generator;
+pub mod fault_injecting_generator;
+pub mod mix_config;
 pub mod nft_mint_and_transfer;
 pub mod p2p_transaction_generator;
 pub mod publish_modules;
 mod publishing;
+pub mod replay_generator;
+pub mod scheduled_mix_generator;
 pub mod transaction_mix_generator;
+pub mod zipf_account_selector;
 pub use publishing::module_simple::EntryPoints;
 
+#[async_trait]
 pub trait TransactionGenerator: Sync + Send {
     fn generate_transactions(
         &mut self,
         accounts: Vec<&mut LocalAccount>,
         transactions_per_account: usize,
     ) -> Vec<SignedTransaction>;
+
+    /// Async variant of `generate_transactions`, for generators that need to wait e.g. for pacing
+    async fn generate_transactions_async(
+        &mut self,
+        accounts: Vec<&mut LocalAccount>,
+        transactions_per_account: usize,
+    ) -> Vec<SignedTransaction> {
+        self.generate_transactions(accounts, transactions_per_account)
+    }
+
+    /// Records the time spent generating a batch and the number of transactions in it
+    ///
+    /// This is a no-op unless the generator is instrumented.
+    fn record_generation(&self, _duration: Duration, _num_txns: usize) {}
+
+    /// Called when the account pool changes, so generators that cache state derived from the
+    /// accounts can refresh it.  This is a no-op by default.
+    fn on_accounts_changed(&mut self, _accounts: &[LocalAccount]) {}
 }
 
 #[async_trait]
 pub trait TransactionGeneratorCreator: Sync + Send {
     async fn create_transaction_generator(&mut self) -> Box<dyn TransactionGenerator>;
+
+    /// Creates a generator with its randomness seeded by `seed`, for reproducible runs
+    ///
+    /// Creators that don't use randomness can ignore the seed, which is the default.
+    async fn create_seeded_transaction_generator(
+        &mut self,
+        _seed: [u8; 32],
+    ) -> Box<dyn TransactionGenerator> {
+        self.create_transaction_generator().await
+    }
 }
 
 #[async_trait]
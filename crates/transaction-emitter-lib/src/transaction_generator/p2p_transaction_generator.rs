@@ -198,4 +198,17 @@ impl TransactionGeneratorCreator for P2PTransactionGeneratorCreator {
             self.invalid_transaction_ratio,
         ))
     }
+
+    async fn create_seeded_transaction_generator(
+        &mut self,
+        seed: [u8; 32],
+    ) -> Box<dyn TransactionGenerator> {
+        Box::new(P2PTransactionGenerator::new(
+            StdRng::from_seed(seed),
+            self.amount,
+            self.txn_factory.clone(),
+            self.all_addresses.clone(),
+            self.invalid_transaction_ratio,
+        ))
+    }
 }
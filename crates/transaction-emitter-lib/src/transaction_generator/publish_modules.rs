@@ -91,4 +91,15 @@ impl TransactionGeneratorCreator for PublishPackageCreator {
             self.txn_factory.clone(),
         ))
     }
+
+    async fn create_seeded_transaction_generator(
+        &mut self,
+        seed: [u8; 32],
+    ) -> Box<dyn TransactionGenerator> {
+        Box::new(PublishPackageGenerator::new(
+            StdRng::from_seed(seed),
+            self.package_handler.clone(),
+            self.txn_factory.clone(),
+        ))
+    }
 }
@@ -0,0 +1,130 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+use crate::transaction_generator::{TransactionGenerator, TransactionGeneratorCreator};
+use anyhow::{Context, Result};
+use aptos_infallible::Mutex;
+use aptos_sdk::{
+    bcs,
+    types::{transaction::SignedTransaction, LocalAccount},
+};
+use async_trait::async_trait;
+use std::{collections::VecDeque, path::Path, sync::Arc};
+
+/// Replays a pre-recorded sequence of transactions, in order
+///
+/// The transactions are already signed, so the accounts given to `generate_transactions` are
+/// ignored, and only the number of transactions requested is used.  Once all transactions have
+/// been replayed, no more are generated.
+pub struct ReplayGenerator {
+    txns: Arc<Mutex<VecDeque<SignedTransaction>>>,
+}
+
+impl ReplayGenerator {
+    pub fn new(txns: Vec<SignedTransaction>) -> Self {
+        Self {
+            txns: Arc::new(Mutex::new(txns.into())),
+        }
+    }
+}
+
+impl TransactionGenerator for ReplayGenerator {
+    fn generate_transactions(
+        &mut self,
+        accounts: Vec<&mut LocalAccount>,
+        transactions_per_account: usize,
+    ) -> Vec<SignedTransaction> {
+        let mut txns = self.txns.lock();
+        let num_txns = txns.len().min(accounts.len() * transactions_per_account);
+        txns.drain(..num_txns).collect()
+    }
+}
+
+/// Creates [`ReplayGenerator`]s which share a single recorded sequence
+///
+/// Each transaction is only replayed once across all of the created generators.
+pub struct ReplayGeneratorCreator {
+    txns: Arc<Mutex<VecDeque<SignedTransaction>>>,
+}
+
+impl ReplayGeneratorCreator {
+    pub fn new(txns: Vec<SignedTransaction>) -> Self {
+        Self {
+            txns: Arc::new(Mutex::new(txns.into())),
+        }
+    }
+
+    /// Loads the recorded sequence from a file of a BCS encoded `Vec<SignedTransaction>`
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read recorded transactions from {:?}", path))?;
+        let txns: Vec<SignedTransaction> = bcs::from_bytes(&bytes)
+            .with_context(|| format!("Failed to decode recorded transactions from {:?}", path))?;
+        Ok(Self::new(txns))
+    }
+}
+
+#[async_trait]
+impl TransactionGeneratorCreator for ReplayGeneratorCreator {
+    async fn create_transaction_generator(&mut self) -> Box<dyn TransactionGenerator> {
+        Box::new(ReplayGenerator {
+            txns: self.txns.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ReplayGeneratorCreator;
+    use crate::transaction_generator::TransactionGeneratorCreator;
+    use aptos_sdk::{
+        bcs,
+        move_types::account_address::AccountAddress,
+        transaction_builder::TransactionFactory,
+        types::{chain_id::ChainId, transaction::SignedTransaction, LocalAccount},
+    };
+    use aptos_temppath::TempPath;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn recorded_txns(num_txns: usize) -> Vec<SignedTransaction> {
+        let mut account = LocalAccount::generate(&mut StdRng::seed_from_u64(0));
+        let factory = TransactionFactory::new(ChainId::test());
+        (0..num_txns)
+            .map(|_| {
+                account.sign_with_transaction_builder(factory.transfer(AccountAddress::ONE, 1))
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn replays_in_order_once() {
+        let txns = recorded_txns(5);
+        let path = TempPath::new();
+        path.create_as_file().unwrap();
+        std::fs::write(path.path(), bcs::to_bytes(&txns).unwrap()).unwrap();
+        let mut creator = ReplayGeneratorCreator::from_file(path.path()).unwrap();
+
+        // The generators share the sequence, and only the number of accounts is used
+        let mut first = creator.create_transaction_generator().await;
+        let mut second = creator.create_transaction_generator().await;
+        let mut accounts: Vec<_> = (0..2)
+            .map(|seed| LocalAccount::generate(&mut StdRng::seed_from_u64(seed)))
+            .collect();
+        assert_eq!(
+            txns[..2],
+            first.generate_transactions(accounts.iter_mut().collect(), 1)[..]
+        );
+        assert_eq!(
+            txns[2..4],
+            second.generate_transactions(accounts.iter_mut().collect(), 1)[..]
+        );
+        assert_eq!(
+            txns[4..],
+            first.generate_transactions(accounts.iter_mut().collect(), 2)[..]
+        );
+
+        // Nothing is generated once exhausted
+        assert!(second
+            .generate_transactions(accounts.iter_mut().collect(), 1)
+            .is_empty());
+    }
+}
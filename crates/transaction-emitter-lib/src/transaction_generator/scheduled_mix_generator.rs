@@ -0,0 +1,212 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+use crate::transaction_generator::{
+    transaction_mix_generator::TxnMixGenerator, TransactionGenerator,
+};
+use anyhow::{bail, Result};
+use aptos_logger::info;
+use aptos_sdk::types::{transaction::SignedTransaction, LocalAccount};
+use aptos_time_service::{TimeService, TimeServiceTrait};
+use async_trait::async_trait;
+use std::time::{Duration, Instant};
+
+/// Changes the weights of a [`TxnMixGenerator`] on a schedule
+///
+/// Each phase is a duration and the weights of the mix during it, and the phases run
+/// back to back from when the generator is created.  After the schedule ends, the
+/// weights of the last phase stay in effect.
+pub struct ScheduledMixGenerator {
+    mix: TxnMixGenerator,
+    phases: Vec<(Duration, Vec<usize>)>,
+    time_service: TimeService,
+    start: Instant,
+    current_phase: usize,
+}
+
+impl ScheduledMixGenerator {
+    /// Fails if there are no phases, or any phase doesn't have a weight for each generator of
+    /// the mix, with at least one non-zero weight for an enabled generator
+    pub fn new(
+        mut mix: TxnMixGenerator,
+        phases: Vec<(Duration, Vec<usize>)>,
+        time_service: TimeService,
+    ) -> Result<Self> {
+        if phases.is_empty() {
+            bail!("Must have at least one phase");
+        }
+        let num_generators = mix.weights().len();
+        for (index, (_, weights)) in phases.iter().enumerate() {
+            if weights.len() != num_generators {
+                bail!(
+                    "Phase {} has {} weights, but the mix has {} generators",
+                    index,
+                    weights.len(),
+                    num_generators
+                );
+            }
+            let total_weight: usize = weights
+                .iter()
+                .enumerate()
+                .filter(|(generator, _)| !mix.is_generator_disabled(*generator))
+                .map(|(_, weight)| weight)
+                .sum();
+            if total_weight == 0 {
+                bail!(
+                    "Phase {} must have a non-zero weight for an enabled generator",
+                    index
+                );
+            }
+        }
+
+        mix.set_weights(&phases[0].1);
+        let start = time_service.now();
+        Ok(Self {
+            mix,
+            phases,
+            time_service,
+            start,
+            current_phase: 0,
+        })
+    }
+
+    /// The phase for the current time, clamped to the last phase
+    fn phase_for_now(&self) -> usize {
+        let elapsed = self.time_service.now().duration_since(self.start);
+        let mut phase_end = Duration::ZERO;
+        for (index, (duration, _)) in self.phases.iter().enumerate() {
+            phase_end += *duration;
+            if elapsed < phase_end {
+                return index;
+            }
+        }
+        self.phases.len() - 1
+    }
+
+    /// Moves the mix to the weights of the current phase, if it has changed
+    fn update_phase(&mut self) {
+        let phase = self.phase_for_now();
+        if phase != self.current_phase {
+            info!(
+                "Transaction mix moving from phase {} to {} with weights {:?}",
+                self.current_phase, phase, self.phases[phase].1
+            );
+            self.mix.set_weights(&self.phases[phase].1);
+            self.current_phase = phase;
+        }
+    }
+
+    /// The weights currently in effect
+    pub fn active_weights(&mut self) -> &[usize] {
+        self.update_phase();
+        &self.phases[self.current_phase].1
+    }
+}
+
+#[async_trait]
+impl TransactionGenerator for ScheduledMixGenerator {
+    fn generate_transactions(
+        &mut self,
+        accounts: Vec<&mut LocalAccount>,
+        transactions_per_account: usize,
+    ) -> Vec<SignedTransaction> {
+        self.update_phase();
+        self.mix
+            .generate_transactions(accounts, transactions_per_account)
+    }
+
+    async fn generate_transactions_async(
+        &mut self,
+        accounts: Vec<&mut LocalAccount>,
+        transactions_per_account: usize,
+    ) -> Vec<SignedTransaction> {
+        self.update_phase();
+        self.mix
+            .generate_transactions_async(accounts, transactions_per_account)
+            .await
+    }
+
+    fn on_accounts_changed(&mut self, accounts: &[LocalAccount]) {
+        self.mix.on_accounts_changed(accounts);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::transaction_generator::{
+        scheduled_mix_generator::ScheduledMixGenerator, transaction_mix_generator::TxnMixGenerator,
+        TransactionGenerator,
+    };
+    use aptos_sdk::types::{transaction::SignedTransaction, LocalAccount};
+    use aptos_time_service::TimeService;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::time::Duration;
+
+    struct EmptyGenerator;
+
+    impl TransactionGenerator for EmptyGenerator {
+        fn generate_transactions(
+            &mut self,
+            _accounts: Vec<&mut LocalAccount>,
+            _transactions_per_account: usize,
+        ) -> Vec<SignedTransaction> {
+            vec![]
+        }
+    }
+
+    fn mix() -> TxnMixGenerator {
+        let txn_mix: Vec<(Box<dyn TransactionGenerator>, usize)> = vec![
+            (Box::new(EmptyGenerator) as Box<dyn TransactionGenerator>, 1),
+            (Box::new(EmptyGenerator), 1),
+        ];
+        TxnMixGenerator::new(StdRng::seed_from_u64(0), txn_mix)
+    }
+
+    #[test]
+    fn test_scheduled_mix_phases() {
+        let time_service = TimeService::mock();
+        let mock_time = time_service.clone().into_mock();
+        let mut generator = ScheduledMixGenerator::new(
+            mix(),
+            vec![
+                (Duration::from_secs(300), vec![9, 1]),
+                (Duration::from_secs(300), vec![1, 9]),
+                (Duration::from_secs(60), vec![5, 5]),
+            ],
+            time_service,
+        )
+        .unwrap();
+
+        assert_eq!(generator.active_weights(), &[9, 1]);
+        mock_time.advance_secs(299);
+        assert_eq!(generator.active_weights(), &[9, 1]);
+        mock_time.advance_secs(1);
+        assert_eq!(generator.active_weights(), &[1, 9]);
+        mock_time.advance_secs(300);
+        assert_eq!(generator.active_weights(), &[5, 5]);
+
+        // After the schedule ends, it should stay on the last phase
+        mock_time.advance_secs(3600);
+        assert_eq!(generator.active_weights(), &[5, 5]);
+        assert!(generator.generate_transactions(vec![], 1).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_phases() {
+        let phases = [
+            vec![],
+            vec![(Duration::from_secs(300), vec![1, 1, 1])],
+            // Every phase is checked, not just the first
+            vec![
+                (Duration::from_secs(300), vec![1, 1]),
+                (Duration::from_secs(300), vec![1]),
+            ],
+            vec![
+                (Duration::from_secs(300), vec![1, 1]),
+                (Duration::from_secs(300), vec![0, 0]),
+            ],
+        ];
+        for phases in phases {
+            assert!(ScheduledMixGenerator::new(mix(), phases, TimeService::mock()).is_err());
+        }
+    }
+}
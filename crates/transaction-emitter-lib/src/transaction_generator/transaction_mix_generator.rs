@@ -1,68 +1,1012 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
-use crate::transaction_generator::{TransactionGenerator, TransactionGeneratorCreator};
+
+//! Mixes transactions from multiple generators by weight
+//!
+//! Weights are either relative integers, or derived from an absolute target TPS for each
+//! generator with [`TxnMixGenerator::from_tps_targets`].
+//!
+//! # Seed derivation
+//!
+//! [`TxnMixGeneratorCreator::with_seed`] makes the generators reproducible, without each
+//! generator's randomness depending on the others.  Every generator is given a stable
+//! identifier, and the seed of its RNG is derived as:
+//!
+//! ```text
+//! SHA3-256(SEED_DERIVATION_PREFIX || top_level_seed || instance || generator_id)
+//! ```
+//!
+//! * `top_level_seed` is 32 bytes, the `u64` seed in little endian followed by zeros, or the
+//!   seed given to [`TransactionGeneratorCreator::create_seeded_transaction_generator`] when
+//!   the mix is itself seeded
+//! * `instance` is the number of mixes created before this one as a little endian `u64`, so
+//!   each worker has its own streams
+//! * `generator_id` is the UTF-8 bytes of the identifier, with [`MIX_GENERATOR_ID`] used for
+//!   the mix's own choice of generator
+//!
+//! Adding, removing, or reordering generators doesn't change the streams of the others.
+
+use crate::transaction_generator::{
+    counters::{
+        TXN_GENERATION_BATCH_SIZE, TXN_GENERATION_DURATION_S, TXN_GENERATION_FALLBACK_COUNT,
+    },
+    TransactionGenerator, TransactionGeneratorCreator,
+};
+use anyhow::{bail, ensure, Result};
+use aptos_crypto::HashValue;
+use aptos_logger::warn;
 use aptos_sdk::types::{transaction::SignedTransaction, LocalAccount};
+use aptos_time_service::{TimeService, TimeServiceTrait};
 use async_trait::async_trait;
+use futures::FutureExt;
 use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    collections::HashSet,
+    panic::{self, AssertUnwindSafe},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Total weight of a mix with TPS targets, each generator's weight is its share of the total
+/// target TPS in these units
+pub const TPS_TARGET_WEIGHT_SCALE: usize = 1_000_000;
 
 pub struct TxnMixGenerator {
     rng: StdRng,
     txn_mix: Vec<(Box<dyn TransactionGenerator>, usize)>,
+    /// Generators that are disabled are never picked, regardless of their weight
+    disabled: Vec<bool>,
+    /// Sum of the weights of the enabled generators
     total_weight: usize,
+    /// Absolute target TPS of each generator, if the weights are derived from them
+    tps_targets: Option<Vec<f64>>,
+    /// Weight of picking no generator, for the share of the total target TPS that isn't
+    /// targeted by any generator
+    idle_weight: usize,
+    target_tps: Option<f64>,
+    /// When the next batch can be returned without going over the target TPS
+    next_batch_at: Option<Instant>,
+    time_service: TimeService,
+    instrumented: bool,
+    /// Used in place of a generator that keeps returning empty batches
+    fallback: Option<Box<dyn TransactionGenerator>>,
+    /// Number of consecutive empty batches before the fallback is used
+    max_consecutive_empty: usize,
+    /// Consecutive empty batches for each generator
+    consecutive_empty: Vec<usize>,
+    /// Number of times the fallback was used in place of each generator
+    fallback_counts: Vec<usize>,
 }
 
 impl TxnMixGenerator {
     pub fn new(rng: StdRng, txn_mix: Vec<(Box<dyn TransactionGenerator>, usize)>) -> Self {
         let total_weight = txn_mix.iter().map(|(_, weight)| weight).sum();
+        let num_generators = txn_mix.len();
         Self {
             rng,
             txn_mix,
+            disabled: vec![false; num_generators],
             total_weight,
+            tps_targets: None,
+            idle_weight: 0,
+            target_tps: None,
+            next_batch_at: None,
+            time_service: TimeService::real(),
+            instrumented: false,
+            fallback: None,
+            max_consecutive_empty: 0,
+            consecutive_empty: vec![0; num_generators],
+            fallback_counts: vec![0; num_generators],
         }
     }
-}
 
-impl TransactionGenerator for TxnMixGenerator {
-    fn generate_transactions(
-        &mut self,
-        accounts: Vec<&mut LocalAccount>,
-        transactions_per_account: usize,
-    ) -> Vec<SignedTransaction> {
-        let mut picked = self.rng.gen_range(0, self.total_weight);
-        for (gen, weight) in &mut self.txn_mix {
+    /// Creates a mix paced to `total_target_tps`, where each generator has an absolute target
+    /// TPS rather than a relative weight
+    ///
+    /// Each generator's weight is its share of the total, see [`TPS_TARGET_WEIGHT_SCALE`].  If
+    /// the targets sum to less than the total, the rest of the total is left idle, so each
+    /// generator still runs at its own target.  Fails if they sum to more than the total.
+    pub fn from_tps_targets(
+        rng: StdRng,
+        txn_mix: Vec<(Box<dyn TransactionGenerator>, f64)>,
+        total_target_tps: f64,
+    ) -> Result<Self> {
+        let tps_targets: Vec<_> = txn_mix.iter().map(|(_, target)| *target).collect();
+        ensure!(
+            tps_targets.iter().all(|target| *target >= 0.0),
+            "Target TPS of each generator can't be negative: {:?}",
+            tps_targets
+        );
+        ensure!(
+            tps_targets.iter().any(|target| *target > 0.0),
+            "At least one generator must have a non-zero target TPS"
+        );
+        let txn_mix = txn_mix
+            .into_iter()
+            .map(|(generator, _)| (generator, 0))
+            .collect();
+        let mut generator = Self::new(rng, txn_mix);
+        generator.tps_targets = Some(tps_targets);
+        generator.set_total_target_tps(total_target_tps)?;
+        Ok(generator)
+    }
+
+    /// Changes the total target TPS of a mix created by [`Self::from_tps_targets`], and
+    /// recomputes the weights so each generator stays at its own target
+    pub fn set_total_target_tps(&mut self, total_target_tps: f64) -> Result<()> {
+        let tps_targets = match &self.tps_targets {
+            Some(tps_targets) => tps_targets,
+            None => bail!("Mix has relative weights rather than TPS targets"),
+        };
+        ensure!(
+            total_target_tps > 0.0,
+            "Total target TPS must be greater than 0"
+        );
+        let targeted_tps: f64 = tps_targets.iter().sum();
+        ensure!(
+            targeted_tps <= total_target_tps,
+            "Target TPS of the generators sum to {}, more than the total of {}",
+            targeted_tps,
+            total_target_tps
+        );
+
+        let weights: Vec<_> = tps_targets
+            .iter()
+            .map(|target| {
+                (target / total_target_tps * TPS_TARGET_WEIGHT_SCALE as f64).round() as usize
+            })
+            .collect();
+        for ((_, weight), new_weight) in self.txn_mix.iter_mut().zip(&weights) {
+            *weight = *new_weight;
+        }
+        self.total_weight = self.weights().iter().sum();
+        self.idle_weight = TPS_TARGET_WEIGHT_SCALE.saturating_sub(weights.iter().sum());
+        self.target_tps = Some(total_target_tps);
+        Ok(())
+    }
+
+    /// Absolute target TPS of each generator, if the mix was created with them
+    pub fn tps_targets(&self) -> Option<&[f64]> {
+        self.tps_targets.as_deref()
+    }
+
+    /// Uses `fallback` in place of a generator once it has returned `max_consecutive_empty`
+    /// empty batches in a row, until it returns a non-empty batch again
+    ///
+    /// A generator that panics is replaced by the fallback for that batch.  Without a
+    /// fallback, the panic isn't caught.
+    pub fn with_fallback(
+        mut self,
+        fallback: Box<dyn TransactionGenerator>,
+        max_consecutive_empty: usize,
+    ) -> Self {
+        assert!(
+            max_consecutive_empty > 0,
+            "Must allow at least one empty batch before falling back"
+        );
+        self.fallback = Some(fallback);
+        self.max_consecutive_empty = max_consecutive_empty;
+        self
+    }
+
+    /// Number of times the fallback was used in place of each generator, in the same order as
+    /// the mix
+    pub fn fallback_counts(&self) -> &[usize] {
+        &self.fallback_counts
+    }
+
+    /// Records the generation time and batch size of each call to Prometheus histograms
+    pub fn with_instrumentation(mut self) -> Self {
+        self.instrumented = true;
+        self
+    }
+
+    /// Limits the transactions returned by `generate_transactions_async` to `target_tps`
+    pub fn with_target_tps(mut self, target_tps: f64) -> Self {
+        assert!(target_tps > 0.0, "Target TPS must be greater than 0");
+        self.target_tps = Some(target_tps);
+        self
+    }
+
+    /// Paces with `time_service` rather than the real clock
+    pub fn with_time_service(mut self, time_service: TimeService) -> Self {
+        self.time_service = time_service;
+        self
+    }
+
+    /// Sum of the weights of the enabled generators
+    pub fn total_weight(&self) -> usize {
+        self.total_weight
+    }
+
+    /// Weights of the generators, in the same order as the mix
+    ///
+    /// Disabled generators have a weight of 0, and get their weight back when enabled.
+    pub fn weights(&self) -> Vec<usize> {
+        self.txn_mix
+            .iter()
+            .zip(&self.disabled)
+            .map(|((_, weight), disabled)| if *disabled { 0 } else { *weight })
+            .collect()
+    }
+
+    /// Stops picking the generator at `index`, until it's enabled again
+    ///
+    /// Fails if it's the last generator with a non-zero weight, as nothing could be picked.
+    pub fn disable_generator(&mut self, index: usize) -> Result<()> {
+        self.check_index(index)?;
+        let weight = self.weights()[index];
+        if weight > 0 && weight == self.total_weight {
+            bail!(
+                "Can't disable generator {}, it's the only one with a non-zero weight",
+                index
+            );
+        }
+        self.disabled[index] = true;
+        self.total_weight -= weight;
+        Ok(())
+    }
+
+    /// Picks the generator at `index` by its weight again, after it was disabled
+    pub fn enable_generator(&mut self, index: usize) -> Result<()> {
+        self.check_index(index)?;
+        if self.disabled[index] {
+            self.disabled[index] = false;
+            self.total_weight += self.txn_mix[index].1;
+        }
+        Ok(())
+    }
+
+    /// Whether the generator at `index` is disabled
+    pub fn is_generator_disabled(&self, index: usize) -> bool {
+        self.disabled[index]
+    }
+
+    fn check_index(&self, index: usize) -> Result<()> {
+        if index >= self.txn_mix.len() {
+            bail!(
+                "Generator {} is out of range, there are {} generators",
+                index,
+                self.txn_mix.len()
+            );
+        }
+        Ok(())
+    }
+
+    /// Human readable summary of the mix e.g. `[0: 3/4 (75.00%), 1: 1/4 (25.00%)]`
+    pub fn mix_summary(&self) -> String {
+        let entries: Vec<_> = self
+            .weights()
+            .into_iter()
+            .enumerate()
+            .map(|(index, weight)| {
+                format!(
+                    "{}: {}/{} ({:.2}%)",
+                    index,
+                    weight,
+                    self.total_weight,
+                    100.0 * weight as f64 / self.total_weight as f64
+                )
+            })
+            .collect();
+        format!("[{}]", entries.join(", "))
+    }
+
+    /// Replaces the weights of the generators, in the same order as the mix
+    ///
+    /// Disabled generators stay disabled, and get their new weight once enabled.  A mix with
+    /// TPS targets goes back to relative weights, with no idle share.
+    pub fn set_weights(&mut self, weights: &[usize]) {
+        assert_eq!(
+            weights.len(),
+            self.txn_mix.len(),
+            "Must have a weight for each generator"
+        );
+        for ((_, weight), new_weight) in self.txn_mix.iter_mut().zip(weights) {
+            *weight = *new_weight;
+        }
+        self.total_weight = self.weights().iter().sum();
+        assert!(self.total_weight > 0, "Total weight must be greater than 0");
+        self.tps_targets = None;
+        self.idle_weight = 0;
+    }
+
+    /// Picks a generator by weight, or `None` for the idle share of a mix with TPS targets
+    fn pick_generator(&mut self) -> Option<usize> {
+        let mut picked = self.rng.gen_range(0, self.total_weight + self.idle_weight);
+        for (index, ((_, weight), disabled)) in self.txn_mix.iter().zip(&self.disabled).enumerate()
+        {
+            if *disabled {
+                continue;
+            }
             if picked < *weight {
-                return gen.generate_transactions(accounts, transactions_per_account);
+                return Some(index);
             }
             picked -= *weight;
         }
+        if picked < self.idle_weight {
+            return None;
+        }
         panic!(
             "Picked {} out of {}, couldn't find correct generator",
             picked, self.total_weight
         );
     }
+
+    /// Tracks empty batches from the generator, and returns whether the fallback should be used
+    fn should_fall_back(&mut self, index: usize, num_requested: usize, num_txns: usize) -> bool {
+        if self.fallback.is_none() || num_requested == 0 {
+            return false;
+        }
+        if num_txns > 0 {
+            self.consecutive_empty[index] = 0;
+            return false;
+        }
+
+        self.consecutive_empty[index] += 1;
+        if self.consecutive_empty[index] < self.max_consecutive_empty {
+            return false;
+        }
+        self.record_fallback(index);
+        true
+    }
+
+    /// Returns the batch from the generator, or `None` if the fallback should be used instead
+    ///
+    /// A panic is resumed if there's no fallback to use.
+    fn checked_batch(
+        &mut self,
+        index: usize,
+        num_requested: usize,
+        batch: thread::Result<Vec<SignedTransaction>>,
+    ) -> Option<Vec<SignedTransaction>> {
+        match batch {
+            Ok(txns) if self.should_fall_back(index, num_requested, txns.len()) => None,
+            Ok(txns) => Some(txns),
+            Err(panic) => {
+                if self.fallback.is_none() {
+                    panic::resume_unwind(panic);
+                }
+                warn!("Generator {} panicked, using the fallback", index);
+                self.record_fallback(index);
+                None
+            },
+        }
+    }
+
+    fn record_fallback(&mut self, index: usize) {
+        self.fallback_counts[index] += 1;
+        TXN_GENERATION_FALLBACK_COUNT
+            .with_label_values(&[&index.to_string()])
+            .inc();
+    }
+
+    /// Waits until the batch can be returned without going over the target TPS, and holds
+    /// back the next batch for as long as this one's transactions take at the target TPS
+    ///
+    /// Time not used while idle isn't saved up, so an idle period doesn't allow a burst of
+    /// transactions afterwards.
+    async fn pace(&mut self, num_txns: usize) {
+        if let Some(target_tps) = self.target_tps {
+            let now = self.time_service.now();
+            let batch_at = self.next_batch_at.map_or(now, |next| next.max(now));
+            self.time_service.sleep_until(batch_at).await;
+            self.next_batch_at =
+                Some(batch_at + Duration::from_secs_f64(num_txns as f64 / target_tps));
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionGenerator for TxnMixGenerator {
+    fn generate_transactions(
+        &mut self,
+        mut accounts: Vec<&mut LocalAccount>,
+        transactions_per_account: usize,
+    ) -> Vec<SignedTransaction> {
+        let start = Instant::now();
+        let num_requested = accounts.len() * transactions_per_account;
+        let index = match self.pick_generator() {
+            Some(index) => index,
+            None => return vec![],
+        };
+        let batch = panic::catch_unwind(AssertUnwindSafe(|| {
+            self.txn_mix[index].0.generate_transactions(
+                accounts.iter_mut().map(|account| &mut **account).collect(),
+                transactions_per_account,
+            )
+        }));
+        let txns = match self.checked_batch(index, num_requested, batch) {
+            Some(txns) => txns,
+            None => self
+                .fallback
+                .as_mut()
+                .expect("Only falls back if there's a fallback")
+                .generate_transactions(accounts, transactions_per_account),
+        };
+        self.record_generation(start.elapsed(), txns.len());
+        txns
+    }
+
+    async fn generate_transactions_async(
+        &mut self,
+        mut accounts: Vec<&mut LocalAccount>,
+        transactions_per_account: usize,
+    ) -> Vec<SignedTransaction> {
+        let start = Instant::now();
+        let num_requested = accounts.len() * transactions_per_account;
+        let index = match self.pick_generator() {
+            Some(index) => index,
+            None => {
+                // Idle for as long as the requested transactions would take
+                self.pace(num_requested).await;
+                return vec![];
+            },
+        };
+        let batch = AssertUnwindSafe(self.txn_mix[index].0.generate_transactions_async(
+            accounts.iter_mut().map(|account| &mut **account).collect(),
+            transactions_per_account,
+        ))
+        .catch_unwind()
+        .await;
+        let txns = match self.checked_batch(index, num_requested, batch) {
+            Some(txns) => txns,
+            None => {
+                self.fallback
+                    .as_mut()
+                    .expect("Only falls back if there's a fallback")
+                    .generate_transactions_async(accounts, transactions_per_account)
+                    .await
+            },
+        };
+        // Pacing is excluded, as it isn't the cost of generating
+        self.record_generation(start.elapsed(), txns.len());
+        self.pace(txns.len()).await;
+        txns
+    }
+
+    fn record_generation(&self, duration: Duration, num_txns: usize) {
+        if self.instrumented {
+            TXN_GENERATION_DURATION_S.observe(duration.as_secs_f64());
+            TXN_GENERATION_BATCH_SIZE.observe(num_txns as f64);
+        }
+    }
+
+    fn on_accounts_changed(&mut self, accounts: &[LocalAccount]) {
+        for (gen, _) in &mut self.txn_mix {
+            gen.on_accounts_changed(accounts);
+        }
+        if let Some(fallback) = self.fallback.as_mut() {
+            fallback.on_accounts_changed(accounts);
+        }
+    }
+}
+
+/// Domain separation for derived seeds
+pub const SEED_DERIVATION_PREFIX: &[u8] = b"APTOS::TxnMixGeneratorSeed";
+/// Identifier used to derive the seed for the mix's own choice of generator
+pub const MIX_GENERATOR_ID: &str = "txn_mix";
+
+/// Derives the seed of a generator, see the module documentation for the scheme
+pub fn derive_generator_seed(
+    top_level_seed: &[u8; 32],
+    instance: u64,
+    generator_id: &str,
+) -> [u8; 32] {
+    let mut bytes = SEED_DERIVATION_PREFIX.to_vec();
+    bytes.extend_from_slice(top_level_seed);
+    bytes.extend_from_slice(&instance.to_le_bytes());
+    bytes.extend_from_slice(generator_id.as_bytes());
+    *HashValue::sha3_256_of(&bytes).as_ref()
 }
 
 pub struct TxnMixGeneratorCreator {
     txn_mix_creators: Vec<(Box<dyn TransactionGeneratorCreator>, usize)>,
+    /// Top level seed and the identifiers of each generator, if seeded
+    seed: Option<([u8; 32], Vec<String>)>,
+    /// Number of mixes created so far
+    num_created: u64,
+    target_tps: Option<f64>,
+    instrumented: bool,
 }
 
 impl TxnMixGeneratorCreator {
     pub fn new(txn_mix_creators: Vec<(Box<dyn TransactionGeneratorCreator>, usize)>) -> Self {
-        Self { txn_mix_creators }
+        Self {
+            txn_mix_creators,
+            seed: None,
+            num_created: 0,
+            target_tps: None,
+            instrumented: false,
+        }
+    }
+
+    /// Seeds each generator from `seed` and its identifier in `generator_ids`, which must be
+    /// unique and in the same order as the generators
+    pub fn with_seed(mut self, seed: u64, generator_ids: Vec<String>) -> Self {
+        assert_eq!(
+            generator_ids.len(),
+            self.txn_mix_creators.len(),
+            "Must have an identifier for each generator"
+        );
+        let unique_ids: HashSet<_> = generator_ids.iter().collect();
+        assert_eq!(
+            unique_ids.len(),
+            generator_ids.len(),
+            "Generator identifiers must be unique"
+        );
+        assert!(
+            !unique_ids.contains(&MIX_GENERATOR_ID.to_string()),
+            "Generator identifier {} is reserved",
+            MIX_GENERATOR_ID
+        );
+        let mut top_level_seed = [0u8; 32];
+        top_level_seed[..8].copy_from_slice(&seed.to_le_bytes());
+        self.seed = Some((top_level_seed, generator_ids));
+        self
+    }
+
+    async fn create_mix(&mut self, top_level_seed: Option<[u8; 32]>) -> TxnMixGenerator {
+        let instance = self.num_created;
+        self.num_created += 1;
+
+        let seed_for = |generator_id: &str| {
+            top_level_seed.map(|seed| derive_generator_seed(&seed, instance, generator_id))
+        };
+        let generator_ids = self.seed.as_ref().map(|(_, ids)| ids.clone());
+
+        let mut txn_mix = Vec::<(Box<dyn TransactionGenerator>, usize)>::new();
+        for (index, (generator_creator, weight)) in self.txn_mix_creators.iter_mut().enumerate() {
+            let seed = generator_ids.as_ref().and_then(|ids| seed_for(&ids[index]));
+            let generator = match seed {
+                Some(seed) => {
+                    generator_creator
+                        .create_seeded_transaction_generator(seed)
+                        .await
+                },
+                None => generator_creator.create_transaction_generator().await,
+            };
+            txn_mix.push((generator, *weight));
+        }
+
+        let rng = match seed_for(MIX_GENERATOR_ID) {
+            Some(seed) => StdRng::from_seed(seed),
+            None => StdRng::from_entropy(),
+        };
+        let mut generator = TxnMixGenerator::new(rng, txn_mix);
+        if let Some(target_tps) = self.target_tps {
+            generator = generator.with_target_tps(target_tps);
+        }
+        if self.instrumented {
+            generator = generator.with_instrumentation();
+        }
+        generator
+    }
+
+    /// Weights of the generators, in the same order as the mix
+    pub fn weights(&self) -> Vec<usize> {
+        self.txn_mix_creators
+            .iter()
+            .map(|(_, weight)| *weight)
+            .collect()
+    }
+
+    /// Paces each created generator to `target_tps`
+    pub fn with_target_tps(mut self, target_tps: f64) -> Self {
+        self.target_tps = Some(target_tps);
+        self
+    }
+
+    /// Instruments each created generator, see [`TxnMixGenerator::with_instrumentation`]
+    pub fn with_instrumentation(mut self) -> Self {
+        self.instrumented = true;
+        self
     }
 }
 
 #[async_trait]
 impl TransactionGeneratorCreator for TxnMixGeneratorCreator {
     async fn create_transaction_generator(&mut self) -> Box<dyn TransactionGenerator> {
-        let mut txn_mix = Vec::<(Box<dyn TransactionGenerator>, usize)>::new();
-        for (generator_creator, weight) in self.txn_mix_creators.iter_mut() {
-            txn_mix.push((
-                generator_creator.create_transaction_generator().await,
-                *weight,
-            ));
+        let top_level_seed = self.seed.as_ref().map(|(seed, _)| *seed);
+        Box::new(self.create_mix(top_level_seed).await)
+    }
+
+    /// The given seed is used in place of the top level seed, if the generators have
+    /// identifiers
+    async fn create_seeded_transaction_generator(
+        &mut self,
+        seed: [u8; 32],
+    ) -> Box<dyn TransactionGenerator> {
+        Box::new(self.create_mix(Some(seed)).await)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        derive_generator_seed, TxnMixGenerator, TxnMixGeneratorCreator, TPS_TARGET_WEIGHT_SCALE,
+    };
+    use crate::transaction_generator::{TransactionGenerator, TransactionGeneratorCreator};
+    use aptos_infallible::Mutex;
+    use aptos_sdk::{
+        move_types::account_address::AccountAddress,
+        transaction_builder::TransactionFactory,
+        types::{chain_id::ChainId, transaction::SignedTransaction, LocalAccount},
+    };
+    use aptos_time_service::{MockTimeService, TimeService, TimeServiceTrait};
+    use async_trait::async_trait;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::{
+        collections::{HashMap, HashSet},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    /// Counts the batches it's asked for
+    struct CountingGenerator(Arc<AtomicUsize>);
+
+    impl TransactionGenerator for CountingGenerator {
+        fn generate_transactions(
+            &mut self,
+            _accounts: Vec<&mut LocalAccount>,
+            _transactions_per_account: usize,
+        ) -> Vec<SignedTransaction> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            vec![]
+        }
+    }
+
+    #[test]
+    fn disabled_generator_is_never_picked() {
+        let counts: Vec<_> = (0..3).map(|_| Arc::new(AtomicUsize::new(0))).collect();
+        let txn_mix: Vec<(Box<dyn TransactionGenerator>, usize)> = counts
+            .iter()
+            .zip([1, 2, 3])
+            .map(|(count, weight)| {
+                (
+                    Box::new(CountingGenerator(count.clone())) as Box<dyn TransactionGenerator>,
+                    weight,
+                )
+            })
+            .collect();
+        let mut generator = TxnMixGenerator::new(StdRng::seed_from_u64(0), txn_mix);
+        let generate = |generator: &mut TxnMixGenerator| {
+            for count in &counts {
+                count.store(0, Ordering::Relaxed);
+            }
+            for _ in 0..300 {
+                generator.generate_transactions(vec![], 1);
+            }
+            counts
+                .iter()
+                .map(|count| count.load(Ordering::Relaxed))
+                .collect::<Vec<_>>()
+        };
+
+        generator.disable_generator(1).unwrap();
+        assert!(generator.is_generator_disabled(1));
+        assert_eq!(vec![1, 0, 3], generator.weights());
+        assert_eq!(4, generator.total_weight());
+        let picked = generate(&mut generator);
+        assert_eq!(0, picked[1]);
+        assert!(picked[0] > 0 && picked[2] > 0);
+
+        // Disabling twice is a no-op, and the last enabled generator can't be disabled
+        generator.disable_generator(1).unwrap();
+        generator.disable_generator(0).unwrap();
+        generator.disable_generator(2).unwrap_err();
+        generator.disable_generator(3).unwrap_err();
+        assert_eq!(3, generator.total_weight());
+        assert_eq!(vec![0, 0, 300], generate(&mut generator));
+
+        // Weights changed while disabled are used once enabled
+        generator.set_weights(&[1, 5, 3]);
+        assert_eq!(3, generator.total_weight());
+        generator.enable_generator(1).unwrap();
+        generator.enable_generator(1).unwrap();
+        assert_eq!(vec![0, 5, 3], generator.weights());
+        assert_eq!(8, generator.total_weight());
+        assert_eq!(0, generate(&mut generator)[0]);
+    }
+
+    #[test]
+    fn mix_is_introspectable() {
+        let txn_mix: Vec<(Box<dyn TransactionGenerator>, usize)> = [3, 1]
+            .into_iter()
+            .map(|weight| {
+                (
+                    Box::new(CountingGenerator(Arc::new(AtomicUsize::new(0))))
+                        as Box<dyn TransactionGenerator>,
+                    weight,
+                )
+            })
+            .collect();
+        let mut generator = TxnMixGenerator::new(StdRng::seed_from_u64(0), txn_mix);
+        assert_eq!(4, generator.total_weight());
+        assert_eq!(vec![3, 1], generator.weights());
+        assert_eq!(
+            "[0: 3/4 (75.00%), 1: 1/4 (25.00%)]",
+            generator.mix_summary()
+        );
+
+        generator.set_weights(&[1, 7]);
+        assert_eq!(8, generator.total_weight());
+        assert_eq!(
+            "[0: 1/8 (12.50%), 1: 7/8 (87.50%)]",
+            generator.mix_summary()
+        );
+    }
+
+    /// Records the seeds of the generators it creates
+    struct SeedRecordingCreator(Arc<Mutex<Vec<[u8; 32]>>>);
+
+    #[async_trait]
+    impl TransactionGeneratorCreator for SeedRecordingCreator {
+        async fn create_transaction_generator(&mut self) -> Box<dyn TransactionGenerator> {
+            Box::new(CountingGenerator(Arc::new(AtomicUsize::new(0))))
+        }
+
+        async fn create_seeded_transaction_generator(
+            &mut self,
+            seed: [u8; 32],
+        ) -> Box<dyn TransactionGenerator> {
+            self.0.lock().push(seed);
+            self.create_transaction_generator().await
+        }
+    }
+
+    /// Creates a mix for each of two workers, returning the seeds given to each generator
+    async fn worker_seeds(seed: u64, generator_ids: &[&str]) -> HashMap<String, Vec<[u8; 32]>> {
+        let seeds: Vec<_> = generator_ids
+            .iter()
+            .map(|_| Arc::new(Mutex::new(vec![])))
+            .collect();
+        let mut creator = TxnMixGeneratorCreator::new(
+            seeds
+                .iter()
+                .map(|seeds| {
+                    (
+                        Box::new(SeedRecordingCreator(seeds.clone()))
+                            as Box<dyn TransactionGeneratorCreator>,
+                        1,
+                    )
+                })
+                .collect(),
+        )
+        .with_seed(
+            seed,
+            generator_ids.iter().map(|id| id.to_string()).collect(),
+        );
+        for _ in 0..2 {
+            creator.create_transaction_generator().await;
+        }
+        generator_ids
+            .iter()
+            .zip(seeds)
+            .map(|(id, seeds)| (id.to_string(), seeds.lock().clone()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn worker_seeds_are_deterministic_and_distinct() {
+        let seeds = worker_seeds(7, &["p2p", "publish"]).await;
+        let distinct: HashSet<_> = seeds.values().flatten().collect();
+        assert_eq!(4, distinct.len());
+        assert_eq!(seeds, worker_seeds(7, &["p2p", "publish"]).await);
+        assert_ne!(seeds, worker_seeds(8, &["p2p", "publish"]).await);
+
+        // Adding or reordering generators doesn't change the others' seeds
+        let reordered = worker_seeds(7, &["call", "publish", "p2p"]).await;
+        assert_eq!(seeds["p2p"], reordered["p2p"]);
+        assert_eq!(seeds["publish"], reordered["publish"]);
+
+        // The seeds follow the documented derivation
+        let mut top_level_seed = [0u8; 32];
+        top_level_seed[..8].copy_from_slice(&7u64.to_le_bytes());
+        assert_eq!(
+            derive_generator_seed(&top_level_seed, 1, "publish"),
+            seeds["publish"][1]
+        );
+    }
+
+    /// Returns copies of a transaction, counting the transactions it returns
+    struct CopyingGenerator(SignedTransaction, Arc<AtomicUsize>);
+
+    impl TransactionGenerator for CopyingGenerator {
+        fn generate_transactions(
+            &mut self,
+            accounts: Vec<&mut LocalAccount>,
+            transactions_per_account: usize,
+        ) -> Vec<SignedTransaction> {
+            let num_txns = accounts.len() * transactions_per_account;
+            self.1.fetch_add(num_txns, Ordering::Relaxed);
+            vec![self.0.clone(); num_txns]
+        }
+    }
+
+    #[tokio::test]
+    async fn batches_are_paced_to_target_tps() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut accounts: Vec<_> = (0..10).map(|_| LocalAccount::generate(&mut rng)).collect();
+        let txn = accounts[0].sign_with_transaction_builder(
+            TransactionFactory::new(ChainId::test()).transfer(AccountAddress::ONE, 1),
+        );
+        let count = Arc::new(AtomicUsize::new(0));
+        let time_service: TimeService = MockTimeService::new_auto_advance().into();
+        let mut generator = TxnMixGenerator::new(StdRng::seed_from_u64(0), vec![(
+            Box::new(CopyingGenerator(txn, count.clone())) as Box<dyn TransactionGenerator>,
+            1,
+        )])
+        .with_target_tps(50.0)
+        .with_time_service(time_service.clone());
+
+        // Batches of 10 transactions at 50 TPS are 200ms apart, and the first isn't held back
+        let start = time_service.now();
+        for _ in 0..51 {
+            generator
+                .generate_transactions_async(accounts.iter_mut().collect(), 1)
+                .await;
+        }
+        assert_eq!(510, count.load(Ordering::Relaxed));
+        assert_eq!(
+            Duration::from_secs(10),
+            time_service.now().duration_since(start)
+        );
+
+        // An idle period doesn't allow a burst afterwards
+        time_service.clone().into_mock().advance_secs(5);
+        let idle_end = time_service.now();
+        for _ in 0..2 {
+            generator
+                .generate_transactions_async(accounts.iter_mut().collect(), 1)
+                .await;
         }
+        assert_eq!(
+            Duration::from_millis(200),
+            time_service.now().duration_since(idle_end)
+        );
+    }
 
-        Box::new(TxnMixGenerator::new(StdRng::from_entropy(), txn_mix))
+    /// Panics on every batch
+    struct PanickingGenerator;
+
+    impl TransactionGenerator for PanickingGenerator {
+        fn generate_transactions(
+            &mut self,
+            _accounts: Vec<&mut LocalAccount>,
+            _transactions_per_account: usize,
+        ) -> Vec<SignedTransaction> {
+            panic!("Generator is misbehaving");
+        }
+    }
+
+    #[tokio::test]
+    async fn fallback_is_used_and_counted() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut account = LocalAccount::generate(&mut rng);
+        let txn = account.sign_with_transaction_builder(
+            TransactionFactory::new(ChainId::test()).transfer(AccountAddress::ONE, 1),
+        );
+        let fallback_txns = Arc::new(AtomicUsize::new(0));
+        let fallback = CopyingGenerator(txn, fallback_txns.clone());
+        let mut generator = TxnMixGenerator::new(StdRng::seed_from_u64(0), vec![
+            (
+                Box::new(CountingGenerator(Arc::new(AtomicUsize::new(0))))
+                    as Box<dyn TransactionGenerator>,
+                1,
+            ),
+            (Box::new(PanickingGenerator), 1),
+        ])
+        .with_fallback(Box::new(fallback), 2);
+
+        // Only the empty generator is picked while the other is disabled, and it falls back
+        // from its second empty batch on
+        generator.disable_generator(1).unwrap();
+        for _ in 0..5 {
+            generator.generate_transactions(vec![&mut account], 1);
+        }
+        assert_eq!(&[4, 0], generator.fallback_counts());
+        assert_eq!(4, fallback_txns.load(Ordering::Relaxed));
+
+        // A panic falls back right away, in both the sync and async paths
+        generator.enable_generator(1).unwrap();
+        generator.disable_generator(0).unwrap();
+        assert_eq!(
+            1,
+            generator.generate_transactions(vec![&mut account], 1).len()
+        );
+        assert_eq!(
+            1,
+            generator
+                .generate_transactions_async(vec![&mut account], 1)
+                .await
+                .len()
+        );
+        assert_eq!(&[4, 2], generator.fallback_counts());
+    }
+
+    #[test]
+    #[should_panic(expected = "Generator is misbehaving")]
+    fn panics_without_fallback() {
+        let mut generator = TxnMixGenerator::new(StdRng::seed_from_u64(0), vec![(
+            Box::new(PanickingGenerator) as Box<dyn TransactionGenerator>,
+            1,
+        )]);
+        generator.generate_transactions(vec![], 1);
+    }
+
+    #[tokio::test]
+    async fn tps_targets_are_met() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut account = LocalAccount::generate(&mut rng);
+        let txn = account.sign_with_transaction_builder(
+            TransactionFactory::new(ChainId::test()).transfer(AccountAddress::ONE, 1),
+        );
+        let counts: Vec<_> = (0..2).map(|_| Arc::new(AtomicUsize::new(0))).collect();
+        let txn_mix: Vec<(Box<dyn TransactionGenerator>, f64)> = counts
+            .iter()
+            .zip([100.0, 50.0])
+            .map(|(count, target)| {
+                (
+                    Box::new(CopyingGenerator(txn.clone(), count.clone()))
+                        as Box<dyn TransactionGenerator>,
+                    target,
+                )
+            })
+            .collect();
+
+        // The targets can't add up to more than the total
+        TxnMixGenerator::from_tps_targets(
+            StdRng::seed_from_u64(0),
+            vec![(
+                Box::new(CopyingGenerator(txn.clone(), Arc::new(AtomicUsize::new(0))))
+                    as Box<dyn TransactionGenerator>,
+                300.0,
+            )],
+            200.0,
+        )
+        .unwrap_err();
+
+        // A quarter of the total is left idle
+        let time_service: TimeService = MockTimeService::new_auto_advance().into();
+        let mut generator =
+            TxnMixGenerator::from_tps_targets(StdRng::seed_from_u64(0), txn_mix, 200.0)
+                .unwrap()
+                .with_time_service(time_service.clone());
+        assert_eq!(Some([100.0, 50.0].as_slice()), generator.tps_targets());
+        assert_eq!(
+            vec![TPS_TARGET_WEIGHT_SCALE / 2, TPS_TARGET_WEIGHT_SCALE / 4],
+            generator.weights()
+        );
+        generator.set_total_target_tps(100.0).unwrap_err();
+
+        // The mock clock advances through the pacing sleeps, so the window takes no real time
+        let window = Duration::from_secs(20);
+        let start = time_service.now();
+        while time_service.now().duration_since(start) < window {
+            generator
+                .generate_transactions_async(vec![&mut account], 1)
+                .await;
+        }
+        let elapsed = time_service.now().duration_since(start).as_secs_f64();
+        for (count, target) in counts.iter().zip([100.0, 50.0]) {
+            let tps = count.load(Ordering::Relaxed) as f64 / elapsed;
+            assert!(
+                (tps - target).abs() < target * 0.1,
+                "Expected {} TPS, got {}",
+                target,
+                tps
+            );
+        }
+
+        // Raising the total keeps the same absolute targets, with a larger idle share
+        generator.set_total_target_tps(400.0).unwrap();
+        assert_eq!(
+            vec![TPS_TARGET_WEIGHT_SCALE / 4, TPS_TARGET_WEIGHT_SCALE / 8],
+            generator.weights()
+        );
     }
 }
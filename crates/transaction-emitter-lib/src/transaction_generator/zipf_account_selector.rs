@@ -0,0 +1,319 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Sends another generator's transactions from a few hot accounts, rather than evenly
+//!
+//! Real traffic is dominated by a small number of very active accounts.  Each batch, the
+//! sender of every requested transaction is drawn from the given accounts by a Zipf
+//! distribution over their rank, so the same accounts stay hot across batches.  With an
+//! exponent of `s`, the account at rank `k` (from 1) sends in proportion to `1 / k^s`, and an
+//! exponent of 0 is uniform.
+//!
+//! Accounts are ranked by their order in the pool passed to `on_accounts_changed`, and accounts
+//! that aren't in it are ranked after those, by address, when first seen.  No account sends
+//! more than the maximum number of transactions in a batch, so hot accounts don't run far
+//! ahead of their sequence numbers.
+
+use crate::transaction_generator::{TransactionGenerator, TransactionGeneratorCreator};
+use aptos_sdk::{
+    move_types::account_address::AccountAddress,
+    types::{transaction::SignedTransaction, LocalAccount},
+};
+use async_trait::async_trait;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{collections::HashMap, time::Duration};
+
+pub struct ZipfAccountSelector {
+    rng: StdRng,
+    generator: Box<dyn TransactionGenerator>,
+    exponent: f64,
+    /// Most transactions an account sends in a batch, though never fewer than requested
+    max_transactions_per_account: usize,
+    /// Rank of each account, from 1
+    ranks: HashMap<AccountAddress, usize>,
+}
+
+impl ZipfAccountSelector {
+    pub fn new(
+        rng: StdRng,
+        generator: Box<dyn TransactionGenerator>,
+        exponent: f64,
+        max_transactions_per_account: usize,
+    ) -> Self {
+        assert!(
+            exponent.is_finite() && exponent >= 0.0,
+            "Zipf exponent must be non-negative, got {}",
+            exponent
+        );
+        Self {
+            rng,
+            generator,
+            exponent,
+            max_transactions_per_account,
+            ranks: HashMap::new(),
+        }
+    }
+
+    pub fn exponent(&self) -> f64 {
+        self.exponent
+    }
+
+    /// Ranks of the accounts, ranking any new ones after the known accounts
+    fn ranks_of(&mut self, addresses: &[AccountAddress]) -> Vec<usize> {
+        let mut unranked: Vec<_> = addresses
+            .iter()
+            .filter(|address| !self.ranks.contains_key(address))
+            .copied()
+            .collect();
+        unranked.sort();
+        unranked.dedup();
+        for address in unranked {
+            let rank = self.ranks.len() + 1;
+            self.ranks.insert(address, rank);
+        }
+        addresses
+            .iter()
+            .map(|address| self.ranks[address])
+            .collect()
+    }
+
+    /// Number of transactions each account sends, for the same total as requested
+    fn transactions_by_account(
+        &mut self,
+        addresses: &[AccountAddress],
+        transactions_per_account: usize,
+    ) -> Vec<usize> {
+        let num_accounts = addresses.len();
+        let max_transactions = self
+            .max_transactions_per_account
+            .max(transactions_per_account);
+        let exponent = self.exponent;
+        let mut weights: Vec<_> = self
+            .ranks_of(addresses)
+            .into_iter()
+            .map(|rank| 1.0 / (rank as f64).powf(exponent))
+            .collect();
+        let mut cumulative_weights = cumulative(&weights);
+
+        // As the maximum is at least the number requested, some account always has room left
+        let mut counts = vec![0; num_accounts];
+        for _ in 0..num_accounts * transactions_per_account {
+            let total = *cumulative_weights
+                .last()
+                .expect("Must have at least one account");
+            let picked = self.rng.gen::<f64>() * total;
+            let index = cumulative_weights
+                .partition_point(|weight| *weight <= picked)
+                .min(num_accounts - 1);
+            counts[index] += 1;
+            if counts[index] >= max_transactions {
+                weights[index] = 0.0;
+                cumulative_weights = cumulative(&weights);
+            }
+        }
+        counts
+    }
+}
+
+fn cumulative(weights: &[f64]) -> Vec<f64> {
+    weights
+        .iter()
+        .scan(0.0, |total, weight| {
+            *total += weight;
+            Some(*total)
+        })
+        .collect()
+}
+
+#[async_trait]
+impl TransactionGenerator for ZipfAccountSelector {
+    fn generate_transactions(
+        &mut self,
+        accounts: Vec<&mut LocalAccount>,
+        transactions_per_account: usize,
+    ) -> Vec<SignedTransaction> {
+        let addresses: Vec<_> = accounts.iter().map(|account| account.address()).collect();
+        let counts = self.transactions_by_account(&addresses, transactions_per_account);
+        let mut txns = Vec::new();
+        for (account, count) in accounts.into_iter().zip(counts) {
+            if count > 0 {
+                txns.extend(self.generator.generate_transactions(vec![account], count));
+            }
+        }
+        txns
+    }
+
+    async fn generate_transactions_async(
+        &mut self,
+        accounts: Vec<&mut LocalAccount>,
+        transactions_per_account: usize,
+    ) -> Vec<SignedTransaction> {
+        let addresses: Vec<_> = accounts.iter().map(|account| account.address()).collect();
+        let counts = self.transactions_by_account(&addresses, transactions_per_account);
+        let mut txns = Vec::new();
+        for (account, count) in accounts.into_iter().zip(counts) {
+            if count > 0 {
+                txns.extend(
+                    self.generator
+                        .generate_transactions_async(vec![account], count)
+                        .await,
+                );
+            }
+        }
+        txns
+    }
+
+    fn record_generation(&self, duration: Duration, num_txns: usize) {
+        self.generator.record_generation(duration, num_txns);
+    }
+
+    fn on_accounts_changed(&mut self, accounts: &[LocalAccount]) {
+        self.ranks = accounts
+            .iter()
+            .enumerate()
+            .map(|(index, account)| (account.address(), index + 1))
+            .collect();
+        self.generator.on_accounts_changed(accounts);
+    }
+}
+
+pub struct ZipfAccountSelectorCreator {
+    creator: Box<dyn TransactionGeneratorCreator>,
+    exponent: f64,
+    max_transactions_per_account: usize,
+}
+
+impl ZipfAccountSelectorCreator {
+    pub fn new(
+        creator: Box<dyn TransactionGeneratorCreator>,
+        exponent: f64,
+        max_transactions_per_account: usize,
+    ) -> Self {
+        Self {
+            creator,
+            exponent,
+            max_transactions_per_account,
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionGeneratorCreator for ZipfAccountSelectorCreator {
+    async fn create_transaction_generator(&mut self) -> Box<dyn TransactionGenerator> {
+        Box::new(ZipfAccountSelector::new(
+            StdRng::from_entropy(),
+            self.creator.create_transaction_generator().await,
+            self.exponent,
+            self.max_transactions_per_account,
+        ))
+    }
+
+    async fn create_seeded_transaction_generator(
+        &mut self,
+        seed: [u8; 32],
+    ) -> Box<dyn TransactionGenerator> {
+        Box::new(ZipfAccountSelector::new(
+            StdRng::from_seed(seed),
+            self.creator.create_seeded_transaction_generator(seed).await,
+            self.exponent,
+            self.max_transactions_per_account,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::transaction_generator::{
+        zipf_account_selector::ZipfAccountSelector, TransactionGenerator,
+    };
+    use aptos_infallible::Mutex;
+    use aptos_sdk::{
+        move_types::account_address::AccountAddress,
+        types::{transaction::SignedTransaction, LocalAccount},
+    };
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+    use std::{collections::HashMap, sync::Arc};
+
+    /// Records how many transactions each account was asked to send
+    struct RecordingGenerator(Arc<Mutex<HashMap<AccountAddress, usize>>>);
+
+    impl TransactionGenerator for RecordingGenerator {
+        fn generate_transactions(
+            &mut self,
+            accounts: Vec<&mut LocalAccount>,
+            transactions_per_account: usize,
+        ) -> Vec<SignedTransaction> {
+            let mut requested = self.0.lock();
+            for account in accounts {
+                *requested.entry(account.address()).or_default() += transactions_per_account;
+            }
+            vec![]
+        }
+    }
+
+    #[test]
+    fn accounts_are_picked_by_zipf() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut accounts: Vec<_> = (0..10).map(|_| LocalAccount::generate(&mut rng)).collect();
+        let requested = Arc::new(Mutex::new(HashMap::new()));
+        let exponent = 1.0;
+        let mut generator = ZipfAccountSelector::new(
+            StdRng::seed_from_u64(1),
+            Box::new(RecordingGenerator(requested.clone())),
+            exponent,
+            usize::MAX,
+        );
+        generator.on_accounts_changed(&accounts);
+
+        // Batches list the accounts in a different order each time, which doesn't change ranks
+        let transactions_per_account = 20;
+        let num_batches = 100;
+        for _ in 0..num_batches {
+            let mut batch: Vec<_> = accounts.iter_mut().collect();
+            batch.shuffle(&mut rng);
+            generator.generate_transactions(batch, transactions_per_account);
+        }
+
+        // The same total is requested, but each account's share follows the distribution
+        let requested = requested.lock();
+        let total = num_batches * accounts.len() * transactions_per_account;
+        assert_eq!(total, requested.values().sum::<usize>());
+        let normalization: f64 = (1..=accounts.len())
+            .map(|rank| 1.0 / (rank as f64).powf(exponent))
+            .sum();
+        for (index, account) in accounts.iter().enumerate() {
+            let rank = (index + 1) as f64;
+            let expected = 1.0 / rank.powf(exponent) / normalization;
+            let actual =
+                requested.get(&account.address()).copied().unwrap_or(0) as f64 / total as f64;
+            assert!(
+                (expected - actual).abs() < 0.02,
+                "Account at rank {} sent {} of transactions, expected {}",
+                rank,
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn accounts_send_at_most_the_maximum() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut accounts: Vec<_> = (0..10).map(|_| LocalAccount::generate(&mut rng)).collect();
+        let requested = Arc::new(Mutex::new(HashMap::new()));
+        let mut generator = ZipfAccountSelector::new(
+            StdRng::seed_from_u64(1),
+            Box::new(RecordingGenerator(requested.clone())),
+            3.0,
+            5,
+        );
+        generator.on_accounts_changed(&accounts);
+
+        // Without a maximum, nearly everything would be sent by the first account
+        generator.generate_transactions(accounts.iter_mut().collect(), 2);
+        let requested = requested.lock();
+        assert_eq!(20, requested.values().sum::<usize>());
+        assert!(requested.values().all(|count| *count <= 5));
+        assert_eq!(Some(&5), requested.get(&accounts[0].address()));
+    }
+}
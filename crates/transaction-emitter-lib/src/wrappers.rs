@@ -6,11 +6,12 @@ use crate::{
     cluster::Cluster,
     emitter::{stats::TxnStats, EmitJobMode, EmitJobRequest, TxnEmitter},
     instance::Instance,
+    transaction_generator::mix_config::MixConfig,
 };
 use anyhow::{Context, Result};
 use aptos_sdk::transaction_builder::TransactionFactory;
 use rand::{rngs::StdRng, SeedableRng};
-use std::time::Duration;
+use std::{path::Path, time::Duration};
 
 pub async fn emit_transactions(
     cluster_args: &ClusterArgs,
@@ -62,6 +63,9 @@ pub async fn emit_transactions_with_cluster(
             .txn_expiration_time_secs(args.txn_expiration_time_secs)
             .delay_after_minting(Duration::from_secs(args.delay_after_minting.unwrap_or(0)))
             .gas_price(aptos_global_constants::GAS_UNIT_PRICE);
+    if let Some(mix_file) = &args.mix_file {
+        emit_job_request = emit_job_request.mix_config(MixConfig::load(Path::new(mix_file))?);
+    }
     if reuse_accounts {
         emit_job_request = emit_job_request.reuse_accounts();
     }
@@ -163,6 +163,16 @@ impl DB {
         Ok(Self::log_construct(name, inner))
     }
 
+    /// Catches a secondary DB up with the primary
+    ///
+    /// A secondary only sees the primary as of when it was opened, or last caught up, so reads
+    /// in between see a consistent point-in-time view.  Writes to the primary since then aren't
+    /// visible until this is called.
+    pub fn try_catch_up_with_primary(&self) -> Result<()> {
+        self.inner.try_catch_up_with_primary()?;
+        Ok(())
+    }
+
     fn log_construct(name: &'static str, inner: rocksdb::DB) -> DB {
         info!(rocksdb_name = name, "Opened RocksDB.");
         DB { name, inner }
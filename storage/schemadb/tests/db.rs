@@ -323,6 +323,26 @@ fn test_open_as_secondary() {
     );
 }
 
+#[test]
+fn test_secondary_catch_up_with_primary() {
+    let tmpdir = aptos_temppath::TempPath::new();
+    let tmpdir_sec = aptos_temppath::TempPath::new();
+
+    let db = open_db(&tmpdir);
+    db.put::<TestSchema1>(&TestField(0), &TestField(0)).unwrap();
+    let db_sec = open_db_as_secondary(&tmpdir, &tmpdir_sec);
+
+    // Writes to the primary aren't visible until the secondary catches up
+    db.put::<TestSchema1>(&TestField(1), &TestField(1)).unwrap();
+    assert_eq!(db_sec.get::<TestSchema1>(&TestField(1)).unwrap(), None);
+
+    db_sec.try_catch_up_with_primary().unwrap();
+    assert_eq!(
+        db_sec.get::<TestSchema1>(&TestField(1)).unwrap(),
+        Some(TestField(1)),
+    );
+}
+
 #[test]
 fn test_report_size() {
     let db = TestDB::new();
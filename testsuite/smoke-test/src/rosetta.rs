@@ -27,12 +27,12 @@ use aptos_rosetta::{
     client::RosettaClient,
     common::{native_coin, BlockHash, BLOCKCHAIN, Y2K_MS},
     types::{
-        AccountBalanceRequest, AccountBalanceResponse, AccountIdentifier, BlockIdentifier,
-        BlockRequest, BlockResponse, NetworkIdentifier, NetworkRequest, Operation,
+        AccountBalanceRequest, AccountBalanceResponse, AccountIdentifier, BlockEventsRequest,
+        BlockIdentifier, BlockRequest, BlockResponse, NetworkIdentifier, NetworkRequest, Operation,
         OperationStatusType, OperationType, PartialBlockIdentifier, TransactionType,
         STAKING_CONTRACT_MODULE, SWITCH_OPERATOR_WITH_SAME_COMMISSION_FUNCTION,
     },
-    ROSETTA_VERSION,
+    RosettaConfig, ROSETTA_VERSION,
 };
 use aptos_sdk::{transaction_builder::TransactionFactory, types::LocalAccount};
 use aptos_types::{
@@ -87,10 +87,11 @@ pub async fn setup_test(
     let _rosetta = aptos_rosetta::bootstrap_async(
         swarm.chain_id(),
         api_config,
-        Some(aptos_rest_client::Client::new(
+        vec![aptos_rest_client::Client::new(
             validator.rest_api_endpoint(),
-        )),
+        )],
         cli.addresses(),
+        RosettaConfig::default(),
     )
     .await
     .unwrap();
@@ -138,10 +139,11 @@ async fn test_block_transactions() {
     let _rosetta = aptos_rosetta::bootstrap_async(
         swarm.chain_id(),
         api_config,
-        Some(aptos_rest_client::Client::new(
+        vec![aptos_rest_client::Client::new(
             validator.rest_api_endpoint(),
-        )),
+        )],
         cli.addresses(),
+        RosettaConfig::default(),
     )
     .await
     .unwrap();
@@ -255,6 +257,78 @@ async fn test_network() {
         .expect_err("Should not work with wrong network chain id");
 }
 
+#[tokio::test]
+async fn test_upstream_failover() {
+    let (mut swarm, cli, _faucet) = SwarmBuilder::new_local(2)
+        .with_aptos()
+        .build_with_cli(0)
+        .await;
+    let chain_id = swarm.chain_id();
+    let mut validators = swarm.validators();
+    let primary = validators.next().unwrap();
+    let primary_peer_id = primary.peer_id();
+    let primary_endpoint = primary.rest_api_endpoint();
+    let secondary_endpoint = validators.next().unwrap().rest_api_endpoint();
+    drop(validators);
+
+    // And the client
+    let rosetta_port = get_available_port();
+    let rosetta_socket_addr = format!("127.0.0.1:{}", rosetta_port);
+    let rosetta_url = format!("http://{}", rosetta_socket_addr.clone())
+        .parse()
+        .unwrap();
+    let rosetta_client = RosettaClient::new(rosetta_url);
+    let api_config = ApiConfig {
+        enabled: true,
+        address: rosetta_socket_addr.parse().unwrap(),
+        tls_cert_path: None,
+        tls_key_path: None,
+        content_length_limit: None,
+        ..Default::default()
+    };
+
+    // Start the server with the second validator as the failover
+    let _rosetta = aptos_rosetta::bootstrap_async(
+        chain_id,
+        api_config,
+        vec![
+            aptos_rest_client::Client::new(primary_endpoint),
+            aptos_rest_client::Client::new(secondary_endpoint),
+        ],
+        cli.addresses(),
+        RosettaConfig::default(),
+    )
+    .await
+    .unwrap();
+
+    let request = NetworkRequest {
+        network_identifier: NetworkIdentifier::from(chain_id),
+    };
+    try_until_ok_default(|| rosetta_client.network_status(&request))
+        .await
+        .unwrap();
+
+    // Take down the primary, requests should go to the secondary after the next health check
+    swarm.validator_mut(primary_peer_id).unwrap().stop();
+    let status = try_until_ok(Duration::from_secs(30), DEFAULT_INTERVAL_DURATION, || {
+        rosetta_client.network_status(&request)
+    })
+    .await
+    .unwrap();
+    let block = try_until_ok_default(|| {
+        rosetta_client.block(&BlockRequest::by_index(
+            chain_id,
+            status.current_block_identifier.index,
+        ))
+    })
+    .await
+    .unwrap();
+    assert_eq!(
+        status.current_block_identifier,
+        block.block.block_identifier
+    );
+}
+
 #[tokio::test]
 async fn test_account_balance() {
     let (mut swarm, cli, _faucet, rosetta_client) = setup_test(1, 3).await;
@@ -971,8 +1045,10 @@ async fn test_block() {
         .await
         .expect("Should be able to get block info for completed txns");
 
+    let final_txn_block_height = final_block_to_check.into_inner().block_height.0;
+
     // Check a couple blocks past the final transaction to check more txns
-    let final_block_height = final_block_to_check.into_inner().block_height.0 + 2;
+    let final_block_height = final_txn_block_height + 2;
 
     // TODO: Track total supply?
     // TODO: Check account balance block hashes?
@@ -1057,6 +1133,43 @@ async fn test_block() {
         previous_block_index = block_height;
     }
 
+    // Versions should resolve to the block that contains them
+    let block_for_final_txn = rosetta_client
+        .block_for_version(&network_identifier, final_txn.info.version.0)
+        .await
+        .expect("Should be able to resolve the block of a committed version");
+    assert_eq!(block_for_final_txn.index, final_txn_block_height);
+    let genesis_block = rosetta_client
+        .block_for_version(&network_identifier, 0)
+        .await
+        .expect("Should be able to resolve the genesis block");
+    assert_eq!(genesis_block.index, 0);
+    rosetta_client
+        .block_for_version(&network_identifier, u64::MAX)
+        .await
+        .expect_err("Versions past the latest block shouldn't resolve");
+
+    // Raw events should match all events emitted in the block
+    let block_events = rosetta_client
+        .block_events(&BlockEventsRequest {
+            network_identifier: network_identifier.clone(),
+            block_identifier: Some(PartialBlockIdentifier::block_index(final_txn_block_height)),
+        })
+        .await
+        .expect("Should be able to get events for a known block");
+    assert_eq!(block_events.block_identifier.index, final_txn_block_height);
+    let expected_num_events: usize = rest_client
+        .get_block_by_height_bcs(final_txn_block_height, true)
+        .await
+        .expect("Should be able to get block for a known block")
+        .into_inner()
+        .transactions
+        .expect("Every actual block should have transactions")
+        .iter()
+        .map(|txn| txn.events.len())
+        .sum();
+    assert_eq!(block_events.events.len(), expected_num_events);
+
     // Reconcile and ensure all balances are calculated correctly
     check_balances(&rosetta_client, chain_id, balances).await;
 }